@@ -1,5 +1,5 @@
 // Standalone tool to generate embeddings from Strudel documentation
-// Usage: cargo run --bin generate_embeddings -- <docs.json> <output.json> [dimension]
+// Usage: cargo run --bin generate_embeddings -- <docs.json> <output.json> [dimension] [--stem]
 
 use anyhow::Result;
 use std::env;
@@ -8,8 +8,12 @@ use std::fs;
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
 
+    // Stem words so different forms of a word ("pattern", "patterns") match
+    let stem = args.iter().any(|arg| arg == "--stem");
+    let args: Vec<String> = args.into_iter().filter(|arg| arg != "--stem").collect();
+
     if args.len() < 3 {
-        eprintln!("Usage: {} <input_docs.json> <output_embeddings.json> [dimension]", args[0]);
+        eprintln!("Usage: {} <input_docs.json> <output_embeddings.json> [dimension] [--stem]", args[0]);
         eprintln!("\nExample:");
         eprintln!("  cargo run --bin generate_embeddings -- docs.json embeddings.json 384");
         std::process::exit(1);
@@ -25,7 +29,7 @@ fn main() -> Result<()> {
     let docs_json = fs::read_to_string(input_path)?;
 
     println!("🔧 Generating embeddings with dimension: {}", dimension);
-    let (embeddings, chunks, embedder) = app_lib::rag::generator::generate_from_strudel_docs(&docs_json, dimension)?;
+    let (embeddings, chunks, embedder) = app_lib::rag::generator::generate_from_strudel_docs(&docs_json, dimension, stem)?;
 
     println!("✅ Generated {} embeddings", embeddings.len());
     println!("📝 Vocabulary size: {}", embedder.vocabulary.len());
@@ -279,7 +279,7 @@ impl ChatState {
         if let Ok(query_embedding) = self.rag_state.embed_query(query).await {
             if let Ok(rag_results) = self
                 .rag_state
-                .search_with_embedding(&query_embedding, 5)
+                .search_for_chat(&query_embedding, 5, &[])
                 .await
             {
                 if !rag_results.is_empty() {
@@ -374,7 +374,7 @@ impl ChatState {
             - **search_strudel_docs(query)** - Search function documentation before suggesting unfamiliar functions\n\
             - **list_available_sounds(type, filter)** - Query available samples, synths, or GM instruments\n\
             - **generate_chord_progression(key, style)** - Generate chord progressions (pop, jazz, blues, folk, rock, classical, modal, edm)\n\
-            - **generate_euclidean_rhythm(hits, steps, sound)** - Create polyrhythmic patterns\n\n\
+            - **generate_euclidean_rhythm(hits, steps, sound, complementary)** - Create polyrhythmic patterns, optionally with hats filling the rests\n\n\
             ## Quick Reference\n\n\
             **Core Functions:**\n\
             - `note()`, `s()`, `sound()` - Create patterns\n\
@@ -1031,6 +1031,17 @@ pub async fn load_strudel_docs(
         } else {
             println!("✅ RAG initialized for semantic search");
         }
+    } else if let Some(embedder) = crate::rag::OllamaEmbedder::from_env() {
+        // Without prebuilt embeddings, index the docs with a local model in the
+        // background; keyword search covers the meantime
+        let rag_state = state.rag_state.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = rag_state.load_with_ollama(&docs_json, embedder).await {
+                eprintln!("Failed to index docs with Ollama: {}", e);
+            } else {
+                println!("✅ RAG initialized with Ollama embeddings");
+            }
+        });
     }
 
     let mut formatted_docs = String::new();
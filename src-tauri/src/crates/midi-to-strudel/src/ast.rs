@@ -44,6 +44,19 @@ impl Bar {
         matches!(self, Bar::Rest)
     }
 
+    /// Replace every note with `f(note)`, keeping the bar's rhythm
+    pub fn map_notes<F: Fn(&str) -> String>(&self, f: &F) -> Bar {
+        match self {
+            Bar::Rest => Bar::Rest,
+            Bar::Note(n) => Bar::Note(f(n)),
+            Bar::Chord(notes) => Bar::Chord(notes.iter().map(|n| f(n)).collect()),
+            Bar::Sequence(notes) => Bar::Sequence(notes.iter().map(|n| f(n)).collect()),
+            Bar::Subdivision(bars) => {
+                Bar::Subdivision(bars.iter().map(|b| b.map_notes(f)).collect())
+            }
+        }
+    }
+
     /// Get all notes in this bar (for analysis)
     pub fn notes(&self) -> Vec<String> {
         match self {
@@ -60,6 +73,7 @@ impl Bar {
 pub enum ModifierValue {
     Single(f32),
     Pattern(Vec<f32>),
+    Steps(Vec<Vec<f32>>), // Values in equal steps within each bar, no values for a rest
 }
 
 impl ModifierValue {
@@ -70,6 +84,21 @@ impl ModifierValue {
                 let formatted: Vec<String> = vals.iter().map(|v| format!("{:.2}", v)).collect();
                 format!("\"<{}>\"", formatted.join(" "))
             }
+            ModifierValue::Steps(bars) => {
+                let formatted: Vec<String> = bars
+                    .iter()
+                    .map(|steps| match steps.as_slice() {
+                        [] => "-".to_string(),
+                        [v] => format!("{:.2}", v),
+                        _ => {
+                            let vals: Vec<String> =
+                                steps.iter().map(|v| format!("{:.2}", v)).collect();
+                            format!("[{}]", vals.join(" "))
+                        }
+                    })
+                    .collect();
+                format!("\"<{}>\"", formatted.join(" "))
+            }
         }
     }
 }
@@ -85,6 +114,7 @@ pub struct Pattern {
     pub gain: Option<ModifierValue>,
     pub pan: Option<f32>,
     pub sustain: Option<f32>,
+    pub effects: Vec<(String, ModifierValue)>, // Further modifiers by function name, e.g. lpf
 }
 
 impl Pattern {
@@ -129,6 +159,10 @@ impl Pattern {
             }
         }
 
+        for (name, value) in &self.effects {
+            output.push_str(&format!(".{}({})", name, value.to_strudel()));
+        }
+
         output
     }
 
@@ -269,6 +303,7 @@ mod tests {
             gain: Some(ModifierValue::Single(0.8)),
             pan: Some(0.6),
             sustain: Some(0.5),
+            effects: Vec::new(),
         };
 
         let output = pattern.to_strudel(false);
@@ -288,6 +323,7 @@ mod tests {
             gain: Some(ModifierValue::Single(0.7)),
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         let output = pattern.to_strudel(false);
@@ -307,6 +343,7 @@ mod tests {
             gain: Some(ModifierValue::Pattern(vec![0.5, 0.7, 0.9])),
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         let output = pattern.to_strudel(false);
@@ -322,6 +359,7 @@ mod tests {
             gain: None,
             pan: Some(0.5), // Centered - should be skipped
             sustain: None,
+            effects: Vec::new(),
         };
 
         let output = pattern.to_strudel(false);
@@ -337,6 +375,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: Some(1.0), // Default - should be skipped
+            effects: Vec::new(),
         };
 
         let output = pattern.to_strudel(false);
@@ -352,6 +391,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         let result = pattern.validate();
@@ -368,6 +408,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         let result = pattern.validate();
@@ -384,6 +425,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         let result = pattern.validate();
@@ -405,6 +447,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         pattern.optimize();
@@ -422,6 +465,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         pattern.optimize();
@@ -438,6 +482,7 @@ mod tests {
             gain: None,
             pan: None,
             sustain: None,
+            effects: Vec::new(),
         };
 
         pattern.optimize();
@@ -470,6 +515,7 @@ mod tests {
             gain: Some(ModifierValue::Single(0.8)),
             pan: Some(0.6),
             sustain: Some(0.5),
+            effects: Vec::new(),
         };
 
         // Serialize to JSON
@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+
 /// General MIDI Drum mapping to Strudel drum sample names
 /// Based on GM Level 1 Percussion Key Map (Channel 10)
 /// Convert a MIDI drum note number to a Strudel drum sample name
@@ -81,6 +85,56 @@ pub fn gm_drum_to_sample(note_num: u8) -> Option<&'static str> {
     }
 }
 
+/// Drum note → sample mapping: General MIDI, with user overrides
+///
+/// For MIDI files made with non-GM kits, overrides replace or extend the GM
+/// mapping, e.g. to play note 38 as `rim` instead of `sd`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DrumMap {
+    overrides: HashMap<u8, String>,
+}
+
+impl DrumMap {
+    /// The plain General MIDI mapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Play a drum note as a different sample
+    pub fn with_override(mut self, note_num: u8, sample: &str) -> Self {
+        self.overrides.insert(note_num, sample.to_string());
+        self
+    }
+
+    /// Load overrides from a JSON object of drum notes to sample names
+    ///
+    /// Notes are MIDI numbers or note names, e.g. `{"38": "rim", "c#3": "cr"}`.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let entries: HashMap<String, String> = serde_json::from_str(json)
+            .context("Drum map must be a JSON object of note → sample")?;
+
+        let mut map = Self::new();
+        for (note, sample) in entries {
+            let note_num = match note.parse::<u8>() {
+                Ok(num) if num <= 127 => num,
+                _ => note_name_to_midi_num(&note)
+                    .with_context(|| format!("Unknown drum note in drum map: {}", note))?,
+            };
+            map = map.with_override(note_num, &sample);
+        }
+
+        Ok(map)
+    }
+
+    /// Sample name for a drum note, preferring an override over the GM sample
+    pub fn sample(&self, note_num: u8) -> Option<&str> {
+        match self.overrides.get(&note_num) {
+            Some(sample) => Some(sample),
+            None => gm_drum_to_sample(note_num),
+        }
+    }
+}
+
 /// Get the human-readable name for a GM drum note
 #[allow(dead_code)]
 pub fn gm_drum_name(note_num: u8) -> &'static str {
@@ -164,7 +218,7 @@ pub fn is_drum_track_name(track_name: &str) -> bool {
 /// Convert a note name (from note conversion) back to MIDI note number for drum conversion
 /// This is needed because we already converted notes to names like "c2", "d2", etc.
 pub fn note_name_to_midi_num(note_name: &str) -> Option<u8> {
-    // Parse note names like "c2", "d#3", "f#1" back to MIDI numbers
+    // Parse note names like "c2", "d#3", "eb3" back to MIDI numbers
     let note_name = note_name.to_lowercase();
     let bytes = note_name.as_bytes();
 
@@ -187,10 +241,13 @@ pub fn note_name_to_midi_num(note_name: &str) -> Option<u8> {
     let mut pos = 1;
     let mut sharp = 0;
 
-    // Check for sharp
+    // Check for sharp or flat
     if pos < bytes.len() && bytes[pos] as char == '#' {
         sharp = 1;
         pos += 1;
+    } else if pos < bytes.len() && bytes[pos] as char == 'b' {
+        sharp = -1;
+        pos += 1;
     }
 
     // Get the octave number
@@ -228,10 +285,24 @@ mod tests {
     fn test_note_name_conversion() {
         assert_eq!(note_name_to_midi_num("c2"), Some(36)); // C2 = MIDI 36 = Bass Drum
         assert_eq!(note_name_to_midi_num("d2"), Some(38)); // D2 = MIDI 38 = Snare
+        assert_eq!(note_name_to_midi_num("eb3"), Some(51));
+        assert_eq!(note_name_to_midi_num("Bb2"), Some(46));
         assert_eq!(note_name_to_midi_num("f#2"), Some(42)); // F#2 = MIDI 42 = Closed Hi-Hat
         assert_eq!(note_name_to_midi_num("c#3"), Some(49)); // C#3 = MIDI 49 = Crash
     }
 
+    #[test]
+    fn test_drum_map_overrides() {
+        let map = DrumMap::from_json(r#"{"38": "rim", "c#3": "cr:2", "90": "zap"}"#).unwrap();
+        assert_eq!(map.sample(38), Some("rim")); // Overridden snare
+        assert_eq!(map.sample(49), Some("cr:2")); // By note name
+        assert_eq!(map.sample(90), Some("zap")); // Outside the GM map
+        assert_eq!(map.sample(36), Some("bd")); // GM default
+
+        assert!(DrumMap::from_json(r#"{"x9": "bd"}"#).is_err());
+        assert!(DrumMap::from_json("[]").is_err());
+    }
+
     #[test]
     fn test_drum_names() {
         assert_eq!(gm_drum_name(36), "Bass Drum 1");
@@ -0,0 +1,326 @@
+use anyhow::{bail, Result};
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use strudel_core::{Fraction, Hap, Pattern, State, TimeSpan, Value};
+
+use crate::drums::note_name_to_midi_num;
+
+/// Velocity used for events without a "gain" entry
+const DEFAULT_VELOCITY: u8 = 100;
+
+/// Resolution of written MIDI files
+pub const TICKS_PER_BEAT: u16 = 480;
+
+/// Beats in one cycle, matching the MIDI import
+const BEATS_PER_CYCLE: i64 = 4;
+
+/// Slowest tempo a MIDI tempo event can hold, in microseconds per beat
+const MAX_MICROS_PER_BEAT: u32 = 0xFF_FFFF;
+
+/// A single note produced by exporting a pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedNote {
+    pub start: Fraction,     // Onset in cycles
+    pub duration: Fraction,  // Length in cycles
+    pub note: u8,
+    pub velocity: u8,
+}
+
+/// Convert a gain (0.0 to 1.0) to a MIDI velocity (0 to 127)
+pub fn gain_to_velocity(gain: f64) -> u8 {
+    (gain.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+/// Query a pattern over the first `cycles` cycles and convert each onset to a MIDI note
+///
+/// Values may be MIDI note numbers or note names like "c4". Events without a
+/// pitch are skipped. Velocity comes from the "gain" metadata written by
+/// `Pattern::velocity`.
+///
+/// MIDI needs discrete note-on/note-off pairs, so continuous patterns (events
+/// without a `whole`, such as signals) are rejected. Discretize them first with
+/// `.segment(n)`.
+pub fn export_notes(pattern: &Pattern, cycles: usize) -> Result<Vec<ExportedNote>> {
+    let span = TimeSpan::new(Fraction::from_int(0), Fraction::from_int(cycles as i64));
+    let haps = pattern.query(State::new(span));
+
+    if let Some(hap) = haps.iter().find(|hap| hap.whole.is_none()) {
+        bail!(
+            "Cannot export continuous pattern to MIDI (event at cycle {} has no discrete onset). \
+             Sample it into steps with .segment(n) first, e.g. sine().segment(8)",
+            hap.part.begin
+        );
+    }
+
+    let mut notes: Vec<ExportedNote> = haps
+        .iter()
+        .filter(|hap| hap.has_onset())
+        .filter_map(hap_to_note)
+        .collect();
+
+    notes.sort_by(|a, b| a.start.cmp(&b.start).then(a.note.cmp(&b.note)));
+    Ok(notes)
+}
+
+/// Write notes as a single-track Standard MIDI File at the given tempo
+///
+/// A cycle is four beats, as on import, so the file plays the notes at the
+/// same speed as Strudel at `bpm`. Notes go out on channel 1.
+pub fn notes_to_midi(notes: &[ExportedNote], bpm: f64) -> Result<Vec<u8>> {
+    // The tempo meta event stores microseconds per beat in 24 bits
+    let micros_per_beat = (60_000_000.0 / bpm).round();
+    if !(1.0..=MAX_MICROS_PER_BEAT as f64).contains(&micros_per_beat) {
+        bail!(
+            "Tempo must be between {:.2} and {} BPM, got {} BPM",
+            60_000_000.0 / MAX_MICROS_PER_BEAT as f64,
+            60_000_000,
+            bpm
+        );
+    }
+
+    let ticks_per_cycle = Fraction::from_int(BEATS_PER_CYCLE * TICKS_PER_BEAT as i64);
+    let tick = |cycles: Fraction| (cycles * ticks_per_cycle).to_float().round() as u32;
+    let midi = |message| TrackEventKind::Midi {
+        channel: u4::new(0),
+        message,
+    };
+
+    // Note-offs sort before note-ons at the same tick, so repeated notes retrigger
+    let mut timed: Vec<(u32, bool, TrackEventKind)> = Vec::new();
+    for note in notes {
+        let key = u7::new(note.note.min(127));
+        let start_tick = tick(note.start);
+        // Every note lasts at least a tick, so its note-off can't sort before its note-on
+        let end_tick = tick(note.start + note.duration).max(start_tick + 1);
+        timed.push((
+            start_tick,
+            true,
+            // A note-on with velocity 0 would be read as a note-off
+            midi(MidiMessage::NoteOn {
+                key,
+                vel: u7::new(note.velocity.clamp(1, 127)),
+            }),
+        ));
+        timed.push((
+            end_tick,
+            false,
+            midi(MidiMessage::NoteOff {
+                key,
+                vel: u7::new(0),
+            }),
+        ));
+    }
+    timed.sort_by_key(|&(tick, is_on, _)| (tick, is_on));
+
+    let mut track = vec![TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(micros_per_beat as u32))),
+    }];
+    let mut last_tick = 0;
+    for (tick, _, kind) in timed {
+        track.push(TrackEvent {
+            delta: u28::new(tick - last_tick),
+            kind,
+        });
+        last_tick = tick;
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+
+    let smf = Smf {
+        header: Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(TICKS_PER_BEAT)),
+        ),
+        tracks: vec![track],
+    };
+    let mut data = Vec::new();
+    smf.write_std(&mut data)?;
+    Ok(data)
+}
+
+/// Export the first `cycles` cycles of a pattern as a MIDI file at the given tempo
+///
+/// See [`export_notes`] for how events become notes.
+pub fn export_midi(pattern: &Pattern, cycles: usize, bpm: f64) -> Result<Vec<u8>> {
+    notes_to_midi(&export_notes(pattern, cycles)?, bpm)
+}
+
+fn hap_to_note(hap: &Hap) -> Option<ExportedNote> {
+    let note = match &hap.value {
+        Value::Number(n) if (0.0..=127.0).contains(n) => n.round() as u8,
+        Value::String(s) => note_name_to_midi_num(s)?,
+        _ => return None,
+    };
+
+    let velocity = match hap.context.metadata.get("gain") {
+        Some(Value::Number(gain)) => gain_to_velocity(*gain),
+        _ => DEFAULT_VELOCITY,
+    };
+
+    let whole = hap.whole_or_part();
+    Some(ExportedNote {
+        start: whole.begin,
+        duration: whole.end - whole.begin,
+        note,
+        velocity,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strudel_core::{fastcat, pure};
+
+    /// A continuous pattern rising from 60 to 72 over each cycle
+    fn ramp() -> Pattern {
+        Pattern::new(|state| {
+            let mid = state.span.midpoint();
+            let pos = (mid - mid.floor()).to_float();
+            vec![Hap::new(None, state.span, Value::Number(60.0 + 12.0 * pos))]
+        })
+    }
+
+    #[test]
+    fn test_gain_to_velocity() {
+        assert_eq!(gain_to_velocity(1.0), 127);
+        assert_eq!(gain_to_velocity(0.5), 64);
+        assert_eq!(gain_to_velocity(0.0), 0);
+        assert_eq!(gain_to_velocity(2.0), 127);
+    }
+
+    #[test]
+    fn test_export_velocity() {
+        let notes = fastcat(vec![pure(Value::String("c4".into())); 4]);
+        let velocities = fastcat(vec![pure(Value::Number(1.0)), pure(Value::Number(0.5))]);
+
+        let exported = export_notes(&notes.velocity(velocities), 1).unwrap();
+
+        assert_eq!(exported.len(), 4);
+        assert_eq!(exported[0].note, 60);
+        assert_eq!(exported[0].start, Fraction::from_int(0));
+        assert_eq!(exported[0].duration, Fraction::new(1, 4));
+        let velocities: Vec<u8> = exported.iter().map(|n| n.velocity).collect();
+        assert_eq!(velocities, vec![127, 127, 64, 64]);
+    }
+
+    #[test]
+    fn test_export_skips_unpitched() {
+        let pattern = fastcat(vec![
+            pure(Value::Number(62.0)),
+            pure(Value::String("bd".into())),
+        ]);
+
+        let exported = export_notes(&pattern, 2).unwrap();
+        assert_eq!(exported.len(), 2);
+        assert!(exported.iter().all(|n| n.note == 62 && n.velocity == DEFAULT_VELOCITY));
+    }
+
+    #[test]
+    fn test_export_midi_file() {
+        let pattern = fastcat(vec![
+            pure(Value::String("c3".into())),
+            pure(Value::String("e3".into())),
+            pure(Value::String("g3".into())),
+        ]);
+
+        let data = export_midi(&pattern, 1, 120.0).unwrap();
+        let smf = Smf::parse(&data).unwrap();
+        assert_eq!(
+            smf.header.timing,
+            Timing::Metrical(u15::new(TICKS_PER_BEAT))
+        );
+        assert_eq!(smf.tracks.len(), 1);
+
+        let mut tick = 0;
+        let mut note_ons = Vec::new();
+        for event in &smf.tracks[0] {
+            tick += event.delta.as_int();
+            match event.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { key, vel },
+                    ..
+                } => note_ons.push((tick, key.as_int(), vel.as_int())),
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    assert_eq!(tempo.as_int(), 500_000)
+                }
+                _ => {}
+            }
+        }
+
+        // A cycle is four beats, so each third of it is 640 ticks
+        assert_eq!(
+            note_ons,
+            vec![
+                (0, 48, DEFAULT_VELOCITY),
+                (640, 52, DEFAULT_VELOCITY),
+                (1280, 55, DEFAULT_VELOCITY),
+            ]
+        );
+        assert_eq!(tick, 1920);
+    }
+
+    #[test]
+    fn test_export_very_short_note() {
+        // Far shorter than a tick, so start and end round to the same one
+        let notes = [ExportedNote {
+            note: 60,
+            start: Fraction::from_int(0),
+            duration: Fraction::new(1, 100_000),
+            velocity: DEFAULT_VELOCITY,
+        }];
+
+        let data = notes_to_midi(&notes, 120.0).unwrap();
+        let smf = Smf::parse(&data).unwrap();
+        let mut tick = 0;
+        let mut events = Vec::new();
+        for event in &smf.tracks[0] {
+            tick += event.delta.as_int();
+            match event.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { .. },
+                    ..
+                } => events.push((tick, true)),
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOff { .. },
+                    ..
+                } => events.push((tick, false)),
+                _ => {}
+            }
+        }
+
+        // The note-off comes a tick after the note-on instead of before it
+        assert_eq!(events, vec![(0, true), (1, false)]);
+    }
+
+    #[test]
+    fn test_export_tempo_out_of_range() {
+        let notes = export_notes(&pure(Value::String("c3".into())), 1).unwrap();
+
+        // About 3.58 BPM is the slowest tempo that fits in 24 bits
+        assert!(notes_to_midi(&notes, 3.0).is_err());
+        assert!(notes_to_midi(&notes, 0.0).is_err());
+        assert!(notes_to_midi(&notes, f64::NAN).is_err());
+        assert!(notes_to_midi(&notes, 3.6).is_ok());
+
+        let err = notes_to_midi(&notes, 2.0).unwrap_err();
+        assert!(err.to_string().contains("3.58"));
+    }
+
+    #[test]
+    fn test_export_continuous_errors() {
+        let err = export_notes(&strudel_core::sine(), 1).unwrap_err();
+        assert!(err.to_string().contains(".segment(n)"));
+    }
+
+    #[test]
+    fn test_export_segmented_continuous() {
+        let exported = export_notes(&ramp().segment(4.0), 1).unwrap();
+
+        assert_eq!(exported.len(), 4);
+        assert!(exported.windows(2).all(|w| w[0].note <= w[1].note));
+        assert!(exported.iter().all(|n| (60..=72).contains(&n.note)));
+    }
+}
@@ -4,6 +4,7 @@
 
 pub mod ast;
 pub mod drums;
+pub mod export;
 pub mod instruments;
 pub mod midi;
 pub mod note;
@@ -12,7 +13,8 @@ pub mod track;
 
 // Re-export main types for convenience
 pub use ast::{Bar, ModifierValue, Pattern};
-pub use drums::is_drum_track_name;
-pub use midi::MidiData;
+pub use drums::{is_drum_track_name, DrumMap};
+pub use export::{export_midi, export_notes, notes_to_midi, ExportedNote};
+pub use midi::{ControlChange, KeySignature, MidiData, PitchBend, TimeSignature};
 pub use output::OutputFormatter;
-pub use track::{ProcessedTrack, TrackBuilder};
+pub use track::{CcEffectMap, CcMapping, ProcessedTrack, TrackBuilder};
@@ -3,7 +3,8 @@ use clap::{Parser, ValueEnum};
 use std::fs;
 use std::path::PathBuf;
 
-use midi_to_strudel::{MidiData, OutputFormatter, TrackBuilder};
+use midi_to_strudel::{CcEffectMap, DrumMap, MidiData, OutputFormatter, TrackBuilder};
+use strudel_core::Scale;
 
 #[derive(Debug, Clone, ValueEnum)]
 enum OutputFormat {
@@ -54,7 +55,8 @@ struct Args {
     #[arg(long, default_value = "1.0")]
     tempo_scale: f64,
 
-    /// Compress repetitive patterns using replication operator (!)
+    /// Compress repetitive patterns using replication operator (!),
+    /// and write tracks that repeat a cycle of a few bars as just that cycle
     #[arg(short, long, default_value = "false")]
     compact: bool,
 
@@ -87,6 +89,40 @@ struct Args {
     /// Use this for MIDI files where drums are on non-standard channels
     #[arg(long)]
     force_drums: Option<String>,
+
+    /// JSON file mapping drum notes to samples, overriding General MIDI
+    /// (e.g., {"38": "rim"}) for files made with non-GM drum kits
+    #[arg(long)]
+    drum_map: Option<PathBuf>,
+
+    /// Write melodic notes as scale degrees with n(...).scale(...)
+    /// Uses the file's key signature unless --key is given
+    #[arg(long)]
+    degrees: bool,
+
+    /// Key for scale degree output (e.g., "C:minor"); implies --degrees
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Write chords in root position by name (e.g., "c3:maj") instead of stacked notes
+    #[arg(long)]
+    chord_names: bool,
+
+    /// Set gain per note from its velocity, instead of averaging it over each bar
+    #[arg(long)]
+    note_gains: bool,
+
+    /// Write CC automation (volume, pan, filter cutoff, reverb send) as gain, pan, lpf and room
+    #[arg(long)]
+    cc_effects: bool,
+
+    /// Loosen the quantized timing with a small random nudge on each note
+    #[arg(long)]
+    humanize: bool,
+
+    /// Seed for --humanize; the same seed always writes the same nudges
+    #[arg(long, default_value = "0")]
+    humanize_seed: u64,
 }
 
 fn filter_tracks(mut tracks: Vec<midi_to_strudel::track::ProcessedTrack>, args: &Args) -> Vec<midi_to_strudel::track::ProcessedTrack> {
@@ -188,22 +224,64 @@ fn main() -> Result<()> {
         Vec::new()
     };
 
+    // Load drum sample overrides if provided
+    let drum_map = if let Some(ref path) = args.drum_map {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        DrumMap::from_json(&json)?
+    } else {
+        DrumMap::new()
+    };
+
     // Build tracks
-    let track_builder = TrackBuilder::new(
+    let mut track_builder = TrackBuilder::new(
         midi_data.cycle_len,
         args.bar_limit,
         args.flat_sequences,
         args.notes_per_bar,
         args.detect_drum_names,
         forced_drum_channels,
-    );
+    )
+    .with_time_signatures(midi_data.time_signatures.clone())
+    .with_drum_map(drum_map);
+    if args.note_gains {
+        track_builder = track_builder.with_note_gains();
+    }
+    if args.cc_effects {
+        track_builder = track_builder.with_cc_effects(CcEffectMap::default());
+    }
     let mut tracks = track_builder.build_tracks(&midi_data.track_info);
 
     // Apply filters
     tracks = filter_tracks(tracks, &args);
 
+    // Pick the key for scale degree output, preferring --key over the file's key signature
+    let key = if args.degrees || args.key.is_some() {
+        let key = args
+            .key
+            .clone()
+            .or_else(|| midi_data.key_signature.map(|k| k.scale_name()));
+        match &key {
+            Some(key) => anyhow::ensure!(Scale::parse(key).is_some(), "Unknown key: {}", key),
+            None if !args.quiet => eprintln!("No key signature found, writing note names"),
+            None => {}
+        }
+        key
+    } else {
+        None
+    };
+
     // Format output based on requested format
-    let formatter = OutputFormatter::new(args.tab_size, args.compact);
+    let mut formatter = OutputFormatter::new(args.tab_size, args.compact);
+    if let Some(key) = key {
+        formatter = formatter.with_scale(key);
+    }
+    if args.chord_names {
+        formatter = formatter.with_chord_names();
+    }
+    if args.humanize {
+        formatter = formatter.with_humanize(args.humanize_seed);
+    }
     let scaled_bpm = midi_data.bpm * args.tempo_scale;
     let output = match args.format {
         OutputFormat::Strudel => formatter.build_output(&tracks, scaled_bpm),
@@ -21,12 +21,84 @@ pub struct TrackInfo {
     pub program: Option<u8>,
     pub name: Option<String>,
     pub pan: Option<u8>,  // MIDI pan value (0=left, 64=center, 127=right)
+    pub pitch_bends: Vec<PitchBend>,  // In time order, across all of the track's channels
+    pub control_changes: Vec<ControlChange>,  // In time order, across all of the track's channels
+}
+
+/// A pitch bend on one channel, in semitones from the unbent pitch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchBend {
+    pub time_sec: f64,
+    pub channel: u8,
+    pub semitones: f64,
+}
+
+/// A Control Change message, such as volume (CC7) or filter brightness (CC74)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlChange {
+    pub time_sec: f64,
+    pub channel: u8,
+    pub controller: u8,
+    pub value: u8,
+}
+
+/// Pitch bend range in semitones until a file sets it (General MIDI default)
+const DEFAULT_BEND_RANGE: f64 = 2.0;
+
+/// Controller number of the sustain (damper) pedal; values of 64 and up hold it down
+const SUSTAIN_PEDAL: u8 = 64;
+
+/// A time signature change, e.g. 3/4 starting at a given tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeSignature {
+    pub tick: u32,
+    pub time_sec: f64,
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+/// A key signature, as sharps (positive) or flats (negative) and mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeySignature {
+    pub sharps: i8,
+    pub minor: bool,
+}
+
+impl KeySignature {
+    /// Get the Strudel scale name for this key, e.g. `"Eb:major"` or `"C:minor"`
+    pub fn scale_name(&self) -> String {
+        const SHARP_NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"
+        ];
+        const FLAT_NAMES: [&str; 12] = [
+            "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"
+        ];
+
+        // Each sharp moves the major key up a fifth; the relative minor is a minor third below
+        let major = (self.sharps as i32 * 7).rem_euclid(12);
+        let tonic = if self.minor { (major + 9) % 12 } else { major };
+        let names = if self.sharps < 0 { &FLAT_NAMES } else { &SHARP_NAMES };
+        let mode = if self.minor { "minor" } else { "major" };
+
+        format!("{}:{}", names[tonic as usize], mode)
+    }
+}
+
+/// A tempo change, in microseconds per quarter note from a given tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    pub tick: u32,
+    pub time_sec: f64,
+    pub micros_per_quarter: u32,
 }
 
 pub struct MidiData {
-    pub bpm: f64,
+    pub bpm: f64,  // Representative BPM (the opening tempo)
     pub cycle_len: f64,
     pub track_info: HashMap<usize, TrackInfo>,
+    pub time_signatures: Vec<TimeSignature>,  // Sorted by tick; empty means 4/4 throughout
+    pub tempo_changes: Vec<TempoChange>,  // Sorted by tick; the first is always at tick 0
+    pub key_signature: Option<KeySignature>,  // The opening key, if the file has one
 }
 
 impl MidiData {
@@ -34,7 +106,11 @@ impl MidiData {
         let data = std::fs::read(path)
             .with_context(|| format!("Failed to read MIDI file: {}", path.display()))?;
 
-        let smf = Smf::parse(&data)
+        Self::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let smf = Smf::parse(data)
             .context("Failed to parse MIDI file")?;
 
         let ticks_per_beat = match smf.header.timing {
@@ -45,55 +121,166 @@ impl MidiData {
             }
         };
 
-        // Extract tempo from first track
-        let tempo = Self::extract_tempo(&smf)?;
+        // Build the tempo map; the opening tempo sets the output BPM
+        let tempo_changes = Self::extract_tempo_changes(&smf, ticks_per_beat);
+        let tempo = tempo_changes[0].micros_per_quarter;
         let bpm = 60_000_000.0 / tempo as f64;
         let cycle_len = 60.0 / bpm * 4.0;
 
         // Collect note events and instrument info from all tracks
-        let track_info = Self::collect_track_info(&smf, ticks_per_beat, tempo);
+        let track_info = Self::collect_track_info(&smf, ticks_per_beat, &tempo_changes);
+        let time_signatures = Self::extract_time_signatures(&smf, ticks_per_beat, &tempo_changes);
+        let key_signature = Self::extract_key_signature(&smf);
 
         Ok(MidiData {
             bpm,
             cycle_len,
             track_info,
+            time_signatures,
+            tempo_changes,
+            key_signature,
         })
     }
 
-    fn extract_tempo(smf: &Smf) -> Result<u32> {
+    fn extract_key_signature(smf: &Smf) -> Option<KeySignature> {
+        let mut first: Option<(u32, KeySignature)> = None;
+
+        for track in &smf.tracks {
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int();
+                if let TrackEventKind::Meta(MetaMessage::KeySignature(sharps, minor)) = event.kind {
+                    if first.is_none_or(|(first_tick, _)| tick < first_tick) {
+                        first = Some((tick, KeySignature { sharps, minor }));
+                    }
+                    break;
+                }
+            }
+        }
+
+        first.map(|(_, key)| key)
+    }
+
+    fn extract_time_signatures(
+        smf: &Smf,
+        ticks_per_beat: u32,
+        tempo_changes: &[TempoChange],
+    ) -> Vec<TimeSignature> {
+        let mut time_signatures = Vec::new();
+
+        for track in &smf.tracks {
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int();
+
+                // Denominator is stored as a power of two (2 = quarter notes)
+                if let TrackEventKind::Meta(MetaMessage::TimeSignature(
+                    numerator,
+                    denom_pow,
+                    _,
+                    _,
+                )) = event.kind
+                {
+                    let denominator = match 1u8.checked_shl(denom_pow as u32) {
+                        Some(d) if numerator > 0 => d,
+                        _ => continue,
+                    };
+                    time_signatures.push(TimeSignature {
+                        tick,
+                        time_sec: tick_to_second(tick, ticks_per_beat, tempo_changes),
+                        numerator,
+                        denominator,
+                    });
+                }
+            }
+        }
+
+        // Sort by position; when tracks disagree at the same tick, the last one wins
+        time_signatures.sort_by_key(|sig| sig.tick);
+        time_signatures.dedup_by(|later, earlier| {
+            if later.tick == earlier.tick {
+                *earlier = *later;
+                true
+            } else {
+                false
+            }
+        });
+
+        time_signatures
+    }
+
+    fn extract_tempo_changes(smf: &Smf, ticks_per_beat: u32) -> Vec<TempoChange> {
+        let mut tempos: Vec<(u32, u32)> = Vec::new();
+
         for track in &smf.tracks {
+            let mut tick = 0;
             for event in track {
+                tick += event.delta.as_int();
                 if let TrackEventKind::Meta(MetaMessage::Tempo(tempo)) = event.kind {
-                    return Ok(tempo.as_int());
+                    tempos.push((tick, tempo.as_int()));
                 }
             }
         }
+
+        // Sort by position; when tracks disagree at the same tick, the last one wins
+        tempos.sort_by_key(|&(tick, _)| tick);
+        tempos.dedup_by(|later, earlier| {
+            if later.0 == earlier.0 {
+                *earlier = *later;
+                true
+            } else {
+                false
+            }
+        });
+
+        // The opening tempo also covers any ticks before it
         // Default tempo: 120 BPM = 500000 microseconds per beat
-        Ok(500000)
+        match tempos.first_mut() {
+            Some(first) => first.0 = 0,
+            None => tempos.push((0, 500000)),
+        }
+
+        let mut tempo_changes: Vec<TempoChange> = Vec::with_capacity(tempos.len());
+        for (tick, micros_per_quarter) in tempos {
+            let time_sec = tick_to_second(tick, ticks_per_beat, &tempo_changes);
+            tempo_changes.push(TempoChange {
+                tick,
+                time_sec,
+                micros_per_quarter,
+            });
+        }
+
+        tempo_changes
     }
 
     fn collect_track_info(
         smf: &Smf,
         ticks_per_beat: u32,
-        tempo: u32,
+        tempo_changes: &[TempoChange],
     ) -> HashMap<usize, TrackInfo> {
         let mut track_info_map = HashMap::new();
 
         for (track_idx, track) in smf.tracks.iter().enumerate() {
-            let mut time_sec = 0.0;
+            let mut tick = 0;
             let mut events = Vec::new();
             let mut channel: Option<u8> = None;
             let mut program: Option<u8> = None;
             let mut track_name: Option<String> = None;
             let mut pan_values: Vec<u8> = Vec::new();  // Collect all pan CC messages
+            let mut pitch_bends: Vec<PitchBend> = Vec::new();
+            let mut control_changes: Vec<ControlChange> = Vec::new();
+
+            // Per channel: the selected RPN (CC101, CC100) and the pitch bend range it may set
+            let mut rpn: HashMap<u8, (u8, u8)> = HashMap::new();
+            let mut bend_range: HashMap<u8, f64> = HashMap::new();
 
             // Track active notes: (channel, note_num) -> (start_time, velocity, event_index)
             let mut active_notes: HashMap<(u8, u8), (f64, u8, usize)> = HashMap::new();
 
             for event in track {
-                // Convert delta time to seconds
-                let delta_sec = tick_to_second(event.delta.as_int(), ticks_per_beat, tempo);
-                time_sec += delta_sec;
+                // Convert absolute ticks to seconds through the tempo map
+                tick += event.delta.as_int();
+                let time_sec = tick_to_second(tick, ticks_per_beat, tempo_changes);
 
                 match event.kind {
                     TrackEventKind::Midi { channel: ch, message } => {
@@ -144,12 +331,37 @@ impl MidiData {
                             MidiMessage::ProgramChange { program: prog } => {
                                 program = Some(prog.as_int());
                             }
-                            // Extract pan (CC10)
+                            // Keep every CC, extracting pan (CC10) and pitch bend range (RPN 0, set via CC6)
                             MidiMessage::Controller { controller, value } => {
-                                if controller.as_int() == 10 {
-                                    pan_values.push(value.as_int());
+                                let ch = ch.as_int();
+                                let value = value.as_int();
+                                control_changes.push(ControlChange {
+                                    time_sec,
+                                    channel: ch,
+                                    controller: controller.as_int(),
+                                    value,
+                                });
+                                match controller.as_int() {
+                                    10 => pan_values.push(value),
+                                    101 => rpn.entry(ch).or_insert((127, 127)).0 = value,
+                                    100 => rpn.entry(ch).or_insert((127, 127)).1 = value,
+                                    6 if rpn.get(&ch) == Some(&(0, 0)) => {
+                                        bend_range.insert(ch, value as f64);
+                                    }
+                                    _ => {}
                                 }
                             }
+                            MidiMessage::PitchBend { bend } => {
+                                let range = bend_range
+                                    .get(&ch.as_int())
+                                    .copied()
+                                    .unwrap_or(DEFAULT_BEND_RANGE);
+                                pitch_bends.push(PitchBend {
+                                    time_sec,
+                                    channel: ch.as_int(),
+                                    semitones: bend.as_f64() * range,
+                                });
+                            }
                             _ => {}
                         }
                     }
@@ -168,8 +380,8 @@ impl MidiData {
             }
 
             // For any notes still active (missing NoteOff), set a default duration
-            // Use 10% of a beat as default (short note)
-            let default_duration = (tempo as f64 / 1_000_000.0) * 0.1;
+            // Use 10% of a beat at the opening tempo as default (short note)
+            let default_duration = (tempo_changes[0].micros_per_quarter as f64 / 1_000_000.0) * 0.1;
             for (_, (_, _, event_idx)) in active_notes {
                 if let Some(note_event) = events.get_mut(event_idx) {
                     if note_event.duration_sec.is_none() {
@@ -178,6 +390,9 @@ impl MidiData {
                 }
             }
 
+            // Notes released under the sustain pedal ring on until it comes up
+            apply_sustain_pedal(&mut events, &control_changes);
+
             if !events.is_empty() {
                 // Calculate average pan value if we have any pan messages
                 let pan = if !pan_values.is_empty() {
@@ -195,6 +410,8 @@ impl MidiData {
                         program,
                         name: track_name,
                         pan,
+                        pitch_bends,
+                        control_changes,
                     },
                 );
             }
@@ -204,7 +421,345 @@ impl MidiData {
     }
 }
 
-fn tick_to_second(ticks: u32, ticks_per_beat: u32, tempo: u32) -> f64 {
+/// Extend notes released while the sustain pedal is down until the pedal comes up
+///
+/// As on a piano, striking the same key again cuts the held note off. Notes
+/// under a pedal that is never released keep their own length.
+fn apply_sustain_pedal(events: &mut [NoteEvent], control_changes: &[ControlChange]) {
+    // Spans of time each channel's pedal is down, from press to release
+    let mut down_since: HashMap<u8, f64> = HashMap::new();
+    let mut held_spans: HashMap<u8, Vec<(f64, f64)>> = HashMap::new();
+    for cc in control_changes
+        .iter()
+        .filter(|c| c.controller == SUSTAIN_PEDAL)
+    {
+        if cc.value >= 64 {
+            down_since.entry(cc.channel).or_insert(cc.time_sec);
+        } else if let Some(down) = down_since.remove(&cc.channel) {
+            held_spans
+                .entry(cc.channel)
+                .or_default()
+                .push((down, cc.time_sec));
+        }
+    }
+
+    // Onsets of each key on each channel, to find when a held note is struck again
+    let mut onsets: HashMap<u8, HashMap<String, Vec<f64>>> = HashMap::new();
+    for event in events.iter() {
+        onsets
+            .entry(event.channel)
+            .or_default()
+            .entry(event.note.clone())
+            .or_default()
+            .push(event.time_sec);
+    }
+    for times in onsets.values_mut().flat_map(|keys| keys.values_mut()) {
+        times.sort_by(f64::total_cmp);
+    }
+
+    for event in events.iter_mut() {
+        let Some(duration) = event.duration_sec else {
+            continue;
+        };
+        let release = event.time_sec + duration;
+
+        let Some(spans) = held_spans.get(&event.channel) else {
+            continue;
+        };
+        let Some(&(_, pedal_up)) = spans[..spans.partition_point(|&(down, _)| down <= release)]
+            .last()
+            .filter(|&&(_, up)| up > release)
+        else {
+            continue;
+        };
+
+        let times = &onsets[&event.channel][&event.note];
+        let restrike = times
+            .get(times.partition_point(|&time| time <= event.time_sec))
+            .copied()
+            .unwrap_or(f64::INFINITY);
+        let end = pedal_up.min(restrike).max(release);
+        event.duration_sec = Some(end - event.time_sec);
+    }
+}
+
+/// Convert an absolute tick to seconds using a sorted tempo map
+///
+/// Ticks before the first change (or with no changes) use 120 BPM.
+fn tick_to_second(tick: u32, ticks_per_beat: u32, tempo_changes: &[TempoChange]) -> f64 {
+    let (start_tick, start_sec, tempo) = match tempo_changes.partition_point(|c| c.tick <= tick) {
+        0 => (0, 0.0, 500000),
+        n => {
+            let change = &tempo_changes[n - 1];
+            (change.tick, change.time_sec, change.micros_per_quarter)
+        }
+    };
+
     let seconds_per_tick = (tempo as f64 / 1_000_000.0) / ticks_per_beat as f64;
-    ticks as f64 * seconds_per_tick
+    start_sec + (tick - start_tick) as f64 * seconds_per_tick
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use midly::num::{u15, u24, u28, u4, u7};
+    use midly::{Format, Header, TrackEvent};
+
+    pub(crate) const TICKS_PER_BEAT: u32 = 480;
+
+    /// Build a single-track file with a note on each given beat plus meta
+    /// events (such as tempo or time signature changes) at given beats
+    pub(crate) fn midi_file(note_beats: &[u32], meta: &[(u32, MetaMessage<'static>)]) -> Vec<u8> {
+        let events: Vec<(u32, TrackEventKind)> = meta
+            .iter()
+            .map(|&(beat, message)| (beat * TICKS_PER_BEAT, TrackEventKind::Meta(message)))
+            .collect();
+        midi_file_with_events(note_beats, events)
+    }
+
+    /// Like `midi_file`, but with arbitrary extra events at given ticks
+    pub(crate) fn midi_file_with_events(
+        note_beats: &[u32],
+        events: Vec<(u32, TrackEventKind<'static>)>,
+    ) -> Vec<u8> {
+        let mut timed = events;
+        for &beat in note_beats {
+            let on = MidiMessage::NoteOn {
+                key: u7::new(60),
+                vel: u7::new(100),
+            };
+            let off = MidiMessage::NoteOff {
+                key: u7::new(60),
+                vel: u7::new(0),
+            };
+            timed.push((
+                beat * TICKS_PER_BEAT,
+                TrackEventKind::Midi {
+                    channel: u4::new(0),
+                    message: on,
+                },
+            ));
+            timed.push((
+                beat * TICKS_PER_BEAT + TICKS_PER_BEAT / 2,
+                TrackEventKind::Midi {
+                    channel: u4::new(0),
+                    message: off,
+                },
+            ));
+        }
+        timed.sort_by_key(|&(tick, _)| tick);
+
+        let mut last_tick = 0;
+        let mut track: Vec<TrackEvent> = timed
+            .into_iter()
+            .map(|(tick, kind)| {
+                let delta = u28::new(tick - last_tick);
+                last_tick = tick;
+                TrackEvent { delta, kind }
+            })
+            .collect();
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let smf = Smf {
+            header: Header::new(
+                Format::SingleTrack,
+                Timing::Metrical(u15::new(TICKS_PER_BEAT as u16)),
+            ),
+            tracks: vec![track],
+        };
+        let mut data = Vec::new();
+        smf.write_std(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_tempo_change_moves_later_notes() {
+        // 120 BPM for four beats, then 240 BPM
+        let data = midi_file(
+            &[0, 2, 4, 6, 8],
+            &[
+                (0, MetaMessage::Tempo(u24::new(500000))),
+                (4, MetaMessage::Tempo(u24::new(250000))),
+            ],
+        );
+        let midi = MidiData::from_bytes(&data).unwrap();
+
+        assert_eq!(midi.bpm, 120.0);
+        assert_eq!(midi.tempo_changes.len(), 2);
+        assert_eq!(midi.tempo_changes[1].tick, 4 * TICKS_PER_BEAT);
+        assert_eq!(midi.tempo_changes[1].time_sec, 2.0);
+
+        let events = &midi.track_info[&0].events;
+        let times: Vec<f64> = events.iter().map(|e| e.time_sec).collect();
+        assert_eq!(times, vec![0.0, 1.0, 2.0, 2.5, 3.0]);
+
+        // Durations follow the tempo too: half a beat is 0.25s before, 0.125s after
+        assert_eq!(events[0].duration_sec, Some(0.25));
+        assert_eq!(events[4].duration_sec, Some(0.125));
+    }
+
+    #[test]
+    fn test_key_signature() {
+        let data = midi_file(&[0], &[(0, MetaMessage::KeySignature(-3, true))]);
+        let midi = MidiData::from_bytes(&data).unwrap();
+
+        assert_eq!(midi.key_signature.map(|k| k.scale_name()), Some("C:minor".to_string()));
+
+        let names: Vec<String> = [(0, false), (-3, false), (2, false), (1, true), (6, true)]
+            .iter()
+            .map(|&(sharps, minor)| KeySignature { sharps, minor }.scale_name())
+            .collect();
+        assert_eq!(names, vec!["C:major", "Eb:major", "D:major", "E:minor", "D#:minor"]);
+    }
+
+    #[test]
+    fn test_pitch_bend() {
+        let midi = |message| TrackEventKind::Midi {
+            channel: u4::new(0),
+            message,
+        };
+        let controller = |controller, value| {
+            midi(MidiMessage::Controller {
+                controller: u7::new(controller),
+                value: u7::new(value),
+            })
+        };
+        let bend = |amount| {
+            midi(MidiMessage::PitchBend {
+                bend: midly::PitchBend::from_int(amount),
+            })
+        };
+        let bent = |time_sec, semitones| PitchBend {
+            time_sec,
+            channel: 0,
+            semitones,
+        };
+
+        // Set a 4 semitone bend range, bend halfway up a quarter beat into
+        // the note, then release the bend after another quarter beat
+        let data = midi_file_with_events(
+            &[0],
+            vec![
+                (0, controller(101, 0)),
+                (0, controller(100, 0)),
+                (0, controller(6, 4)),
+                (120, bend(0x1000)),
+                (240, bend(0)),
+            ],
+        );
+        let midi = MidiData::from_bytes(&data).unwrap();
+
+        // 120 BPM: a quarter beat is 0.125s
+        assert_eq!(
+            midi.track_info[&0].pitch_bends,
+            vec![bent(0.125, 2.0), bent(0.25, 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_sustain_pedal_extends_notes() {
+        let midi = |message| TrackEventKind::Midi {
+            channel: u4::new(0),
+            message,
+        };
+        let pedal = |value| {
+            midi(MidiMessage::Controller {
+                controller: u7::new(SUSTAIN_PEDAL),
+                value: u7::new(value),
+            })
+        };
+        let note_on = |key| {
+            midi(MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(100),
+            })
+        };
+        let note_off = |key| {
+            midi(MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0),
+            })
+        };
+
+        // A C major chord held for half a beat under the pedal, which comes
+        // up on beat 2; the note on beat 3 is played without it
+        let beat = TICKS_PER_BEAT;
+        let data = midi_file_with_events(
+            &[3],
+            vec![
+                (0, pedal(127)),
+                (0, note_on(60)),
+                (0, note_on(64)),
+                (0, note_on(67)),
+                (beat / 2, note_off(60)),
+                (beat / 2, note_off(64)),
+                (beat / 2, note_off(67)),
+                (2 * beat, pedal(0)),
+            ],
+        );
+        let midi = MidiData::from_bytes(&data).unwrap();
+
+        // 120 BPM: the chord rings for a second, the last note for a quarter second
+        let durations: Vec<(String, Option<f64>)> = midi.track_info[&0]
+            .events
+            .iter()
+            .map(|e| (e.note.clone(), e.duration_sec))
+            .collect();
+        assert_eq!(
+            durations,
+            vec![
+                ("c4".to_string(), Some(1.0)),
+                ("e4".to_string(), Some(1.0)),
+                ("g4".to_string(), Some(1.0)),
+                ("c4".to_string(), Some(0.25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sustain_pedal_per_channel_presses() {
+        let note = |time_sec, channel| NoteEvent {
+            time_sec,
+            note: "c4".to_string(),
+            velocity: 100,
+            duration_sec: Some(0.5),
+            channel,
+        };
+        let pedal = |time_sec, channel, value| ControlChange {
+            time_sec,
+            channel,
+            controller: SUSTAIN_PEDAL,
+            value,
+        };
+
+        // Channel 0 presses the pedal twice; channel 1 never lifts it
+        let mut events = vec![note(0.0, 0), note(2.0, 0), note(4.0, 0), note(0.0, 1)];
+        let control_changes = [
+            pedal(0.0, 0, 127),
+            pedal(0.0, 1, 127),
+            pedal(1.0, 0, 0),
+            pedal(2.0, 0, 127),
+            pedal(3.0, 0, 0),
+        ];
+        apply_sustain_pedal(&mut events, &control_changes);
+
+        let durations: Vec<Option<f64>> = events.iter().map(|e| e.duration_sec).collect();
+        assert_eq!(durations, vec![Some(1.0), Some(1.0), Some(0.5), Some(0.5)]);
+    }
+
+    #[test]
+    fn test_no_tempo_defaults_to_120_bpm() {
+        let midi = MidiData::from_bytes(&midi_file(&[0, 1], &[])).unwrap();
+
+        assert_eq!(midi.bpm, 120.0);
+        let times: Vec<f64> = midi.track_info[&0]
+            .events
+            .iter()
+            .map(|e| e.time_sec)
+            .collect();
+        assert_eq!(times, vec![0.0, 0.5]);
+    }
 }
@@ -1,15 +1,57 @@
 use crate::ast::{Bar, ModifierValue, Pattern};
 use crate::instruments::get_track_sound;
 use crate::track::ProcessedTrack;
+use strudel_core::random::random_at;
+use strudel_core::scale::note_to_midi;
+use strudel_core::{Chord, Fraction, Scale};
+
+/// Largest humanize nudge either way, in seconds
+const HUMANIZE_AMOUNT: f64 = 0.02;
+
+/// Number of nudge values per cycle, so nearby notes are nudged differently
+const HUMANIZE_STEPS: i64 = 8;
 
 pub struct OutputFormatter {
     tab_size: usize,
     compact: bool,
+    scale: Option<String>,
+    chord_names: bool,
+    humanize: Option<u64>,
 }
 
 impl OutputFormatter {
     pub fn new(tab_size: usize, compact: bool) -> Self {
-        Self { tab_size, compact }
+        Self {
+            tab_size,
+            compact,
+            scale: None,
+            chord_names: false,
+            humanize: None,
+        }
+    }
+
+    /// Write chords in root position by name (e.g. `c3:maj`) instead of as stacked notes
+    ///
+    /// Only applies to note names; scale degree output keeps stacked degrees.
+    pub fn with_chord_names(mut self) -> Self {
+        self.chord_names = true;
+        self
+    }
+
+    /// Loosen the quantized timing with a small random `.nudge` on each track
+    ///
+    /// Only the written code changes; the same seed always gives the same nudges.
+    pub fn with_humanize(mut self, seed: u64) -> Self {
+        self.humanize = Some(seed);
+        self
+    }
+
+    /// Write melodic notes as degrees of a scale (e.g. "C:minor") using `n(...).scale(...)`
+    ///
+    /// Tracks with notes outside the scale keep their note names.
+    pub fn with_scale(mut self, scale: String) -> Self {
+        self.scale = Some(scale);
+        self
     }
 
     /// Build JSON output of the AST
@@ -39,8 +81,11 @@ impl OutputFormatter {
 
     /// Convert ProcessedTrack to Pattern AST
     fn track_to_pattern(&self, track: &ProcessedTrack) -> Pattern {
-        // Convert gain values to ModifierValue
-        let gain = self.format_gain_to_modifier(&track.gains);
+        // Convert gain values to ModifierValue, per note when tracked
+        let gain = match &track.note_gains {
+            Some(note_gains) => Some(ModifierValue::Steps(note_gains.clone())),
+            None => self.format_gain_to_modifier(&track.gains),
+        };
 
         // Convert sustain values to single value
         let sustain = if !track.is_drum {
@@ -63,6 +108,11 @@ impl OutputFormatter {
             gain,
             pan: track.pan,
             sustain,
+            effects: track
+                .effects
+                .iter()
+                .map(|(name, values)| (name.clone(), self.format_effect_to_modifier(values)))
+                .collect(),
         }
     }
 
@@ -131,6 +181,16 @@ impl OutputFormatter {
         Some(ModifierValue::Pattern(section_gains))
     }
 
+    /// Format per-bar effect values - single value if constant, otherwise one value per bar
+    fn format_effect_to_modifier(&self, values: &[f32]) -> ModifierValue {
+        match values.first() {
+            Some(&first) if values.iter().all(|&v| (v - first).abs() < 0.005) => {
+                ModifierValue::Single(first)
+            }
+            _ => ModifierValue::Pattern(values.to_vec()),
+        }
+    }
+
     /// Format sustain values using 75th percentile
     fn format_sustain_to_value(&self, sustains: &[f32]) -> Option<f32> {
         if sustains.is_empty() {
@@ -179,8 +239,38 @@ impl OutputFormatter {
                 eprintln!("Warning: Track {} validation error: {}", idx + 1, e);
             }
 
+            // Switch melodic tracks to scale degrees when they fit the scale
+            let (pattern, scale) = match self.to_scale_degrees(&pattern) {
+                Some((degrees, scale)) => (degrees, Some(scale)),
+                None => {
+                    let has_notes = pattern.bars.iter().any(|bar| !bar.is_silent());
+                    if let (Some(scale), false, true) = (&self.scale, pattern.is_drum, has_notes) {
+                        eprintln!(
+                            "Warning: Track {} has notes outside {}, keeping note names",
+                            idx + 1,
+                            scale
+                        );
+                    }
+                    (pattern, None)
+                }
+            };
+
+            let pattern = if self.chord_names && scale.is_none() && !pattern.is_drum {
+                Pattern {
+                    bars: pattern.bars.iter().map(name_chords).collect(),
+                    ..pattern
+                }
+            } else {
+                pattern
+            };
+
             // Convert pattern to Strudel code using AST
-            let pattern_str = self.format_pattern_with_indent(&pattern);
+            let mut pattern_str = self.format_pattern_with_indent(&pattern, scale.as_deref());
+
+            // Each track gets its own nudges, so they don't drift together
+            if let Some(seed) = self.humanize {
+                pattern_str.push_str(&humanize_nudge(seed.wrapping_add(idx as u64)));
+            }
 
             output.push(format!("$: {}\n", pattern_str));
         }
@@ -188,11 +278,64 @@ impl OutputFormatter {
         output.join("\n")
     }
 
+    /// Rewrite a melodic pattern's notes as degrees of the output scale
+    ///
+    /// The scale is rooted in the octave of the lowest note so degrees stay
+    /// small. Returns the rewritten pattern and the scale name to use, or None
+    /// if there's no scale, the pattern is drums, or a note is outside the scale.
+    fn to_scale_degrees(&self, pattern: &Pattern) -> Option<(Pattern, String)> {
+        if pattern.is_drum {
+            return None;
+        }
+
+        let (root, mode) = self.scale.as_deref()?.split_once(':')?;
+        let root = root.trim_end_matches(|c: char| c.is_ascii_digit() || c == '-');
+        let pitch_class = Scale::parse(&format!("{}:{}", root, mode))?
+            .root
+            .rem_euclid(12);
+
+        let notes: Vec<i32> = pattern
+            .bars
+            .iter()
+            .flat_map(|bar| bar.notes())
+            .map(|note| note_to_midi(&note))
+            .collect::<Option<_>>()?;
+        let lowest = *notes.iter().min()?;
+        let octave = (lowest - (lowest - pitch_class).rem_euclid(12)).div_euclid(12) - 1;
+
+        let scale_name = format!("{}{}:{}", root, octave, mode);
+        let scale = Scale::parse(&scale_name)?;
+        if notes
+            .iter()
+            .any(|&midi| scale.midi_to_degree(midi).is_none())
+        {
+            return None;
+        }
+
+        let to_degree = |note: &str| {
+            let midi = note_to_midi(note).unwrap_or_default();
+            scale.midi_to_degree(midi).unwrap_or_default().to_string()
+        };
+
+        let degrees = Pattern {
+            bars: pattern
+                .bars
+                .iter()
+                .map(|bar| bar.map_notes(&to_degree))
+                .collect(),
+            ..pattern.clone()
+        };
+
+        Some((degrees, scale_name))
+    }
+
     /// Format a pattern with proper indentation for multi-line output
-    fn format_pattern_with_indent(&self, pattern: &Pattern) -> String {
+    ///
+    /// With a scale, the bars hold scale degrees and are written as `n(...).scale(...)`.
+    fn format_pattern_with_indent(&self, pattern: &Pattern, scale: Option<&str>) -> String {
         // Get bars as strings (with compression if compact mode)
         let bars_str: Vec<String> = if self.compact {
-            self.compress_bars(&pattern.bars)
+            self.compress_bars(repeating_period(&pattern.bars))
         } else {
             pattern.bars.iter().map(|b| b.to_strudel()).collect()
         };
@@ -201,6 +344,8 @@ impl OutputFormatter {
         let mut output = Vec::new();
         if pattern.is_drum {
             output.push("s(`<".to_string());
+        } else if scale.is_some() {
+            output.push("n(`<".to_string());
         } else {
             output.push("note(`<".to_string());
         }
@@ -218,6 +363,11 @@ impl OutputFormatter {
         let last_idx = output.len() - 1;
         output[last_idx].push_str(">`)");
 
+        // Scale for degree output
+        if let Some(scale) = scale {
+            output[last_idx].push_str(&format!(".scale(\"{}\")", scale));
+        }
+
         // Add modifiers using Pattern's logic
         // Sound
         if let Some(sound) = &pattern.sound {
@@ -243,6 +393,11 @@ impl OutputFormatter {
             }
         }
 
+        // CC-driven effects
+        for (name, value) in &pattern.effects {
+            output[last_idx].push_str(&format!(".{}({})", name, value.to_strudel()));
+        }
+
         output.join("\n")
     }
 
@@ -280,5 +435,222 @@ impl OutputFormatter {
 
         result
     }
+}
+
+/// Replace chords that have a name with it, e.g. `[c3,e3,g3]` with `c3:maj`
+fn name_chords(bar: &Bar) -> Bar {
+    match bar {
+        Bar::Chord(notes) => {
+            let name = notes
+                .iter()
+                .map(|note| note_to_midi(note))
+                .collect::<Option<Vec<i32>>>()
+                .and_then(|midi| Chord::identify(&midi));
+            match name {
+                Some(name) => Bar::Note(name),
+                None => bar.clone(),
+            }
+        }
+        Bar::Subdivision(bars) => Bar::Subdivision(bars.iter().map(name_chords).collect()),
+        _ => bar.clone(),
+    }
+}
+
+/// The bars of a track that repeats a cycle of up to 4 bars, or all of its bars
+///
+/// Bars play one per cycle and the track loops, so `<A B A B>` and `<A B>`
+/// sound the same. Only whole repeats count: `<A B A>` is not `<A B>`.
+fn repeating_period(bars: &[Bar]) -> &[Bar] {
+    (1..=4)
+        .filter(|&period| bars.len() > period && bars.len().is_multiple_of(period))
+        .find(|&period| bars.chunks(period).all(|chunk| chunk == &bars[..period]))
+        .map_or(bars, |period| &bars[..period])
+}
+
+/// A `.nudge(...)` of random offsets up to `HUMANIZE_AMOUNT` seconds either way
+fn humanize_nudge(seed: u64) -> String {
+    let offsets: Vec<String> = (0..HUMANIZE_STEPS)
+        .map(|step| {
+            let random = random_at(Fraction::new(step, HUMANIZE_STEPS), seed);
+            format!("{:.3}", (random * 2.0 - 1.0) * HUMANIZE_AMOUNT)
+        })
+        .collect();
+    format!(".nudge(\"{}\")", offsets.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A C minor melody: c4 d4 eb4 g4 | [c4,eb4,g4] | - | bb3 c4
+    fn melody() -> ProcessedTrack {
+        let notes = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        ProcessedTrack {
+            bars: vec![
+                Bar::Sequence(notes(&["c4", "d4", "d#4", "g4"])),
+                Bar::Chord(notes(&["c4", "d#4", "g4"])),
+                Bar::Rest,
+                Bar::Subdivision(vec![
+                    Bar::Note("a#3".to_string()),
+                    Bar::Note("c4".to_string()),
+                ]),
+            ],
+            gains: vec![1.0; 4],
+            note_gains: None,
+            sustains: vec![1.0; 4],
+            pan: None,
+            effects: Vec::new(),
+            channel: Some(0),
+            program: Some(0),
+            name: None,
+            is_drum: false,
+        }
+    }
+
+    #[test]
+    fn test_note_name_output() {
+        let output = OutputFormatter::new(2, false).build_output(&[melody()], 120.0);
+
+        assert!(output.contains("note(`<"), "{}", output);
+        assert!(
+            output.contains("[c4 d4 d#4 g4] [c4,d#4,g4] - [a#3 c4]>`)"),
+            "{}",
+            output
+        );
+        assert!(!output.contains(".scale("), "{}", output);
+    }
+
+    #[test]
+    fn test_scale_degree_output() {
+        let output = OutputFormatter::new(2, false)
+            .with_scale("C:minor".to_string())
+            .build_output(&[melody()], 120.0);
+
+        // Rooted at C3, the octave of the lowest note (bb3)
+        assert!(output.contains("n(`<"), "{}", output);
+        assert!(
+            output.contains("[7 8 9 11] [7,9,11] - [6 7]>`).scale(\"C3:minor\")"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_chord_names() {
+        let formatter = OutputFormatter::new(2, false).with_chord_names();
+        let output = formatter.build_output(&[melody()], 120.0);
+
+        // The C minor triad is named; the run of notes is left alone
+        assert!(
+            output.contains("[c4 d4 d#4 g4] c4:min - [a#3 c4]>`)"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_chord_names_play_their_notes() {
+        use strudel_core::{State, TimeSpan, Value};
+
+        let chords: [&[&str]; 4] = [
+            &["g3", "b3", "d4", "f4"],
+            &["c3", "e3", "g3", "a3"],
+            &["c3", "e3", "g3", "a#3", "d4"],
+            &["d3", "f3", "a3", "c4"],
+        ];
+        for chord in chords {
+            let bar = Bar::Chord(chord.iter().map(|n| n.to_string()).collect());
+            let Bar::Note(name) = name_chords(&bar) else {
+                panic!("{:?} was not named", chord);
+            };
+
+            // The name evaluates back to the chord's notes in mini notation
+            let pattern = strudel_mini::evaluate(&strudel_mini::parse(&name).unwrap()).unwrap();
+            let cycle = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            let mut played: Vec<i32> = pattern
+                .query(cycle)
+                .into_iter()
+                .map(|hap| match hap.value {
+                    Value::String(note) => note_to_midi(&note).unwrap(),
+                    other => panic!("{} played {:?}", name, other),
+                })
+                .collect();
+            played.sort_unstable();
+            let expected: Vec<i32> = chord.iter().map(|n| note_to_midi(n).unwrap()).collect();
+            assert_eq!(played, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_humanize() {
+        let output = |seed: Option<u64>| {
+            let formatter = OutputFormatter::new(2, false);
+            let formatter = match seed {
+                Some(seed) => formatter.with_humanize(seed),
+                None => formatter,
+            };
+            formatter.build_output(&[melody()], 120.0)
+        };
+        let plain = output(None);
+        let humanized = output(Some(7));
+
+        // The nudge follows the rest of the track unchanged
+        let (body, nudge) = humanized.split_once(".nudge(\"").unwrap();
+        assert_eq!(plain.trim_end(), body.trim_end());
+        let offsets: Vec<f64> = nudge
+            .trim_end()
+            .trim_end_matches("\")")
+            .split(' ')
+            .map(|offset| offset.parse().unwrap())
+            .collect();
+        assert_eq!(offsets.len(), HUMANIZE_STEPS as usize);
+        assert!(offsets.iter().all(|offset| offset.abs() <= HUMANIZE_AMOUNT));
+
+        // Same seed, same nudges
+        assert_eq!(humanized, output(Some(7)));
+        assert_ne!(humanized, output(Some(8)));
+    }
+
+    #[test]
+    fn test_compact_alternating_bars() {
+        let track = |names: &[&str]| ProcessedTrack {
+            bars: names.iter().map(|n| Bar::Note(n.to_string())).collect(),
+            gains: vec![1.0; names.len()],
+            note_gains: None,
+            sustains: vec![1.0; names.len()],
+            ..melody()
+        };
+        let output_of = |names: &[&str], compact: bool| {
+            let formatter = OutputFormatter::new(2, compact);
+            formatter.build_output(&[track(names)], 120.0)
+        };
+        let compact = |names: &[&str]| output_of(names, true);
+
+        let output = compact(&["c4", "e4", "c4", "e4"]);
+        assert!(output.contains("note(`<\n    c4 e4>`)"), "{}", output);
+
+        let output = compact(&["c4", "e4", "g4", "c4", "e4", "g4"]);
+        assert!(output.contains("note(`<\n    c4 e4 g4>`)"), "{}", output);
+
+        // A partial repeat would change what plays
+        let output = compact(&["c4", "e4", "c4"]);
+        assert!(output.contains("note(`<\n    c4 e4 c4>`)"), "{}", output);
 
+        // Without --compact every bar is written out
+        let output = output_of(&["c4", "e4", "c4", "e4"], false);
+        assert!(output.contains("note(`<\n    c4 e4 c4 e4>`)"), "{}", output);
+    }
+
+    #[test]
+    fn test_notes_outside_scale_keep_names() {
+        let mut track = melody();
+        track.bars.push(Bar::Note("e4".to_string()));
+
+        let output = OutputFormatter::new(2, false)
+            .with_scale("C:minor".to_string())
+            .build_output(&[track], 120.0);
+
+        assert!(output.contains("note(`<"), "{}", output);
+        assert!(!output.contains(".scale("), "{}", output);
+    }
 }
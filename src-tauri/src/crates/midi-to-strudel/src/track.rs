@@ -1,15 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::ast::Bar;
-use crate::drums::{gm_drum_to_sample, note_name_to_midi_num};
-use crate::midi::{NoteEvent, TrackInfo};
+use crate::drums::{note_name_to_midi_num, DrumMap};
+use crate::midi::{ControlChange, NoteEvent, TimeSignature, TrackInfo};
+
+/// Tolerance (in seconds) when matching time signature changes to downbeats
+const DOWNBEAT_EPSILON: f64 = 1e-6;
 
 #[derive(Debug, Clone)]
 pub struct ProcessedTrack {
     pub bars: Vec<Bar>,
     pub gains: Vec<f32>,  // Gain value for each bar (0.0 to 1.0)
+    pub note_gains: Option<Vec<Vec<f32>>>,  // Per-note gains of each bar, in equal steps (see `with_note_gains`)
     pub sustains: Vec<f32>,  // Sustain value for each bar (relative to cycle_len)
     pub pan: Option<f32>,  // Pan value (0.0=left, 0.5=center, 1.0=right)
+    pub effects: Vec<(String, Vec<f32>)>,  // Per-bar values of CC-driven effects, e.g. ("lpf", ...)
     #[allow(dead_code)]
     pub channel: Option<u8>,
     pub program: Option<u8>,
@@ -17,6 +22,75 @@ pub struct ProcessedTrack {
     pub is_drum: bool,
 }
 
+/// Maps one MIDI controller onto a Strudel effect
+#[derive(Debug, Clone, PartialEq)]
+pub struct CcMapping {
+    pub controller: u8,
+    pub effect: String,  // Strudel function name, e.g. "lpf"
+    pub min: f32,  // Effect value at CC 0
+    pub max: f32,  // Effect value at CC 127
+    pub exponential: bool,  // Interpolate exponentially (for frequencies) instead of linearly
+}
+
+impl CcMapping {
+    pub fn new(controller: u8, effect: &str, min: f32, max: f32) -> Self {
+        Self {
+            controller,
+            effect: effect.to_string(),
+            min,
+            max,
+            exponential: false,
+        }
+    }
+
+    /// Interpolate exponentially between `min` and `max`, which must both be positive
+    pub fn exponential(mut self) -> Self {
+        self.exponential = true;
+        self
+    }
+
+    /// Effect value for a controller level between 0.0 and 1.0
+    fn scale(&self, level: f32) -> f32 {
+        if self.exponential {
+            self.min * (self.max / self.min).powf(level)
+        } else {
+            self.min + (self.max - self.min) * level
+        }
+    }
+}
+
+/// Which Control Change messages are written as which effects
+///
+/// A `gain` mapping scales each bar's velocity gain and a `pan` mapping
+/// replaces the track's pan; any other effect gets its own modifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CcEffectMap {
+    pub mappings: Vec<CcMapping>,
+}
+
+impl CcEffectMap {
+    /// Drop all CC automation
+    pub fn empty() -> Self {
+        Self {
+            mappings: Vec::new(),
+        }
+    }
+}
+
+impl Default for CcEffectMap {
+    /// CC74 (brightness) → lpf, CC91 (reverb send) → room, CC7 (volume) → gain, CC10 → pan
+    fn default() -> Self {
+        Self {
+            mappings: vec![
+                CcMapping::new(74, "lpf", 100.0, 20000.0).exponential(),
+                CcMapping::new(91, "room", 0.0, 1.0),
+                CcMapping::new(7, "gain", 0.0, 1.0),
+                CcMapping::new(10, "pan", 0.0, 1.0),
+            ],
+        }
+    }
+}
+
 pub struct TrackBuilder {
     cycle_len: f64,
     bar_limit: usize,
@@ -24,6 +98,10 @@ pub struct TrackBuilder {
     notes_per_bar: usize,
     detect_drum_names: bool,
     forced_drum_channels: Vec<u8>,
+    time_signatures: Vec<TimeSignature>,
+    cc_effects: CcEffectMap,
+    drum_map: DrumMap,
+    note_gains: bool,
 }
 
 impl TrackBuilder {
@@ -42,9 +120,42 @@ impl TrackBuilder {
             notes_per_bar,
             detect_drum_names,
             forced_drum_channels,
+            time_signatures: Vec::new(),
+            cc_effects: CcEffectMap::empty(),
+            drum_map: DrumMap::new(),
+            note_gains: false,
         }
     }
 
+    /// Use the file's time signature changes for bar lengths instead of 4/4 throughout
+    pub fn with_time_signatures(mut self, time_signatures: Vec<TimeSignature>) -> Self {
+        self.time_signatures = time_signatures;
+        self
+    }
+
+    /// Write Control Change automation as effects, using this table of mappings
+    ///
+    /// Off by default; `CcEffectMap::default()` covers volume, pan, filter
+    /// and reverb.
+    pub fn with_cc_effects(mut self, cc_effects: CcEffectMap) -> Self {
+        self.cc_effects = cc_effects;
+        self
+    }
+
+    /// Use a different drum note → sample mapping than General MIDI
+    pub fn with_drum_map(mut self, drum_map: DrumMap) -> Self {
+        self.drum_map = drum_map;
+        self
+    }
+
+    /// Track the gain of each note as well as each bar's average
+    ///
+    /// Keeps dynamics within a bar, like a crescendo, that the average loses.
+    pub fn with_note_gains(mut self) -> Self {
+        self.note_gains = true;
+        self
+    }
+
     pub fn build_tracks(&self, track_info: &HashMap<usize, TrackInfo>) -> Vec<ProcessedTrack> {
         let mut tracks = Vec::new();
 
@@ -54,12 +165,17 @@ impl TrackBuilder {
 
         for &track_idx in track_indices {
             let info = &track_info[&track_idx];
-            let adjusted = self.adjust_near_cycle_end(&info.events);
+            let max_time = info.events.iter().map(|e| e.time_sec).fold(0.0, f64::max);
+            let adjusted = self.adjust_near_bar_end(&info.events, &self.bar_grid(max_time));
 
             if adjusted.is_empty() {
                 continue;
             }
 
+            // Adjusting can push the last notes onto the next downbeat
+            let max_time = adjusted.iter().map(|e| e.time_sec).fold(0.0, f64::max);
+            let grid = self.bar_grid(max_time);
+
             // Group events by channel (since a single MIDI track can have multiple channels)
             let mut events_by_channel: HashMap<u8, Vec<NoteEvent>> = HashMap::new();
             for event in adjusted {
@@ -80,7 +196,7 @@ impl TrackBuilder {
                         }));
 
                 let max_time = channel_events.iter().map(|e| e.time_sec).fold(0.0, f64::max);
-                let num_cycles = ((max_time / self.cycle_len) as usize + 1).min(
+                let num_cycles = grid.iter().filter(|&&(start, _)| start <= max_time).count().min(
                     if self.bar_limit > 0 {
                         self.bar_limit
                     } else {
@@ -91,10 +207,10 @@ impl TrackBuilder {
                 let mut bars = Vec::new();
                 let mut gains = Vec::new();
                 let mut sustains = Vec::new();
+                let mut note_gains = Vec::new();
 
-                for cycle in 0..num_cycles {
-                    let start = cycle as f64 * self.cycle_len;
-                    let end = start + self.cycle_len;
+                for &(start, bar_len) in &grid[..num_cycles] {
+                    let end = start + bar_len;
 
                     let notes_in_cycle: Vec<_> = channel_events
                         .iter()
@@ -106,56 +222,83 @@ impl TrackBuilder {
                     bars.push(Bar::Rest); // Use - for rests (same as Python version)
                     gains.push(0.0); // No gain for empty bars
                     sustains.push(0.0); // No sustain for empty bars
+                    note_gains.push(Vec::new());
                     continue;
                 }
 
                 // Calculate average gain from velocities with musical scaling
-                // Use logarithmic scaling for more perceptual accuracy
                 let avg_velocity: f32 = notes_in_cycle.iter().map(|e| e.velocity as f32).sum::<f32>()
                     / notes_in_cycle.len() as f32;
-
-                // Logarithmic velocity curve (more perceptually accurate):
-                // velocity 32 (pp) → 0.25 gain
-                // velocity 64 (mf) → 0.50 gain
-                // velocity 96 (f)  → 0.75 gain
-                // velocity 127(ff) → 1.0 gain
-                // Allow values > 1.0 for very loud notes (boosting)
-                let base_gain = (avg_velocity / 127.0).powf(0.5).max(0.15);
-
-                // Reduce drum gain by 40% since drum samples are naturally louder
-                let gain = if is_drum { base_gain * 0.6 } else { base_gain };
+                let gain = velocity_gain(avg_velocity, is_drum);
 
                 // Calculate sustain from note durations
                 // Use maximum duration to preserve sustained notes (not average, which gets
                 // pulled down by short articulation notes like grace notes or ornaments)
-                // Normalize to cycle_len (1.0 = full cycle duration)
+                // Normalize to the bar length (1.0 = full cycle duration)
                 let max_duration: f32 = notes_in_cycle
                     .iter()
                     .filter_map(|e| e.duration_sec.map(|d| d as f32))
                     .fold(0.0, |a, b| a.max(b));
 
                 // Normalize to cycle length and clamp to reasonable range
-                let sustain = (max_duration / self.cycle_len as f32).clamp(0.01, 2.0);
+                let sustain = (max_duration / bar_len as f32).clamp(0.01, 2.0);
 
                 let bar = if is_drum {
                     // Convert drum notes to samples with proper timing
-                    self.get_drum_bar(&notes_in_cycle, start)
+                    self.get_drum_bar(&notes_in_cycle, start, bar_len)
                 } else if self.flat_sequences {
                     self.get_flat_mode_bar(&notes_in_cycle)
                 } else {
-                    self.get_poly_mode_bar(&notes_in_cycle, start)
+                    self.get_poly_mode_bar(&notes_in_cycle, start, bar_len)
                 };
 
                     bars.push(bar);
                     gains.push(gain);
                     sustains.push(sustain);
+                    if self.note_gains {
+                        let steps = self.get_note_gains(&notes_in_cycle, start, bar_len, is_drum);
+                        note_gains.push(steps);
+                    }
                 }
 
                 if !bars.is_empty() {
                     // Convert MIDI pan (0-127) to Strudel pan (0.0-1.0)
                     // MIDI: 0=left, 64=center, 127=right
                     // Strudel: 0.0=left, 0.5=center, 1.0=right
-                    let pan = info.pan.map(|midi_pan| midi_pan as f32 / 127.0);
+                    let mut pan = info.pan.map(|midi_pan| midi_pan as f32 / 127.0);
+
+                    // Map this channel's CC automation to per-bar effect values
+                    let mut effects = Vec::new();
+                    for mapping in &self.cc_effects.mappings {
+                        let changes: Vec<&ControlChange> = info
+                            .control_changes
+                            .iter()
+                            .filter(|c| c.channel == channel && c.controller == mapping.controller)
+                            .collect();
+                        let Some(levels) = cc_levels(&changes, &grid[..num_cycles]) else {
+                            continue;
+                        };
+                        let values: Vec<f32> = levels.iter().map(|&l| mapping.scale(l)).collect();
+
+                        match mapping.effect.as_str() {
+                            "gain" => {
+                                for (gain, value) in gains.iter_mut().zip(&values) {
+                                    *gain *= value;
+                                }
+                                for (bar, value) in note_gains.iter_mut().zip(&values) {
+                                    bar.iter_mut().for_each(|gain| *gain *= value);
+                                }
+                            }
+                            "pan" if values.iter().all(|&v| v == values[0]) => {
+                                pan = Some(values[0])
+                            }
+                            "pan" => {
+                                pan = None;
+                                effects.push(("pan".to_string(), values));
+                            }
+                            _ => effects.push((mapping.effect.clone(), values)),
+                        }
+                    }
 
                     // Use channel-specific name if available
                     let track_name = info.name.clone().map(|name| {
@@ -170,8 +313,10 @@ impl TrackBuilder {
                     tracks.push(ProcessedTrack {
                         bars,
                         gains,
+                        note_gains: self.note_gains.then_some(note_gains),
                         sustains,
                         pan,
+                        effects,
                         channel: Some(channel),
                         program: info.program,
                         name: track_name,
@@ -184,14 +329,59 @@ impl TrackBuilder {
         tracks
     }
 
-    fn adjust_near_cycle_end(&self, events: &[NoteEvent]) -> Vec<NoteEvent> {
+    /// Start time and length (in seconds) of each bar, up to the one containing `until`
+    ///
+    /// Bars are `cycle_len` long (4/4) until the first time signature change.
+    /// A change always starts a new bar, so if one falls mid-bar that bar is
+    /// cut short and the new meter still begins on a downbeat.
+    fn bar_grid(&self, until: f64) -> Vec<(f64, f64)> {
+        let mut grid = Vec::new();
+        let mut changes = self.time_signatures.iter().peekable();
+        let mut bar_len = self.cycle_len;
+        let mut section_start = 0.0;
+        let mut bars_in_section = 0;
+
+        loop {
+            let start = section_start + bars_in_section as f64 * bar_len;
+            if start > until {
+                break;
+            }
+
+            // Apply changes landing on this downbeat
+            while let Some(sig) = changes.next_if(|sig| sig.time_sec <= start + DOWNBEAT_EPSILON) {
+                bar_len = self.cycle_len * sig.numerator as f64 / sig.denominator as f64;
+                section_start = start;
+                bars_in_section = 0;
+            }
+
+            match changes.peek() {
+                Some(sig) if sig.time_sec < start + bar_len - DOWNBEAT_EPSILON => {
+                    grid.push((start, sig.time_sec - start));
+                    section_start = sig.time_sec;
+                    bars_in_section = 0;
+                }
+                _ => {
+                    grid.push((start, bar_len));
+                    bars_in_section += 1;
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn adjust_near_bar_end(&self, events: &[NoteEvent], grid: &[(f64, f64)]) -> Vec<NoteEvent> {
         events
             .iter()
             .map(|event| {
-                let rel = (event.time_sec % self.cycle_len) / self.cycle_len;
+                let bar = grid
+                    .partition_point(|&(start, _)| start <= event.time_sec)
+                    .saturating_sub(1);
+                let (start, bar_len) = grid[bar];
+                let rel = (event.time_sec - start) / bar_len;
                 if rel > 0.95 {
                     NoteEvent {
-                        time_sec: (event.time_sec / self.cycle_len).ceil() * self.cycle_len,
+                        time_sec: start + bar_len,
                         note: event.note.clone(),
                         velocity: event.velocity,
                         duration_sec: event.duration_sec,
@@ -204,14 +394,31 @@ impl TrackBuilder {
             .collect()
     }
 
-    fn get_drum_bar(&self, events: &[NoteEvent], start: f64) -> Bar {
+    /// Get the drum sample for a note name, falling back to `perc:<note>`
+    fn drum_sample(&self, note: &str) -> Option<String> {
+        let midi_num = note_name_to_midi_num(note)?;
+        Some(match self.drum_map.sample(midi_num) {
+            Some(s) => s.to_string(),
+            None => format!("perc:{}", note),
+        })
+    }
+
+    fn get_drum_bar(&self, events: &[NoteEvent], start: f64, bar_len: f64) -> Bar {
+        let onsets: Vec<(f64, String)> = events
+            .iter()
+            .filter_map(|e| Some(((e.time_sec - start) / bar_len, self.drum_sample(&e.note)?)))
+            .collect();
+        if let Some(bar) = self.get_triplet_bar(&onsets, self.beats_in_bar(bar_len)) {
+            return bar;
+        }
+
         // Use subdivision logic like melodic tracks for proper timing
         let mut subdivisions = vec![Bar::Rest; self.notes_per_bar];
         let mut time_groups: std::collections::HashMap<usize, Vec<String>> = std::collections::HashMap::new();
 
         for event in events {
             // Quantize the event time to a subdivision index
-            let rel_time = self.quantize_time(event.time_sec, start);
+            let rel_time = self.quantize_time(event.time_sec, start, bar_len);
             let idx = (rel_time * self.notes_per_bar as f64).round() as usize;
 
             if idx >= self.notes_per_bar {
@@ -219,13 +426,7 @@ impl TrackBuilder {
             }
 
             // Convert note to drum sample
-            let sample = if let Some(midi_num) = note_name_to_midi_num(&event.note) {
-                if let Some(s) = gm_drum_to_sample(midi_num) {
-                    s.to_string()
-                } else {
-                    format!("perc:{}", event.note)
-                }
-            } else {
+            let Some(sample) = self.drum_sample(&event.note) else {
                 continue;
             };
 
@@ -273,12 +474,20 @@ impl TrackBuilder {
         }
     }
 
-    fn get_poly_mode_bar(&self, events: &[NoteEvent], cycle_start: f64) -> Bar {
+    fn get_poly_mode_bar(&self, events: &[NoteEvent], cycle_start: f64, bar_len: f64) -> Bar {
+        let onsets: Vec<(f64, String)> = events
+            .iter()
+            .map(|e| ((e.time_sec - cycle_start) / bar_len, e.note.clone()))
+            .collect();
+        if let Some(bar) = self.get_triplet_bar(&onsets, self.beats_in_bar(bar_len)) {
+            return bar;
+        }
+
         // Group notes by their quantized time position
         let mut time_groups: HashMap<usize, Vec<String>> = HashMap::new();
 
         for event in events {
-            let pos = self.quantize_time(event.time_sec, cycle_start);
+            let pos = self.quantize_time(event.time_sec, cycle_start, bar_len);
             let idx = (pos * self.notes_per_bar as f64).round() as usize;
 
             // Check if we should merge with an existing time group (within threshold)
@@ -305,11 +514,7 @@ impl TrackBuilder {
 
         for (idx, notes) in time_groups {
             if idx < self.notes_per_bar {
-                subdivisions[idx] = if notes.len() == 1 {
-                    Bar::Note(notes[0].clone())
-                } else {
-                    Bar::Chord(notes)
-                };
+                subdivisions[idx] = stacked_step(notes);
             }
         }
 
@@ -328,8 +533,113 @@ impl TrackBuilder {
         }
     }
 
-    fn quantize_time(&self, timestamp: f64, cycle_start: f64) -> f64 {
-        let rel_time = (timestamp - cycle_start) / self.cycle_len;
+    /// Number of quarter-note beats in a bar of `bar_len` seconds
+    fn beats_in_bar(&self, bar_len: f64) -> usize {
+        ((bar_len / self.cycle_len * 4.0).round() as usize).max(1)
+    }
+
+    /// Lay the bar out beat by beat if any beat holds a triplet
+    ///
+    /// Onsets are (position in the bar from 0.0 to 1.0, note) pairs. Each beat
+    /// gets its share of `notes_per_bar` as a binary grid, unless its onsets sit
+    /// clearly closer to thirds of the beat; then it becomes a three-note group
+    /// like `[a b c]` instead of being smeared onto the binary grid. Returns
+    /// None when every beat is binary, so straight rhythms keep the bar grid.
+    fn get_triplet_bar(&self, onsets: &[(f64, String)], beats: usize) -> Option<Bar> {
+        let binary = (self.notes_per_bar / beats).max(1);
+
+        // Notes played just ahead of a beat belong to that beat
+        let mut beat_onsets: Vec<Vec<(f64, &str)>> = vec![Vec::new(); beats];
+        for (pos, note) in onsets {
+            let rel = pos * beats as f64;
+            let beat = ((rel + 0.5 / binary as f64).floor().max(0.0) as usize).min(beats - 1);
+            beat_onsets[beat].push((rel - beat as f64, note));
+        }
+
+        // Worst distance (in beats) from an onset to a grid of `n` per beat
+        let grid_error = |onsets: &[(f64, &str)], n: usize| {
+            onsets
+                .iter()
+                .map(|(pos, _)| (pos * n as f64 - (pos * n as f64).round()).abs() / n as f64)
+                .fold(0.0, f64::max)
+        };
+        let triplets: Vec<bool> = beat_onsets
+            .iter()
+            .map(|onsets| grid_error(onsets, 3) * 2.0 < grid_error(onsets, binary))
+            .collect();
+
+        if !triplets.contains(&true) {
+            return None;
+        }
+
+        let beat_bars = beat_onsets
+            .iter()
+            .zip(triplets)
+            .map(|(onsets, triplet)| {
+                let slots = if triplet { 3 } else { binary };
+                let mut groups: Vec<Vec<String>> = vec![Vec::new(); slots];
+                for (pos, note) in onsets {
+                    let idx = ((pos * slots as f64).round().max(0.0) as usize).min(slots - 1);
+                    groups[idx].push(note.to_string());
+                }
+
+                let subdivisions: Vec<Bar> = groups.into_iter().map(stacked_step).collect();
+                let simplified = self.simplify_subdivisions(&subdivisions);
+
+                if simplified.len() == 1 {
+                    simplified[0].clone()
+                } else {
+                    Bar::Subdivision(simplified)
+                }
+            })
+            .collect();
+
+        Some(Bar::Subdivision(beat_bars))
+    }
+
+    /// Gain of each note in a bar, held until the next note, in equal steps
+    ///
+    /// Each of the `notes_per_bar` steps takes the gain of the latest note to
+    /// start by then (notes starting together share their average). Steps are
+    /// merged in pairs while that loses nothing, so a soft note then a loud one
+    /// over the bar gives just two values.
+    fn get_note_gains(
+        &self,
+        notes: &[NoteEvent],
+        start: f64,
+        bar_len: f64,
+        is_drum: bool,
+    ) -> Vec<f32> {
+        let steps = self.notes_per_bar.max(1);
+        let mut onsets: Vec<Vec<f32>> = vec![Vec::new(); steps];
+        for note in notes {
+            let pos = self.quantize_time(note.time_sec, start, bar_len);
+            let step = ((pos * steps as f64).round() as usize).min(steps - 1);
+            onsets[step].push(note.velocity as f32);
+        }
+
+        // Steps before the first note take its gain
+        let mut gains = Vec::with_capacity(steps);
+        let mut current = None;
+        for velocities in &onsets {
+            if !velocities.is_empty() {
+                let avg_velocity = velocities.iter().sum::<f32>() / velocities.len() as f32;
+                // Rounded as written, so steps that print the same merge
+                current = Some((velocity_gain(avg_velocity, is_drum) * 100.0).round() / 100.0);
+            }
+            gains.push(current);
+        }
+        let first = gains.iter().flatten().next().copied().unwrap_or(0.0);
+        let mut gains: Vec<f32> = gains.into_iter().map(|g| g.unwrap_or(first)).collect();
+
+        while gains.len().is_multiple_of(2) && gains.chunks(2).all(|pair| pair[0] == pair[1]) {
+            gains = gains.into_iter().step_by(2).collect();
+        }
+        gains
+    }
+
+    fn quantize_time(&self, timestamp: f64, cycle_start: f64, bar_len: f64) -> f64 {
+        let rel_time = (timestamp - cycle_start) / bar_len;
         let quantized = (rel_time * self.notes_per_bar as f64).round() / self.notes_per_bar as f64;
         quantized.min(1.0 - 1e-9)
     }
@@ -386,3 +696,319 @@ impl TrackBuilder {
         current
     }
 }
+
+/// Gain for a MIDI velocity, with musical scaling
+fn velocity_gain(velocity: f32, is_drum: bool) -> f32 {
+    // Logarithmic velocity curve (more perceptually accurate):
+    // velocity 32 (pp) → 0.25 gain
+    // velocity 64 (mf) → 0.50 gain
+    // velocity 96 (f)  → 0.75 gain
+    // velocity 127(ff) → 1.0 gain
+    // Allow values > 1.0 for very loud notes (boosting)
+    let base_gain = (velocity / 127.0).powf(0.5).max(0.15);
+
+    // Reduce drum gain by 40% since drum samples are naturally louder
+    if is_drum {
+        base_gain * 0.6
+    } else {
+        base_gain
+    }
+}
+
+/// One step of notes that start together: a rest, a note, or a chord
+///
+/// Chord notes are stacked from the lowest up, each written once. Drum
+/// samples have no pitch and keep their order.
+fn stacked_step(mut notes: Vec<String>) -> Bar {
+    notes.sort_by_key(|note| note_name_to_midi_num(note));
+    let mut seen = HashSet::new();
+    notes.retain(|note| seen.insert(note.clone()));
+
+    match notes.len() {
+        0 => Bar::Rest,
+        1 => Bar::Note(notes.remove(0)),
+        _ => Bar::Chord(notes),
+    }
+}
+
+/// Time-weighted average level (0.0 to 1.0) of one controller over each bar
+///
+/// A controller holds its value until the next change, and bars before the
+/// first change take its value. Returns None if there are no changes.
+fn cc_levels(changes: &[&ControlChange], grid: &[(f64, f64)]) -> Option<Vec<f32>> {
+    let first = changes.first()?;
+
+    let levels = grid
+        .iter()
+        .map(|&(start, bar_len)| {
+            let end = start + bar_len;
+            let mut value = changes
+                .iter()
+                .take_while(|c| c.time_sec <= start)
+                .last()
+                .unwrap_or(first)
+                .value as f64;
+            let mut time = start;
+            let mut total = 0.0;
+
+            for change in changes
+                .iter()
+                .filter(|c| c.time_sec > start && c.time_sec < end)
+            {
+                total += value * (change.time_sec - time);
+                value = change.value as f64;
+                time = change.time_sec;
+            }
+            total += value * (end - time);
+
+            (total / bar_len / 127.0) as f32
+        })
+        .collect();
+
+    Some(levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ModifierValue;
+    use crate::midi::tests::{midi_file, midi_file_with_events, TICKS_PER_BEAT};
+    use crate::midi::MidiData;
+    use crate::output::OutputFormatter;
+    use midly::num::{u4, u7};
+    use midly::{MetaMessage, MidiMessage, TrackEventKind};
+
+    /// A 4/4 or 3/4 (etc.) time signature meta event
+    fn time_signature(numerator: u8) -> MetaMessage<'static> {
+        MetaMessage::TimeSignature(numerator, 2, 24, 8)
+    }
+
+    fn builder(midi: &MidiData) -> TrackBuilder {
+        TrackBuilder::new(midi.cycle_len, 0, false, 16, false, Vec::new())
+            .with_time_signatures(midi.time_signatures.clone())
+    }
+
+    #[test]
+    fn test_time_signature_change_shifts_bars() {
+        // One bar of 4/4, then 3/4 with a note on each downbeat
+        let data = midi_file(
+            &[0, 4, 7, 10],
+            &[(0, time_signature(4)), (4, time_signature(3))],
+        );
+        let midi = MidiData::from_bytes(&data).unwrap();
+
+        let signatures: Vec<(u32, u8, u8)> = midi
+            .time_signatures
+            .iter()
+            .map(|sig| (sig.tick, sig.numerator, sig.denominator))
+            .collect();
+        assert_eq!(signatures, vec![(0, 4, 4), (4 * TICKS_PER_BEAT, 3, 4)]);
+
+        // 120 BPM: 4/4 bars are 2s, 3/4 bars are 1.5s
+        let grid = builder(&midi).bar_grid(5.0);
+        assert_eq!(grid, vec![(0.0, 2.0), (2.0, 1.5), (3.5, 1.5), (5.0, 1.5)]);
+
+        // Every downbeat note starts its own bar
+        let tracks = builder(&midi).build_tracks(&midi.track_info);
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].bars.len(), 4);
+        assert!(
+            tracks[0]
+                .bars
+                .iter()
+                .all(|bar| *bar == Bar::Note("c4".to_string())),
+            "{:?}",
+            tracks[0].bars
+        );
+    }
+
+    #[test]
+    fn test_mid_bar_change_starts_new_bar() {
+        // 3/4 announced two beats into the first 4/4 bar
+        let data = midi_file(&[0, 2, 5], &[(2, time_signature(3))]);
+        let midi = MidiData::from_bytes(&data).unwrap();
+
+        let grid = builder(&midi).bar_grid(2.5);
+        assert_eq!(grid, vec![(0.0, 1.0), (1.0, 1.5), (2.5, 1.5)]);
+    }
+
+    #[test]
+    fn test_eighth_note_triplets() {
+        let note = |time_sec: f64, note: &str| NoteEvent {
+            time_sec,
+            note: note.to_string(),
+            velocity: 100,
+            duration_sec: Some(0.1),
+            channel: 0,
+        };
+        let triplet = || {
+            Bar::Subdivision(vec![
+                Bar::Note("c4".to_string()),
+                Bar::Note("e4".to_string()),
+                Bar::Note("g4".to_string()),
+            ])
+        };
+
+        // 120 BPM 4/4: half-second beats, triplets on beats 1 and 3
+        let events = vec![
+            note(0.0, "c4"),
+            note(1.0 / 6.0, "e4"),
+            note(2.0 / 6.0, "g4"),
+            note(0.5, "c5"),
+            note(1.0, "c4"),
+            note(1.0 + 1.0 / 6.0, "e4"),
+            note(1.0 + 2.0 / 6.0, "g4"),
+            note(1.5, "c5"),
+        ];
+        let builder = TrackBuilder::new(2.0, 0, false, 16, false, Vec::new());
+        assert_eq!(
+            builder.get_poly_mode_bar(&events, 0.0, 2.0),
+            Bar::Subdivision(vec![
+                triplet(),
+                Bar::Note("c5".to_string()),
+                triplet(),
+                Bar::Note("c5".to_string()),
+            ])
+        );
+
+        // Straight eighths stay on the regular grid
+        let events: Vec<_> = (0..8).map(|i| note(i as f64 * 0.25, "c4")).collect();
+        assert_eq!(
+            builder.get_poly_mode_bar(&events, 0.0, 2.0),
+            Bar::Subdivision(vec![Bar::Note("c4".to_string()); 8])
+        );
+    }
+
+    #[test]
+    fn test_simultaneous_notes_stack() {
+        let note = |time_sec: f64, note: &str| NoteEvent {
+            time_sec,
+            note: note.to_string(),
+            velocity: 100,
+            duration_sec: Some(0.5),
+            channel: 0,
+        };
+        let chord = |notes: &[&str]| Bar::Chord(notes.iter().map(|n| n.to_string()).collect());
+
+        // A C major triad struck out of order, then a doubled G and a lone C
+        let events = vec![
+            note(0.0, "g3"),
+            note(0.0, "c3"),
+            note(0.01, "e3"),
+            note(1.0, "g3"),
+            note(1.0, "g3"),
+            note(1.5, "c4"),
+        ];
+        let builder = TrackBuilder::new(2.0, 0, false, 4, false, Vec::new());
+        assert_eq!(
+            builder.get_poly_mode_bar(&events, 0.0, 2.0),
+            Bar::Subdivision(vec![
+                chord(&["c3", "e3", "g3"]),
+                Bar::Rest,
+                Bar::Note("g3".to_string()),
+                Bar::Note("c4".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_drum_map_override() {
+        let hit = |time_sec: f64, note: &str| NoteEvent {
+            time_sec,
+            note: note.to_string(),
+            velocity: 100,
+            duration_sec: Some(0.1),
+            channel: 9,
+        };
+        let note = |sample: &str| Bar::Note(sample.to_string());
+        let bar = |samples: [&str; 4]| Bar::Subdivision(samples.map(note).to_vec());
+
+        // Bass drum (36) and snare (38) on alternate beats
+        let events: Vec<_> = [(0.0, "c2"), (0.5, "d2"), (1.0, "c2"), (1.5, "d2")]
+            .iter()
+            .map(|&(time_sec, note)| hit(time_sec, note))
+            .collect();
+
+        let builder = TrackBuilder::new(2.0, 0, false, 4, false, Vec::new());
+        let bar_sd = builder.get_drum_bar(&events, 0.0, 2.0);
+        assert_eq!(bar_sd, bar(["bd", "sd", "bd", "sd"]));
+
+        let builder = builder.with_drum_map(DrumMap::new().with_override(38, "rim"));
+        let bar_rim = builder.get_drum_bar(&events, 0.0, 2.0);
+        assert_eq!(bar_rim, bar(["bd", "rim", "bd", "rim"]));
+    }
+
+    #[test]
+    fn test_note_gains_keep_crescendo() {
+        let note = |time_sec: f64, velocity: u8| NoteEvent {
+            time_sec,
+            note: "c4".to_string(),
+            velocity,
+            duration_sec: Some(1.0),
+            channel: 0,
+        };
+        let events = [note(0.0, 32), note(1.0, 127)];
+
+        // A soft note then a loud one: two gains, not their average
+        let builder = TrackBuilder::new(2.0, 0, false, 4, false, Vec::new()).with_note_gains();
+        let gains = builder.get_note_gains(&events, 0.0, 2.0, false);
+        assert_eq!(gains, vec![0.5, 1.0]);
+        assert_eq!(
+            ModifierValue::Steps(vec![gains, Vec::new()]).to_strudel(),
+            "\"<[0.50 1.00] ->\""
+        );
+    }
+
+    #[test]
+    fn test_volume_ramp_raises_gain() {
+        let controller = |controller, value| TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: MidiMessage::Controller {
+                controller: u7::new(controller),
+                value: u7::new(value),
+            },
+        };
+
+        // A note per bar with the volume (CC7) turned up at each downbeat,
+        // and the filter (CC74) fully open throughout
+        let bar = 4 * TICKS_PER_BEAT;
+        let data = midi_file_with_events(
+            &[0, 4, 8, 12],
+            vec![
+                (0, controller(74, 127)),
+                (0, controller(7, 32)),
+                (bar, controller(7, 64)),
+                (2 * bar, controller(7, 96)),
+                (3 * bar, controller(7, 127)),
+            ],
+        );
+        let midi = MidiData::from_bytes(&data).unwrap();
+        let tracks = builder(&midi)
+            .with_cc_effects(CcEffectMap::default())
+            .build_tracks(&midi.track_info);
+
+        let gains = &tracks[0].gains;
+        assert!(
+            gains.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            gains
+        );
+        assert_eq!(
+            tracks[0].effects,
+            vec![("lpf".to_string(), vec![20000.0; 4])]
+        );
+
+        let output = OutputFormatter::new(2, false).build_output(&tracks, midi.bpm);
+        assert!(
+            output.contains(".gain(\"<0.22 0.45 0.67 0.89>\")"),
+            "{}",
+            output
+        );
+        assert!(output.contains(".lpf(20000.00)"), "{}", output);
+
+        // Without opting in the automation is dropped
+        let tracks = builder(&midi).build_tracks(&midi.track_info);
+        assert!(tracks[0].effects.is_empty());
+        assert!(tracks[0].gains.iter().all(|&gain| gain == gains[3]));
+    }
+}
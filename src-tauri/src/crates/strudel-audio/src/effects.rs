@@ -0,0 +1,124 @@
+//! Effects applied to the mix of several voices
+//!
+//! Voices send part of their output into these, and their output is added
+//! back to the mix.
+
+/// Delay time in seconds when none is set
+pub const DEFAULT_DELAY_TIME: f64 = 0.25;
+
+/// Delay feedback when none is set
+pub const DEFAULT_DELAY_FEEDBACK: f32 = 0.5;
+
+/// Highest feedback allowed, so repeats always die away
+const MAX_DELAY_FEEDBACK: f32 = 0.95;
+
+/// A stereo feedback delay (echo)
+///
+/// Input comes back out after the delay time, and is fed back into the
+/// line at the feedback level, so each repeat is quieter than the last.
+pub struct DelayLine {
+    /// Interleaved stereo ring buffer, one delay time long
+    buffer: Vec<f32>,
+    /// Current frame in the ring buffer
+    position: usize,
+    /// Delay time in seconds
+    time: f64,
+    /// Level of each repeat relative to the one before
+    feedback: f32,
+}
+
+impl DelayLine {
+    /// Create a silent delay line with the default time and feedback
+    pub fn new() -> Self {
+        DelayLine {
+            buffer: Vec::new(),
+            position: 0,
+            time: DEFAULT_DELAY_TIME,
+            feedback: DEFAULT_DELAY_FEEDBACK,
+        }
+    }
+
+    /// Set the delay time in seconds
+    ///
+    /// Changing the time clears any repeats still in the line.
+    pub fn set_time(&mut self, seconds: f64) {
+        self.time = seconds.max(0.0);
+    }
+
+    /// Set the feedback level, kept below 1.0 so repeats never build up
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(0.0, MAX_DELAY_FEEDBACK);
+    }
+
+    /// Silence any repeats still in the line
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.position = 0;
+    }
+
+    /// Feed `input` into the line and add the delayed signal to `output`
+    ///
+    /// Both buffers are interleaved stereo (L, R, L, R, ...) of the same length.
+    pub fn process(&mut self, output: &mut [f32], input: &[f32], sample_rate: u32) {
+        let frames = ((self.time * sample_rate as f64).round() as usize).max(1);
+        if self.buffer.len() != frames * 2 {
+            // Nothing to repeat and nothing coming in: stay unallocated
+            if self.buffer.is_empty() && input.iter().all(|&s| s == 0.0) {
+                return;
+            }
+            self.buffer = vec![0.0; frames * 2];
+            self.position = 0;
+        }
+
+        for (out, inp) in output.chunks_mut(2).zip(input.chunks(2)) {
+            let index = self.position * 2;
+            for channel in 0..out.len().min(inp.len()) {
+                let delayed = self.buffer[index + channel];
+                out[channel] += delayed;
+                self.buffer[index + channel] = inp[channel] + delayed * self.feedback;
+            }
+            self.position = (self.position + 1) % frames;
+        }
+    }
+}
+
+impl Default for DelayLine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_repeats_decay() {
+        // One second at 1kHz, with an impulse at the start
+        let mut input = vec![0.0; 2000];
+        input[0] = 1.0;
+        input[1] = 1.0;
+        let mut output = vec![0.0; 2000];
+
+        let mut delay = DelayLine::new();
+        delay.set_time(0.25);
+        delay.set_feedback(0.5);
+        delay.process(&mut output, &input, 1000);
+
+        // Repeats every 250 frames, each at half the level of the one before
+        let left: Vec<(usize, f32)> = output
+            .chunks(2)
+            .enumerate()
+            .filter(|(_, frame)| frame[0] != 0.0)
+            .map(|(i, frame)| (i, frame[0]))
+            .collect();
+        assert_eq!(left, vec![(250, 1.0), (500, 0.5), (750, 0.25)]);
+    }
+
+    #[test]
+    fn test_delay_feedback_is_clamped() {
+        let mut delay = DelayLine::new();
+        delay.set_feedback(1.5);
+        assert!(delay.feedback < 1.0);
+    }
+}
@@ -23,10 +23,32 @@ pub struct AudioEngine {
 impl AudioEngine {
     /// Create a new audio engine with the default output device
     pub fn new() -> Result<Self> {
+        Self::with_device(None)
+    }
+
+    /// Create a new audio engine with a named output device
+    ///
+    /// Names are as returned by `list_output_devices`. If the device isn't
+    /// available, a warning is printed and the default device is used.
+    pub fn with_device(name: Option<&str>) -> Result<Self> {
         let host = cpal::default_host();
 
-        let device = host
-            .default_output_device()
+        let named = name.and_then(|name| {
+            let device = host
+                .output_devices()
+                .ok()
+                .and_then(|mut devices| devices.find(|d| d.name().is_ok_and(|n| n == name)));
+            if device.is_none() {
+                eprintln!(
+                    "Audio device '{}' not found, using default output device",
+                    name
+                );
+            }
+            device
+        });
+
+        let device = named
+            .or_else(|| host.default_output_device())
             .ok_or_else(|| AudioError::DeviceError("No output device available".to_string()))?;
 
         let config = device
@@ -45,6 +67,32 @@ impl AudioEngine {
         })
     }
 
+    /// List the names of the available output devices
+    ///
+    /// The default device is always included (first, if the host doesn't
+    /// list it itself). Returns an empty list when there is no audio output.
+    pub fn list_output_devices() -> Vec<String> {
+        let host = cpal::default_host();
+
+        let mut names: Vec<String> = host
+            .output_devices()
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default();
+
+        if let Some(default_name) = host.default_output_device().and_then(|d| d.name().ok()) {
+            if !names.contains(&default_name) {
+                names.insert(0, default_name);
+            }
+        }
+
+        names
+    }
+
+    /// Get the name of the output device
+    pub fn device_name(&self) -> String {
+        self.device.name().unwrap_or_default()
+    }
+
     /// Get the sample rate of the output device
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
@@ -107,3 +155,28 @@ impl Default for AudioEngine {
         Self::new().expect("Failed to create default audio engine")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_output_devices_includes_default() {
+        // Note: Skipped if no audio device is available
+        let Ok(engine) = AudioEngine::new() else {
+            return;
+        };
+
+        assert!(AudioEngine::list_output_devices().contains(&engine.device_name()));
+    }
+
+    #[test]
+    fn test_unknown_device_falls_back_to_default() {
+        let Ok(default_engine) = AudioEngine::new() else {
+            return;
+        };
+
+        let engine = AudioEngine::with_device(Some("no such device")).unwrap();
+        assert_eq!(engine.device_name(), default_engine.device_name());
+    }
+}
@@ -5,15 +5,23 @@
 //! - Fall back to HTTP loading for additional samples
 //! - Schedule sample triggers with precise timing
 //! - Mix multiple voices with gain control
+//! - Send events to SuperDirt over OSC instead of playing them
+//! - Render patterns to WAV files without an audio device
 
+pub mod effects;
 pub mod engine;
+pub mod osc;
 pub mod player;
+pub mod render;
 pub mod samples;
 pub mod scheduler;
 pub mod voice;
 
+pub use effects::DelayLine;
 pub use engine::AudioEngine;
-pub use player::{Player, PlayerConfig};
+pub use osc::OscSender;
+pub use player::{Player, PlayerConfig, StopHandle};
+pub use render::Renderer;
 pub use samples::{Sample, SampleBank, SampleLoader};
 pub use scheduler::Scheduler;
 pub use voice::Voice;
@@ -33,6 +41,9 @@ pub enum AudioError {
     #[error("Failed to decode audio: {0}")]
     DecodeError(String),
 
+    #[error("Failed to encode audio: {0}")]
+    EncodeError(String),
+
     #[error("Failed to load sample from URL: {0}")]
     HttpError(String),
 
@@ -0,0 +1,217 @@
+//! OSC output, sending pattern events to SuperDirt instead of playing them
+//!
+//! Events are timed by the same [`Scheduler`] as audio playback and sent
+//! ahead of time as OSC bundles, timetagged with the wall-clock time they
+//! should sound so the receiver can play them precisely.
+
+use crate::scheduler::sample_of;
+use crate::{AudioError, Fraction, Hap, Pattern, Result, SampleLoader, Scheduler, Value};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Address SuperDirt listens on by default
+pub const DEFAULT_OSC_ADDR: &str = "127.0.0.1:57120";
+
+/// OSC address for triggering a sound in SuperDirt
+const DIRT_PLAY: &str = "/dirt/play";
+
+/// Seconds between the OSC (NTP) epoch of 1900 and the Unix epoch
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Controls passed on to SuperDirt when an event sets them
+const DIRT_CONTROLS: [&str; 2] = ["gain", "pan"];
+
+/// An argument of an OSC message
+#[derive(Debug, Clone, PartialEq)]
+enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+/// Sends pattern events as SuperDirt-style `/dirt/play` OSC bundles
+pub struct OscSender {
+    /// Socket events are sent from
+    socket: UdpSocket,
+    /// Address of the receiver
+    target: SocketAddr,
+    /// Scheduler deciding when each event is due
+    scheduler: Scheduler,
+    /// How far ahead of their time events are sent
+    lookahead: Duration,
+}
+
+impl OscSender {
+    /// Create a sender for a receiver at `addr` (such as `127.0.0.1:57120`)
+    ///
+    /// Nothing is sent until `send`, and a receiver that isn't running is not
+    /// an error: like other OSC senders, events are fire-and-forget.
+    pub fn new(addr: impl ToSocketAddrs, tempo: f64) -> Result<Self> {
+        let target = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| AudioError::Other("OSC address did not resolve".into()))?;
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+
+        Ok(OscSender {
+            socket,
+            target,
+            scheduler: Scheduler::new(Arc::new(SampleLoader::new()), tempo),
+            lookahead: Duration::from_millis(100),
+        })
+    }
+
+    /// Send the events of `duration_secs` of a pattern, in real time
+    ///
+    /// Blocks until every event has been sent, which is up to the lookahead
+    /// before the last one is due. Returns the number of bundles sent.
+    pub fn send(&mut self, pattern: &Pattern, duration_secs: f64) -> Result<usize> {
+        // Tempo is in beats per minute, with four beats per cycle
+        let cycles_per_second = self.scheduler.tempo() / 60.0 / 4.0;
+        let end = Fraction::from_float(duration_secs * cycles_per_second);
+        self.scheduler.reset();
+        self.scheduler.set_end_cycle(Some(end));
+        let start = SystemTime::now();
+
+        let mut sent = 0;
+        while !self.scheduler.is_finished() {
+            for hap in self.scheduler.due_onsets(pattern, self.lookahead) {
+                let Some(args) = dirt_args(&hap, cycles_per_second) else {
+                    continue;
+                };
+                let cycle = hap.whole_or_part().begin;
+                let time = start + self.scheduler.time_of_cycle(cycle);
+                self.socket
+                    .send_to(&encode_bundle(time, DIRT_PLAY, &args), self.target)?;
+                sent += 1;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        Ok(sent)
+    }
+}
+
+/// Build the `/dirt/play` arguments for an event, or None if it names no sound
+fn dirt_args(hap: &Hap, cps: f64) -> Option<Vec<OscArg>> {
+    let (name, index) = sample_of(hap)?;
+    let whole = hap.whole_or_part();
+
+    let mut args = vec![
+        OscArg::String("s".into()),
+        OscArg::String(name),
+        OscArg::String("n".into()),
+        OscArg::Int(index as i32),
+        OscArg::String("cps".into()),
+        OscArg::Float(cps as f32),
+        OscArg::String("cycle".into()),
+        OscArg::Float(whole.begin.to_float() as f32),
+        OscArg::String("delta".into()),
+        OscArg::Float(((whole.end - whole.begin).to_float() / cps) as f32),
+    ];
+    for control in DIRT_CONTROLS {
+        if let Some(Value::Number(value)) = hap.context.metadata.get(control) {
+            args.push(OscArg::String(control.into()));
+            args.push(OscArg::Float(*value as f32));
+        }
+    }
+
+    Some(args)
+}
+
+/// Encode a single message in a bundle timetagged to play at `time`
+fn encode_bundle(time: SystemTime, address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut message = Vec::new();
+    write_string(&mut message, address);
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::String(_) => 's',
+        }))
+        .collect();
+    write_string(&mut message, &tags);
+    for arg in args {
+        match arg {
+            OscArg::Int(value) => message.extend_from_slice(&value.to_be_bytes()),
+            OscArg::Float(value) => message.extend_from_slice(&value.to_be_bytes()),
+            OscArg::String(value) => write_string(&mut message, value),
+        }
+    }
+
+    let mut bundle = Vec::new();
+    write_string(&mut bundle, "#bundle");
+    bundle.extend_from_slice(&timetag(time).to_be_bytes());
+    bundle.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    bundle.extend_from_slice(&message);
+    bundle
+}
+
+/// Write a null-terminated string, padded to a multiple of four bytes
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    buffer.resize(buffer.len() + padding, 0);
+}
+
+/// Convert a time to an OSC timetag: NTP seconds and a 32-bit fraction
+fn timetag(time: SystemTime) -> u64 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_unix.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (since_unix.subsec_nanos() as u64) * (1 << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read a padded OSC string starting at `pos`, moving `pos` past it
+    fn read_string(buffer: &[u8], pos: &mut usize) -> String {
+        let end = *pos + buffer[*pos..].iter().position(|&b| b == 0).unwrap();
+        let value = String::from_utf8(buffer[*pos..end].to_vec()).unwrap();
+        *pos += (end - *pos) / 4 * 4 + 4;
+        value
+    }
+
+    #[test]
+    fn test_timetag() {
+        let time = UNIX_EPOCH + Duration::from_millis(1500);
+        assert_eq!(timetag(time), ((NTP_UNIX_OFFSET + 1) << 32) | (1 << 31));
+    }
+
+    #[test]
+    fn test_send_dirt_bundles() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+
+        // Four cycles per second, so half a second is two cycles of "bd sd"
+        let mut sender = OscSender::new(receiver.local_addr().unwrap(), 960.0).unwrap();
+        let pattern = strudel_core::fastcat(vec![
+            strudel_core::pure(Value::String("bd".into())),
+            strudel_core::pure(Value::String("sd:2".into())).tag("gain", Value::Number(0.5)),
+        ]);
+        assert_eq!(sender.send(&pattern, 0.5).unwrap(), 4);
+
+        let mut sounds = Vec::new();
+        let mut buffer = [0; 1024];
+        for _ in 0..4 {
+            let len = receiver.recv(&mut buffer).unwrap();
+            let packet = &buffer[..len];
+
+            let mut pos = 0;
+            assert_eq!(read_string(packet, &mut pos), "#bundle");
+            pos += 12; // timetag and element size
+            assert_eq!(read_string(packet, &mut pos), DIRT_PLAY);
+            let tags = read_string(packet, &mut pos);
+            assert!(tags.starts_with(",sss"));
+            assert_eq!(read_string(packet, &mut pos), "s");
+            sounds.push(read_string(packet, &mut pos));
+        }
+        assert_eq!(sounds, vec!["bd", "sd", "bd", "sd"]);
+    }
+}
@@ -1,10 +1,16 @@
 //! High-level audio player for Strudel patterns
 
-use crate::{AudioEngine, Pattern, Result, SampleLoader, Scheduler};
+use crate::scheduler::DEFAULT_MAX_VOICES;
+use crate::{AudioEngine, Fraction, Pattern, Renderer, Result, SampleLoader, Scheduler};
 use parking_lot::Mutex;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
 use std::time::Duration;
 
+/// How long voices get to ring out after a stop is requested
+const RELEASE_TIME: Duration = Duration::from_millis(250);
+
 /// Configuration for the audio player
 #[derive(Debug, Clone)]
 pub struct PlayerConfig {
@@ -14,6 +20,12 @@ pub struct PlayerConfig {
     pub lookahead: Duration,
     /// Fallback URL for sample loading
     pub fallback_url: Option<String>,
+    /// Output device name (see `AudioEngine::list_output_devices`), or the default device
+    pub device_name: Option<String>,
+    /// Number of cycles to play before going quiet, or None to loop forever
+    pub cycles: Option<u32>,
+    /// Most voices that play at once; the oldest is stopped to make room
+    pub max_voices: usize,
 }
 
 impl Default for PlayerConfig {
@@ -24,10 +36,63 @@ impl Default for PlayerConfig {
             fallback_url: Some(
                 "https://raw.githubusercontent.com/tidalcycles/Dirt-Samples/master".to_string(),
             ),
+            device_name: None,
+            cycles: None,
+            max_voices: DEFAULT_MAX_VOICES,
         }
     }
 }
 
+/// Lets another thread, such as a Ctrl-C handler, stop a playing [`Player`]
+#[derive(Debug, Clone)]
+pub struct StopHandle(Sender<()>);
+
+impl StopHandle {
+    /// Ask the player to stop
+    ///
+    /// Does nothing if the player has already been dropped.
+    pub fn stop(&self) {
+        let _ = self.0.send(());
+    }
+}
+
+/// Channel that stop requests arrive on while a player waits
+struct StopSignal {
+    /// Kept so handles can be made at any time
+    sender: Sender<()>,
+    /// Receives requests from every handle
+    receiver: Mutex<Receiver<()>>,
+}
+
+impl StopSignal {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        StopSignal {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    fn handle(&self) -> StopHandle {
+        StopHandle(self.sender.clone())
+    }
+
+    /// Block until a stop is requested or the timeout passes, or forever
+    /// without one. Returns true if a stop was requested.
+    fn wait(&self, timeout: Option<Duration>) -> bool {
+        let receiver = self.receiver.lock();
+        match timeout {
+            Some(timeout) => receiver.recv_timeout(timeout).is_ok(),
+            None => receiver.recv().is_ok(),
+        }
+    }
+
+    /// Forget requests made before now, so they don't stop the next pattern
+    fn clear(&self) {
+        while self.receiver.lock().try_recv().is_ok() {}
+    }
+}
+
 /// High-level audio player for Strudel patterns
 pub struct Player {
     /// Audio engine
@@ -40,24 +105,21 @@ pub struct Player {
     pattern: Arc<Mutex<Option<Pattern>>>,
     /// Configuration
     config: PlayerConfig,
+    /// Stop requests from `StopHandle`s
+    stop_signal: StopSignal,
 }
 
 impl Player {
     /// Create a new player with the given configuration
     pub fn new(config: PlayerConfig) -> Result<Self> {
         #[allow(clippy::arc_with_non_send_sync)]
-        let engine = Arc::new(AudioEngine::new()?);
+        let engine = Arc::new(AudioEngine::with_device(config.device_name.as_deref())?);
 
-        let mut loader = SampleLoader::new();
-        if let Some(url) = &config.fallback_url {
-            loader = loader.with_fallback_url(url.clone());
-        }
-        let loader = Arc::new(loader);
+        let loader = Arc::new(sample_loader(&config));
 
-        let scheduler = Arc::new(Mutex::new(Scheduler::new(
-            Arc::clone(&loader),
-            config.tempo,
-        )));
+        let mut scheduler = Scheduler::new(Arc::clone(&loader), config.tempo);
+        scheduler.set_max_voices(config.max_voices);
+        let scheduler = Arc::new(Mutex::new(scheduler));
 
         Ok(Player {
             engine,
@@ -65,6 +127,7 @@ impl Player {
             scheduler,
             pattern: Arc::new(Mutex::new(None)),
             config,
+            stop_signal: StopSignal::new(),
         })
     }
 
@@ -83,6 +146,14 @@ impl Player {
         self.scheduler.lock().tempo()
     }
 
+    /// Get the current position in cycles since playback started
+    ///
+    /// The fractional part is the phase within the cycle, so callers can
+    /// line changes up with the next cycle boundary.
+    pub fn current_cycle(&self) -> f64 {
+        self.scheduler.lock().current_cycle()
+    }
+
     /// Get the sample loader (for preloading samples)
     pub fn loader(&self) -> Arc<SampleLoader> {
         Arc::clone(&self.loader)
@@ -92,9 +163,14 @@ impl Player {
     pub fn play(&self, pattern: Pattern) -> Result<()> {
         // Store the pattern
         *self.pattern.lock() = Some(pattern);
+        self.stop_signal.clear();
 
         // Reset the scheduler
-        self.scheduler.lock().reset();
+        {
+            let mut sched = self.scheduler.lock();
+            sched.reset();
+            sched.set_end_cycle(self.config.cycles.map(|c| Fraction::from(c as i64)));
+        }
 
         // Start the audio stream
         let scheduler = Arc::clone(&self.scheduler);
@@ -124,10 +200,67 @@ impl Player {
         Ok(())
     }
 
+    /// Get a handle for stopping playback from another thread
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop_signal.handle()
+    }
+
+    /// Keep playing until a stop is requested or `duration` passes
+    ///
+    /// With no duration, plays until a [`StopHandle`] asks it to stop. When
+    /// stopped early, voices get a moment to ring out so the output doesn't
+    /// cut off mid-sound. Either way, playback is stopped on return.
+    pub fn play_until_stopped(&self, duration: Option<Duration>) -> Result<()> {
+        if self.stop_signal.wait(duration) {
+            self.hush();
+            std::thread::sleep(RELEASE_TIME);
+        }
+        self.stop()
+    }
+
+    /// Stop triggering new events but let sounding voices ring out
+    ///
+    /// The output stream stays open; call `stop` to close it, or `play` to
+    /// start a new pattern.
+    pub fn hush(&self) {
+        self.scheduler.lock().hush();
+    }
+
+    /// Check if the configured number of cycles has been scheduled
+    ///
+    /// Always false when looping forever.
+    pub fn is_finished(&self) -> bool {
+        self.scheduler.lock().is_finished()
+    }
+
     /// Check if currently playing
     pub fn is_playing(&self) -> bool {
         self.engine.is_running()
     }
+
+    /// Render a pattern to a 16-bit stereo WAV file instead of playing it
+    ///
+    /// Rendering runs offline at the engine's sample rate on its own
+    /// scheduler, so it doesn't disturb live playback and the output doesn't
+    /// depend on timing. The configured number of cycles still applies. To
+    /// render without an audio device, use a [`Renderer`] directly.
+    pub fn render_to_wav(&self, pattern: Pattern, duration_secs: f64, path: &Path) -> Result<()> {
+        let config = PlayerConfig {
+            tempo: self.tempo(),
+            ..self.config.clone()
+        };
+        let renderer = Renderer::with_loader(self.loader(), config, self.engine.sample_rate());
+        renderer.render_to_wav(&pattern, duration_secs, path)
+    }
+}
+
+/// Build a sample loader with the configured fallback URL
+pub(crate) fn sample_loader(config: &PlayerConfig) -> SampleLoader {
+    let loader = SampleLoader::new();
+    match &config.fallback_url {
+        Some(url) => loader.with_fallback_url(url.clone()),
+        None => loader,
+    }
 }
 
 #[cfg(test)]
@@ -139,4 +272,56 @@ mod tests {
         let _player = Player::with_defaults();
         // Note: This will fail if no audio device is available
     }
+
+    #[test]
+    fn test_stop_signal() {
+        let signal = StopSignal::new();
+
+        // Nothing requested: waiting runs out the timeout
+        assert!(!signal.wait(Some(Duration::from_millis(10))));
+
+        // A request from another thread ends an unbounded wait
+        let handle = signal.handle();
+        let stopper = std::thread::spawn(move || handle.stop());
+        assert!(signal.wait(None));
+        stopper.join().unwrap();
+
+        // Requests from before a new pattern starts are forgotten
+        signal.handle().stop();
+        signal.clear();
+        assert!(!signal.wait(Some(Duration::from_millis(10))));
+    }
+
+    #[test]
+    fn test_stop_handle_ends_playback() {
+        // Note: Skipped if no audio device is available
+        let config = PlayerConfig {
+            fallback_url: None,
+            ..Default::default()
+        };
+        let Ok(player) = Player::new(config) else {
+            return;
+        };
+        let pattern = strudel_core::pure(crate::Value::String("bd".into()));
+
+        // A stop requested before playing is forgotten
+        player.stop_handle().stop();
+        player.play(pattern.clone()).unwrap();
+        assert!(player.is_playing());
+
+        // A stop requested while playing ends an unbounded wait
+        let stop = player.stop_handle();
+        let stopper = std::thread::spawn(move || stop.stop());
+        player.play_until_stopped(None).unwrap();
+        stopper.join().unwrap();
+        assert!(!player.is_playing());
+
+        // The player can start again afterwards
+        player.play(pattern).unwrap();
+        assert!(player.is_playing());
+        player
+            .play_until_stopped(Some(Duration::from_millis(10)))
+            .unwrap();
+        assert!(!player.is_playing());
+    }
 }
@@ -0,0 +1,103 @@
+//! Offline rendering, writing pattern audio to a WAV file instead of playing it
+//!
+//! Rendering uses the same [`Scheduler`] as playback but no output stream,
+//! so it works on machines without an audio device and the output doesn't
+//! depend on timing.
+
+use crate::player::sample_loader;
+use crate::{AudioError, Fraction, Pattern, PlayerConfig, Result, SampleLoader, Scheduler};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Sample rate of rendered files when there is no device to take it from
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Renders patterns to 16-bit stereo WAV files without an audio device
+pub struct Renderer {
+    /// Sample loader
+    loader: Arc<SampleLoader>,
+    /// Tempo, cycle count and voice limit to render with
+    config: PlayerConfig,
+    /// Sample rate of the output
+    sample_rate: u32,
+}
+
+impl Renderer {
+    /// Create a renderer with its own sample loader
+    pub fn new(config: PlayerConfig, sample_rate: u32) -> Self {
+        let loader = Arc::new(sample_loader(&config));
+        Self::with_loader(loader, config, sample_rate)
+    }
+
+    /// Create a renderer sharing an existing sample loader, such as a player's
+    pub fn with_loader(loader: Arc<SampleLoader>, config: PlayerConfig, sample_rate: u32) -> Self {
+        Renderer {
+            loader,
+            config,
+            sample_rate,
+        }
+    }
+
+    /// Get the sample loader (for preloading samples)
+    pub fn loader(&self) -> Arc<SampleLoader> {
+        Arc::clone(&self.loader)
+    }
+
+    /// Render `duration_secs` of a pattern to a WAV file
+    ///
+    /// The configured number of cycles still applies.
+    pub fn render_to_wav(&self, pattern: &Pattern, duration_secs: f64, path: &Path) -> Result<()> {
+        let mut scheduler = Scheduler::new(Arc::clone(&self.loader), self.config.tempo);
+        scheduler.set_max_voices(self.config.max_voices);
+        scheduler.set_end_cycle(self.config.cycles.map(|c| Fraction::from(c as i64)));
+
+        let buffer = scheduler.render(pattern, duration_secs, self.sample_rate);
+        write_wav(path, &buffer, self.sample_rate)
+    }
+}
+
+/// Write an interleaved stereo buffer as a 16-bit PCM WAV file
+fn write_wav(path: &Path, buffer: &[f32], sample_rate: u32) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let encode_error = |e: hound::Error| AudioError::EncodeError(e.to_string());
+
+    let mut writer = hound::WavWriter::create(path, spec).map_err(encode_error)?;
+    for &sample in buffer {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(value).map_err(encode_error)?;
+    }
+    writer.finalize().map_err(encode_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_to_wav() {
+        let path = std::env::temp_dir().join("strudel-audio-test-render.wav");
+        let pattern = strudel_core::pure(crate::Value::String("bd".into())).fast(4.0);
+
+        // No audio device or network needed
+        let config = PlayerConfig {
+            fallback_url: None,
+            ..Default::default()
+        };
+        let renderer = Renderer::new(config, DEFAULT_SAMPLE_RATE);
+        renderer.render_to_wav(&pattern, 1.0, &path).unwrap();
+
+        // One second of 16-bit stereo
+        let reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!((spec.channels, spec.bits_per_sample), (2, 16));
+        assert_eq!(spec.sample_rate, DEFAULT_SAMPLE_RATE);
+        assert_eq!(reader.duration(), DEFAULT_SAMPLE_RATE);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -7,6 +7,7 @@ use crate::{AudioError, Result};
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::path::Path;
 use std::sync::Arc;
 use symphonia::core::audio::AudioBufferRef;
 use symphonia::core::codecs::DecoderOptions;
@@ -15,6 +16,56 @@ use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 
+/// A bundled sample file, embedded from `assets/samples`
+macro_rules! bundled {
+    ($path:literal) => {
+        (
+            $path,
+            include_bytes!(concat!("../assets/samples/", $path)).as_slice(),
+        )
+    };
+}
+
+/// A bundled sample's file name and contents
+type BundledSample = (&'static str, &'static [u8]);
+
+/// Banks embedded in the binary: name and samples in index order
+const BUNDLED_BANKS: &[(&str, &[BundledSample])] = &[
+    // Bass drums
+    (
+        "bd",
+        &[
+            bundled!("bd/BT0A0A7.wav"),
+            bundled!("bd/BT0A0D0.wav"),
+            bundled!("bd/BT0A0D3.wav"),
+            bundled!("bd/BT0A0DA.wav"),
+            bundled!("bd/BT0AAD0.wav"),
+        ],
+    ),
+    // Snare drums
+    (
+        "sd",
+        &[
+            bundled!("sd/rytm-00-hard.wav"),
+            bundled!("sd/rytm-01-classic.wav"),
+        ],
+    ),
+    // Hi-hats
+    (
+        "hh",
+        &[
+            bundled!("hh/000_hh3closedhh.wav"),
+            bundled!("hh/001_hh3crash.wav"),
+            bundled!("hh/002_hh3hit1.wav"),
+        ],
+    ),
+    // Claps
+    (
+        "cp",
+        &[bundled!("cp/HANDCLP0.wav"), bundled!("cp/HANDCLPA.wav")],
+    ),
+];
+
 /// An audio sample with decoded PCM data
 #[derive(Debug, Clone)]
 pub struct Sample {
@@ -138,6 +189,54 @@ impl SampleLoader {
         Err(AudioError::SampleNotFound(bank_name.to_string()))
     }
 
+    /// Load every bank in a directory of `<bank>/<file>` audio files
+    ///
+    /// Each subdirectory becomes a bank named after it, with its files in
+    /// name order as the sample indices. Banks that are already loaded are
+    /// kept, and files that fail to decode are skipped with a warning.
+    /// Returns the names of the newly loaded banks, sorted.
+    pub fn load_from_dir(&self, path: &Path) -> Result<Vec<String>> {
+        let mut loaded = Vec::new();
+
+        for entry in std::fs::read_dir(path)? {
+            let bank_dir = entry?.path();
+            let Some(bank_name) = bank_dir.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !bank_dir.is_dir() || self.banks.read().contains_key(bank_name) {
+                continue;
+            }
+
+            let mut files: Vec<_> = std::fs::read_dir(&bank_dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|file| {
+                    file.extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| ["wav", "mp3", "ogg", "flac"].contains(&ext))
+                })
+                .collect();
+            files.sort();
+
+            let mut bank = SampleBank::new(bank_name.to_string());
+            for file in files {
+                let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+                let name = format!("{}/{}", bank_name, file_name);
+                match self.decode_audio(&std::fs::read(&file)?, &name, bank.len()) {
+                    Ok(sample) => bank.add_sample(sample),
+                    Err(e) => eprintln!("Warning: Failed to decode {}: {}", name, e),
+                }
+            }
+
+            if !bank.is_empty() {
+                loaded.push(bank_name.to_string());
+                self.banks.write().insert(bank_name.to_string(), bank);
+            }
+        }
+
+        loaded.sort();
+        Ok(loaded)
+    }
+
     /// Get a sample from a loaded bank
     pub fn get_sample(&self, bank_name: &str, index: usize) -> Result<Sample> {
         let banks = self.banks.read();
@@ -150,61 +249,27 @@ impl SampleLoader {
             .ok_or_else(|| AudioError::SampleNotFound(format!("{}:{}", bank_name, index)))
     }
 
+    /// List the bundled sample banks and how many samples each has
+    ///
+    /// These load without network access, in the order they're listed.
+    pub fn bundled_banks() -> Vec<(String, usize)> {
+        BUNDLED_BANKS
+            .iter()
+            .map(|&(name, samples)| (name.to_string(), samples.len()))
+            .collect()
+    }
+
     /// Try to load a sample bank from bundled assets
     fn try_load_bundled(&self, bank_name: &str) -> Result<Option<SampleBank>> {
-        let mut bank = SampleBank::new(bank_name.to_string());
+        let Some(&(_, samples_data)) = BUNDLED_BANKS.iter().find(|&&(name, _)| name == bank_name)
+        else {
+            return Ok(None); // Bank not bundled
+        };
 
-        // Load bundled samples using include_bytes!()
-        match bank_name {
-            "bd" => {
-                // Bass drums
-                let samples_data = [
-                    ("bd/BT0A0A7.wav", include_bytes!("../assets/samples/bd/BT0A0A7.wav").as_slice()),
-                    ("bd/BT0A0D0.wav", include_bytes!("../assets/samples/bd/BT0A0D0.wav").as_slice()),
-                    ("bd/BT0A0D3.wav", include_bytes!("../assets/samples/bd/BT0A0D3.wav").as_slice()),
-                    ("bd/BT0A0DA.wav", include_bytes!("../assets/samples/bd/BT0A0DA.wav").as_slice()),
-                    ("bd/BT0AAD0.wav", include_bytes!("../assets/samples/bd/BT0AAD0.wav").as_slice()),
-                ];
-                for (i, (name, data)) in samples_data.iter().enumerate() {
-                    let sample = self.decode_audio(data, name, i)?;
-                    bank.add_sample(sample);
-                }
-            }
-            "sd" => {
-                // Snare drums
-                let samples_data = [
-                    ("sd/rytm-00-hard.wav", include_bytes!("../assets/samples/sd/rytm-00-hard.wav").as_slice()),
-                    ("sd/rytm-01-classic.wav", include_bytes!("../assets/samples/sd/rytm-01-classic.wav").as_slice()),
-                ];
-                for (i, (name, data)) in samples_data.iter().enumerate() {
-                    let sample = self.decode_audio(data, name, i)?;
-                    bank.add_sample(sample);
-                }
-            }
-            "hh" => {
-                // Hi-hats
-                let samples_data = [
-                    ("hh/000_hh3closedhh.wav", include_bytes!("../assets/samples/hh/000_hh3closedhh.wav").as_slice()),
-                    ("hh/001_hh3crash.wav", include_bytes!("../assets/samples/hh/001_hh3crash.wav").as_slice()),
-                    ("hh/002_hh3hit1.wav", include_bytes!("../assets/samples/hh/002_hh3hit1.wav").as_slice()),
-                ];
-                for (i, (name, data)) in samples_data.iter().enumerate() {
-                    let sample = self.decode_audio(data, name, i)?;
-                    bank.add_sample(sample);
-                }
-            }
-            "cp" => {
-                // Claps
-                let samples_data = [
-                    ("cp/HANDCLP0.wav", include_bytes!("../assets/samples/cp/HANDCLP0.wav").as_slice()),
-                    ("cp/HANDCLPA.wav", include_bytes!("../assets/samples/cp/HANDCLPA.wav").as_slice()),
-                ];
-                for (i, (name, data)) in samples_data.iter().enumerate() {
-                    let sample = self.decode_audio(data, name, i)?;
-                    bank.add_sample(sample);
-                }
-            }
-            _ => return Ok(None), // Bank not bundled
+        let mut bank = SampleBank::new(bank_name.to_string());
+        for (i, (name, data)) in samples_data.iter().enumerate() {
+            let sample = self.decode_audio(data, name, i)?;
+            bank.add_sample(sample);
         }
 
         Ok(Some(bank))
@@ -220,32 +285,17 @@ impl SampleLoader {
             .json()
             .map_err(|e| AudioError::HttpError(format!("Failed to parse JSON: {}", e)))?;
 
-        // Get the bank entry
-        let bank_entry = json.get(bank_name);
-        if bank_entry.is_none() {
-            return Ok(None);
-        }
-
-        let bank_entry = bank_entry.unwrap();
-
-        // Get sample paths (could be array or object)
-        let sample_paths: Vec<String> = if let Some(arr) = bank_entry.as_array() {
-            arr.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect()
-        } else {
+        let Some((base_url, sample_paths)) =
+            sample_map_entry(&json, bank_name, &self.fallback_base_url)
+        else {
             return Ok(None);
         };
 
-        if sample_paths.is_empty() {
-            return Ok(None);
-        }
-
         // Download and decode each sample
         let mut bank = SampleBank::new(bank_name.to_string());
 
         for (i, path) in sample_paths.iter().enumerate() {
-            let sample_url = format!("{}/{}", self.fallback_base_url, path);
+            let sample_url = format!("{}/{}", base_url, path);
 
             // Download the sample
             let bytes = reqwest::blocking::get(&sample_url)
@@ -421,8 +471,135 @@ impl SampleLoader {
     }
 }
 
+/// Look up a bank in a `strudel.json` sample map
+///
+/// Returns the base URL its sample paths are relative to and the paths
+/// themselves, or None if the map has no samples for the bank. Maps can set
+/// their own base URL with a `_base` entry, as drum machine maps do for
+/// banked names like `RolandTR909_bd`; otherwise `fallback_base_url` is used.
+fn sample_map_entry(
+    json: &serde_json::Value,
+    bank_name: &str,
+    fallback_base_url: &str,
+) -> Option<(String, Vec<String>)> {
+    let sample_paths: Vec<String> = json
+        .get(bank_name)?
+        .as_array()?
+        .iter()
+        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+        .collect();
+    if sample_paths.is_empty() {
+        return None;
+    }
+
+    let base_url = json
+        .get("_base")
+        .and_then(|base| base.as_str())
+        .unwrap_or(fallback_base_url)
+        .trim_end_matches('/')
+        .to_string();
+
+    Some((base_url, sample_paths))
+}
+
 impl Default for SampleLoader {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_banks() {
+        let banks = SampleLoader::bundled_banks();
+        let expected = [("bd", 5), ("sd", 2), ("hh", 3), ("cp", 2)];
+        for (name, count) in expected {
+            assert!(
+                banks.contains(&(name.to_string(), count)),
+                "{} with {} samples missing from {:?}",
+                name,
+                count,
+                banks
+            );
+        }
+
+        // Every listed bank loads offline with that many samples
+        let loader = SampleLoader::new().with_fallback_url("http://localhost:0".to_string());
+        for (name, count) in banks {
+            loader.load_bank(&name).unwrap();
+            assert_eq!(loader.banks.read()[&name].len(), count);
+        }
+    }
+
+    #[test]
+    fn test_sample_map_entry() {
+        let fallback = "https://example.com/samples";
+        let json = serde_json::json!({
+            "bd": ["bd/BT0A0A7.wav", "bd/BT0AADA.wav"],
+            "empty": [],
+        });
+        let (base, paths) = sample_map_entry(&json, "bd", fallback).unwrap();
+        assert_eq!(base, fallback);
+        assert_eq!(paths, vec!["bd/BT0A0A7.wav", "bd/BT0AADA.wav"]);
+        assert!(sample_map_entry(&json, "empty", fallback).is_none());
+        assert!(sample_map_entry(&json, "sd", fallback).is_none());
+
+        // Drum machine maps set their own base for banked names
+        let json = serde_json::json!({
+            "_base": "https://example.com/machines/",
+            "RolandTR909_bd": ["RolandTR909/rolandtr909-bd/Bassdrum-01.wav"],
+        });
+        let (base, paths) = sample_map_entry(&json, "RolandTR909_bd", fallback).unwrap();
+        assert_eq!(base, "https://example.com/machines");
+        assert_eq!(paths.len(), 1);
+    }
+
+    #[test]
+    fn test_load_from_dir() {
+        let dir = std::env::temp_dir().join("strudel-audio-test-kit");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Two banks of short mono WAVs, plus a file that isn't audio
+        let write_wav = |path: &Path, value: i16| {
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: 44100,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = hound::WavWriter::create(path, spec).unwrap();
+            for _ in 0..100 {
+                writer.write_sample(value).unwrap();
+            }
+            writer.finalize().unwrap();
+        };
+        for bank in ["kick", "snare"] {
+            std::fs::create_dir_all(dir.join(bank)).unwrap();
+        }
+        write_wav(&dir.join("kick/b.wav"), 16384);
+        write_wav(&dir.join("kick/a.wav"), -16384);
+        write_wav(&dir.join("snare/one.wav"), 8192);
+        std::fs::write(dir.join("snare/notes.txt"), "not audio").unwrap();
+
+        let loader = SampleLoader::new();
+        let loaded = loader.load_from_dir(&dir).unwrap();
+        assert_eq!(loaded, vec!["kick", "snare"]);
+
+        // Samples are indexed in file name order
+        let kick = loader.get_sample("kick", 0).unwrap();
+        assert_eq!(kick.name, "kick/a.wav");
+        assert_eq!(kick.data[0], -0.5);
+        assert_eq!(loader.get_sample("kick", 1).unwrap().data[0], 0.5);
+        assert_eq!(loader.get_sample("snare", 0).unwrap().frames(), 100);
+        assert_eq!(loader.banks.read()["snare"].len(), 1);
+
+        // Loaded banks are cached, so loading again adds nothing
+        assert!(loader.load_from_dir(&dir).unwrap().is_empty());
+        loader.load_bank("kick").unwrap();
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
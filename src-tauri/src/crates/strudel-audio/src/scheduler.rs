@@ -1,10 +1,25 @@
 //! Scheduler for triggering pattern events at precise times
 
-use crate::{Fraction, Hap, Pattern, SampleLoader, State, TimeSpan, Value, Voice};
+use crate::effects::{DEFAULT_DELAY_FEEDBACK, DEFAULT_DELAY_TIME};
+use crate::voice::DEFAULT_ATTACK;
+use crate::{DelayLine, Fraction, Hap, Pattern, SampleLoader, State, TimeSpan, Value, Voice};
 use parking_lot::Mutex;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// Most voices that play at once unless configured otherwise
+pub const DEFAULT_MAX_VOICES: usize = 64;
+
+/// Effect bus that voices on the same orbit share
+#[derive(Default)]
+struct Orbit {
+    /// Feedback delay that the orbit's voices send to
+    delay: DelayLine,
+    /// Mix of the voices' delay sends for the current buffer
+    delay_send: Vec<f32>,
+}
+
 /// Scheduler for querying patterns and triggering samples
 pub struct Scheduler {
     /// Sample loader
@@ -15,8 +30,18 @@ pub struct Scheduler {
     tempo: f64,
     /// When playback started
     start_time: Instant,
-    /// Current cycle position
-    current_cycle: Fraction,
+    /// Cycle position up to which events have been triggered
+    scheduled_until: Fraction,
+    /// Cycle position at which playback ends, or None to loop forever
+    end_cycle: Option<Fraction>,
+    /// Whether new events are suppressed so playing voices can ring out
+    hushed: bool,
+    /// Most voices that play at once; the oldest is stopped to make room
+    max_voices: usize,
+    /// Effect buses by orbit number, created when first sent to
+    orbits: BTreeMap<usize, Orbit>,
+    /// Output of a single voice, for splitting it between the mix and its sends
+    voice_buffer: Vec<f32>,
 }
 
 impl Scheduler {
@@ -27,7 +52,12 @@ impl Scheduler {
             voices: Arc::new(Mutex::new(Vec::new())),
             tempo,
             start_time: Instant::now(),
-            current_cycle: Fraction::from(0),
+            scheduled_until: Fraction::from(0),
+            end_cycle: None,
+            hushed: false,
+            max_voices: DEFAULT_MAX_VOICES,
+            orbits: BTreeMap::new(),
+            voice_buffer: Vec::new(),
         }
     }
 
@@ -41,57 +71,146 @@ impl Scheduler {
         self.tempo
     }
 
+    /// Limit how many voices play at once (at least one)
+    ///
+    /// When a new event would go over the limit, the oldest voice is stopped.
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices.max(1);
+    }
+
     /// Get the current time in cycles since start
     pub fn current_time(&self) -> Fraction {
-        let elapsed = self.start_time.elapsed();
-        let seconds = elapsed.as_secs_f64();
+        Fraction::from_float(self.current_cycle())
+    }
+
+    /// Get the current position in cycles since start, as a float
+    ///
+    /// The fractional part is the phase within the current cycle.
+    pub fn current_cycle(&self) -> f64 {
+        let seconds = self.start_time.elapsed().as_secs_f64();
 
         // Convert seconds to cycles based on tempo
         // tempo is in beats per minute, assuming 4 beats per cycle
         let cycles_per_second = self.tempo / 60.0 / 4.0;
-        let cycles = seconds * cycles_per_second;
+        seconds * cycles_per_second
+    }
+
+    /// Stop triggering events at a cycle position, or None to loop forever
+    ///
+    /// Events starting at or after `end` are never triggered, so playing a
+    /// whole number of cycles ends cleanly on a cycle boundary.
+    pub fn set_end_cycle(&mut self, end: Option<Fraction>) {
+        self.end_cycle = end;
+    }
+
+    /// Check if playback has reached the end cycle
+    pub fn is_finished(&self) -> bool {
+        self.end_cycle.is_some_and(|end| self.scheduled_until >= end)
+    }
+
+    /// Stop triggering new events, letting active voices play to the end
+    ///
+    /// Unlike `reset`, voices that are already sounding keep going, so
+    /// playback ends with their natural tails instead of a hard cut.
+    pub fn hush(&mut self) {
+        self.hushed = true;
+    }
 
-        Fraction::from_float(cycles)
+    /// Check if the scheduler has been hushed
+    pub fn is_hushed(&self) -> bool {
+        self.hushed
     }
 
     /// Query a pattern for the current time window and trigger any new events
     pub fn update(&mut self, pattern: &Pattern, lookahead: Duration) {
+        for hap in self.due_onsets(pattern, lookahead) {
+            self.trigger_hap(&hap);
+        }
+    }
+
+    /// Take the events due within the lookahead, without triggering them
+    ///
+    /// Each onset is returned once across calls, so other outputs (like OSC)
+    /// can follow the same timeline as `update`. Returns nothing while hushed.
+    pub fn due_onsets(&mut self, pattern: &Pattern, lookahead: Duration) -> Vec<Hap> {
         let now = self.current_time();
+        if self.hushed {
+            self.scheduled_until = self.scheduled_until.max(now);
+            return Vec::new();
+        }
         let lookahead_cycles = Fraction::from_float(
             lookahead.as_secs_f64() * self.tempo / 60.0 / 4.0
         );
 
-        // Query the pattern for events in the lookahead window
-        let span = TimeSpan::new(now, now + lookahead_cycles);
-        let state = State::new(span);
-        let haps = pattern.query(state);
+        self.onsets_until(pattern, now + lookahead_cycles)
+    }
 
-        // Trigger each event
-        for hap in haps {
-            if hap.part.begin >= self.current_cycle {
-                self.trigger_hap(&hap);
-            }
+    /// Get the time from the start of playback to a cycle position
+    pub fn time_of_cycle(&self, cycle: Fraction) -> Duration {
+        Duration::from_secs_f64((cycle.to_float() * 4.0 * 60.0 / self.tempo).max(0.0))
+    }
+
+    /// Trigger the events that start between the last update and `until`
+    fn schedule_until(&mut self, pattern: &Pattern, until: Fraction) {
+        for hap in self.onsets_until(pattern, until) {
+            self.trigger_hap(&hap);
+        }
+    }
+
+    /// Take the events that start between the last update and `until`
+    fn onsets_until(&mut self, pattern: &Pattern, until: Fraction) -> Vec<Hap> {
+        let until = match self.end_cycle {
+            Some(end) => until.min(end),
+            None => until,
+        };
+        if until <= self.scheduled_until {
+            return Vec::new();
         }
 
-        self.current_cycle = now;
+        // Query only the time not yet scheduled, so each onset triggers once
+        let span = TimeSpan::new(self.scheduled_until, until);
+        let state = State::new(span);
+        let haps = pattern.query(state);
+
+        self.scheduled_until = until;
+        haps.into_iter().filter(|hap| hap.has_onset()).collect()
     }
 
     /// Trigger a single hap (event)
     fn trigger_hap(&mut self, hap: &Hap) {
-        // Extract sample name and index from the value
-        let (sample_name, index, gain, speed) = match &hap.value {
-            Value::String(s) => {
-                // Parse "bd:0" or just "bd"
-                if let Some((name, idx)) = s.split_once(':') {
-                    let index = idx.parse::<usize>().unwrap_or(0);
-                    (name.to_string(), index, 1.0, 1.0)
-                } else {
-                    (s.clone(), 0, 1.0, 1.0)
-                }
-            }
-            _ => return, // Skip non-string values for now
+        let Some((sample_name, index)) = sample_of(hap) else {
+            return; // Skip non-string values for now
+        };
+
+        // Gain set by `velocity` (or other controls) overrides the default
+        let gain = match hap.context.metadata.get("gain") {
+            Some(Value::Number(g)) => *g as f32,
+            _ => 1.0,
         };
 
+        // Envelope set by `attack`, `decay`, `sustain` and `release`
+        let control = |name: &str| match hap.context.metadata.get(name) {
+            Some(Value::Number(v)) => Some(*v),
+            _ => None,
+        };
+        let release = control("release");
+
+        // Effects are shared by the voices on an orbit, 0 unless set by `orbit`
+        let orbit = control("orbit").map_or(0, |o| o.max(0.0) as usize);
+
+        // Delay send set by `delay`; the time and feedback apply to the orbit's delay
+        let delay = control("delay").unwrap_or(0.0) as f32;
+        if delay > 0.0 {
+            let line = &mut self.orbits.entry(orbit).or_default().delay;
+            line.set_time(control("delaytime").unwrap_or(DEFAULT_DELAY_TIME));
+            line.set_feedback(
+                control("delayfeedback").map_or(DEFAULT_DELAY_FEEDBACK, |f| f as f32),
+            );
+        }
+
+        // Playback speed set by `speed`, negative to play the sample backwards
+        let speed = control("speed").unwrap_or(1.0);
+
         // Try to load the sample
         if self.loader.load_bank(&sample_name).is_err() {
             // Sample not available
@@ -101,39 +220,146 @@ impl Scheduler {
         // Get the sample
         if let Ok(sample) = self.loader.get_sample(&sample_name, index) {
             // Create a voice
-            let voice = Voice::new(Arc::new(sample))
+            let mut voice = Voice::new(Arc::new(sample))
                 .set_gain(gain)
-                .set_speed(speed);
+                .set_speed(speed)
+                .set_delay(delay)
+                .set_orbit(orbit)
+                .set_envelope(
+                    control("attack").unwrap_or(DEFAULT_ATTACK),
+                    control("decay").unwrap_or(0.0),
+                    control("sustain").unwrap_or(1.0),
+                    release.unwrap_or(0.0),
+                );
+
+            // Part of the sample to play, set by `begin`/`end` (or `chop`, `striate` and `slice`)
+            let (begin, end) = (control("begin"), control("end"));
+            if begin.is_some() || end.is_some() {
+                voice = voice.set_slice(begin.unwrap_or(0.0), end.unwrap_or(1.0));
+            }
+
+            // Low-pass filter set by `lpf` (or its alias `cutoff`)
+            if let Some(cutoff) = control("lpf").or_else(|| control("cutoff")) {
+                voice = voice.set_lpf(cutoff);
+            }
 
-            // Add to active voices
-            self.voices.lock().push(voice);
+            // With a release set, the note is released when the event ends
+            if let (Some(_), Some(whole)) = (release, hap.whole) {
+                let cycles = (whole.end - whole.begin).to_float();
+                voice = voice.set_hold(cycles * 4.0 * 60.0 / self.tempo);
+            }
+
+            // Add to active voices, stealing the oldest if at the limit
+            let mut voices = self.voices.lock();
+            if voices.len() >= self.max_voices {
+                let excess = voices.len() + 1 - self.max_voices;
+                voices.drain(..excess);
+            }
+            voices.push(voice);
         }
     }
 
     /// Fill an audio buffer with the output of all active voices
     pub fn fill_buffer(&mut self, buffer: &mut [f32], sample_rate: u32) {
-        // Clear buffer
+        // Clear buffers
         buffer.fill(0.0);
+        for orbit in self.orbits.values_mut() {
+            orbit.delay_send.clear();
+            orbit.delay_send.resize(buffer.len(), 0.0);
+        }
+        self.voice_buffer.resize(buffer.len(), 0.0);
 
-        // Mix all voices
+        // Mix all voices, collecting what they send to their orbit's delay
         let mut voices = self.voices.lock();
         voices.retain_mut(|voice| {
-            voice.fill_buffer(buffer, sample_rate);
+            let send = voice.delay_send();
+            if send > 0.0 {
+                let orbit = self.orbits.entry(voice.orbit()).or_default();
+                orbit.delay_send.resize(buffer.len(), 0.0);
+
+                self.voice_buffer.fill(0.0);
+                voice.fill_buffer(&mut self.voice_buffer, sample_rate);
+                for ((out, wet), &dry) in buffer
+                    .iter_mut()
+                    .zip(orbit.delay_send.iter_mut())
+                    .zip(&self.voice_buffer)
+                {
+                    *out += dry;
+                    *wet += dry * send;
+                }
+            } else {
+                voice.fill_buffer(buffer, sample_rate);
+            }
             voice.is_active()
         });
+
+        for orbit in self.orbits.values_mut() {
+            orbit.delay.process(buffer, &orbit.delay_send, sample_rate);
+        }
+    }
+
+    /// Render a pattern offline into an interleaved stereo buffer
+    ///
+    /// Time advances with the rendered frames rather than the clock, so the
+    /// same pattern always renders the same audio. The scheduler is reset first.
+    pub fn render(&mut self, pattern: &Pattern, duration_secs: f64, sample_rate: u32) -> Vec<f32> {
+        // Small blocks keep onsets close to their exact frame
+        const BLOCK_FRAMES: usize = 64;
+
+        self.reset();
+
+        let frames = (duration_secs * sample_rate as f64).round() as usize;
+        let cycles_per_frame = self.tempo / 60.0 / 4.0 / sample_rate as f64;
+        let mut buffer = vec![0.0; frames * 2];
+
+        for (index, block) in buffer.chunks_mut(BLOCK_FRAMES * 2).enumerate() {
+            let block_end = (index * BLOCK_FRAMES + block.len() / 2) as f64;
+            self.schedule_until(pattern, Fraction::from_float(block_end * cycles_per_frame));
+            self.fill_buffer(block, sample_rate);
+        }
+
+        buffer
     }
 
     /// Reset the scheduler
     pub fn reset(&mut self) {
         self.start_time = Instant::now();
-        self.current_cycle = Fraction::from(0);
+        self.scheduled_until = Fraction::from(0);
+        self.hushed = false;
         self.voices.lock().clear();
+        for orbit in self.orbits.values_mut() {
+            orbit.delay.clear();
+        }
     }
 }
 
+/// Get the sample name and index a hap plays
+///
+/// The value names the sample, as `bd` or `bd:3`; an index set by `n`
+/// overrides the one in the name. Returns None for non-string values.
+pub(crate) fn sample_of(hap: &Hap) -> Option<(String, usize)> {
+    let (name, index) = match &hap.value {
+        // Parse "bd:0" or just "bd"
+        Value::String(s) => match s.split_once(':') {
+            Some((name, idx)) => (name.to_string(), idx.parse::<usize>().unwrap_or(0)),
+            None => (s.clone(), 0),
+        },
+        _ => return None,
+    };
+
+    // Sample index set by `n` (mini notation `bd:3`) overrides the default
+    let index = match hap.context.metadata.get("n") {
+        Some(Value::Number(n)) if *n >= 0.0 => *n as usize,
+        _ => index,
+    };
+
+    Some((name, index))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Sample;
 
     #[test]
     fn test_scheduler_timing() {
@@ -144,4 +370,141 @@ mod tests {
         let time = scheduler.current_time();
         assert_eq!(time, Fraction::from(0));
     }
+
+    #[test]
+    fn test_current_cycle_follows_tempo() {
+        let loader = Arc::new(SampleLoader::new());
+        let mut scheduler = Scheduler::new(loader, 1200.0);
+
+        // At 1200 BPM (5 cycles per second), 100ms is half a cycle. The
+        // sleep can overrun, so allow up to however long actually passed.
+        let start = Instant::now();
+        scheduler.reset();
+        std::thread::sleep(Duration::from_millis(100));
+        let cycle = scheduler.current_cycle();
+        let most = start.elapsed().as_secs_f64() * 5.0;
+        assert!(cycle >= 0.5 && cycle <= most, "cycle was {}", cycle);
+    }
+
+    #[test]
+    fn test_hush_lets_voices_ring_out() {
+        let loader = Arc::new(SampleLoader::new());
+        let mut scheduler = Scheduler::new(loader, 120.0);
+        let pattern = strudel_core::pure(Value::String("bd".into()));
+        let lookahead = Duration::from_millis(100);
+        let mut buffer = vec![0.0; 512];
+
+        scheduler.update(&pattern, lookahead);
+        assert_eq!(scheduler.voices.lock().len(), 1);
+
+        scheduler.hush();
+        assert!(scheduler.is_hushed());
+
+        // No new onsets after hush
+        scheduler.update(&pattern, lookahead);
+        assert_eq!(scheduler.voices.lock().len(), 1);
+
+        // The voice that was already playing keeps sounding
+        scheduler.fill_buffer(&mut buffer, 44100);
+        assert!(buffer.iter().any(|&s| s != 0.0));
+        assert_eq!(scheduler.voices.lock().len(), 1);
+
+        // Resetting clears the hush
+        scheduler.reset();
+        assert!(!scheduler.is_hushed());
+    }
+
+    #[test]
+    fn test_end_cycle_plays_whole_cycles() {
+        let loader = Arc::new(SampleLoader::new());
+        let mut scheduler = Scheduler::new(loader, 120.0);
+        let bd = strudel_core::pure(Value::String("bd".into()));
+        let pattern = strudel_core::fastcat(vec![bd.clone(), bd]);
+
+        scheduler.set_end_cycle(Some(Fraction::from(4)));
+
+        // Step through time in uneven increments, well past the end
+        let mut until = Fraction::from(0);
+        while until < Fraction::from(6) {
+            until = until + Fraction::new(3, 10);
+            scheduler.schedule_until(&pattern, until);
+        }
+
+        // Two onsets per cycle for exactly four cycles, each triggered once
+        assert!(scheduler.is_finished());
+        assert_eq!(scheduler.voices.lock().len(), 8);
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let loader = Arc::new(SampleLoader::new());
+        let mut scheduler = Scheduler::new(loader, 120.0);
+        let pattern = strudel_core::pure(Value::String("bd".into())).fast(4.0);
+
+        let first = scheduler.render(&pattern, 0.5, 44100);
+        let second = scheduler.render(&pattern, 0.5, 44100);
+
+        assert_eq!(first.len(), 44100);
+        assert!(first.iter().any(|&s| s != 0.0));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_max_voices_steals_oldest() {
+        let loader = Arc::new(SampleLoader::new());
+        let mut scheduler = Scheduler::new(loader, 120.0);
+        scheduler.set_max_voices(16);
+
+        // 100 simultaneous triggers
+        let bd = strudel_core::pure(Value::String("bd".into()));
+        let pattern = strudel_core::stack(vec![bd; 100]);
+        scheduler.schedule_until(&pattern, Fraction::new(1, 2));
+
+        assert_eq!(scheduler.voices.lock().len(), 16);
+    }
+
+    #[test]
+    fn test_orbits_have_independent_delays() {
+        let loader = Arc::new(SampleLoader::new());
+        let mut scheduler = Scheduler::new(loader, 120.0);
+
+        // A single-frame click, so each repeat shows up as one frame
+        let click = Arc::new(Sample {
+            name: "click".into(),
+            index: 0,
+            data: Arc::new(vec![1.0, 1.0]),
+            sample_rate: 1000,
+            channels: 2,
+        });
+        let voice = |orbit| {
+            Voice::new(click.clone())
+                .set_envelope(0.0, 0.0, 1.0, 0.0)
+                .set_delay(1.0)
+                .set_orbit(orbit)
+        };
+        scheduler.voices.lock().extend([voice(0), voice(1)]);
+
+        let mut delay = |orbit, time| {
+            let line = &mut scheduler.orbits.entry(orbit).or_default().delay;
+            line.set_time(time);
+            line.set_feedback(0.5);
+        };
+        delay(0, 0.1);
+        delay(1, 0.3);
+
+        let mut buffer = vec![0.0; 1000];
+        scheduler.fill_buffer(&mut buffer, 1000);
+
+        // Orbit 0 repeats every 100 frames and orbit 1 every 300, each decaying
+        let left: Vec<(usize, f32)> = buffer
+            .chunks(2)
+            .enumerate()
+            .filter(|(_, frame)| frame[0] != 0.0)
+            .map(|(i, frame)| (i, frame[0]))
+            .collect();
+        assert_eq!(
+            left,
+            vec![(0, 2.0), (100, 1.0), (200, 0.5), (300, 1.25), (400, 0.125)]
+        );
+    }
 }
@@ -3,16 +3,43 @@
 use crate::Sample;
 use std::sync::Arc;
 
+/// Attack time (in seconds) when none is set, short enough to sound instant
+pub const DEFAULT_ATTACK: f64 = 0.001;
+
 /// A voice for playing back a single sample
 pub struct Voice {
     /// The sample being played
     sample: Arc<Sample>,
     /// Current playback position (in frames)
     position: f64,
-    /// Playback speed multiplier (1.0 = normal, 2.0 = double speed)
+    /// Playback speed multiplier (1.0 = normal, 2.0 = double speed, negative = reversed)
     speed: f64,
+    /// Where in the sample playback starts, as a fraction of its length
+    begin: f64,
+    /// Where in the sample playback stops, as a fraction of its length
+    end: f64,
     /// Gain/volume (0.0 to 1.0)
     gain: f32,
+    /// Envelope attack time in seconds (silence to full level)
+    attack: f64,
+    /// Envelope decay time in seconds (full level to the sustain level)
+    decay: f64,
+    /// Envelope sustain level (0.0 to 1.0)
+    sustain: f64,
+    /// Envelope release time in seconds (to silence, after the hold time)
+    release: f64,
+    /// How long the note is held before releasing, or None to play the whole sample
+    hold: Option<f64>,
+    /// Number of output frames played so far
+    elapsed_frames: u64,
+    /// Low-pass filter cutoff in Hz, or None for no filtering
+    lpf: Option<f64>,
+    /// Low-pass filter output for the previous frame (L, R)
+    filter_state: (f32, f32),
+    /// How much of the output is sent to the delay (0.0 to 1.0)
+    delay: f32,
+    /// Effect bus (orbit) the delay send goes to
+    orbit: usize,
     /// Whether this voice is still active
     active: bool,
 }
@@ -24,23 +51,152 @@ impl Voice {
             sample,
             position: 0.0,
             speed: 1.0,
+            begin: 0.0,
+            end: 1.0,
             gain: 1.0,
+            attack: DEFAULT_ATTACK,
+            decay: 0.0,
+            sustain: 1.0,
+            release: 0.0,
+            hold: None,
+            elapsed_frames: 0,
+            lpf: None,
+            filter_state: (0.0, 0.0),
+            delay: 0.0,
+            orbit: 0,
             active: true,
         }
     }
 
     /// Set the playback speed
+    ///
+    /// A negative speed plays the sample backwards from its end, and a speed
+    /// of zero plays nothing.
     pub fn set_speed(mut self, speed: f64) -> Self {
         self.speed = speed;
+        self.position = self.start_position();
+        self.active = speed != 0.0;
+        self
+    }
+
+    /// Play only the part of the sample between `begin` and `end`
+    ///
+    /// Both are fractions of the sample's length, clamped to 0.0..=1.0 with
+    /// `end` no earlier than `begin`. Reversed playback runs from `end` back
+    /// to `begin`.
+    pub fn set_slice(mut self, begin: f64, end: f64) -> Self {
+        self.begin = begin.clamp(0.0, 1.0);
+        self.end = end.clamp(self.begin, 1.0);
+        self.position = self.start_position();
         self
     }
 
+    /// First frame of the slice being played, and the frame just past its end
+    fn frame_range(&self) -> (f64, f64) {
+        let frames = self.sample.frames() as f64;
+        ((self.begin * frames).round(), (self.end * frames).round())
+    }
+
+    /// Where playback starts: the first frame of the slice, or its last when reversed
+    fn start_position(&self) -> f64 {
+        let (start, end) = self.frame_range();
+        if self.speed < 0.0 {
+            (end - 1.0).max(start)
+        } else {
+            start
+        }
+    }
+
     /// Set the gain
     pub fn set_gain(mut self, gain: f32) -> Self {
         self.gain = gain.clamp(0.0, 1.0);
         self
     }
 
+    /// Set the amplitude envelope
+    ///
+    /// Times are in seconds, `sustain` is a level between 0.0 and 1.0. The
+    /// release only starts once the hold time (see `set_hold`) has passed.
+    pub fn set_envelope(mut self, attack: f64, decay: f64, sustain: f64, release: f64) -> Self {
+        self.attack = attack.max(0.0);
+        self.decay = decay.max(0.0);
+        self.sustain = sustain.clamp(0.0, 1.0);
+        self.release = release.max(0.0);
+        self
+    }
+
+    /// Release the note after `seconds` instead of playing the whole sample
+    pub fn set_hold(mut self, seconds: f64) -> Self {
+        self.hold = Some(seconds.max(0.0));
+        self
+    }
+
+    /// Apply a one-pole low-pass filter with the given cutoff in Hz
+    pub fn set_lpf(mut self, cutoff: f64) -> Self {
+        self.lpf = Some(cutoff.max(0.0));
+        self
+    }
+
+    /// Send part of the output (0.0 to 1.0) to the delay
+    pub fn set_delay(mut self, amount: f32) -> Self {
+        self.delay = amount.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How much of the output is sent to the delay
+    pub fn delay_send(&self) -> f32 {
+        self.delay
+    }
+
+    /// Route the voice's effect sends to an orbit
+    pub fn set_orbit(mut self, orbit: usize) -> Self {
+        self.orbit = orbit;
+        self
+    }
+
+    /// The orbit the voice's effect sends go to
+    pub fn orbit(&self) -> usize {
+        self.orbit
+    }
+
+    /// Run a frame through the low-pass filter, if there is one
+    fn filter(&mut self, left: f32, right: f32, output_sample_rate: u32) -> (f32, f32) {
+        let Some(cutoff) = self.lpf else {
+            return (left, right);
+        };
+
+        // Each frame moves the output towards the input by a fixed fraction
+        let alpha = 1.0 - (-std::f64::consts::TAU * cutoff / output_sample_rate as f64).exp();
+        let alpha = alpha as f32;
+        let (prev_left, prev_right) = self.filter_state;
+        self.filter_state = (
+            prev_left + alpha * (left - prev_left),
+            prev_right + alpha * (right - prev_right),
+        );
+        self.filter_state
+    }
+
+    /// Envelope level at `time` seconds into the note, or None once released
+    fn envelope_at(&self, time: f64) -> Option<f64> {
+        let adsr = |time: f64| {
+            if time < self.attack {
+                time / self.attack
+            } else if time < self.attack + self.decay {
+                1.0 - (1.0 - self.sustain) * (time - self.attack) / self.decay
+            } else {
+                self.sustain
+            }
+        };
+
+        match self.hold {
+            Some(hold) if time >= hold => {
+                let released = (time - hold) / self.release;
+                (released < 1.0).then(|| adsr(hold) * (1.0 - released))
+            }
+            _ => Some(adsr(time)),
+        }
+    }
+
     /// Check if this voice is still active
     pub fn is_active(&self) -> bool {
         self.active
@@ -54,9 +210,9 @@ impl Voice {
             return None;
         }
 
-        let frames = self.sample.frames();
+        let (start, end) = self.frame_range();
 
-        if self.position >= frames as f64 {
+        if self.position >= end || self.position < start {
             self.active = false;
             return None;
         }
@@ -75,9 +231,17 @@ impl Voice {
             (left, right)
         };
 
-        // Apply gain
-        let left = left * self.gain;
-        let right = right * self.gain;
+        let (left, right) = self.filter(left, right, output_sample_rate);
+
+        // Apply gain and the envelope
+        let time = self.elapsed_frames as f64 / output_sample_rate as f64;
+        let Some(envelope) = self.envelope_at(time) else {
+            self.active = false;
+            return None;
+        };
+        self.elapsed_frames += 1;
+        let left = left * self.gain * envelope as f32;
+        let right = right * self.gain * envelope as f32;
 
         // Advance position (accounting for sample rate differences and speed)
         let rate_ratio = self.sample.sample_rate as f64 / output_sample_rate as f64;
@@ -307,4 +471,156 @@ mod tests {
         // The position should advance by 2.0 per call
         assert!(voice.position >= 1.9 && voice.position <= 2.1);
     }
+
+    /// A mono sample of constant full level, `seconds` long at 44.1kHz
+    fn constant_sample(seconds: f64) -> Arc<Sample> {
+        let frames = (44100.0 * seconds) as usize;
+        Arc::new(Sample {
+            name: "test_constant".to_string(),
+            index: 0,
+            data: Arc::new(vec![1.0; frames]),
+            sample_rate: 44100,
+            channels: 1,
+        })
+    }
+
+    #[test]
+    fn test_attack_ramps_to_full_gain() {
+        let mut voice = Voice::new(constant_sample(0.2)).set_envelope(0.1, 0.0, 1.0, 0.0);
+        let levels: Vec<f32> = (0..8820)
+            .map(|_| voice.next_sample(44100).unwrap().0)
+            .collect();
+
+        // Silent at the start, halfway at 0.05s, full from 0.1s on
+        assert_eq!(levels[0], 0.0);
+        assert!((levels[2205] - 0.5).abs() < 0.001, "{}", levels[2205]);
+        assert!(levels.windows(2).take(4410).all(|pair| pair[0] < pair[1]));
+        assert!(levels[4410..].iter().all(|&level| level == 1.0));
+    }
+
+    #[test]
+    fn test_release_after_hold() {
+        let mut voice = Voice::new(constant_sample(1.0))
+            .set_envelope(0.0, 0.1, 0.5, 0.1)
+            .set_hold(0.2);
+        let mut levels = Vec::new();
+        while let Some((left, _)) = voice.next_sample(44100) {
+            levels.push(left);
+        }
+
+        // Decays to the sustain level, then fades out over the release time
+        assert_eq!(levels[0], 1.0);
+        assert_eq!(levels[6615], 0.5);
+        assert!((levels[11025] - 0.25).abs() < 0.001, "{}", levels[11025]);
+        assert!((13230..=13231).contains(&levels.len()), "{}", levels.len());
+        assert!(!voice.is_active());
+    }
+
+    #[test]
+    fn test_lpf_attenuates_high_frequencies() {
+        // A tone at the Nyquist frequency (alternating full-scale frames)
+        let data: Vec<f32> = (0..4410)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let sample = Arc::new(Sample {
+            name: "test_nyquist".to_string(),
+            index: 0,
+            data: Arc::new(data),
+            sample_rate: 44100,
+            channels: 1,
+        });
+        let energy = |mut voice: Voice| {
+            let mut total = 0.0;
+            while let Some((left, _)) = voice.next_sample(44100) {
+                total += left * left;
+            }
+            total
+        };
+
+        let dry = energy(Voice::new(sample.clone()).set_envelope(0.0, 0.0, 1.0, 0.0));
+        let filtered = energy(
+            Voice::new(sample)
+                .set_envelope(0.0, 0.0, 1.0, 0.0)
+                .set_lpf(1000.0),
+        );
+        assert!(filtered < dry * 0.01, "{} vs {}", filtered, dry);
+
+        // Low frequencies pass: a constant signal settles at full level
+        let mut voice = Voice::new(constant_sample(0.1)).set_lpf(1000.0);
+        let last = std::iter::from_fn(|| voice.next_sample(44100))
+            .last()
+            .unwrap();
+        assert!((last.0 - 1.0).abs() < 0.001, "{}", last.0);
+    }
+
+    #[test]
+    fn test_speed_changes_playback_duration() {
+        let ramp: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        let sample = Arc::new(Sample {
+            name: "test_ramp".to_string(),
+            index: 0,
+            data: Arc::new(ramp),
+            sample_rate: 44100,
+            channels: 1,
+        });
+        let play = |speed: f64, output_sample_rate: u32| {
+            let mut voice = Voice::new(sample.clone())
+                .set_envelope(0.0, 0.0, 1.0, 0.0)
+                .set_speed(speed);
+            std::iter::from_fn(|| voice.next_sample(output_sample_rate))
+                .map(|(left, _)| left)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(play(1.0, 44100).len(), 1000);
+        assert_eq!(play(2.0, 44100).len(), 500);
+
+        // The sample's own rate is accounted for: at twice the output rate,
+        // double speed takes a quarter of the frames
+        assert_eq!(play(2.0, 22050).len(), 250);
+
+        // Negative speed plays backwards from the end
+        let reversed = play(-1.0, 44100);
+        assert_eq!(reversed.len(), 1000);
+        assert_eq!(reversed[0], 0.999);
+        assert_eq!(reversed[999], 0.0);
+
+        assert!(play(0.0, 44100).is_empty());
+    }
+
+    #[test]
+    fn test_slice_playback() {
+        let ramp: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        let sample = Arc::new(Sample {
+            name: "test_slice".to_string(),
+            index: 0,
+            data: Arc::new(ramp),
+            sample_rate: 44100,
+            channels: 1,
+        });
+        let play = |voice: Voice| {
+            let mut voice = voice.set_envelope(0.0, 0.0, 1.0, 0.0);
+            std::iter::from_fn(|| voice.next_sample(44100))
+                .map(|(left, _)| left)
+                .collect::<Vec<_>>()
+        };
+
+        // The second half starts at frame 500 and plays 500 frames
+        let second_half = play(Voice::new(sample.clone()).set_slice(0.5, 1.0));
+        assert_eq!(second_half.len(), 500);
+        assert_eq!(second_half[0], 0.5);
+        assert_eq!(second_half[499], 0.999);
+
+        // Reversed, the slice plays from its end back to its start
+        let backwards = Voice::new(sample.clone()).set_speed(-1.0);
+        let reversed = play(backwards.set_slice(0.25, 0.5));
+        assert_eq!(reversed.len(), 250);
+        assert_eq!(reversed[0], 0.499);
+        assert_eq!(reversed[249], 0.25);
+
+        // Out of range values are clamped, and an empty slice plays nothing
+        let clamped = play(Voice::new(sample.clone()).set_slice(-1.0, 2.0));
+        assert_eq!(clamped.len(), 1000);
+        assert!(play(Voice::new(sample).set_slice(0.75, 0.5)).is_empty());
+    }
 }
@@ -0,0 +1,197 @@
+//! Chord names for expanding a symbol like `c:maj7` into its notes
+//!
+//! A chord is a root followed by a quality, either separated by a colon
+//! (`"c:maj7"`, `"Eb4:min"`) or written together (`"Am"`, `"Cmaj7"`). A bare
+//! root like `"c"` is a note, not a chord, and so is a root followed by a
+//! number (`"g7"` is the note G7).
+//!
+//! The numeric qualities `7`, `6` and `9` parse here in the colon form, but
+//! mini notation reads `g:7` as sample index 7 of `g`, so patterns can't use
+//! them. Write `dom7`, `maj6` and `dom9` instead.
+//!
+//! Mini notation only reads the colon form as a chord, since compact
+//! names like `em` or `am` are also common sample names.
+//!
+//! Notes are in octave 3 unless the colon form gives the root an octave.
+
+use crate::scale::{midi_to_note, parse_root, uses_flats};
+
+/// A chord rooted at a specific pitch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    /// MIDI note number of the root
+    pub root: i32,
+    /// Semitone offsets of each chord tone from the root
+    pub intervals: &'static [i32],
+    /// Whether to spell notes with flats rather than sharps
+    pub flats: bool,
+}
+
+impl Chord {
+    /// Parse a chord name like `"c:maj7"` or `"Am"`
+    ///
+    /// Returns None if the name has no quality or the quality is unknown.
+    pub fn parse(name: &str) -> Option<Chord> {
+        let name = name.trim();
+        let (root, quality) = match name.split_once(':') {
+            Some((root, quality)) => (root, quality),
+            None => {
+                // Root letter plus accidentals; the rest is the quality
+                let root_len = 1 + name
+                    .get(1..)?
+                    .chars()
+                    .take_while(|c| matches!(c, '#' | 'b'))
+                    .count();
+                let (root, quality) = name.split_at(root_len);
+                if quality.starts_with(|c: char| c.is_ascii_digit()) {
+                    return None;
+                }
+                (root, quality)
+            }
+        };
+
+        if quality.is_empty() {
+            return None;
+        }
+
+        let (pitch_class, octave) = parse_root(root)?;
+        let (intervals, major_key) = chord_intervals(quality)?;
+
+        Some(Chord {
+            root: (octave + 1) * 12 + pitch_class,
+            intervals,
+            flats: uses_flats(root, pitch_class + major_key),
+        })
+    }
+
+    /// Get the MIDI note numbers of the chord tones, from the root up
+    pub fn midi_notes(&self) -> Vec<i32> {
+        self.intervals.iter().map(|i| self.root + i).collect()
+    }
+
+    /// Get the note names (with octave) of the chord tones, e.g. `["C3", "E3", "G3"]`
+    pub fn notes(&self) -> Vec<String> {
+        self.midi_notes()
+            .into_iter()
+            .map(|midi| midi_to_note(midi, self.flats))
+            .collect()
+    }
+
+    /// Name the chord formed by some MIDI notes, e.g. `"c3:maj"` for C3, E3 and G3
+    ///
+    /// Only chords in root position and close voicing are named, so parsing
+    /// the name gives back the same notes. Qualities are never bare numbers
+    /// (`dom7`, not `7`), since mini notation reads `g3:7` as a sample index.
+    /// Returns None for anything else.
+    pub fn identify(midi_notes: &[i32]) -> Option<String> {
+        let mut notes = midi_notes.to_vec();
+        notes.sort_unstable();
+        notes.dedup();
+
+        let root = *notes.first()?;
+        let intervals: Vec<i32> = notes.iter().map(|n| n - root).collect();
+        let quality = NAMED_QUALITIES
+            .iter()
+            .find(|q| chord_intervals(q).is_some_and(|(i, _)| i == intervals.as_slice()))?;
+
+        let root_name = midi_to_note(root, false).to_lowercase();
+        Some(format!("{}:{}", root_name, quality))
+    }
+}
+
+/// Qualities tried, in order, when naming a chord from its notes
+const NAMED_QUALITIES: [&str; 16] = [
+    "maj", "min", "dom7", "maj7", "min7", "dim", "dim7", "m7b5", "aug", "sus2", "sus4", "maj6",
+    "m6", "dom9", "maj9", "m9",
+];
+
+/// Get the intervals of a chord quality and the offset of the major key
+/// whose signature spells it (e.g. a minor chord borrows its relative major)
+fn chord_intervals(quality: &str) -> Option<(&'static [i32], i32)> {
+    let chord: (&'static [i32], i32) = match quality {
+        "maj" | "M" | "major" => (&[0, 4, 7], 0),
+        "min" | "m" | "minor" => (&[0, 3, 7], 3),
+        "maj7" | "M7" | "^7" => (&[0, 4, 7, 11], 0),
+        "min7" | "m7" => (&[0, 3, 7, 10], 3),
+        "7" | "dom7" => (&[0, 4, 7, 10], 5),
+        "dim" => (&[0, 3, 6], 3),
+        "dim7" => (&[0, 3, 6, 9], 3),
+        "m7b5" => (&[0, 3, 6, 10], 3),
+        "aug" | "+" => (&[0, 4, 8], 0),
+        "sus2" => (&[0, 2, 7], 0),
+        "sus4" => (&[0, 5, 7], 0),
+        "6" | "maj6" => (&[0, 4, 7, 9], 0),
+        "m6" => (&[0, 3, 7, 9], 3),
+        "9" | "dom9" => (&[0, 4, 7, 10, 14], 5),
+        "maj9" => (&[0, 4, 7, 11, 14], 0),
+        "m9" => (&[0, 3, 7, 10, 14], 3),
+        _ => return None,
+    };
+    Some(chord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(name: &str) -> Vec<String> {
+        Chord::parse(name).unwrap().notes()
+    }
+
+    #[test]
+    fn test_triads() {
+        assert_eq!(notes("c:maj"), vec!["C3", "E3", "G3"]);
+        assert_eq!(notes("c:min"), vec!["C3", "Eb3", "G3"]);
+        assert_eq!(notes("b:dim"), vec!["B3", "D4", "F4"]);
+        assert_eq!(notes("c:aug"), vec!["C3", "E3", "G#3"]);
+    }
+
+    #[test]
+    fn test_sevenths() {
+        assert_eq!(notes("c:maj7"), vec!["C3", "E3", "G3", "B3"]);
+        assert_eq!(notes("d:min7"), vec!["D3", "F3", "A3", "C4"]);
+        assert_eq!(notes("c:dom7"), vec!["C3", "E3", "G3", "Bb3"]);
+        assert_eq!(notes("g:7"), vec!["G3", "B3", "D4", "F4"]);
+    }
+
+    #[test]
+    fn test_compact_names() {
+        assert_eq!(notes("Am"), vec!["A3", "C4", "E4"]);
+        assert_eq!(notes("Cmaj7"), vec!["C3", "E3", "G3", "B3"]);
+        assert_eq!(notes("F#m7"), vec!["F#3", "A3", "C#4", "E4"]);
+        assert_eq!(notes("Bbmaj7"), vec!["Bb3", "D4", "F4", "A4"]);
+    }
+
+    #[test]
+    fn test_root_octave() {
+        assert_eq!(notes("c4:maj"), vec!["C4", "E4", "G4"]);
+    }
+
+    #[test]
+    fn test_identify() {
+        let name = |notes: &[i32]| Chord::identify(notes).unwrap();
+        assert_eq!(name(&[48, 52, 55]), "c3:maj");
+        assert_eq!(name(&[67, 61, 64, 61]), "c#4:dim");
+        assert_eq!(name(&[50, 53, 57, 60]), "d3:min7");
+        assert_eq!(name(&[55, 59, 62, 65]), "g3:dom7");
+        assert_eq!(name(&[48, 52, 55, 57]), "c3:maj6");
+        assert_eq!(name(&[48, 52, 55, 58, 62]), "c3:dom9");
+
+        // Inversions, open voicings and clusters have no name
+        assert_eq!(Chord::identify(&[52, 55, 60]), None);
+        assert_eq!(Chord::identify(&[48, 55, 64]), None);
+        assert_eq!(Chord::identify(&[48, 49, 50]), None);
+        assert_eq!(Chord::identify(&[]), None);
+
+        // Names parse back to the same notes
+        let bb_major = Chord::parse(&name(&[46, 50, 53])).unwrap();
+        assert_eq!(bb_major.midi_notes(), vec![46, 50, 53]);
+    }
+
+    #[test]
+    fn test_not_a_chord() {
+        for name in ["c", "bd", "sd", "hh", "cp", "bo", "c4", "g7", "bb", "c:nonsense"] {
+            assert_eq!(Chord::parse(name), None, "{}", name);
+        }
+    }
+}
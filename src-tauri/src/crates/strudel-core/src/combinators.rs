@@ -1,20 +1,34 @@
-use crate::{Fraction, Hap, Pattern, TimeSpan, Value};
+use crate::random::random_at;
+use crate::{Fraction, Hap, Pattern, PatternKind, TimeSpan, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Create a pattern with a single constant value
 ///
-/// The value is active for all time
+/// The value is active for all time, as one event per cycle. As in Strudel,
+/// each event's whole is a cycle, so speeding the pattern up gives more
+/// onsets: `pure(x).fast(2)` plays twice a cycle, not once.
 pub fn pure(value: Value) -> Pattern {
     Pattern::new(move |state| {
-        vec![Hap::new(Some(state.span), state.span, value.clone())]
+        state
+            .span
+            .span_cycles()
+            .into_iter()
+            .map(|part| {
+                let cycle = part.begin.floor();
+                let whole = TimeSpan::new(cycle, cycle + Fraction::from_int(1));
+                Hap::new(Some(whole), part, value.clone())
+            })
+            .collect()
     })
+    .set_kind(PatternKind::Pure)
 }
 
 /// Create an empty/silent pattern
 ///
 /// Returns no events for any query
 pub fn silence() -> Pattern {
-    Pattern::new(|_state| Vec::new())
+    Pattern::new(|_state| Vec::new()).set_kind(PatternKind::Silence)
 }
 
 /// Concatenate patterns, switching between them successively per cycle
@@ -53,9 +67,10 @@ pub fn slowcat(patterns: Vec<Pattern>) -> Pattern {
             let pat_n = ((begin_cycle % pat_count) + pat_count) % pat_count; // Handle negative cycles
 
             if let Some(pat) = patterns_rc.get(pat_n as usize) {
-                // Calculate offset to make pattern cycles line up correctly
-                let cycle_offset = Fraction::from_int(begin_cycle)
-                    - (Fraction::from_int(begin_cycle / pat_count) * Fraction::from_int(pat_count));
+                // Shift time so each pattern only advances on the cycles it
+                // plays: the nth time it comes round, it plays its nth cycle
+                let cycle_offset =
+                    Fraction::from_int(begin_cycle - begin_cycle.div_euclid(pat_count));
 
                 // Query the pattern with adjusted timespan
                 let adjusted_span = TimeSpan::new(span.begin - cycle_offset, span.end - cycle_offset);
@@ -76,6 +91,7 @@ pub fn slowcat(patterns: Vec<Pattern>) -> Pattern {
         steps,
     )
     .split_queries()
+    .set_kind(PatternKind::Slowcat(pat_count as usize))
 }
 
 /// Concatenate patterns, cramming them all into one cycle
@@ -91,6 +107,15 @@ pub fn fastcat(patterns: Vec<Pattern>) -> Pattern {
         return patterns.into_iter().next().unwrap();
     }
 
+    if patterns.len() >= FLAT_FASTCAT_THRESHOLD {
+        flat_fastcat(patterns)
+    } else {
+        nested_fastcat(patterns)
+    }
+}
+
+/// Fastcat for short sequences: a slowcat sped up by the number of patterns
+fn nested_fastcat(patterns: Vec<Pattern>) -> Pattern {
     let pat_count = patterns.len();
     let mut result = slowcat(patterns);
 
@@ -98,7 +123,85 @@ pub fn fastcat(patterns: Vec<Pattern>) -> Pattern {
     result = result.with_query_time(move |t| t * Fraction::from_int(pat_count as i64));
     result = result.with_hap_time(move |t| t / Fraction::from_int(pat_count as i64));
 
-    result.set_steps(Some(Fraction::from_int(pat_count as i64)))
+    result
+        .set_steps(Some(Fraction::from_int(pat_count as i64)))
+        .set_kind(PatternKind::Fastcat(pat_count))
+}
+
+/// Sequence length at which `fastcat` switches to the flat implementation
+///
+/// The flat version skips the per-step query splitting and time remapping
+/// of the slowcat-based one. `bench_fastcat_threshold` times both: on
+/// sequences of plain values the flat one is around a third faster at any
+/// length, and once the children are sequences themselves their own
+/// queries dominate and the two are within noise. There is no crossover,
+/// so the threshold only decides where the saving is worth leaving the
+/// slowcat-based path; short sequences, the common case, stay on it.
+const FLAT_FASTCAT_THRESHOLD: usize = 16;
+
+/// Fastcat for long sequences, computing each step's span arithmetically
+///
+/// Step `k` of the query is played by pattern `k mod n`, queried with the
+/// same local time mapping as the slowcat-based version, so the results are
+/// identical.
+fn flat_fastcat(patterns: Vec<Pattern>) -> Pattern {
+    let pat_count = patterns.len() as i64;
+    let n = Fraction::from_int(pat_count);
+    let patterns_rc = Arc::new(patterns);
+
+    Pattern::with_steps(
+        move |state| {
+            let span = state.span;
+            let mut haps = Vec::new();
+            let mut step = (span.begin * n).floor().numerator;
+
+            while Fraction::from_int(step) < span.end * n {
+                let whole = TimeSpan::new(
+                    Fraction::new(step, pat_count),
+                    Fraction::new(step + 1, pat_count),
+                );
+                if let Some(part) = whole.intersection(&span) {
+                    // Same pattern choice and cycle offset as slowcat
+                    let index = step.rem_euclid(pat_count);
+                    let cycle_offset = step - step.div_euclid(pat_count);
+                    let offset = Fraction::from_int(cycle_offset);
+
+                    // Query in the child's time, then map the results back
+                    let child_span = if part == whole {
+                        TimeSpan::new(
+                            Fraction::from_int(step - cycle_offset),
+                            Fraction::from_int(step - cycle_offset + 1),
+                        )
+                    } else {
+                        TimeSpan::new(part.begin * n - offset, part.end * n - offset)
+                    };
+
+                    haps.extend(
+                        patterns_rc[index as usize]
+                            .query(state.set_span(child_span))
+                            .into_iter()
+                            .map(|hap| {
+                                hap.with_span(|ts| {
+                                    if *ts == child_span {
+                                        part
+                                    } else {
+                                        TimeSpan::new(
+                                            (ts.begin + offset) / n,
+                                            (ts.end + offset) / n,
+                                        )
+                                    }
+                                })
+                            }),
+                    );
+                }
+                step += 1;
+            }
+
+            haps
+        },
+        Some(n),
+    )
+    .set_kind(PatternKind::Fastcat(pat_count as usize))
 }
 
 /// Alias for fastcat
@@ -106,6 +209,51 @@ pub fn sequence(patterns: Vec<Pattern>) -> Pattern {
     fastcat(patterns)
 }
 
+/// Concatenate patterns into one cycle, each taking time in proportion to its weight
+///
+/// Matches Strudel's `timeCat`: each pattern's cycle is squeezed into its
+/// share of the cycle, so `a@3 b` in mini notation is
+/// `time_cat(vec![(3.0, a), (1.0, b)])`, with `a` playing for the first three
+/// quarters. Patterns weighted zero or less are left out, and the step
+/// count is the total weight.
+///
+/// # Examples
+/// ```
+/// use strudel_core::{pure, time_cat, Fraction, Value};
+///
+/// let bd = pure(Value::String("bd".into()));
+/// let sd = pure(Value::String("sd".into()));
+/// let pattern = time_cat(vec![(3.0, bd), (1.0, sd)]);
+/// assert_eq!(pattern.get_steps(), Some(Fraction::from_int(4)));
+/// ```
+pub fn time_cat(pairs: Vec<(f64, Pattern)>) -> Pattern {
+    let pairs: Vec<(Fraction, Pattern)> = pairs
+        .into_iter()
+        .filter(|(weight, _)| *weight > 0.0)
+        .map(|(weight, pat)| (Fraction::from_float(weight), pat))
+        .collect();
+    if pairs.is_empty() {
+        return silence();
+    }
+
+    let total = pairs
+        .iter()
+        .fold(Fraction::from_int(0), |acc, (weight, _)| acc + *weight);
+
+    let mut begin = Fraction::from_int(0);
+    let sections = pairs
+        .into_iter()
+        .map(|(weight, pat)| {
+            let end = begin + weight;
+            let section = pat.compress_frac(begin / total, end / total);
+            begin = end;
+            section
+        })
+        .collect();
+
+    stack(sections).set_steps(Some(total))
+}
+
 /// Stack/layer multiple patterns on top of each other
 ///
 /// All patterns play simultaneously (polyrhythm)
@@ -118,6 +266,7 @@ pub fn stack(patterns: Vec<Pattern>) -> Pattern {
         return patterns.into_iter().next().unwrap();
     }
 
+    let pat_count = patterns.len();
     let patterns_rc = Arc::new(patterns);
 
     // Calculate LCM of all pattern steps
@@ -141,6 +290,7 @@ pub fn stack(patterns: Vec<Pattern>) -> Pattern {
         },
         steps,
     )
+    .set_kind(PatternKind::Stack(pat_count))
 }
 
 /// Alias for stack
@@ -196,6 +346,263 @@ pub fn polymeter(patterns: Vec<Pattern>) -> Pattern {
     stack(adjusted_patterns)
 }
 
+/// Arrange - play sections one after another, each for a number of cycles
+///
+/// Each `(cycles, pattern)` section plays its own cycles `0..cycles` in turn,
+/// and the whole arrangement loops. Matches Strudel's `arrange`, useful for
+/// stitching motifs into a song structure.
+///
+/// # Examples
+/// ```
+/// use strudel_core::{arrange, pure, Value};
+///
+/// // Four cycles of verse, then two of chorus
+/// let verse = pure(Value::String("bd".into()));
+/// let chorus = pure(Value::String("cp".into()));
+/// let song = arrange(vec![(4, verse), (2, chorus)]);
+/// ```
+pub fn arrange(sections: Vec<(usize, Pattern)>) -> Pattern {
+    let sections: Vec<(i64, Pattern)> = sections
+        .into_iter()
+        .filter(|(cycles, _)| *cycles > 0)
+        .map(|(cycles, pat)| (cycles as i64, pat))
+        .collect();
+
+    let total: i64 = sections.iter().map(|(cycles, _)| cycles).sum();
+    if total == 0 {
+        return silence();
+    }
+
+    Pattern::new(move |state| {
+        let cycle = state.span.begin.floor().numerator;
+        let mut position = cycle.rem_euclid(total);
+
+        for (cycles, pat) in &sections {
+            if position < *cycles {
+                // Line the section's own cycle `position` up with the current cycle
+                let offset = Fraction::from_int(cycle - position);
+                let span = TimeSpan::new(state.span.begin - offset, state.span.end - offset);
+
+                return pat
+                    .query(state.set_span(span))
+                    .into_iter()
+                    .map(|hap| hap.with_span(|ts| TimeSpan::new(ts.begin + offset, ts.end + offset)))
+                    .collect();
+            }
+            position -= cycles;
+        }
+
+        Vec::new()
+    })
+    .split_queries()
+}
+
+/// Pick - play a named pattern per cycle, chosen by a selector pattern
+///
+/// Each cycle, the selector's value at the start of the cycle is looked up in
+/// `sections` and that whole pattern plays for the cycle, keeping its own
+/// timeline. Unknown names are silent. Useful for arranging named sections
+/// with a control pattern like `"<verse verse chorus>"`.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use strudel_core::{pick, pure, slowcat, Value};
+///
+/// let mut sections = HashMap::new();
+/// sections.insert("verse".to_string(), pure(Value::String("bd".into())));
+/// sections.insert("chorus".to_string(), pure(Value::String("cp".into())));
+///
+/// let selector = slowcat(vec![
+///     pure(Value::String("verse".into())),
+///     pure(Value::String("chorus".into())),
+/// ]);
+/// let song = pick(sections, selector);
+/// ```
+pub fn pick(sections: HashMap<String, Pattern>, selector: Pattern) -> Pattern {
+    Pattern::new(move |state| {
+        let cycle = state.span.begin.floor();
+        let cycle_span = TimeSpan::new(cycle, cycle + Fraction::from_int(1));
+
+        let name = selector
+            .query(state.set_span(cycle_span))
+            .into_iter()
+            .find(|hap| hap.part.begin == cycle)
+            .map(|hap| hap.value.to_string());
+
+        match name.and_then(|name| sections.get(&name)) {
+            Some(pat) => pat.query(state),
+            None => Vec::new(),
+        }
+    })
+    .split_queries()
+}
+
+/// Wedge - play one pattern for part of each cycle and another for the rest
+///
+/// `a` is squashed into the first `t` of every cycle and `b` into the
+/// remaining `1 - t`, so both complete a full cycle of their own within one
+/// cycle of the result. Matches Strudel's `wedge`.
+///
+/// # Examples
+/// ```
+/// use strudel_core::{pure, wedge, Value};
+///
+/// // Kick for the first quarter, hats for the rest
+/// let bd = pure(Value::String("bd".into()));
+/// let hh = pure(Value::String("hh".into()));
+/// let pattern = wedge(0.25, bd, hh);
+/// ```
+pub fn wedge(t: f64, a: Pattern, b: Pattern) -> Pattern {
+    let t = Fraction::from_float(t);
+    let zero = Fraction::from_int(0);
+    let one = Fraction::from_int(1);
+
+    if t <= zero {
+        return b;
+    }
+    if t >= one {
+        return a;
+    }
+
+    Pattern::new(move |state| {
+        let cycle = state.span.begin.floor();
+        let mut haps = Vec::new();
+
+        // Each section maps its own cycle onto [offset, offset + width) of this cycle
+        for (pat, offset, width) in [(&a, zero, t), (&b, t, one - t)] {
+            let section = TimeSpan::new(cycle + offset, cycle + offset + width);
+            let span = match section.intersection(&state.span) {
+                Some(span) => span,
+                None => continue,
+            };
+
+            let to_inner = |x: Fraction| cycle + (x - cycle - offset) / width;
+            let to_outer = |x: Fraction| cycle + offset + (x - cycle) * width;
+
+            let inner_span = TimeSpan::new(to_inner(span.begin), to_inner(span.end));
+            haps.extend(
+                pat.query(state.set_span(inner_span))
+                    .into_iter()
+                    .map(|hap| hap.with_span(|ts| TimeSpan::new(to_outer(ts.begin), to_outer(ts.end)))),
+            );
+        }
+
+        haps
+    })
+    .split_queries()
+}
+
+/// Necklace - build a boolean rhythm from the distances between onsets
+///
+/// Each interval is the number of steps from one hit to the next, so the
+/// total number of steps is the sum of the intervals. For example, `[3, 3, 2]`
+/// gives `x . . x . . x .` over 8 steps. Every step produces a `Bool` event,
+/// which makes the result suitable as a structure pattern for `struct_`.
+///
+/// # Examples
+/// ```
+/// use strudel_core::{necklace, Fraction};
+///
+/// let tresillo = necklace(vec![3, 3, 2]);
+/// assert_eq!(tresillo.get_steps(), Some(Fraction::from_int(8)));
+/// ```
+pub fn necklace(intervals: Vec<usize>) -> Pattern {
+    let mut rhythm = Vec::new();
+    for interval in intervals.into_iter().filter(|&i| i > 0) {
+        rhythm.push(true);
+        rhythm.extend(vec![false; interval - 1]);
+    }
+
+    fastcat(rhythm.into_iter().map(|hit| pure(Value::Bool(hit))).collect())
+}
+
+/// Run - count up from 0 to n - 1 within each cycle
+///
+/// Each number gets an equal slice of the cycle, which makes the result
+/// handy for picking sample indices or chopped slices in order.
+///
+/// # Examples
+/// ```
+/// use strudel_core::{run, Fraction};
+///
+/// let counter = run(4);
+/// assert_eq!(counter.get_steps(), Some(Fraction::from_int(4)));
+/// ```
+pub fn run(n: usize) -> Pattern {
+    fastcat((0..n).map(|i| pure(Value::Number(i as f64))).collect())
+}
+
+/// Build a continuous signal from a waveform function
+///
+/// The function receives the position within the cycle (`[0, 1)`) at the
+/// midpoint of each query and returns a value in `[0, 1]`. Each query yields a
+/// single event without a whole, so use `segment` to sample it into steps.
+fn signal<F>(func: F) -> Pattern
+where
+    F: Fn(f64) -> f64 + Send + Sync + 'static,
+{
+    Pattern::new(move |state| {
+        let mid = state.span.midpoint();
+        let phase = (mid - mid.floor()).to_float();
+        vec![Hap::new(None, state.span, Value::Number(func(phase)))]
+    })
+}
+
+/// Sine - a sine wave signal between 0 and 1, starting at 0.5
+pub fn sine() -> Pattern {
+    signal(|phase| (std::f64::consts::TAU * phase).sin() * 0.5 + 0.5)
+}
+
+/// Saw - a rising ramp from 0 to 1 each cycle
+pub fn saw() -> Pattern {
+    signal(|phase| phase)
+}
+
+/// Isaw - a falling ramp from 1 to 0 each cycle
+pub fn isaw() -> Pattern {
+    signal(|phase| 1.0 - phase)
+}
+
+/// Square - 0 for the first half of each cycle, 1 for the second half
+pub fn square() -> Pattern {
+    signal(|phase| if phase < 0.5 { 0.0 } else { 1.0 })
+}
+
+/// Tri - a triangle wave rising from 0 to 1 and back each cycle
+pub fn tri() -> Pattern {
+    signal(|phase| 1.0 - (2.0 * phase - 1.0).abs())
+}
+
+/// Perlin - smooth random noise between 0 and 1
+///
+/// Interpolates between random values at integer cycles with a smoothstep
+/// curve, so the signal changes gradually rather than jumping.
+pub fn perlin() -> Pattern {
+    Pattern::new(|state| {
+        let mid = state.span.midpoint();
+        let cycle = mid.floor();
+        let t = (mid - cycle).to_float();
+
+        let from = random_at(cycle, 0);
+        let to = random_at(cycle + Fraction::from_int(1), 0);
+        let smooth = t * t * (3.0 - 2.0 * t);
+
+        vec![Hap::new(None, state.span, Value::Number(from + smooth * (to - from)))]
+    })
+}
+
+/// Rand - a continuous random signal in `[0, 1)`
+///
+/// Produces a single event without a whole for each query, valued by a hash
+/// of the query start and the seed. Use `segment` to sample it into steps.
+pub fn rand(seed: u64) -> Pattern {
+    Pattern::new(move |state| {
+        let value = random_at(state.span.begin, seed);
+        vec![Hap::new(None, state.span, Value::Number(value))]
+    })
+}
+
 /// Choose - randomly select one pattern per cycle
 ///
 /// Uses seed for deterministic selection based on cycle number.
@@ -228,6 +635,44 @@ pub fn choose(patterns: Vec<Pattern>, seed: u64) -> Pattern {
     .split_queries()
 }
 
+/// Choose cycles - play one pattern per cycle, working through them in a shuffled order
+///
+/// Every `patterns.len()` cycles is a round in which each pattern plays
+/// exactly once, so unlike `choose` no pattern repeats until all have played.
+/// The order is reshuffled each round, deterministically for the same seed.
+///
+/// # Arguments
+/// * `patterns` - Patterns to choose between
+/// * `seed` - Random seed for deterministic shuffling
+pub fn choose_cycles(patterns: Vec<Pattern>, seed: u64) -> Pattern {
+    if patterns.is_empty() {
+        return silence();
+    }
+
+    if patterns.len() == 1 {
+        return patterns.into_iter().next().unwrap();
+    }
+
+    let patterns_rc = Arc::new(patterns);
+    let pat_count = patterns_rc.len() as i64;
+
+    Pattern::new(move |state| {
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        // Shuffle the patterns for this round, seeded by the round number
+        let cycle = state.span.begin.floor().numerator;
+        let round = cycle.div_euclid(pat_count);
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(round as u64));
+        let mut order: Vec<usize> = (0..patterns_rc.len()).collect();
+        order.shuffle(&mut rng);
+
+        patterns_rc[order[cycle.rem_euclid(pat_count) as usize]].query(state)
+    })
+    .split_queries()
+}
+
 /// Choose with weights - randomly select one pattern per cycle using weighted probabilities
 ///
 /// Uses seed for deterministic selection based on cycle number.
@@ -315,6 +760,28 @@ mod tests {
         assert_eq!(haps[0].value, Value::Number(42.0));
     }
 
+    #[test]
+    fn test_pure_splits_at_cycle_boundaries() {
+        let pat = pure(Value::Number(42.0));
+
+        // Queries spanning cycles get one event per cycle
+        let haps = pat.query(State::new(TimeSpan::from_floats(0.5, 2.0)));
+        assert_eq!(haps.len(), 2);
+        assert_eq!(haps[0].whole, Some(TimeSpan::from_ints(0, 1)));
+        assert_eq!(haps[0].part, TimeSpan::from_floats(0.5, 1.0));
+        assert_eq!(haps[1].whole, Some(TimeSpan::from_ints(1, 2)));
+
+        // Speeding up or slowing down scales those cycle-long events
+        let haps = pat
+            .clone()
+            .fast(2.0)
+            .query(State::new(TimeSpan::from_ints(0, 1)));
+        assert_eq!(haps.len(), 2);
+        let haps = pat.slow(2.0).query(State::new(TimeSpan::from_ints(0, 2)));
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].whole, Some(TimeSpan::from_ints(0, 2)));
+    }
+
     #[test]
     fn test_silence() {
         let pat = silence();
@@ -350,6 +817,132 @@ mod tests {
         assert_eq!(haps[2].value, Value::String("c".into()));
     }
 
+    /// Time both fastcat paths over a range of sequence lengths
+    ///
+    /// Run with `cargo test --release -p strudel-core -- --ignored
+    /// --nocapture bench_fastcat_threshold` and compare the columns when
+    /// changing `FLAT_FASTCAT_THRESHOLD`.
+    #[test]
+    #[ignore]
+    fn bench_fastcat_threshold() {
+        use std::time::Instant;
+
+        // Whole cycles, as for rendering, and short slices, as for playback
+        let time = |pattern: &Pattern, slices: i64| {
+            let start = Instant::now();
+            for slice in 0..2000 * slices {
+                let span = TimeSpan::new(
+                    Fraction::new(slice, slices),
+                    Fraction::new(slice + 1, slices),
+                );
+                std::hint::black_box(pattern.query(State::new(span)));
+            }
+            start.elapsed()
+        };
+        let plain = |i: usize| pure(Value::Number(i as f64));
+        let nested = |i: usize| fastcat(vec![plain(i), slowcat(vec![plain(1), plain(2)])]);
+
+        for (kind, child) in [
+            ("plain", &plain as &dyn Fn(usize) -> Pattern),
+            ("nested", &nested),
+        ] {
+            for slices in [1, 10] {
+                println!("{} children, {} queries per cycle", kind, slices);
+                println!("{:>6} {:>12} {:>12}", "length", "slowcat", "flat");
+                for len in [2, 4, 8, 12, 16, 24, 32, 64] {
+                    let children = || (0..len).map(child).collect();
+                    let slowcat_based = time(&nested_fastcat(children()), slices);
+                    let flat = time(&flat_fastcat(children()), slices);
+                    println!("{:>6} {:>12?} {:>12?}", len, slowcat_based, flat);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fastcat_large_sequence() {
+        use std::time::{Duration, Instant};
+
+        let patterns: Vec<Pattern> = (0..1000).map(|i| pure(Value::Number(i as f64))).collect();
+        let combined = fastcat(patterns);
+
+        let start = Instant::now();
+        let state = State::new(TimeSpan::new(Fraction::from_int(3), Fraction::from_int(4)));
+        let haps = combined.query(state);
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(haps.len(), 1000);
+        for (i, hap) in haps.iter().enumerate() {
+            let i = i as i64;
+            assert_eq!(hap.value, Value::Number(i as f64));
+            assert_eq!(
+                hap.whole,
+                Some(TimeSpan::new(
+                    Fraction::new(3000 + i, 1000),
+                    Fraction::new(3001 + i, 1000)
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_flat_fastcat_matches_fastcat() {
+        let patterns = || {
+            vec![
+                pure(Value::String("a".into())),
+                slowcat(vec![
+                    pure(Value::String("b".into())),
+                    pure(Value::String("c".into())),
+                ]),
+                fastcat(vec![
+                    pure(Value::String("d".into())),
+                    pure(Value::String("e".into())),
+                ]),
+            ]
+        };
+        let nested = fastcat(patterns());
+        let flat = flat_fastcat(patterns());
+
+        let spans = [(0, 1, 1, 1), (1, 1, 3, 1), (1, 4, 5, 6), (-1, 1, 0, 1)];
+        for (bn, bd, en, ed) in spans {
+            let span = TimeSpan::new(Fraction::new(bn, bd), Fraction::new(en, ed));
+            let expected = nested.query(State::new(span));
+            let actual = flat.query(State::new(span));
+            assert_eq!(actual.len(), expected.len(), "span {:?}", span);
+            for (a, e) in actual.iter().zip(&expected) {
+                assert_eq!((a.whole, a.part, &a.value), (e.whole, e.part, &e.value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cat_advances_each_child_when_played() {
+        let s = |name: &str| pure(Value::String(name.into()));
+        let values = |pattern: &Pattern, span: TimeSpan| {
+            let mut haps = pattern.query(State::new(span));
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.iter()
+                .map(|hap| hap.value.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // The inner alternation moves on each time the outer one reaches it
+        let nested = slowcat(vec![s("a"), slowcat(vec![s("b"), s("c")])]);
+        assert_eq!(
+            values(&nested, TimeSpan::from_ints(0, 4)),
+            vec!["a", "b", "a", "c"]
+        );
+        assert_eq!(values(&nested, TimeSpan::from_ints(-2, 0)), vec!["a", "c"]);
+
+        // The same holds in a long sequence, which takes the flat path
+        let mut children: Vec<Pattern> = (0..FLAT_FASTCAT_THRESHOLD - 1).map(|_| s("x")).collect();
+        children.push(slowcat(vec![s("b"), s("c")]));
+        let sequence = fastcat(children);
+        let last = |cycle: i64| values(&sequence, TimeSpan::from_ints(cycle, cycle + 1)).pop();
+        assert_eq!(last(0).as_deref(), Some("b"));
+        assert_eq!(last(1).as_deref(), Some("c"));
+    }
+
     #[test]
     fn test_slowcat() {
         let pat1 = pure(Value::String("a".into()));
@@ -428,6 +1021,266 @@ mod tests {
         assert!(values.contains(&&Value::String("c".into())));
     }
 
+    #[test]
+    fn test_necklace() {
+        let pat = necklace(vec![2, 2]);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+
+        let haps = pat.query(state);
+        assert_eq!(haps.len(), 4);
+
+        let hits: Vec<Fraction> = haps
+            .iter()
+            .filter(|h| h.value == Value::Bool(true))
+            .map(|h| h.whole.unwrap().begin)
+            .collect();
+        assert_eq!(hits, vec![Fraction::from_int(0), Fraction::new(1, 2)]);
+    }
+
+    #[test]
+    fn test_run() {
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = run(4).query(state);
+        assert_eq!(haps.len(), 4);
+
+        for (i, hap) in haps.iter().enumerate() {
+            let begin = Fraction::new(i as i64, 4);
+            let end = begin + Fraction::new(1, 4);
+            assert_eq!(hap.value, Value::Number(i as f64));
+            assert_eq!(hap.whole, Some(TimeSpan::new(begin, end)));
+        }
+    }
+
+    #[test]
+    fn test_necklace_total_steps() {
+        let pat = necklace(vec![3, 3, 2]);
+        assert_eq!(pat.get_steps(), Some(Fraction::from_int(8)));
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pat.query(state);
+        assert_eq!(haps.len(), 8);
+
+        let hit_steps: Vec<usize> = haps
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.value == Value::Bool(true))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(hit_steps, vec![0, 3, 6]);
+    }
+
+    #[test]
+    fn test_rand_segmented() {
+        let pattern = rand(5).segment(16.0);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state.clone());
+
+        assert_eq!(haps.len(), 16);
+        let values: Vec<f64> = haps
+            .iter()
+            .map(|h| match h.value {
+                Value::Number(n) => n,
+                _ => panic!("expected numeric value"),
+            })
+            .collect();
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+
+        // Same values on every query
+        let again: Vec<_> = pattern.query(state).into_iter().map(|h| h.value).collect();
+        assert_eq!(again, haps.into_iter().map(|h| h.value).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_wedge() {
+        let a = fastcat(vec![
+            pure(Value::String("a1".into())),
+            pure(Value::String("a2".into())),
+        ]);
+        let b = pure(Value::String("b".into()));
+
+        let pattern = wedge(0.25, a, b);
+        let state = State::new(TimeSpan::new(Fraction::from_int(1), Fraction::from_int(2)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 3);
+        let spans: Vec<_> = haps.iter().map(|h| (h.value.clone(), h.part)).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (
+                    Value::String("a1".into()),
+                    TimeSpan::new(Fraction::from_int(1), Fraction::new(9, 8))
+                ),
+                (
+                    Value::String("a2".into()),
+                    TimeSpan::new(Fraction::new(9, 8), Fraction::new(5, 4))
+                ),
+                (
+                    Value::String("b".into()),
+                    TimeSpan::new(Fraction::new(5, 4), Fraction::from_int(2))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_cat() {
+        let a = pure(Value::String("a".into()));
+        let b = fastcat(vec![
+            pure(Value::String("b1".into())),
+            pure(Value::String("b2".into())),
+        ]);
+
+        // `a` takes three quarters of each cycle and `b` the last quarter
+        let pattern = time_cat(vec![(3.0, a), (0.0, silence()), (1.0, b)]);
+        let state = State::new(TimeSpan::new(Fraction::from_int(1), Fraction::from_int(2)));
+        let mut haps = pattern.query(state);
+        haps.sort_by_key(|h| h.part.begin);
+
+        let wholes: Vec<_> = haps
+            .iter()
+            .map(|h| (h.value.clone(), h.whole.unwrap()))
+            .collect();
+        assert_eq!(
+            wholes,
+            vec![
+                (
+                    Value::String("a".into()),
+                    TimeSpan::new(Fraction::from_int(1), Fraction::new(7, 4))
+                ),
+                (
+                    Value::String("b1".into()),
+                    TimeSpan::new(Fraction::new(7, 4), Fraction::new(15, 8))
+                ),
+                (
+                    Value::String("b2".into()),
+                    TimeSpan::new(Fraction::new(15, 8), Fraction::from_int(2))
+                ),
+            ]
+        );
+        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(4)));
+    }
+
+    /// Sample a signal over a tiny span around `t`
+    fn sample_at(pattern: &Pattern, t: Fraction) -> f64 {
+        let epsilon = Fraction::new(1, 1000);
+        let haps = pattern.query(State::new(TimeSpan::new(t - epsilon, t + epsilon)));
+        assert_eq!(haps.len(), 1);
+        assert!(haps[0].whole.is_none());
+        match haps[0].value {
+            Value::Number(n) => n,
+            _ => panic!("expected numeric value"),
+        }
+    }
+
+    #[test]
+    fn test_sine() {
+        let pattern = sine();
+        assert!((sample_at(&pattern, Fraction::from_int(0)) - 0.5).abs() < 1e-6);
+        assert!((sample_at(&pattern, Fraction::new(1, 4)) - 1.0).abs() < 1e-6);
+        assert!((sample_at(&pattern, Fraction::new(1, 2)) - 0.5).abs() < 1e-6);
+        assert!(sample_at(&pattern, Fraction::new(3, 4)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_waveforms() {
+        let quarter = Fraction::new(1, 4);
+        let three_quarters = Fraction::new(3, 4);
+
+        assert!((sample_at(&saw(), quarter) - 0.25).abs() < 1e-6);
+        assert!((sample_at(&isaw(), quarter) - 0.75).abs() < 1e-6);
+        assert_eq!(sample_at(&square(), quarter), 0.0);
+        assert_eq!(sample_at(&square(), three_quarters), 1.0);
+        assert!((sample_at(&tri(), quarter) - 0.5).abs() < 1e-6);
+        assert!((sample_at(&tri(), Fraction::new(1, 2)) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_perlin_range_and_continuity() {
+        let pattern = perlin();
+        let mut previous = sample_at(&pattern, Fraction::from_int(0));
+        for i in 1..400 {
+            let value = sample_at(&pattern, Fraction::new(i, 100));
+            assert!((0.0..=1.0).contains(&value));
+            assert!((value - previous).abs() < 0.1);
+            previous = value;
+        }
+    }
+
+    #[test]
+    fn test_pattern_kind() {
+        let a = pure(Value::String("a".into()));
+        let b = pure(Value::String("b".into()));
+        let c = pure(Value::String("c".into()));
+
+        assert_eq!(a.kind(), PatternKind::Pure);
+        assert_eq!(silence().kind(), PatternKind::Silence);
+        assert_eq!(fastcat(vec![a.clone(), b.clone(), c.clone()]).kind(), PatternKind::Fastcat(3));
+        assert_eq!(slowcat(vec![a.clone(), b.clone()]).kind(), PatternKind::Slowcat(2));
+        assert_eq!(stack(vec![a.clone(), b]).kind(), PatternKind::Stack(2));
+
+        // Value changes keep the tag, restructuring and custom queries have none
+        assert_eq!(a.clone().add_scalar(1.0).kind(), PatternKind::Pure);
+        assert_eq!(a.clone().fast(2.0).kind(), PatternKind::Unknown);
+        let seq = fastcat(vec![a.clone(), silence(), c]);
+        assert_eq!(seq.clone().rev().kind(), PatternKind::Unknown);
+        assert_eq!(seq.clone().late(0.25).kind(), PatternKind::Unknown);
+        assert_eq!(seq.clone().ply(2).kind(), PatternKind::Unknown);
+        assert_eq!(a.clone().euclid(3, 8, None).kind(), PatternKind::Unknown);
+        assert_eq!(a.struct_(seq).kind(), PatternKind::Unknown);
+        assert_eq!(Pattern::new(|_| Vec::new()).kind(), PatternKind::Unknown);
+    }
+
+    #[test]
+    fn test_arrange() {
+        let a = slowcat(vec![
+            pure(Value::String("a0".into())),
+            pure(Value::String("a1".into())),
+        ]);
+        let b = pure(Value::String("b".into()));
+        let pattern = arrange(vec![(2, a), (1, b)]);
+
+        let value_at = |cycle: i64| {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let haps = pattern.query(State::new(span));
+            assert_eq!(haps.len(), 1);
+            assert_eq!(haps[0].part, span);
+            haps[0].value.clone()
+        };
+
+        assert_eq!(value_at(0), Value::String("a0".into()));
+        assert_eq!(value_at(1), Value::String("a1".into()));
+        assert_eq!(value_at(2), Value::String("b".into()));
+        assert_eq!(value_at(3), Value::String("a0".into()));
+    }
+
+    #[test]
+    fn test_pick() {
+        let word = |s: &str| pure(Value::String(s.into()));
+        let mut sections = HashMap::new();
+        sections.insert("verse".to_string(), fastcat(vec![word("bd"), word("sd")]));
+        sections.insert("chorus".to_string(), fastcat(vec![word("cp"); 3]));
+
+        let selector = slowcat(vec![word("verse"), word("chorus"), word("bridge")]);
+        let pattern = pick(sections, selector);
+
+        let values_at = |cycle: i64| {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let mut haps = pattern.query(State::new(span));
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.iter()
+                .map(|hap| hap.value.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(values_at(0), vec!["bd", "sd"]);
+        assert_eq!(values_at(1), vec!["cp", "cp", "cp"]);
+
+        // Unknown names are silent, then the selector loops
+        assert!(values_at(2).is_empty());
+        assert_eq!(values_at(3), vec!["bd", "sd"]);
+    }
+
     #[test]
     fn test_choose_deterministic() {
         let pat1 = pure(Value::String("a".into()));
@@ -481,4 +1334,28 @@ mod tests {
         assert!(valid_values.contains(&haps2[0].value));
         assert!(valid_values.contains(&haps3[0].value));
     }
+
+    #[test]
+    fn test_choose_cycles_plays_each_per_round() {
+        let letters = ["a", "b", "c"];
+        let patterns = letters.iter().map(|l| pure(Value::String(l.to_string())));
+        let combined = choose_cycles(patterns.collect(), 42);
+
+        let value_at = |cycle: i64| {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let haps = combined.query(State::new(span));
+            assert_eq!(haps.len(), 1);
+            haps[0].value.to_string()
+        };
+
+        // Each round of three cycles plays all three, including before cycle 0
+        for round in -1..4 {
+            let mut values: Vec<String> = (0..3).map(|i| value_at(round * 3 + i)).collect();
+            values.sort();
+            assert_eq!(values, letters, "round {}", round);
+        }
+
+        // The same cycle always plays the same pattern
+        assert_eq!(value_at(7), value_at(7));
+    }
 }
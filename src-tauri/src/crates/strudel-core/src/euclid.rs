@@ -36,51 +36,30 @@ pub fn bjorklund(pulse: usize, step: usize, rotation: usize) -> Vec<bool> {
         return vec![true; step];
     }
 
-    // Initialize pattern with pulses at the start
-    let mut pattern = vec![true; pulse];
-    pattern.extend(vec![false; step - pulse]);
-
-    // Bjorklund algorithm
-    let mut groups: Vec<Vec<bool>> = pattern.into_iter().map(|b| vec![b]).collect();
-
-    loop {
-        // Count groups that can be paired
-        let ones = groups.iter().filter(|g| g.iter().all(|&x| x)).count();
-        let zeros = groups.len() - ones;
-
-        if zeros <= 1 {
-            break;
-        }
-
-        let pairs = ones.min(zeros);
-
-        if pairs == 0 {
-            break;
-        }
-
-        // Pair groups
-        let mut new_groups = Vec::new();
-
-        for i in 0..pairs {
-            let mut combined = groups[i].clone();
-            combined.extend_from_slice(&groups[ones + i]);
-            new_groups.push(combined);
-        }
-
-        // Add remaining groups
-        for group in groups.iter().take(ones).skip(pairs) {
-            new_groups.push(group.clone());
-        }
-
-        for group in groups.iter().skip(ones + pairs) {
-            new_groups.push(group.clone());
+    // Start with one group per pulse and one group per rest
+    let mut groups: Vec<Vec<bool>> = vec![vec![true]; pulse];
+    let mut remainder: Vec<Vec<bool>> = vec![vec![false]; step - pulse];
+
+    // Bjorklund algorithm: repeatedly append remainder groups onto the
+    // leading groups until at most one remainder group is left
+    while remainder.len() > 1 {
+        let pairs = groups.len().min(remainder.len());
+
+        let leftover = if groups.len() > pairs {
+            groups.split_off(pairs)
+        } else {
+            remainder.split_off(pairs)
+        };
+
+        for (group, rest) in groups.iter_mut().zip(remainder) {
+            group.extend(rest);
         }
 
-        groups = new_groups;
+        remainder = leftover;
     }
 
     // Flatten groups into pattern
-    let mut result: Vec<bool> = groups.into_iter().flatten().collect();
+    let mut result: Vec<bool> = groups.into_iter().chain(remainder).flatten().collect();
 
     // Apply rotation
     if rotation > 0 && !result.is_empty() {
@@ -112,7 +91,10 @@ mod tests {
         let pattern = bjorklund(3, 8, 0);
         assert_eq!(pattern.len(), 8);
         assert_eq!(pattern.iter().filter(|&&x| x).count(), 3);
-        // Should be [T, F, F, T, F, F, T, F] or similar even distribution
+        assert_eq!(
+            pattern,
+            vec![true, false, false, true, false, false, true, false]
+        );
     }
 
     #[test]
@@ -120,6 +102,10 @@ mod tests {
         let pattern = bjorklund(5, 8, 0);
         assert_eq!(pattern.len(), 8);
         assert_eq!(pattern.iter().filter(|&&x| x).count(), 5);
+        assert_eq!(
+            pattern,
+            vec![true, false, true, true, false, true, true, false]
+        );
     }
 
     #[test]
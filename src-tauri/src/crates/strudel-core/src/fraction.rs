@@ -69,6 +69,9 @@ impl Fraction {
 
     /// Least common multiple
     pub fn lcm(a: i64, b: i64) -> i64 {
+        if a == 0 && b == 0 {
+            return 0;
+        }
         (a * b) / Self::gcd(a, b)
     }
 
@@ -233,4 +236,11 @@ mod tests {
         let f2 = Fraction::new(2, 3);
         assert!(f1 < f2);
     }
+
+    #[test]
+    fn test_lcm_of_zeros() {
+        assert_eq!(Fraction::lcm(4, 6), 12);
+        assert_eq!(Fraction::lcm(0, 3), 0);
+        assert_eq!(Fraction::lcm(0, 0), 0);
+    }
 }
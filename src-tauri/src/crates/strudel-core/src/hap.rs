@@ -1,6 +1,6 @@
 use crate::{Fraction, TimeSpan, Value};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Context metadata for a Hap (event)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -43,6 +43,23 @@ impl Default for Context {
     }
 }
 
+/// A hap flattened into a stable shape for external tools
+///
+/// Times are decimal cycles and values are plain JSON, so consumers don't
+/// need to know about fractions or the `Value` enum. `controls` holds the
+/// context metadata (gain, pan, sample index `n`, target, ...), sorted by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HapEvent {
+    /// Start of the event in cycles
+    pub begin: f64,
+    /// End of the event in cycles
+    pub end: f64,
+    /// The event's value
+    pub value: serde_json::Value,
+    /// Control values from the event's metadata
+    pub controls: BTreeMap<String, serde_json::Value>,
+}
+
 /// A Hap (Happening/Event) represents a value active during a timespan
 ///
 /// The 'part' is the timespan fragment of this event, which may be smaller
@@ -189,6 +206,22 @@ impl Hap {
         let w = self.whole_or_part();
         w.begin <= max && w.end >= min
     }
+
+    /// Flatten into a [`HapEvent`], timed by the whole (or the part if there is none)
+    pub fn to_event(&self) -> HapEvent {
+        let span = self.whole_or_part();
+        HapEvent {
+            begin: span.begin.to_float(),
+            end: span.end.to_float(),
+            value: self.value.to_json(),
+            controls: self
+                .context
+                .metadata
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_json()))
+                .collect(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -254,6 +287,32 @@ mod tests {
         assert!(!hap.is_active(Fraction::from_int(2)));
     }
 
+    #[test]
+    fn test_to_event_json() {
+        let whole = TimeSpan::new(Fraction::new(1, 4), Fraction::new(1, 2));
+        let mut hap = Hap::new(Some(whole), whole, Value::String("bd".into()));
+        hap.context
+            .metadata
+            .insert("gain".into(), Value::Number(0.8));
+
+        let json = serde_json::to_value(hap.to_event()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "begin": 0.25,
+                "end": 0.5,
+                "value": "bd",
+                "controls": { "gain": 0.8 },
+            })
+        );
+
+        // Without metadata, controls is empty rather than missing
+        let hap = Hap::new(Some(whole), whole, Value::Silence);
+        let json = serde_json::to_value(hap.to_event()).unwrap();
+        assert_eq!(json["value"], serde_json::Value::Null);
+        assert_eq!(json["controls"], serde_json::json!({}));
+    }
+
     #[test]
     fn test_duration() {
         let ts = TimeSpan::new(Fraction::from_int(0), Fraction::from_int(2));
@@ -24,20 +24,30 @@
 //! - **TimeSpan**: Represents time intervals
 //! - **Combinators**: Functions for combining and transforming patterns
 
+pub mod chord;
 pub mod combinators;
 pub mod euclid;
 pub mod fraction;
 pub mod hap;
+mod notation;
 pub mod pattern;
+pub mod random;
+pub mod scale;
 pub mod state;
 pub mod timespan;
 pub mod value;
 
-pub use combinators::{choose, choose_weighted, fastcat, polymeter, polyrhythm, pure, sequence, silence, slowcat, stack};
+pub use chord::Chord;
+pub use combinators::{
+    arrange, choose, choose_cycles, choose_weighted, fastcat, isaw, necklace, perlin, pick,
+    polymeter, polyrhythm, pure, rand, run, saw, sequence, silence, sine, slowcat, square, stack,
+    time_cat, tri, wedge,
+};
 pub use euclid::bjorklund;
 pub use fraction::Fraction;
-pub use hap::{Context, Hap};
-pub use pattern::Pattern;
+pub use hap::{Context, Hap, HapEvent};
+pub use pattern::{Pattern, PatternKind};
+pub use scale::Scale;
 pub use state::State;
 pub use timespan::TimeSpan;
 pub use value::Value;
@@ -0,0 +1,444 @@
+//! Best-effort reconstruction of mini notation from a built pattern
+//!
+//! Patterns are opaque closures, so the structural tag only says how the
+//! top level was built. The values and timing are recovered by querying the
+//! pattern and reading the events back: a cycle is split into equal slots,
+//! and each slot must hold a rest, a value repeated evenly (`bd*2`), a
+//! Euclidean rhythm (`bd(3,8)`) or a nested sequence (`[bd sd]`).
+
+use crate::euclid::bjorklund;
+use crate::pattern::{Pattern, PatternKind};
+use crate::{Fraction, State, TimeSpan, Value};
+
+/// Longest period (in cycles) searched for when recovering `slow`
+const MAX_PERIOD: i64 = 8;
+
+/// Most slots a cycle (or slot) is split into
+const MAX_SLOTS: usize = 16;
+
+/// Deepest nesting of bracketed sequences
+const MAX_DEPTH: usize = 3;
+
+/// Most steps a recovered Euclidean rhythm may have
+const MAX_EUCLID_STEPS: i64 = 64;
+
+/// An event read back from a pattern, with its value already in mini notation
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    begin: Fraction,
+    end: Fraction,
+    value: String,
+}
+
+/// Reconstruct mini notation for a pattern, see [`Pattern::to_mini`]
+pub(crate) fn to_mini(pattern: &Pattern) -> Option<String> {
+    match pattern.kind() {
+        PatternKind::Silence => Some("~".to_string()),
+        PatternKind::Slowcat(n) => slowcat_to_mini(pattern, n as i64),
+        kind => {
+            let period = (1..=MAX_PERIOD).find(|&period| {
+                match (
+                    events(pattern, 0, period),
+                    events(pattern, period, 2 * period),
+                ) {
+                    (Some(first), Some(second)) => first == shift(second, period),
+                    _ => false,
+                }
+            })?;
+
+            let scale = Fraction::new(1, period);
+            let cycle: Vec<Event> = events(pattern, 0, period)?
+                .into_iter()
+                .map(|event| Event {
+                    begin: event.begin * scale,
+                    end: event.end * scale,
+                    value: event.value,
+                })
+                .collect();
+
+            let (body, compound) = match kind {
+                PatternKind::Pure => (
+                    slot(&cycle, Fraction::from_int(0), Fraction::from_int(1), 0)?,
+                    false,
+                ),
+                PatternKind::Fastcat(n) => fastcat(&cycle, n)?,
+                PatternKind::Unknown => untagged(&cycle)?,
+                PatternKind::Stack(n) => {
+                    let layers = layers(&cycle);
+                    if layers.len() != n {
+                        return None;
+                    }
+                    let layers = layers
+                        .iter()
+                        .map(|layer| {
+                            Some(
+                                sequence(layer, Fraction::from_int(0), Fraction::from_int(1), 0)?
+                                    .join(" "),
+                            )
+                        })
+                        .collect::<Option<Vec<_>>>()?;
+                    (layers.join(","), n > 1)
+                }
+                _ => return None,
+            };
+
+            Some(match (period, compound) {
+                (1, _) => body,
+                (_, true) => format!("[{}]/{}", body, period),
+                (_, false) => format!("{}/{}", body, period),
+            })
+        }
+    }
+}
+
+/// Render a slowcat as `<a b>`, one part per cycle
+fn slowcat_to_mini(pattern: &Pattern, n: i64) -> Option<String> {
+    if n == 0 {
+        return Some("~".to_string());
+    }
+
+    let all = events(pattern, 0, n)?;
+    if all != shift(events(pattern, n, 2 * n)?, n) {
+        return None;
+    }
+
+    let cycles = (0..n)
+        .map(|cycle| {
+            let begin = Fraction::from_int(cycle);
+            let cycle_events: Vec<Event> = all
+                .iter()
+                .filter(|event| event.begin.floor() == begin)
+                .cloned()
+                .collect();
+            if cycle_events
+                .iter()
+                .any(|event| event.end > begin + Fraction::from_int(1))
+            {
+                return None;
+            }
+
+            let parts = sequence(
+                &shift(cycle_events, cycle),
+                Fraction::from_int(0),
+                Fraction::from_int(1),
+                0,
+            )?;
+            Some(if parts.len() > 1 {
+                format!("[{}]", parts.join(" "))
+            } else {
+                parts.join(" ")
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(format!("<{}>", cycles.join(" ")))
+}
+
+/// Query the events starting in `begin..end`
+///
+/// Returns None if an event has no whole (a continuous signal), runs past
+/// `end`, or holds a value mini notation can't spell.
+fn events(pattern: &Pattern, begin: i64, end: i64) -> Option<Vec<Event>> {
+    let end = Fraction::from_int(end);
+    let span = TimeSpan::new(Fraction::from_int(begin), end);
+
+    let haps = pattern.query(State::new(span));
+    if haps.iter().any(|hap| hap.whole.is_none()) {
+        return None;
+    }
+
+    haps.into_iter()
+        .filter(|hap| hap.has_onset())
+        .map(|hap| {
+            let whole = hap.whole?;
+            if whole.end > end {
+                return None;
+            }
+            Some(Event {
+                begin: whole.begin,
+                end: whole.end,
+                value: format_value(&hap.value)?,
+            })
+        })
+        .collect()
+}
+
+/// Move events `cycles` earlier in time
+fn shift(events: Vec<Event>, cycles: i64) -> Vec<Event> {
+    let offset = Fraction::from_int(cycles);
+    events
+        .into_iter()
+        .map(|event| Event {
+            begin: event.begin - offset,
+            end: event.end - offset,
+            value: event.value,
+        })
+        .collect()
+}
+
+/// Spell a value as a mini notation atom
+fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 => Some(format!("{}", *n as i64)),
+        Value::Number(n) => Some(format!("{}", n)),
+        Value::String(s) if !s.is_empty() && !s.contains(char::is_whitespace) => Some(s.clone()),
+        Value::Silence => Some("~".to_string()),
+        _ => None,
+    }
+}
+
+/// Render a fastcat of `n` children, recovering a `fast` factor as `[..]*k`
+///
+/// Returns the notation and whether it is a bare sequence that needs
+/// brackets before another operator can be applied.
+fn fastcat(events: &[Event], n: usize) -> Option<(String, bool)> {
+    if n == 0 {
+        return None;
+    }
+
+    // Prefer the largest repeat count, so `[bd sd]*2` isn't spelled out twice
+    (1..=MAX_SLOTS / n).rev().find_map(|repeats| {
+        let parts = slots(
+            events,
+            Fraction::from_int(0),
+            Fraction::from_int(1),
+            n * repeats,
+            0,
+        )?;
+        if parts
+            .iter()
+            .enumerate()
+            .any(|(i, part)| *part != parts[i % n])
+        {
+            return None;
+        }
+
+        let body = parts[..n].join(" ");
+        Some(match repeats {
+            1 => (body, n > 1),
+            _ if n > 1 => (format!("[{}]*{}", body, repeats), false),
+            _ => (format!("{}*{}", body, repeats), false),
+        })
+    })
+}
+
+/// Render a cycle with no structural tag, as the fewest equal slots
+///
+/// Slots that all read the same collapse into one repeated step, so
+/// `[bd sd]` played twice a cycle comes back as `[bd sd]*2`.
+fn untagged(events: &[Event]) -> Option<(String, bool)> {
+    let parts = sequence(events, Fraction::from_int(0), Fraction::from_int(1), 0)?;
+    Some(match parts.as_slice() {
+        [part] => (part.clone(), false),
+        [first, rest @ ..] if rest.iter().all(|part| part == first) => {
+            (format!("{}*{}", first, parts.len()), false)
+        }
+        _ => (parts.join(" "), true),
+    })
+}
+
+/// Split overlapping events into stack layers, in query order
+///
+/// A stack queries each child in turn, so a new layer starts whenever an
+/// event begins before the previous one has ended.
+fn layers(events: &[Event]) -> Vec<Vec<Event>> {
+    let mut layers: Vec<Vec<Event>> = Vec::new();
+    for event in events {
+        match layers.last_mut() {
+            Some(layer) if layer.last().is_some_and(|last| last.end <= event.begin) => {
+                layer.push(event.clone())
+            }
+            _ => layers.push(vec![event.clone()]),
+        }
+    }
+    layers
+}
+
+/// Render events in `begin..begin + width` as a sequence with the fewest slots
+fn sequence(
+    events: &[Event],
+    begin: Fraction,
+    width: Fraction,
+    depth: usize,
+) -> Option<Vec<String>> {
+    match atom(events, begin, width) {
+        Some(atom) => Some(vec![atom]),
+        None => (2..=MAX_SLOTS).find_map(|count| slots(events, begin, width, count, depth)),
+    }
+}
+
+/// Render events in `begin..begin + width` as exactly `count` equal slots
+fn slots(
+    events: &[Event],
+    begin: Fraction,
+    width: Fraction,
+    count: usize,
+    depth: usize,
+) -> Option<Vec<String>> {
+    let step = width / Fraction::from_int(count as i64);
+
+    (0..count)
+        .map(|i| {
+            let slot_begin = begin + step * Fraction::from_int(i as i64);
+            let slot_end = slot_begin + step;
+            let inside: Vec<Event> = events
+                .iter()
+                .filter(|event| slot_begin <= event.begin && event.begin < slot_end)
+                .cloned()
+                .collect();
+            if inside.iter().any(|event| event.end > slot_end) {
+                return None;
+            }
+            slot(&inside, slot_begin, step, depth)
+        })
+        .collect()
+}
+
+/// Render the events of a single slot as one element
+fn slot(events: &[Event], begin: Fraction, width: Fraction, depth: usize) -> Option<String> {
+    atom(events, begin, width).or_else(|| {
+        if depth >= MAX_DEPTH {
+            return None;
+        }
+        sequence(events, begin, width, depth + 1).map(|parts| format!("[{}]", parts.join(" ")))
+    })
+}
+
+/// Render a slot holding a rest, a repeated value or a Euclidean rhythm
+fn atom(events: &[Event], begin: Fraction, width: Fraction) -> Option<String> {
+    let Some(first) = events.first() else {
+        return Some("~".to_string());
+    };
+    if events.iter().any(|event| event.value != first.value) {
+        return None;
+    }
+
+    let count = events.len() as i64;
+    let even = events.iter().enumerate().all(|(i, event)| {
+        let i = i as i64;
+        event.begin == begin + width * Fraction::new(i, count)
+            && event.end == begin + width * Fraction::new(i + 1, count)
+    });
+    if even {
+        return Some(match count {
+            1 => first.value.clone(),
+            _ => format!("{}*{}", first.value, count),
+        });
+    }
+
+    euclid(events, begin, width).map(|rhythm| format!("{}{}", first.value, rhythm))
+}
+
+/// Recover the `(pulse,step,rotation)` suffix of a Euclidean rhythm
+fn euclid(events: &[Event], begin: Fraction, width: Fraction) -> Option<String> {
+    let step_width = events[0].end - events[0].begin;
+    if events
+        .iter()
+        .any(|event| event.end - event.begin != step_width)
+    {
+        return None;
+    }
+
+    let steps = width / step_width;
+    if steps.denominator != 1 || steps.numerator > MAX_EUCLID_STEPS {
+        return None;
+    }
+    let steps = steps.numerator as usize;
+
+    let mut hits = vec![false; steps];
+    for event in events {
+        let index = (event.begin - begin) / step_width;
+        if index.denominator != 1 {
+            return None;
+        }
+        hits[index.numerator as usize] = true;
+    }
+
+    let pulse = events.len();
+    let rotation = (0..steps).find(|&rotation| bjorklund(pulse, steps, rotation) == hits)?;
+    Some(match rotation {
+        0 => format!("({},{})", pulse, steps),
+        _ => format!("({},{},{})", pulse, steps, rotation),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fastcat, pure, silence, slowcat, stack, Pattern, Value};
+
+    fn s(value: &str) -> Pattern {
+        pure(Value::String(value.into()))
+    }
+
+    #[test]
+    fn test_sequence_and_stack() {
+        assert_eq!(
+            fastcat(vec![s("bd"), s("sd")]).to_mini().as_deref(),
+            Some("bd sd")
+        );
+        assert_eq!(
+            stack(vec![s("bd"), s("sd")]).to_mini().as_deref(),
+            Some("bd,sd")
+        );
+        assert_eq!(s("bd").to_mini().as_deref(), Some("bd"));
+        assert_eq!(silence().to_mini().as_deref(), Some("~"));
+        assert_eq!(pure(Value::Number(3.0)).to_mini().as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_nested() {
+        let pattern = fastcat(vec![s("bd"), fastcat(vec![s("sd"), s("hh")]), silence()]);
+        assert_eq!(pattern.to_mini().as_deref(), Some("bd [sd hh] ~"));
+
+        let pattern = stack(vec![fastcat(vec![s("bd"), s("sd")]), s("hh")]);
+        assert_eq!(pattern.to_mini().as_deref(), Some("bd sd,hh"));
+
+        let pattern = slowcat(vec![s("bd"), fastcat(vec![s("sd"), s("cp")])]);
+        assert_eq!(pattern.to_mini().as_deref(), Some("<bd [sd cp]>"));
+    }
+
+    #[test]
+    fn test_fast_slow_euclid() {
+        assert_eq!(s("bd").fast(2.0).to_mini().as_deref(), Some("bd*2"));
+        assert_eq!(s("bd").slow(2.0).to_mini().as_deref(), Some("bd/2"));
+        assert_eq!(
+            fastcat(vec![s("bd"), s("sd")])
+                .fast(2.0)
+                .to_mini()
+                .as_deref(),
+            Some("[bd sd]*2")
+        );
+        assert_eq!(
+            fastcat(vec![s("bd"), s("sd")])
+                .slow(2.0)
+                .to_mini()
+                .as_deref(),
+            Some("[bd sd]/2")
+        );
+        assert_eq!(
+            s("bd").euclid(3, 8, None).to_mini().as_deref(),
+            Some("bd(3,8)")
+        );
+        assert_eq!(
+            s("bd").euclid(3, 8, Some(2)).to_mini().as_deref(),
+            Some("bd(3,8,2)")
+        );
+    }
+
+    #[test]
+    fn test_untagged() {
+        let pattern = fastcat(vec![s("bd"), s("sd"), s("hh")]).rev();
+        assert_eq!(pattern.to_mini().as_deref(), Some("hh sd bd"));
+        let pattern = fastcat(vec![s("bd"), s("sd")]).fast(2.0).late(0.5);
+        assert_eq!(pattern.to_mini().as_deref(), Some("[bd sd]*2"));
+        let pattern = s("bd").ply(3);
+        assert_eq!(pattern.to_mini().as_deref(), Some("bd*3"));
+        assert_eq!(Pattern::new(|_| Vec::new()).to_mini().as_deref(), Some("~"));
+    }
+
+    #[test]
+    fn test_unspellable() {
+        assert_eq!(s("two words").fast(2.0).to_mini(), None);
+        assert_eq!(crate::sine().to_mini(), None);
+    }
+}
@@ -1,6 +1,31 @@
-use crate::{Fraction, Hap, State, TimeSpan, Value};
+use crate::random::random_at;
+use crate::scale::Scale;
+use crate::{Context, Fraction, Hap, State, TimeSpan, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Structural description of how a pattern was built
+///
+/// Patterns are opaque closures, so combinators record a lightweight tag that
+/// tooling (e.g. the visualizer) can use to introspect the top-level structure.
+/// Transformations that only change values keep the tag of the pattern they
+/// were applied to; ones that move, drop or add events reset it to `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    /// Built directly from a query function
+    Unknown,
+    /// A single constant value (`pure`)
+    Pure,
+    /// No events (`silence`)
+    Silence,
+    /// Patterns crammed into one cycle (`fastcat`/`sequence`), with the number of children
+    Fastcat(usize),
+    /// Patterns alternating per cycle (`slowcat`), with the number of children
+    Slowcat(usize),
+    /// Patterns playing simultaneously (`stack`), with the number of children
+    Stack(usize),
+}
+
 /// A Pattern represents a time-varying sequence of values
 ///
 /// Patterns are queried with a State (containing a timespan) and return
@@ -11,6 +36,9 @@ pub struct Pattern {
 
     /// Optional step count (number of steps per cycle)
     steps: Option<Fraction>,
+
+    /// Structural tag describing how the pattern was built
+    kind: PatternKind,
 }
 
 impl Pattern {
@@ -22,6 +50,7 @@ impl Pattern {
         Pattern {
             query_func: Arc::new(query_func),
             steps: None,
+            kind: PatternKind::Unknown,
         }
     }
 
@@ -33,6 +62,7 @@ impl Pattern {
         Pattern {
             query_func: Arc::new(query_func),
             steps,
+            kind: PatternKind::Unknown,
         }
     }
 
@@ -52,6 +82,29 @@ impl Pattern {
         self
     }
 
+    /// Get the structural kind of this pattern
+    pub fn kind(&self) -> PatternKind {
+        self.kind
+    }
+
+    /// Set the structural kind of this pattern
+    pub fn set_kind(mut self, kind: PatternKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Reconstruct mini notation for this pattern
+    ///
+    /// Best effort: the values are read back from the events, and the
+    /// structural tag, when there is one, gives the shape. Sequences, stacks,
+    /// alternations, `fast`/`slow` and Euclidean rhythms of plain values
+    /// round-trip, e.g. `fastcat([bd, sd])` becomes `"bd sd"`. Untagged
+    /// patterns are spelled with the fewest equal steps per cycle. Returns
+    /// None for patterns it can't spell, such as continuous signals.
+    pub fn to_mini(&self) -> Option<String> {
+        crate::notation::to_mini(self)
+    }
+
     /// Apply a function to each value in the pattern
     ///
     /// This is the functor map operation (fmap)
@@ -61,6 +114,7 @@ impl Pattern {
     {
         let query_func = self.query_func.clone();
         let steps = self.steps;
+        let kind = self.kind;
 
         Pattern {
             query_func: Arc::new(move |state| {
@@ -70,6 +124,7 @@ impl Pattern {
                     .collect()
             }),
             steps,
+            kind,
         }
     }
 
@@ -87,6 +142,7 @@ impl Pattern {
                 query_func(state.set_span(new_span))
             }),
             steps,
+            kind: PatternKind::Unknown,
         }
     }
 
@@ -108,6 +164,7 @@ impl Pattern {
                     .collect()
             }),
             steps,
+            kind: PatternKind::Unknown,
         }
     }
 
@@ -124,6 +181,7 @@ impl Pattern {
                 query_func(state).into_iter().map(|hap| func(&hap)).collect()
             }),
             steps,
+            kind: PatternKind::Unknown,
         }
     }
 
@@ -138,6 +196,7 @@ impl Pattern {
         Pattern {
             query_func: Arc::new(move |state| func(query_func(state))),
             steps,
+            kind: PatternKind::Unknown,
         }
     }
 
@@ -147,6 +206,7 @@ impl Pattern {
     pub fn split_queries(self) -> Pattern {
         let query_func = self.query_func.clone();
         let steps = self.steps;
+        let kind = self.kind;
 
         Pattern {
             query_func: Arc::new(move |state| {
@@ -180,25 +240,52 @@ impl Pattern {
                 all_haps
             }),
             steps,
+            kind,
         }
     }
 
     /// Speed up the pattern by a constant factor
     ///
-    /// Multiplies the query time by the factor and divides hap times
+    /// Multiplies the query time by the factor and divides hap times. The
+    /// step count is multiplied by the factor too.
     pub fn fast(self, factor: f64) -> Pattern {
-        let frac_factor = Fraction::from_float(factor);
-        self.with_query_time(move |t| t * frac_factor)
-            .with_hap_time(move |t| t / frac_factor)
+        self.fast_frac(Fraction::from_float(factor))
     }
 
     /// Slow down the pattern by a constant factor
     ///
-    /// Divides the query time by the factor and multiplies hap times
+    /// Divides the query time by the factor and multiplies hap times. The
+    /// step count is divided by the factor too.
     pub fn slow(self, factor: f64) -> Pattern {
-        let frac_factor = Fraction::from_float(factor);
-        self.with_query_time(move |t| t / frac_factor)
-            .with_hap_time(move |t| t * frac_factor)
+        self.slow_frac(Fraction::from_float(factor))
+    }
+
+    /// Speed up the pattern by an exact fractional factor
+    ///
+    /// Unlike `fast`, the factor is not approximated from a float, so ratios
+    /// like 1/3 stay exact and events never drift off cycle boundaries.
+    pub fn fast_frac(self, factor: Fraction) -> Pattern {
+        // A pattern stopped or reversed by the factor has no steps to align
+        let steps = self
+            .steps
+            .filter(|_| factor > Fraction::from_int(0))
+            .map(|s| s * factor);
+        self.with_query_time(move |t| t * factor)
+            .with_hap_time(move |t| t / factor)
+            .set_steps(steps)
+    }
+
+    /// Slow down the pattern by an exact fractional factor
+    ///
+    /// See `fast_frac`.
+    pub fn slow_frac(self, factor: Fraction) -> Pattern {
+        let steps = self
+            .steps
+            .filter(|_| factor > Fraction::from_int(0))
+            .map(|s| s / factor);
+        self.with_query_time(move |t| t / factor)
+            .with_hap_time(move |t| t * factor)
+            .set_steps(steps)
     }
 
     /// Repeat each cycle n times
@@ -242,6 +329,7 @@ impl Pattern {
                     .collect()
             }),
             steps,
+            kind: PatternKind::Unknown,
         }
         .split_queries()
     }
@@ -262,550 +350,3962 @@ impl Pattern {
         self.repeat_cycles(n).fast(n as f64)
     }
 
-    /// Apply a Euclidean rhythm pattern
+    /// Reverse each cycle
     ///
-    /// Filters events based on the Bjorklund algorithm distribution
-    pub fn euclid(self, pulse: usize, step: usize, rotation: Option<usize>) -> Pattern {
-        let rot = rotation.unwrap_or(0);
-        let rhythm = crate::euclid::bjorklund(pulse, step, rot);
+    /// Events are mirrored within their cycle, so `"a b c".rev()` plays
+    /// `c b a`. Matches Strudel's `rev`; the step count is unchanged.
+    pub fn rev(self) -> Pattern {
+        let query_func = self.query_func.clone();
+        let steps = self.steps;
+
+        Pattern {
+            query_func: Arc::new(move |state| {
+                let cycle = state.span.begin.floor();
+                let next_cycle = cycle + Fraction::from_int(1);
+                let reflect = move |span: &TimeSpan| {
+                    TimeSpan::new(
+                        cycle + (next_cycle - span.end),
+                        cycle + (next_cycle - span.begin),
+                    )
+                };
+
+                query_func(state.with_span(reflect))
+                    .into_iter()
+                    .map(|hap| hap.with_span(reflect))
+                    .collect()
+            }),
+            steps,
+            kind: PatternKind::Unknown,
+        }
+        .split_queries()
+    }
+
+    /// Alternate between playing forwards and backwards
+    ///
+    /// Even cycles play as-is and odd cycles play reversed, so
+    /// `"a b c".palindrome()` plays `a b c` then `c b a`. Matches Strudel's
+    /// `palindrome`.
+    pub fn palindrome(self) -> Pattern {
+        let steps = self.steps;
+        let reversed = self.clone().rev();
+
+        Pattern::with_steps(
+            move |state| {
+                let cycle = state.span.begin.floor().numerator;
+                if cycle.rem_euclid(2) == 0 {
+                    self.query(state)
+                } else {
+                    reversed.query(state)
+                }
+            },
+            steps,
+        )
+        .split_queries()
+    }
 
-        if rhythm.is_empty() {
+    /// Bite - cut each cycle into n chunks and re-sequence them
+    ///
+    /// Each event of `indices` selects a chunk by number (wrapping modulo n)
+    /// and squeezes it into that event's timespan. Matches Strudel's `bite`,
+    /// e.g. `bite(4, "0 0 1 1")` plays the first quarter twice, then the
+    /// second quarter twice.
+    ///
+    /// # Arguments
+    /// * `n` - Number of chunks per cycle. `0` yields silence
+    /// * `indices` - Pattern of chunk numbers
+    pub fn bite(self, n: usize, indices: Pattern) -> Pattern {
+        if n == 0 {
             return Pattern::new(|_| Vec::new());
         }
 
-        self.split_queries().with_haps(move |haps| {
-            haps.into_iter()
-                .enumerate()
-                .filter(|(i, _)| rhythm[i % rhythm.len()])
-                .map(|(_, hap)| hap)
-                .collect()
+        let n_frac = Fraction::from_int(n as i64);
+
+        Pattern::new(move |state| {
+            let mut result = Vec::new();
+
+            for index_hap in indices.query(state.clone()) {
+                let index = match index_hap.value {
+                    Value::Number(i) => (i.floor() as i64).rem_euclid(n as i64),
+                    _ => continue,
+                };
+
+                // Map the index event's span onto the selected chunk of its cycle
+                let slot = index_hap.whole_or_part();
+                let slot_len = slot.end - slot.begin;
+                let chunk_begin = slot.begin.floor() + Fraction::from_int(index) / n_frac;
+
+                let to_inner = |x: Fraction| chunk_begin + (x - slot.begin) / slot_len / n_frac;
+                let to_outer = |x: Fraction| slot.begin + (x - chunk_begin) * n_frac * slot_len;
+
+                let inner_span = TimeSpan::new(to_inner(index_hap.part.begin), to_inner(index_hap.part.end));
+                result.extend(
+                    self.query(state.set_span(inner_span))
+                        .into_iter()
+                        .map(|hap| hap.with_span(|ts| TimeSpan::new(to_outer(ts.begin), to_outer(ts.end)))),
+                );
+            }
+
+            result
         })
+        .split_queries()
     }
 
-    /// Randomly remove events with a given probability
+    /// Play only part of each cycle, stretched to fill the whole cycle
+    ///
+    /// Matches Strudel's `zoom`, e.g. `"a b c d".zoom(0.5, 1.0)` plays `c d`.
+    /// This is the inverse of squeezing a cycle into `[begin, end)`. The step
+    /// count is scaled by the size of the window.
     ///
     /// # Arguments
-    /// * `amount` - Probability of removing each event (0.0 = keep all, 1.0 = remove all)
-    /// * `seed` - Random seed for reproducibility
-    pub fn degrade_by(self, amount: f64, seed: u64) -> Pattern {
-        use rand::{Rng, SeedableRng};
-        use rand::rngs::StdRng;
+    /// * `begin` - Start of the window within the cycle
+    /// * `end` - End of the window. An empty window yields silence
+    pub fn zoom(self, begin: f64, end: f64) -> Pattern {
+        self.zoom_frac(Fraction::from_float(begin), Fraction::from_float(end))
+    }
 
-        self.with_haps(move |haps| {
-            let mut rng = StdRng::seed_from_u64(seed);
-            haps.into_iter()
-                .filter(|_| rng.gen::<f64>() > amount)
+    /// Zoom into part of each cycle, with exact bounds
+    ///
+    /// See `zoom`.
+    pub fn zoom_frac(self, begin: Fraction, end: Fraction) -> Pattern {
+        if end <= begin {
+            return Pattern::new(|_| Vec::new());
+        }
+
+        let len = end - begin;
+        let steps = self.steps.map(|s| s * len);
+
+        Pattern::new(move |state| {
+            let cycle = state.span.begin.floor();
+            let to_inner = |t: Fraction| cycle + begin + (t - cycle) * len;
+            let to_outer = |t: Fraction| cycle + (t - cycle - begin) / len;
+
+            let inner_span = TimeSpan::new(to_inner(state.span.begin), to_inner(state.span.end));
+            self.query(state.set_span(inner_span))
+                .into_iter()
+                .map(|hap| hap.with_span(|ts| TimeSpan::new(to_outer(ts.begin), to_outer(ts.end))))
                 .collect()
         })
+        .set_steps(steps)
+        .split_queries()
     }
 
-    /// Randomly remove 50% of events
-    pub fn degrade(self) -> Pattern {
-        self.degrade_by(0.5, 0)
-    }
-
-    /// Map numeric values to a musical scale
+    /// Squeeze each cycle into part of the cycle, leaving silence elsewhere
     ///
-    /// This is a simplified implementation that stores the scale name in context
-    /// and maps numbers to notes. In the future, this could be enhanced with
-    /// a proper music theory library.
+    /// Matches Strudel's `compress`, e.g. `"a b".compress(0.25, 0.75)` plays
+    /// `a` and `b` in the middle half of each cycle. This is the inverse of
+    /// `zoom`.
     ///
     /// # Arguments
-    /// * `scale_name` - Name of the scale (e.g., "C:major", "D:minor")
+    /// * `begin` - Start of the window within the cycle
+    /// * `end` - End of the window. Windows that are empty or fall outside
+    ///   `[0, 1]` yield silence
+    pub fn compress(self, begin: f64, end: f64) -> Pattern {
+        self.compress_frac(Fraction::from_float(begin), Fraction::from_float(end))
+    }
+
+    /// Compress each cycle into a window, with exact bounds
     ///
-    /// For now, this implementation:
-    /// - Stores the scale name in the event context
-    /// - Converts numeric values to string notes based on a simple major scale
-    pub fn scale(self, scale_name: String) -> Pattern {
-        self.with_hap(move |hap| {
-            // Store scale in context
-            let mut new_context = hap.context.clone();
-            new_context.metadata.insert("scale".to_string(), Value::String(scale_name.clone()));
+    /// See `compress`.
+    pub fn compress_frac(self, begin: Fraction, end: Fraction) -> Pattern {
+        if begin >= end || begin < Fraction::from_int(0) || end > Fraction::from_int(1) {
+            return Pattern::new(|_| Vec::new());
+        }
 
-            // If value is a number, map it to a note in the scale
-            let new_value = match &hap.value {
-                Value::Number(n) => {
-                    // Simple major scale mapping: C D E F G A B
-                    // This is a basic implementation - could be enhanced with proper scale theory
-                    let note_names = ["C", "D", "E", "F", "G", "A", "B"];
-                    let step = (*n as i32).rem_euclid(note_names.len() as i32) as usize;
-                    let octave = (*n as i32).div_euclid(note_names.len() as i32) + 3; // Start at octave 3
-                    Value::String(format!("{}{}", note_names[step], octave))
-                }
-                _ => hap.value.clone(),
+        let len = end - begin;
+        let steps = self.steps;
+
+        Pattern::new(move |state| {
+            let cycle = state.span.begin.floor();
+            let window = TimeSpan::new(cycle + begin, cycle + end);
+            let Some(span) = state.span.intersection(&window) else {
+                return Vec::new();
             };
 
-            Hap::with_context(hap.whole, hap.part, new_value, new_context)
+            let to_inner = |t: Fraction| cycle + (t - cycle - begin) / len;
+            let to_outer = |t: Fraction| cycle + begin + (t - cycle) * len;
+
+            let inner_span = TimeSpan::new(to_inner(span.begin), to_inner(span.end));
+            self.query(state.set_span(inner_span))
+                .into_iter()
+                .map(|hap| hap.with_span(|ts| TimeSpan::new(to_outer(ts.begin), to_outer(ts.end))))
+                .collect()
         })
+        .set_steps(steps)
+        .split_queries()
     }
 
-    /// Apply a boolean/rhythm structure pattern to this pattern
-    ///
-    /// The structure pattern determines when events from this pattern should occur.
-    /// Events are only kept where the structure pattern has truthy values.
+    /// Loop the first fraction of each cycle to fill the whole cycle
     ///
-    /// This is similar to TidalCycles/Strudel's `struct` function, which applies
-    /// a rhythmic structure to a pattern. The structure pattern provides the timing,
-    /// while this pattern provides the values.
+    /// Matches Strudel's `linger`, e.g. `"a b c d".linger(0.25)` plays `a`
+    /// four times. A negative fraction loops the end of the cycle instead,
+    /// so `linger(-0.25)` plays `d` four times. Like Strudel, this is
+    /// `zoom(0, t).fast(1 / t)`, so each repeat comes from the next cycle
+    /// of the zoomed pattern.
     ///
     /// # Arguments
-    /// * `structure` - A pattern that provides the rhythmic structure. Events from
-    ///   this pattern are kept only where the structure has truthy values (non-zero
-    ///   numbers, non-empty strings, true booleans, etc.)
-    ///
-    /// # Example
-    /// ```
-    /// use strudel_core::{pure, fastcat, Value};
-    ///
-    /// // Apply a simple on-off rhythm
-    /// let values = fastcat(vec![
-    ///     pure(Value::String("a".into())),
-    ///     pure(Value::String("b".into())),
-    ///     pure(Value::String("c".into())),
-    ///     pure(Value::String("d".into())),
-    /// ]);
-    ///
-    /// let structure = fastcat(vec![
-    ///     pure(Value::Number(1.0)),  // keep
-    ///     pure(Value::Number(0.0)),  // skip
-    ///     pure(Value::Number(1.0)),  // keep
-    ///     pure(Value::Number(0.0)),  // skip
-    /// ]);
+    /// * `t` - Fraction of the cycle to repeat. `0` or a value that is not
+    ///   finite yields silence, and `1` or more leaves the pattern unchanged
+    pub fn linger(self, t: f64) -> Pattern {
+        if !t.is_finite() {
+            return Pattern::new(|_| Vec::new());
+        }
+        let len = Fraction::from_float(t.abs().min(1.0));
+        if len.is_zero() {
+            return Pattern::new(|_| Vec::new());
+        }
+        if len >= Fraction::from_int(1) {
+            return self;
+        }
+
+        let one = Fraction::from_int(1);
+        let zoomed = if t < 0.0 {
+            self.zoom_frac(one - len, one)
+        } else {
+            self.zoom_frac(Fraction::from_int(0), len)
+        };
+        zoomed.fast_frac(one / len)
+    }
+
+    /// Repeat each event n times within its own timespan
     ///
-    /// let result = values.struct_(structure);
-    /// // Result will only have events at positions 0 and 2
-    /// ```
-    pub fn struct_(self, structure: Pattern) -> Pattern {
-        let value_pattern = self;
-        let structure_pattern = structure;
+    /// Every event's whole is divided into n equal slices, each carrying the
+    /// event's value. Matches Strudel's `ply`, e.g. `"bd sd".ply(2)` plays
+    /// `bd bd sd sd`. Continuous events (without a whole) pass through unchanged.
+    pub fn ply(self, n: usize) -> Pattern {
+        if n == 0 {
+            return Pattern::new(|_| Vec::new());
+        }
 
-        Pattern::new(move |state| {
-            let mut result_haps = Vec::new();
-
-            // Query the structure pattern to get the timing information
-            let structure_haps = structure_pattern.query(state.clone());
-
-            // For each structure hap, query the value pattern at that timespan
-            for structure_hap in structure_haps {
-                // Check if the structure value is truthy
-                if is_truthy(&structure_hap.value) {
-                    // Query the value pattern for this timespan
-                    let value_state = state.set_span(structure_hap.whole_or_part());
-                    let value_haps = value_pattern.query(value_state);
-
-                    // For each value hap that intersects with the structure hap
-                    for value_hap in value_haps {
-                        if let Some(new_part) = structure_hap.part.intersection(&value_hap.part) {
-                            // Create a new hap with the structure's whole timespan
-                            // but the value from the value pattern
-                            let new_hap = Hap::new(
-                                structure_hap.whole,
-                                new_part,
-                                value_hap.value.clone(),
-                            );
-                            result_haps.push(new_hap);
+        if n == 1 {
+            return self;
+        }
+
+        let query_func = self.query_func.clone();
+        let steps = self.steps.map(|s| s * Fraction::from_int(n as i64));
+        let n_frac = Fraction::from_int(n as i64);
+
+        Pattern {
+            query_func: Arc::new(move |state| {
+                let mut result = Vec::new();
+
+                for hap in query_func(state) {
+                    let whole = match hap.whole {
+                        Some(whole) => whole,
+                        None => {
+                            result.push(hap);
+                            continue;
+                        }
+                    };
+
+                    let slice_len = (whole.end - whole.begin) / n_frac;
+                    for i in 0..n {
+                        let begin = whole.begin + slice_len * Fraction::from_int(i as i64);
+                        let slice = TimeSpan::new(begin, begin + slice_len);
+
+                        if let Some(part) = slice.intersection(&hap.part) {
+                            result.push(Hap::with_context(
+                                Some(slice),
+                                part,
+                                hap.value.clone(),
+                                hap.context.clone(),
+                            ));
                         }
                     }
                 }
-            }
-
-            result_haps
-        })
-    }
 
-    /// Append another pattern to this one (list cons operator)
-    ///
-    /// Creates a sequence where this pattern is followed by the given pattern.
-    /// Similar to fastcat([self, other]) - concatenates patterns within a cycle.
-    /// This implements the ":" operator from mini notation (e.g., "a:b" becomes [a, b]).
-    pub fn tail(self, other: Pattern) -> Pattern {
-        crate::fastcat(vec![self, other])
+                result
+            }),
+            steps,
+            kind: PatternKind::Unknown,
+        }
     }
 
-    /// Shift pattern in time (nudge forward or backward)
-    ///
-    /// Positive values shift the pattern later in time (equivalent to `late`).
-    /// Negative values shift the pattern earlier in time (equivalent to `early`).
+    /// Apply a transformation every n cycles
     ///
-    /// This is implemented by:
-    /// - Subtracting the offset from query time (asking for earlier/later events)
-    /// - Adding the offset to hap time (placing events earlier/later in output)
+    /// The function is applied on cycles where `cycle % n == 0` (so the first
+    /// cycle is always transformed), and all other cycles play unchanged.
+    /// Matches Strudel's `every`, e.g. `.every(4, x => x.rev())`.
     ///
     /// # Arguments
-    /// * `amount` - Number of cycles to shift. Positive = later, negative = earlier
+    /// * `n` - Period in cycles. `0` leaves the pattern unchanged
+    /// * `f` - Transformation to apply on matching cycles
     ///
     /// # Example
     /// ```
-    /// use strudel_core::{pure, Value, State, TimeSpan, Fraction};
-    ///
-    /// // Shift pattern 0.25 cycles later
-    /// let pattern = pure(Value::String("bd".into())).split_queries().shift(0.25);
-    /// let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-    /// let haps = pattern.query(state);
+    /// use strudel_core::{pure, Value};
     ///
-    /// // Event that was at 0.0 is now at 0.25
-    /// assert_eq!(haps[0].part.begin, Fraction::new(1, 4));
+    /// // Double the speed on every fourth cycle
+    /// let pattern = pure(Value::String("bd".into())).every(4, |p| p.fast(2.0));
     /// ```
-    pub fn shift(self, amount: f64) -> Pattern {
-        let frac_amount = Fraction::from_float(amount);
+    pub fn every<F>(self, n: usize, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        if n == 0 {
+            return self;
+        }
 
-        // Shift works like late: subtract from query time, add to hap time
-        // This makes positive values shift the pattern later
-        self.with_query_time(move |t| t - frac_amount)
-            .with_hap_time(move |t| t + frac_amount)
+        let steps = self.steps;
+        let transformed = f(self.clone());
+        let n = n as i64;
+
+        Pattern::with_steps(
+            move |state| {
+                let cycle = state.span.begin.floor().numerator;
+                if cycle.rem_euclid(n) == 0 {
+                    transformed.query(state)
+                } else {
+                    self.query(state)
+                }
+            },
+            steps,
+        )
+        .split_queries()
     }
 
-    /// Nudge pattern earlier in time (equivalent to shift with negative value)
-    ///
-    /// This is the same as Strudel/TidalCycles' `early` function or `<~` operator.
+    /// Apply a function to a different part of each cycle in turn
     ///
-    /// # Arguments
-    /// * `amount` - Number of cycles to shift earlier (positive value shifts earlier)
+    /// Divides each cycle into `n` equal parts and applies `f` to part 0 on
+    /// cycle 0, part 1 on cycle 1, and so on, wrapping after `n` cycles.
+    /// Each part is transformed as by `within`. Matches Strudel's `chunk`.
     ///
     /// # Example
     /// ```
-    /// use strudel_core::{pure, Value};
+    /// use strudel_core::{fastcat, pure, Value};
     ///
-    /// // These are equivalent:
-    /// let pattern1 = pure(Value::String("bd".into())).early(0.25);
-    /// let pattern2 = pure(Value::String("bd".into())).shift(-0.25);
+    /// // Speed up one quarter of the cycle, moving along each cycle
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::String("bd".into())),
+    ///     pure(Value::String("sd".into())),
+    /// ])
+    /// .chunk(4, |p| p.fast(2.0));
     /// ```
-    pub fn early(self, amount: f64) -> Pattern {
-        self.shift(-amount)
+    pub fn chunk<F>(self, n: usize, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        if n == 0 {
+            return self;
+        }
+
+        let steps = self.steps;
+        let n = n as i64;
+        let parts: Vec<Pattern> = (0..n)
+            .map(|i| {
+                self.clone()
+                    .within_frac(Fraction::new(i, n), Fraction::new(i + 1, n), &f)
+            })
+            .collect();
+
+        Pattern::with_steps(
+            move |state| {
+                let cycle = state.span.begin.floor().numerator;
+                parts[cycle.rem_euclid(n) as usize].query(state)
+            },
+            steps,
+        )
+        .split_queries()
     }
 
-    /// Nudge pattern later in time (equivalent to shift with positive value)
-    ///
-    /// This is the same as Strudel/TidalCycles' `late` function or `~>` operator.
+    /// Apply a function to only part of each cycle
     ///
-    /// # Arguments
-    /// * `amount` - Number of cycles to shift later (positive value shifts later)
+    /// Events whose onset falls in `[begin, end)` of their cycle come from
+    /// `f(pattern)`; all other events play unchanged. Matches Strudel's
+    /// `within`.
     ///
     /// # Example
     /// ```
-    /// use strudel_core::{pure, Value};
+    /// use strudel_core::{fastcat, pure, Value};
     ///
-    /// // These are equivalent:
-    /// let pattern1 = pure(Value::String("bd".into())).late(0.25);
-    /// let pattern2 = pure(Value::String("bd".into())).shift(0.25);
+    /// // Double the speed of the first half of each cycle
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::String("bd".into())),
+    ///     pure(Value::String("sd".into())),
+    /// ])
+    /// .within(0.0, 0.5, |p| p.fast(2.0));
     /// ```
-    pub fn late(self, amount: f64) -> Pattern {
-        self.shift(amount)
+    pub fn within<F>(self, begin: f64, end: f64, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        self.within_frac(Fraction::from_float(begin), Fraction::from_float(end), f)
     }
 
-    /// Set target destination for pattern events
-    ///
-    /// Adds a "target" metadata entry to each event's context, indicating where
-    /// the event should be routed (e.g., to specific OSC targets, orbits, or MIDI devices).
-    ///
-    /// This corresponds to the `target` operator in Strudel/TidalCycles mini notation,
-    /// which is used like: `target "mysynth" $ pattern`
+    /// Apply a function to only part of each cycle, with exact bounds
     ///
-    /// # Arguments
-    /// * `target_name` - Name of the target destination
-    ///
-    /// # Example
+    /// See `within`.
+    pub fn within_frac<F>(self, begin: Fraction, end: Fraction, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let steps = self.steps;
+        let inside = move |pos: Fraction| pos >= begin && pos < end;
+
+        crate::stack(vec![
+            f(self.clone()).filter_onsets(inside),
+            self.filter_onsets(move |pos| !inside(pos)),
+        ])
+        .set_steps(steps)
+    }
+
+    /// Keep only events whose onset's position within its cycle passes `pred`
+    fn filter_onsets<F>(self, pred: F) -> Pattern
+    where
+        F: Fn(Fraction) -> bool + Send + Sync + 'static,
+    {
+        self.with_haps(move |haps| {
+            haps.into_iter()
+                .filter(|hap| {
+                    let onset = hap.whole_or_part().begin;
+                    pred(onset - onset.floor())
+                })
+                .collect()
+        })
+    }
+
+    /// Apply a function at a finer time scale
+    ///
+    /// The pattern is slowed by `n`, transformed by `f` and sped back up, so
+    /// `f` sees each `1/n` of a cycle as a whole cycle: `.inside(2, |p| p.rev())`
+    /// reverses each half cycle. Matches Strudel's `inside`. A factor that
+    /// is not positive and finite yields silence.
+    ///
+    /// # Example
     /// ```
-    /// use strudel_core::{pure, Value};
+    /// use strudel_core::{fastcat, pure, Value};
     ///
-    /// // Route pattern to a specific target
-    /// let pattern = pure(Value::String("bd".into())).target("drums".to_string());
+    /// // Plays `b a d c`
+    /// let pattern = fastcat(
+    ///     ["a", "b", "c", "d"].map(|s| pure(Value::String(s.into()))).to_vec(),
+    /// )
+    /// .inside(2.0, |p| p.rev());
+    /// ```
+    pub fn inside<F>(self, n: f64, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let factor = Fraction::from_float(n);
+        if !n.is_finite() || factor <= Fraction::from_int(0) {
+            return Pattern::new(|_| Vec::new());
+        }
+        f(self.slow_frac(factor)).fast_frac(factor)
+    }
+
+    /// Apply a function at a coarser time scale
     ///
-    /// // Multiple patterns can have different targets
-    /// let synth_pattern = pure(Value::String("c4".into())).target("synth".to_string());
-    /// let drums_pattern = pure(Value::String("bd".into())).target("drums".to_string());
+    /// The inverse of `inside`: the pattern is sped up by `n`, transformed
+    /// and slowed back down, so `f` sees `n` cycles as one.
+    /// `.outside(2, |p| p.rev())` reverses each pair of cycles. Matches
+    /// Strudel's `outside`. A factor that is not positive and finite
+    /// yields silence.
+    pub fn outside<F>(self, n: f64, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let factor = Fraction::from_float(n);
+        if !n.is_finite() || factor <= Fraction::from_int(0) {
+            return Pattern::new(|_| Vec::new());
+        }
+        f(self.fast_frac(factor)).slow_frac(factor)
+    }
+
+    /// Apply a function only to events of one sound
+    ///
+    /// Events whose value is the string `sound` come from `f(pattern)`; all
+    /// other events play unchanged. Matches Strudel's `fix`, e.g. to add an
+    /// effect to just the snares of a drum pattern.
+    ///
+    /// # Example
     /// ```
-    pub fn target(self, target_name: String) -> Pattern {
-        self.with_hap(move |hap| {
-            let mut new_context = hap.context.clone();
-            new_context.metadata.insert("target".to_string(), Value::String(target_name.clone()));
-            Hap::with_context(hap.whole, hap.part, hap.value.clone(), new_context)
-        })
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // Quieten only the snares
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::String("bd".into())),
+    ///     pure(Value::String("sd".into())),
+    /// ])
+    /// .fix(|p| p.tag("gain", Value::Number(0.5)), "sd");
+    /// ```
+    pub fn fix<F>(self, f: F, sound: &str) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let sound = sound.to_string();
+        self.fix_where(
+            f,
+            move |value| matches!(value, Value::String(s) if *s == sound),
+        )
     }
-}
 
-/// Helper function to determine if a value is "truthy"
-/// In the context of struct, we consider:
-/// - Numbers: 0 is false, anything else is true
-/// - Strings: empty string is false, anything else is true (including "~")
-/// - Booleans: use their boolean value
-/// - Lists: empty list is false, non-empty is true
-/// - Silence: false
-fn is_truthy(value: &crate::Value) -> bool {
-    match value {
-        crate::Value::Number(n) => *n != 0.0,
-        crate::Value::String(s) => !s.is_empty() && s != "~",
-        crate::Value::Bool(b) => *b,
-        crate::Value::List(l) => !l.is_empty(),
-        crate::Value::Silence => false,
+    /// Apply a function only to events with a numeric value in a range
+    ///
+    /// Events whose value is a number from `min` to `max` inclusive come from
+    /// `f(pattern)`; all other events play unchanged. Like Strudel's
+    /// `fixRange`, e.g. to soften just the low notes of a melody.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // Quieten the notes below middle C
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::Number(48.0)),
+    ///     pure(Value::Number(64.0)),
+    /// ])
+    /// .fix_range(|p| p.tag("gain", Value::Number(0.5)), 0.0, 59.0);
+    /// ```
+    pub fn fix_range<F>(self, f: F, min: f64, max: f64) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        self.fix_where(
+            f,
+            move |value| matches!(value, Value::Number(n) if (min..=max).contains(n)),
+        )
     }
-}
 
-// Implement Clone for Pattern
-impl Clone for Pattern {
-    fn clone(&self) -> Self {
-        Pattern {
-            query_func: self.query_func.clone(),
-            steps: self.steps,
+    /// Apply `f` to the events whose value passes `pred`, keeping the rest
+    fn fix_where<F, P>(self, f: F, pred: P) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+        P: Fn(&Value) -> bool + Clone + Send + Sync + 'static,
+    {
+        let steps = self.steps;
+        let is_other = {
+            let pred = pred.clone();
+            move |value: &Value| !pred(value)
+        };
+
+        crate::stack(vec![
+            f(self.clone().filter_values(pred)),
+            self.filter_values(is_other),
+        ])
+        .set_steps(steps)
+    }
+
+    /// Keep only events whose value passes `pred`
+    fn filter_values<F>(self, pred: F) -> Pattern
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        self.with_haps(move |haps| haps.into_iter().filter(|hap| pred(&hap.value)).collect())
+    }
+
+    /// Apply a Euclidean rhythm pattern
+    ///
+    /// Restructures the pattern into `step` steps per cycle and keeps the
+    /// steps chosen by the Bjorklund algorithm, so `pure("bd").euclid(3, 8)`
+    /// plays three hits per cycle.
+    pub fn euclid(self, pulse: usize, step: usize, rotation: Option<usize>) -> Pattern {
+        self.euclid_by(pulse, step, rotation.unwrap_or(0) as i64)
+    }
+
+    /// Apply a Euclidean rhythm pattern with a signed rotation
+    ///
+    /// The rotation is normalized into `0..step`, so negative values rotate
+    /// right and values larger than `step` wrap around.
+    ///
+    /// # Arguments
+    /// * `pulse` - Number of hits per cycle
+    /// * `step` - Number of steps per cycle
+    /// * `rotation` - Steps to rotate the rhythm left by
+    pub fn euclid_by(self, pulse: usize, step: usize, rotation: i64) -> Pattern {
+        self.euclid_steps(pulse, step, rotation, true)
+    }
+
+    /// Apply the inverse of a Euclidean rhythm, playing on the steps it rests
+    ///
+    /// `euclid_inv(3, 8, None)` plays `.xx.xx.x`, exactly the steps that
+    /// `euclid(3, 8, None)` leaves out.
+    pub fn euclid_inv(self, pulse: usize, step: usize, rotation: Option<usize>) -> Pattern {
+        self.euclid_steps(pulse, step, rotation.unwrap_or(0) as i64, false)
+    }
+
+    /// Play this pattern on the hits of a Euclidean rhythm and `off` on its rests
+    pub fn euclid_full(
+        self,
+        pulse: usize,
+        step: usize,
+        rotation: Option<usize>,
+        off: Pattern,
+    ) -> Pattern {
+        crate::stack(vec![
+            self.euclid(pulse, step, rotation),
+            off.euclid_inv(pulse, step, rotation),
+        ])
+    }
+
+    /// Keep the steps of a Euclidean rhythm that are hits, or those that are rests
+    fn euclid_steps(self, pulse: usize, step: usize, rotation: i64, hits: bool) -> Pattern {
+        if step == 0 {
+            return Pattern::new(|_| Vec::new());
         }
+
+        let rot = rotation.rem_euclid(step as i64) as usize;
+        let structure = crate::fastcat(
+            crate::euclid::bjorklund(pulse, step, rot)
+                .into_iter()
+                .map(|hit| crate::pure(Value::Bool(hit == hits)))
+                .collect(),
+        );
+
+        self.struct_(structure)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::Value;
+    /// Apply a Euclidean rhythm whose arguments are themselves patterns
+    ///
+    /// The pulse, step and rotation patterns are sampled at the start of
+    /// each cycle and the rhythm is rebuilt for that cycle, so a pulse
+    /// pattern of `<3 5>` alternates between 3 and 5 hits. A negative pulse
+    /// plays the inverse rhythm, as with `euclid_inv`. Cycles where pulse or
+    /// step has no numeric value are silent.
+    pub fn euclid_pat(self, pulse: Pattern, step: Pattern, rotation: Option<Pattern>) -> Pattern {
+        let source = self;
 
-    #[test]
-    fn test_pattern_creation() {
-        let pattern = Pattern::new(|_state| Vec::new());
-        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        Pattern::new(move |state| {
+            let cycle = state.span.begin.floor();
+            let pulse_val = number_at_cycle(&pulse, cycle);
+            let step_val = number_at_cycle(&step, cycle);
+            let rotation_val = rotation
+                .as_ref()
+                .and_then(|r| number_at_cycle(r, cycle))
+                .unwrap_or(0.0);
 
-        let haps = pattern.query(state);
-        assert_eq!(haps.len(), 0);
+            match (pulse_val, step_val) {
+                (Some(p), Some(s)) if s >= 0.0 => source
+                    .clone()
+                    .euclid_steps(p.abs() as usize, s as usize, rotation_val as i64, p >= 0.0)
+                    .query(state),
+                _ => Vec::new(),
+            }
+        })
+        .split_queries()
+    }
+
+    /// Randomly remove events with a given probability
+    ///
+    /// The decision for each event is keyed by its onset, so an event is kept
+    /// or removed consistently no matter how the pattern is queried.
+    ///
+    /// # Arguments
+    /// * `amount` - Probability of removing each event (0.0 = keep all, 1.0 = remove all)
+    /// * `seed` - Random seed for reproducibility
+    pub fn degrade_by(self, amount: f64, seed: u64) -> Pattern {
+        self.with_haps(move |haps| {
+            haps.into_iter()
+                .filter(|hap| random_at(hap.whole_or_part().begin, seed) >= amount)
+                .collect()
+        })
+    }
+
+    /// Randomly remove 50% of events
+    pub fn degrade(self) -> Pattern {
+        self.degrade_by(0.5, 0)
+    }
+
+    /// Keep only the events `degrade_by` would remove with the same amount and seed
+    ///
+    /// The two are complements, so stacking `degrade_by` and `undegrade_by`
+    /// with the same arguments gives back every event exactly once. This is
+    /// Strudel's `undegradeBy`.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, stack, Value};
+    ///
+    /// // Half the events on one sound, the other half on a different one
+    /// let hh = pure(Value::String("hh".into())).fast(8.0);
+    /// let oh = pure(Value::String("oh".into())).fast(8.0);
+    /// let pattern = stack(vec![hh.degrade_by(0.5, 1), oh.undegrade_by(0.5, 1)]);
+    /// ```
+    pub fn undegrade_by(self, amount: f64, seed: u64) -> Pattern {
+        self.with_haps(move |haps| {
+            haps.into_iter()
+                .filter(|hap| random_at(hap.whole_or_part().begin, seed) < amount)
+                .collect()
+        })
+    }
+
+    /// Keep only the 50% of events that `degrade` removes
+    pub fn undegrade(self) -> Pattern {
+        self.undegrade_by(0.5, 0)
+    }
+
+    /// Apply a transformation to a random selection of events
+    ///
+    /// Each event is picked with probability `prob`, decided by the same
+    /// onset-keyed randomness as `degrade_by`. Picked events are passed through
+    /// `f`; the rest play unchanged.
+    ///
+    /// # Arguments
+    /// * `prob` - Probability of transforming each event (0.0 = never, 1.0 = always)
+    /// * `f` - Transformation applied to the picked events
+    /// * `seed` - Random seed for reproducibility
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // Double the speed of about a third of the hats
+    /// let hats = fastcat(vec![pure(Value::String("hh".into())); 8]);
+    /// let pattern = hats.sometimes_by(0.3, |p| p.fast(2.0), 1);
+    /// ```
+    pub fn sometimes_by<F>(self, prob: f64, f: F, seed: u64) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let transformed = f(self.clone().undegrade_by(prob, seed));
+        crate::stack(vec![self.degrade_by(prob, seed), transformed])
+    }
+
+    /// Apply a transformation to whole cycles at random
+    ///
+    /// Unlike `sometimes_by`, the choice is made once per cycle: each cycle
+    /// is transformed entirely with probability `prob`, or plays unchanged.
+    /// Uses the same time-keyed randomness as `degrade_by`, so a cycle's
+    /// choice is stable across queries. Matches Strudel's `someCyclesBy`.
+    ///
+    /// # Arguments
+    /// * `prob` - Probability of transforming each cycle (0.0 = never, 1.0 = always)
+    /// * `f` - Transformation applied to the picked cycles
+    /// * `seed` - Random seed for reproducibility
+    pub fn sometimes_cycle<F>(self, prob: f64, f: F, seed: u64) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let steps = self.steps;
+        let transformed = f(self.clone());
+
+        Pattern::with_steps(
+            move |state| {
+                let cycle = state.span.begin.floor();
+                if random_at(cycle, seed) < prob {
+                    transformed.query(state)
+                } else {
+                    self.query(state)
+                }
+            },
+            steps,
+        )
+        .split_queries()
+    }
+
+    /// Apply a transformation to half of the events
+    pub fn sometimes<F>(self, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        self.sometimes_by(0.5, f, 0)
+    }
+
+    /// Apply a transformation to 75% of the events
+    pub fn often<F>(self, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        self.sometimes_by(0.75, f, 0)
+    }
+
+    /// Apply a transformation to 25% of the events
+    pub fn rarely<F>(self, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        self.sometimes_by(0.25, f, 0)
+    }
+
+    /// Map numeric values to a musical scale
+    ///
+    /// Numbers are treated as scale degrees and replaced by note names with
+    /// octave (e.g. `2` in `"C:minor"` becomes `"Eb3"`). Degrees outside the
+    /// scale wrap into neighbouring octaves. The scale name is also stored in
+    /// the event context. See `crate::scale::Scale` for the supported names;
+    /// with an unknown scale, values are left unchanged.
+    ///
+    /// # Arguments
+    /// * `scale_name` - Name of the scale (e.g., "C:major", "D:minor")
+    pub fn scale(self, scale_name: String) -> Pattern {
+        let scale = Scale::parse(&scale_name);
+
+        self.with_hap(move |hap| {
+            // Store scale in context
+            let mut new_context = hap.context.clone();
+            new_context.metadata.insert("scale".to_string(), Value::String(scale_name.clone()));
+
+            // If value is a number, map it to a note in the scale
+            let new_value = match (&hap.value, &scale) {
+                (Value::Number(n), Some(scale)) => {
+                    Value::String(scale.degree_to_note(n.floor() as i64))
+                }
+                _ => hap.value.clone(),
+            };
+
+            Hap::with_context(hap.whole, hap.part, new_value, new_context)
+        })
+    }
+
+    /// Apply a boolean/rhythm structure pattern to this pattern
+    ///
+    /// The structure pattern determines when events from this pattern should occur.
+    /// Events are only kept where the structure pattern has truthy values.
+    ///
+    /// This is similar to TidalCycles/Strudel's `struct` function, which applies
+    /// a rhythmic structure to a pattern. The structure pattern provides the timing,
+    /// while this pattern provides the values.
+    ///
+    /// # Arguments
+    /// * `structure` - A pattern that provides the rhythmic structure. Events from
+    ///   this pattern are kept only where the structure has truthy values (non-zero
+    ///   numbers, non-empty strings, true booleans, etc.)
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, fastcat, Value};
+    ///
+    /// // Apply a simple on-off rhythm
+    /// let values = fastcat(vec![
+    ///     pure(Value::String("a".into())),
+    ///     pure(Value::String("b".into())),
+    ///     pure(Value::String("c".into())),
+    ///     pure(Value::String("d".into())),
+    /// ]);
+    ///
+    /// let structure = fastcat(vec![
+    ///     pure(Value::Number(1.0)),  // keep
+    ///     pure(Value::Number(0.0)),  // skip
+    ///     pure(Value::Number(1.0)),  // keep
+    ///     pure(Value::Number(0.0)),  // skip
+    /// ]);
+    ///
+    /// let result = values.struct_(structure);
+    /// // Result will only have events at positions 0 and 2
+    /// ```
+    pub fn struct_(self, structure: Pattern) -> Pattern {
+        let value_pattern = self;
+        let steps = structure.get_steps();
+        let structure_pattern = structure;
+
+        Pattern::with_steps(
+            move |state| {
+                let mut result_haps = Vec::new();
+
+                // Query the structure pattern to get the timing information
+                let structure_haps = structure_pattern.query(state.clone());
+
+                // For each structure hap, query the value pattern at that timespan
+                for structure_hap in structure_haps {
+                    // Check if the structure value is truthy
+                    if is_truthy(&structure_hap.value) {
+                        // Query the value pattern for this timespan
+                        let value_state = state.set_span(structure_hap.whole_or_part());
+                        let value_haps = value_pattern.query(value_state);
+
+                        // For each value hap that intersects with the structure hap
+                        for value_hap in value_haps {
+                            if let Some(new_part) = structure_hap.part.intersection(&value_hap.part) {
+                                // Create a new hap with the structure's whole timespan
+                                // but the value from the value pattern
+                                let new_hap = Hap::with_context(
+                                    structure_hap.whole,
+                                    new_part,
+                                    value_hap.value.clone(),
+                                    value_hap.context.clone(),
+                                );
+                                result_haps.push(new_hap);
+                            }
+                        }
+                    }
+                }
+
+                result_haps
+            },
+            steps,
+        )
+    }
+
+    /// Silence the parts of this pattern where a boolean pattern is falsy
+    ///
+    /// Unlike `struct_`, the timing comes from this pattern: events keep
+    /// their own onsets and values, and the boolean pattern's onsets don't
+    /// trigger anything. An event that straddles a truthy and a falsy region
+    /// keeps only its truthy fragments, and only the fragment containing its
+    /// onset plays it.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // "a b c d" with the second half silenced
+    /// let values = fastcat(vec![
+    ///     pure(Value::String("a".into())),
+    ///     pure(Value::String("b".into())),
+    ///     pure(Value::String("c".into())),
+    ///     pure(Value::String("d".into())),
+    /// ]);
+    /// let pattern = values.mask(fastcat(vec![
+    ///     pure(Value::Bool(true)),
+    ///     pure(Value::Bool(false)),
+    /// ]));
+    /// ```
+    pub fn mask(self, bool_pattern: Pattern) -> Pattern {
+        let source = self;
+        let steps = source.get_steps();
+
+        Pattern::with_steps(
+            move |state| {
+                let mut haps = Vec::new();
+
+                for hap in source.query(state.clone()) {
+                    let mask_haps = bool_pattern.query(state.set_span(hap.part));
+                    for mask_hap in mask_haps {
+                        if !is_truthy(&mask_hap.value) {
+                            continue;
+                        }
+                        if let Some(part) = hap.part.intersection(&mask_hap.part) {
+                            haps.push(Hap::with_context(
+                                hap.whole,
+                                part,
+                                hap.value.clone(),
+                                hap.context.clone(),
+                            ));
+                        }
+                    }
+                }
+
+                haps
+            },
+            steps,
+        )
+    }
+
+    /// Restart the pattern from its beginning on each truthy trigger event
+    ///
+    /// Each truthy event in `trigger` plays this pattern from cycle 0 for the
+    /// length of the event, so a trigger firing every two cycles re-syncs it
+    /// to that downbeat instead of letting it keep evolving. Time not covered
+    /// by a truthy trigger event is silent. This is Strudel's `restart`.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, slowcat, Value};
+    ///
+    /// // Plays "a b a b ..." instead of "a b c a ..."
+    /// let abc = slowcat(vec![
+    ///     pure(Value::String("a".into())),
+    ///     pure(Value::String("b".into())),
+    ///     pure(Value::String("c".into())),
+    /// ]);
+    /// let pattern = abc.restart(pure(Value::Bool(true)).slow(2.0));
+    /// ```
+    pub fn restart(self, trigger: Pattern) -> Pattern {
+        let source = self;
+        let steps = source.get_steps();
+
+        Pattern::with_steps(
+            move |state| {
+                let mut haps = Vec::new();
+
+                for trigger_hap in trigger.query(state.clone()) {
+                    let Some(whole) = trigger_hap.whole else {
+                        continue;
+                    };
+                    if !is_truthy(&trigger_hap.value) {
+                        continue;
+                    }
+
+                    // Query the source as if the trigger's onset were cycle 0
+                    let offset = whole.begin;
+                    let part = trigger_hap.part;
+                    let inner =
+                        state.set_span(TimeSpan::new(part.begin - offset, part.end - offset));
+                    haps.extend(
+                        source
+                            .query(inner)
+                            .into_iter()
+                            .map(|hap| hap.with_span(|span| span.shift(offset))),
+                    );
+                }
+
+                haps
+            },
+            steps,
+        )
+    }
+
+    /// Accumulate - fold each cycle's events in onset order, carrying state
+    ///
+    /// Every event takes the value `f(previous, value)`, where `previous` is
+    /// the accumulated value of the event before it in the same cycle, or
+    /// `init` for the first one. State resets at each cycle, and an event
+    /// keeps the value from the cycle in which it starts. Continuous events
+    /// (without a whole) are dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // A melody from steps: "2 1 -3" plays 2, 3, 0
+    /// let steps = fastcat(vec![
+    ///     pure(Value::Number(2.0)),
+    ///     pure(Value::Number(1.0)),
+    ///     pure(Value::Number(-3.0)),
+    /// ]);
+    /// let melody = steps.accumulate(
+    ///     |a, b| Value::Number(a.as_number().unwrap_or(0.0) + b.as_number().unwrap_or(0.0)),
+    ///     Value::Number(0.0),
+    /// );
+    /// ```
+    pub fn accumulate<F>(self, f: F, init: Value) -> Pattern
+    where
+        F: Fn(&Value, &Value) -> Value + Send + Sync + 'static,
+    {
+        let source = self;
+        let steps = source.get_steps();
+
+        Pattern::with_steps(
+            move |state| {
+                // The cycles in which the events overlapping this span start
+                let mut cycles: Vec<Fraction> = source
+                    .query(state.clone())
+                    .iter()
+                    .filter_map(|hap| hap.whole.map(|whole| whole.begin.floor()))
+                    .collect();
+                cycles.sort();
+                cycles.dedup();
+
+                let mut haps = Vec::new();
+                for cycle in cycles {
+                    let cycle_span = TimeSpan::new(cycle, cycle + Fraction::from_int(1));
+                    let mut onsets: Vec<Hap> = source
+                        .query(state.set_span(cycle_span))
+                        .into_iter()
+                        .filter(|hap| hap.has_onset())
+                        .collect();
+                    onsets.sort_by_key(|hap| hap.part.begin);
+
+                    let mut acc = init.clone();
+                    for hap in onsets {
+                        acc = f(&acc, &hap.value);
+                        let Some(whole) = hap.whole else {
+                            continue;
+                        };
+                        if let Some(part) = whole.intersection(&state.span) {
+                            haps.push(Hap {
+                                part,
+                                value: acc.clone(),
+                                ..hap
+                            });
+                        }
+                    }
+                }
+
+                haps
+            },
+            steps,
+        )
+    }
+
+    /// Segment - sample the pattern into n discrete steps per cycle
+    ///
+    /// Each step takes the value active at its onset, which turns continuous
+    /// patterns (signals) into regular events. Implemented with `struct_`
+    /// against an n-step boolean structure, matching Strudel's `segment`.
+    ///
+    /// # Arguments
+    /// * `n` - Number of steps per cycle. Non-positive values yield silence
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // Four evenly spaced events per cycle
+    /// let pattern = pure(Value::Number(1.0)).segment(4.0);
+    /// ```
+    pub fn segment(self, n: f64) -> Pattern {
+        let n_frac = Fraction::from_float(n);
+        if n_frac <= Fraction::from_int(0) {
+            return Pattern::new(|_| Vec::new());
+        }
+
+        let one = Fraction::from_int(1);
+        let structure = Pattern::new(move |state| {
+            let mut haps = Vec::new();
+            let mut step = (state.span.begin * n_frac).floor();
+
+            while step < state.span.end * n_frac {
+                let whole = TimeSpan::new(step / n_frac, (step + one) / n_frac);
+                if let Some(part) = whole.intersection(&state.span) {
+                    haps.push(Hap::new(Some(whole), part, Value::Bool(true)));
+                }
+                step = step + one;
+            }
+
+            haps
+        });
+
+        // Collapse each query to the single value active at its start
+        let source = self;
+        let sampled = Pattern::new(move |state| {
+            let onset = state.span.begin;
+            source
+                .query(state.clone())
+                .into_iter()
+                .find(|hap| hap.part.begin <= onset && onset < hap.part.end)
+                .map(|hap| vec![Hap::new(None, state.span, hap.value)])
+                .unwrap_or_default()
+        });
+
+        sampled.struct_(structure).set_steps(Some(n_frac))
+    }
+
+    /// Append another pattern to this one (list cons operator)
+    ///
+    /// Creates a sequence where this pattern is followed by the given pattern.
+    /// Similar to fastcat([self, other]) - concatenates patterns within a cycle.
+    /// This implements the ":" operator from mini notation (e.g., "a:b" becomes [a, b]).
+    pub fn tail(self, other: Pattern) -> Pattern {
+        crate::fastcat(vec![self, other])
+    }
+
+    /// Shift pattern in time (nudge forward or backward)
+    ///
+    /// Positive values shift the pattern later in time (equivalent to `late`).
+    /// Negative values shift the pattern earlier in time (equivalent to `early`).
+    ///
+    /// This is implemented by:
+    /// - Subtracting the offset from query time (asking for earlier/later events)
+    /// - Adding the offset to hap time (placing events earlier/later in output)
+    ///
+    /// # Arguments
+    /// * `amount` - Number of cycles to shift. Positive = later, negative = earlier
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value, State, TimeSpan, Fraction};
+    ///
+    /// // Shift pattern 0.25 cycles later
+    /// let pattern = pure(Value::String("bd".into())).split_queries().shift(0.25);
+    /// let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+    /// let haps = pattern.query(state);
+    ///
+    /// // Event that was at 0.0 is now at 0.25
+    /// assert_eq!(haps[0].part.begin, Fraction::new(1, 4));
+    /// ```
+    pub fn shift(self, amount: f64) -> Pattern {
+        let frac_amount = Fraction::from_float(amount);
+
+        // Shift works like late: subtract from query time, add to hap time
+        // This makes positive values shift the pattern later
+        self.with_query_time(move |t| t - frac_amount)
+            .with_hap_time(move |t| t + frac_amount)
+    }
+
+    /// Nudge pattern earlier in time (equivalent to shift with negative value)
+    ///
+    /// This is the same as Strudel/TidalCycles' `early` function or `<~` operator.
+    ///
+    /// # Arguments
+    /// * `amount` - Number of cycles to shift earlier (positive value shifts earlier)
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // These are equivalent:
+    /// let pattern1 = pure(Value::String("bd".into())).early(0.25);
+    /// let pattern2 = pure(Value::String("bd".into())).shift(-0.25);
+    /// ```
+    pub fn early(self, amount: f64) -> Pattern {
+        self.shift(-amount)
+    }
+
+    /// Nudge pattern later in time (equivalent to shift with positive value)
+    ///
+    /// This is the same as Strudel/TidalCycles' `late` function or `~>` operator.
+    ///
+    /// # Arguments
+    /// * `amount` - Number of cycles to shift later (positive value shifts later)
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // These are equivalent:
+    /// let pattern1 = pure(Value::String("bd".into())).late(0.25);
+    /// let pattern2 = pure(Value::String("bd".into())).shift(0.25);
+    /// ```
+    pub fn late(self, amount: f64) -> Pattern {
+        self.shift(amount)
+    }
+
+    /// Swing the pattern, delaying every second quarter of a cycle
+    ///
+    /// Shorthand for `swing_by(amount, 4)`.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // Four hi-hats a cycle, the second and fourth a little late
+    /// let pattern = pure(Value::String("hh".into())).fast(4.0).swing(0.1);
+    /// ```
+    pub fn swing(self, amount: f64) -> Pattern {
+        self.swing_by(amount, 4)
+    }
+
+    /// Swing the pattern, delaying every second step by `amount` of a step
+    ///
+    /// The cycle is split into `subdivision` steps, and events starting in
+    /// the odd ones (the off-beats) are pushed later by `amount` of a step.
+    /// Events on the even steps don't move. The amount is clamped to
+    /// `0.0..=0.5`, so an off-beat never passes the next on-beat.
+    ///
+    /// This is Strudel's `swingBy`: the off-beat half of each pair of steps
+    /// is delayed with `within` and `late`, applied inside the pair.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // Eighth-note hi-hats with a light shuffle
+    /// let pattern = pure(Value::String("hh".into())).fast(8.0).swing_by(0.1, 8);
+    /// ```
+    pub fn swing_by(self, amount: f64, subdivision: usize) -> Pattern {
+        if subdivision == 0 {
+            return self;
+        }
+
+        let amount = amount.clamp(0.0, 0.5);
+        let pairs = subdivision as f64 / 2.0;
+        let steps = self.steps;
+
+        // Within a pair, a step is half a cycle
+        self.slow(pairs)
+            .within(0.5, 1.0, move |p| p.late(amount / 2.0))
+            .fast(pairs)
+            .set_steps(steps)
+    }
+
+    /// Start each cycle one `n`th of a cycle further into the pattern
+    ///
+    /// Over `n` cycles the pattern rotates left a step at a time, so cycle
+    /// `k` starts at step `k`, and then starts over. This is Strudel's `iter`.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // "a b c d", then "b c d a", "c d a b", "d a b c", and again
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::String("a".into())),
+    ///     pure(Value::String("b".into())),
+    ///     pure(Value::String("c".into())),
+    ///     pure(Value::String("d".into())),
+    /// ])
+    /// .iter(4);
+    /// ```
+    pub fn iter(self, n: usize) -> Pattern {
+        self.iter_dir(n, false)
+    }
+
+    /// Like `iter`, but rotating right: cycle `k` starts `k` steps from the end
+    ///
+    /// This is Strudel's `iterBack`.
+    pub fn iter_back(self, n: usize) -> Pattern {
+        self.iter_dir(n, true)
+    }
+
+    /// Shift each cycle by its position in an `n`-cycle rotation
+    fn iter_dir(self, n: usize, backwards: bool) -> Pattern {
+        if n == 0 {
+            return self;
+        }
+
+        let source = self;
+        let steps = source.get_steps();
+        let n = n as i64;
+
+        Pattern::with_steps(
+            move |state| {
+                let k = state.span.begin.floor().numerator.rem_euclid(n);
+                let offset = Fraction::new(if backwards { -k } else { k }, n);
+
+                // Query later in the source (or earlier, going backwards)
+                source
+                    .clone()
+                    .with_query_time(move |t| t + offset)
+                    .with_hap_time(move |t| t - offset)
+                    .query(state)
+            },
+            steps,
+        )
+        .split_queries()
+    }
+
+    /// Rotate each cycle's values left, keeping the rhythm in place
+    ///
+    /// Every event takes the value of the event `amount` places after it in
+    /// the same cycle (by onset, wrapping around), so `a b c d` becomes
+    /// `b c d a` with the same onsets. Unlike `early`, timing doesn't change.
+    /// `amount` is rounded to a whole number of events. This is Strudel's
+    /// `rot` with a positive amount.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // "b c d a"
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::String("a".into())),
+    ///     pure(Value::String("b".into())),
+    ///     pure(Value::String("c".into())),
+    ///     pure(Value::String("d".into())),
+    /// ])
+    /// .rot_left(1.0);
+    /// ```
+    pub fn rot_left(self, amount: f64) -> Pattern {
+        let source = self;
+        let steps = source.get_steps();
+        let amount = amount.round() as i64;
+
+        Pattern::with_steps(
+            move |state| {
+                let haps = source.query(state.clone());
+
+                // Rotated values of the events starting in each cycle, in onset order
+                let mut cycles: HashMap<i64, Vec<(TimeSpan, Value, Value)>> = HashMap::new();
+                // Fragments already rotated, to tell stacked copies of an event apart
+                let mut seen: Vec<(TimeSpan, TimeSpan, Value)> = Vec::new();
+                let mut result = Vec::with_capacity(haps.len());
+                for hap in haps {
+                    let Some(whole) = hap.whole else {
+                        result.push(hap);
+                        continue;
+                    };
+
+                    let cycle = whole.begin.floor();
+                    let events = cycles.entry(cycle.numerator).or_insert_with(|| {
+                        let span = TimeSpan::new(cycle, cycle + Fraction::from_int(1));
+                        let mut onsets: Vec<Hap> = source
+                            .query(state.set_span(span))
+                            .into_iter()
+                            .filter(|hap| hap.has_onset())
+                            .collect();
+                        onsets.sort_by_key(|hap| hap.part.begin);
+
+                        let len = onsets.len() as i64;
+                        (0..onsets.len())
+                            .map(|i| {
+                                let from = (i as i64 + amount).rem_euclid(len) as usize;
+                                (
+                                    onsets[i].whole_or_part(),
+                                    onsets[i].value.clone(),
+                                    onsets[from].value.clone(),
+                                )
+                            })
+                            .collect()
+                    });
+
+                    // Every fragment of an event shares its whole, so the nth
+                    // copy of a fragment takes the nth event with that whole
+                    let key = (whole, hap.part, hap.value.clone());
+                    let nth = seen.iter().filter(|seen| **seen == key).count();
+                    seen.push(key);
+
+                    let same_whole = || events.iter().filter(|(onset, _, _)| *onset == whole);
+                    let rotated = same_whole()
+                        .filter(|(_, value, _)| *value == hap.value)
+                        .nth(nth)
+                        .or_else(|| same_whole().nth(nth))
+                        .map(|(_, _, rotated)| rotated.clone());
+                    match rotated {
+                        Some(rotated) => result.push(hap.with_value(|_| rotated)),
+                        None => result.push(hap),
+                    }
+                }
+
+                result
+            },
+            steps,
+        )
+        .split_queries()
+    }
+
+    /// Rotate each cycle's values right, keeping the rhythm in place
+    ///
+    /// The opposite of `rot_left`: `a b c d` becomes `d a b c`.
+    pub fn rot_right(self, amount: f64) -> Pattern {
+        self.rot_left(-amount)
+    }
+
+    /// Superimpose a transformed copy of the pattern, offset in time
+    ///
+    /// Stacks the original pattern with `f` applied to it and then shifted
+    /// `time` cycles later. This is Strudel's `off`, commonly used for
+    /// echo and delay-style figures.
+    ///
+    /// # Arguments
+    /// * `time` - Offset in cycles for the transformed copy
+    /// * `f` - Transformation applied before the offset
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // Echo the pattern an eighth of a cycle later, twice as fast
+    /// let pattern = pure(Value::String("bd".into())).off(0.125, |p| p.fast(2.0));
+    /// ```
+    pub fn off<F>(self, time: f64, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let offset = f(self.clone()).late(time);
+        crate::stack(vec![self, offset])
+    }
+
+    /// Layer - stack the results of several transformations of the pattern
+    ///
+    /// Each function in `fs` receives a copy of the pattern and all results play
+    /// simultaneously. This is Strudel's `layer`, handy for building harmonies.
+    ///
+    /// # Arguments
+    /// * `fs` - Transformations to apply, one per layer
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Pattern, Value};
+    ///
+    /// // Root plus a fifth above
+    /// let root = pure(Value::Number(0.0));
+    /// let pattern = root.layer(vec![|p: Pattern| p, |p: Pattern| p.add_scalar(7.0)]);
+    /// ```
+    pub fn layer<F>(self, fs: Vec<F>) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        crate::stack(fs.iter().map(|f| f(self.clone())).collect())
+    }
+
+    /// Add another pattern's values to this pattern's numeric values
+    ///
+    /// Structure comes from this pattern: for each event, `other` is queried over
+    /// the event's timespan and the overlapping values are added. Non-numeric
+    /// values, and any stretch of an event with no value from `other` under
+    /// it, pass through unchanged. E.g. `n("0 2").add("<0 12>")`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, other: Pattern) -> Pattern {
+        self.op_left(other, |a, b| a + b)
+    }
+
+    /// Subtract another pattern's values from this pattern's numeric values
+    ///
+    /// Structure comes from this pattern, as with `add`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn sub(self, other: Pattern) -> Pattern {
+        self.op_left(other, |a, b| a - b)
+    }
+
+    /// Multiply this pattern's numeric values by another pattern's values
+    ///
+    /// Structure comes from this pattern, as with `add`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn mul(self, other: Pattern) -> Pattern {
+        self.op_left(other, |a, b| a * b)
+    }
+
+    /// Divide this pattern's numeric values by another pattern's values
+    ///
+    /// Structure comes from this pattern, as with `add`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn div(self, other: Pattern) -> Pattern {
+        self.op_left(other, |a, b| a / b)
+    }
+
+    /// Combine numeric values with another pattern, keeping this pattern's structure
+    fn op_left(self, other: Pattern, op: fn(f64, f64) -> f64) -> Pattern {
+        let steps = self.steps;
+        let kind = self.kind;
+
+        Pattern::with_steps(
+            move |state| {
+                let mut result = Vec::new();
+
+                for hap in self.query(state.clone()) {
+                    let mut parts: Vec<(TimeSpan, Value)> = other
+                        .query(state.set_span(hap.whole_or_part()))
+                        .into_iter()
+                        .filter_map(|other_hap| {
+                            let part = hap.part.intersection(&other_hap.part)?;
+                            let value = match (&hap.value, &other_hap.value) {
+                                (Value::Number(a), Value::Number(b)) => Value::Number(op(*a, *b)),
+                                _ => hap.value.clone(),
+                            };
+                            Some((part, value))
+                        })
+                        .collect();
+                    parts.sort_by_key(|(part, _)| part.begin);
+
+                    let fragment = |part: TimeSpan, value: Value| {
+                        Hap::with_context(hap.whole, part, value, hap.context.clone())
+                    };
+
+                    // Where nothing on the right overlaps, the left value plays as is
+                    let mut cursor = hap.part.begin;
+                    for (part, value) in parts {
+                        if cursor < part.begin {
+                            let gap = TimeSpan::new(cursor, part.begin);
+                            result.push(fragment(gap, hap.value.clone()));
+                        }
+                        cursor = cursor.max(part.end);
+                        result.push(fragment(part, value));
+                    }
+                    if cursor < hap.part.end {
+                        let gap = TimeSpan::new(cursor, hap.part.end);
+                        result.push(fragment(gap, hap.value.clone()));
+                    }
+                }
+
+                result
+            },
+            steps,
+        )
+        .set_kind(kind)
+    }
+
+    /// Add a constant to every numeric value
+    ///
+    /// Non-numeric values pass through unchanged.
+    pub fn add_scalar(self, amount: f64) -> Pattern {
+        self.with_value(move |value| match value {
+            Value::Number(n) => Value::Number(n + amount),
+            other => other.clone(),
+        })
+    }
+
+    /// Transpose notes by a number of semitones
+    ///
+    /// Note names are respelled, wrapping into the next octave as needed, so
+    /// `"c3 e3"` up 12 becomes `"c4 e4"`. Numbers are taken as MIDI note
+    /// numbers. Other values, like sample names, pass through unchanged; to
+    /// shift scale degrees, use `add` instead.
+    pub fn transpose(self, semitones: i32) -> Pattern {
+        self.with_value(move |value| match value {
+            Value::Number(n) => Value::Number(n + semitones as f64),
+            Value::String(s) => match crate::scale::transpose_note(s, semitones) {
+                Some(note) => Value::String(note),
+                None => value.clone(),
+            },
+            other => other.clone(),
+        })
+    }
+
+    /// Set target destination for pattern events
+    ///
+    /// Adds a "target" metadata entry to each event's context, indicating where
+    /// the event should be routed (e.g., to specific OSC targets, orbits, or MIDI devices).
+    ///
+    /// This corresponds to the `target` operator in Strudel/TidalCycles mini notation,
+    /// which is used like: `target "mysynth" $ pattern`
+    ///
+    /// # Arguments
+    /// * `target_name` - Name of the target destination
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// // Route pattern to a specific target
+    /// let pattern = pure(Value::String("bd".into())).target("drums".to_string());
+    ///
+    /// // Multiple patterns can have different targets
+    /// let synth_pattern = pure(Value::String("c4".into())).target("synth".to_string());
+    /// let drums_pattern = pure(Value::String("bd".into())).target("drums".to_string());
+    /// ```
+    pub fn target(self, target_name: String) -> Pattern {
+        self.tag("target", Value::String(target_name))
+    }
+
+    /// Play samples from a bank, such as a drum machine
+    ///
+    /// Prefixes each sample name with the bank name, the naming used by
+    /// sample maps like tidal-drum-machines: `bank("RolandTR909")` turns `bd`
+    /// into `RolandTR909_bd`. Only string values are renamed, and names that
+    /// are already in the bank are left as they are.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, Value};
+    ///
+    /// let pattern = pure(Value::String("bd".into())).bank("RolandTR909".to_string());
+    /// ```
+    pub fn bank(self, bank_name: String) -> Pattern {
+        if bank_name.is_empty() {
+            return self;
+        }
+
+        let prefix = format!("{}_", bank_name);
+        self.with_value(move |value| match value {
+            Value::String(name) if !name.starts_with(&prefix) => {
+                Value::String(format!("{}{}", prefix, name))
+            }
+            other => other.clone(),
+        })
+    }
+
+    /// Tag every event with a metadata entry
+    ///
+    /// Inserts `key` into each event's context metadata, replacing any
+    /// existing entry. Used for per-event controls and for annotations such
+    /// as the mini notation source span (`source_start`/`source_end`) that
+    /// lets the editor highlight playing events.
+    pub fn tag(self, key: &str, value: Value) -> Pattern {
+        let key = key.to_string();
+        self.with_hap(move |hap| {
+            let mut new_context = hap.context.clone();
+            new_context.metadata.insert(key.clone(), value.clone());
+            Hap::with_context(hap.whole, hap.part, hap.value.clone(), new_context)
+        })
+    }
+
+    /// Range - scale numeric values from `[0, 1]` to `[min, max]`
+    ///
+    /// Typically used on signals, e.g. `sine().range(200.0, 2000.0)` for a
+    /// filter sweep. Non-numeric values pass through unchanged.
+    pub fn range(self, min: f64, max: f64) -> Pattern {
+        self.with_value(move |value| match value {
+            Value::Number(n) => Value::Number(min + n * (max - min)),
+            other => other.clone(),
+        })
+    }
+
+    /// Set the sample index for pattern events
+    ///
+    /// Adds an "n" metadata entry to each event's context, selecting a sample
+    /// from the bank named by the event's value. This is what `bd:3` means in
+    /// mini notation.
+    ///
+    /// # Arguments
+    /// * `index` - Index of the sample within its bank
+    pub fn n(self, index: usize) -> Pattern {
+        self.tag("n", Value::Number(index as f64))
+    }
+
+    /// Cut each event into n parts, each playing the next slice of its sample
+    ///
+    /// Like `ply`, every event's whole is divided into n equal slices, but
+    /// slice i plays only the i-th nth of the sample, set through the "begin"
+    /// and "end" metadata entries (fractions of the sample's length). An event
+    /// that already plays part of a sample is chopped within that part.
+    /// Continuous events (without a whole) pass through unchanged.
+    pub fn chop(self, n: usize) -> Pattern {
+        if n == 0 {
+            return Pattern::new(|_| Vec::new());
+        }
+
+        let query_func = self.query_func.clone();
+        let steps = self.steps.map(|s| s * Fraction::from_int(n as i64));
+        let n_frac = Fraction::from_int(n as i64);
+
+        Pattern {
+            query_func: Arc::new(move |state| {
+                let mut result = Vec::new();
+
+                for hap in query_func(state) {
+                    let whole = match hap.whole {
+                        Some(whole) => whole,
+                        None => {
+                            result.push(hap);
+                            continue;
+                        }
+                    };
+
+                    let slice_len = (whole.end - whole.begin) / n_frac;
+                    for i in 0..n {
+                        let begin = whole.begin + slice_len * Fraction::from_int(i as i64);
+                        let slice = TimeSpan::new(begin, begin + slice_len);
+
+                        if let Some(part) = slice.intersection(&hap.part) {
+                            let (from, to) = (i as f64 / n as f64, (i + 1) as f64 / n as f64);
+                            result.push(Hap::with_context(
+                                Some(slice),
+                                part,
+                                hap.value.clone(),
+                                sample_slice(&hap.context, from, to),
+                            ));
+                        }
+                    }
+                }
+
+                result
+            }),
+            steps,
+            kind: PatternKind::Unknown,
+        }
+    }
+
+    /// Play the pattern n times per cycle, each time with the next slice of its samples
+    ///
+    /// Where `chop` plays every slice of one event before moving on, striate
+    /// plays the first slice of every event, then the second, and so on,
+    /// interleaving the slices across the cycle. Slices are set through the
+    /// "begin" and "end" metadata entries, within any part of the sample
+    /// already selected, as with `chop`.
+    pub fn striate(self, n: usize) -> Pattern {
+        if n == 0 {
+            return Pattern::new(|_| Vec::new());
+        }
+
+        crate::fastcat(
+            (0..n)
+                .map(|i| {
+                    let (from, to) = (i as f64 / n as f64, (i + 1) as f64 / n as f64);
+                    self.clone().with_hap(move |hap| {
+                        let context = sample_slice(&hap.context, from, to);
+                        Hap::with_context(hap.whole, hap.part, hap.value.clone(), context)
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Play slices of each sample in the order of an index pattern
+    ///
+    /// Divides the sample into n equal slices and, for each event of
+    /// `index_pattern`, plays the slice it selects (wrapping indices outside
+    /// `0..n`) of the sound playing at its onset. Structure comes from the
+    /// index pattern, so `slice(4, "3 2 1 0")` plays a break backwards.
+    /// Slices are set through the "begin" and "end" metadata entries, within
+    /// any part of the sample already selected. Matches Strudel's `slice`.
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{pure, run, Value};
+    ///
+    /// // The eight slices of a break, in their original order
+    /// let pattern = pure(Value::String("break".into())).slice(8, run(8));
+    /// ```
+    pub fn slice(self, n: usize, index_pattern: Pattern) -> Pattern {
+        if n == 0 {
+            return Pattern::new(|_| Vec::new());
+        }
+
+        let steps = index_pattern.get_steps();
+
+        Pattern::with_steps(
+            move |state| {
+                let mut result = Vec::new();
+
+                for index in index_pattern.query(state.clone()) {
+                    let Value::Number(i) = index.value else {
+                        continue;
+                    };
+                    let i = (i.floor() as i64).rem_euclid(n as i64) as f64;
+                    let n = n as f64;
+                    let span = index.whole_or_part();
+                    let onset = span.begin;
+
+                    for hap in self.query(state.set_span(span)) {
+                        let whole = hap.whole_or_part();
+                        if !(whole.begin <= onset && onset < whole.end) {
+                            continue;
+                        }
+
+                        result.push(Hap::with_context(
+                            index.whole,
+                            index.part,
+                            hap.value.clone(),
+                            sample_slice(&hap.context, i / n, (i + 1.0) / n),
+                        ));
+                    }
+                }
+
+                result
+            },
+            steps,
+        )
+    }
+
+    /// Jux - split a pattern across the stereo field
+    ///
+    /// Plays the original pattern panned hard left alongside `f` applied to the
+    /// pattern panned hard right. The pan position is written to the "pan"
+    /// metadata entry of each event's context (0.0 = left, 1.0 = right).
+    ///
+    /// # Arguments
+    /// * `f` - Transformation applied to the right channel
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // Original on the left, double speed on the right
+    /// let pattern = fastcat(vec![
+    ///     pure(Value::String("bd".into())),
+    ///     pure(Value::String("sd".into())),
+    /// ])
+    /// .jux(|p| p.fast(2.0));
+    /// ```
+    pub fn jux<F>(self, f: F) -> Pattern
+    where
+        F: Fn(Pattern) -> Pattern,
+    {
+        let right = f(self.clone());
+        crate::stack(vec![self.pan(0.0), right.pan(1.0)])
+    }
+
+    /// Write a pan position into each event's context metadata
+    fn pan(self, position: f64) -> Pattern {
+        self.tag("pan", Value::Number(position))
+    }
+
+    /// Velocity - set each event's gain from a velocity pattern
+    ///
+    /// The velocity pattern is sampled at the onset of each event and the
+    /// resulting number (0.0 to 1.0) is written to the "gain" metadata entry.
+    /// Structure comes from this pattern; non-numeric velocities are ignored.
+    ///
+    /// # Arguments
+    /// * `v_pattern` - Pattern of velocities between 0.0 and 1.0
+    ///
+    /// # Example
+    /// ```
+    /// use strudel_core::{fastcat, pure, Value};
+    ///
+    /// // Accent every other hi-hat
+    /// let hats = fastcat(vec![pure(Value::String("hh".into())); 4]);
+    /// let pattern = hats.velocity(fastcat(vec![
+    ///     pure(Value::Number(1.0)),
+    ///     pure(Value::Number(0.5)),
+    /// ]));
+    /// ```
+    pub fn velocity(self, v_pattern: Pattern) -> Pattern {
+        let steps = self.steps;
+
+        Pattern::with_steps(
+            move |state| {
+                self.query(state.clone())
+                    .into_iter()
+                    .map(|hap| {
+                        let span = hap.whole_or_part();
+                        let onset = span.begin;
+
+                        let velocity = v_pattern
+                            .query(state.set_span(span))
+                            .into_iter()
+                            .find(|v| v.part.begin <= onset && onset < v.part.end)
+                            .and_then(|v| match v.value {
+                                Value::Number(n) => Some(n),
+                                _ => None,
+                            });
+
+                        match velocity {
+                            Some(gain) => {
+                                let mut new_context = hap.context.clone();
+                                new_context
+                                    .metadata
+                                    .insert("gain".to_string(), Value::Number(gain));
+                                Hap::with_context(hap.whole, hap.part, hap.value, new_context)
+                            }
+                            None => hap,
+                        }
+                    })
+                    .collect()
+            },
+            steps,
+        )
+    }
+
+    /// Tremolo - modulate each event's gain with a sine LFO
+    ///
+    /// The LFO is sampled at each event's onset and scales the "gain"
+    /// metadata (1.0 if unset) between `1.0 - depth` and `1.0`.
+    ///
+    /// # Arguments
+    /// * `rate` - LFO cycles per pattern cycle
+    /// * `depth` - Amount of gain reduction at the LFO peak (0.0 to 1.0)
+    pub fn tremolo(self, rate: f64, depth: f64) -> Pattern {
+        let lfo = crate::sine().fast(rate);
+
+        self.with_hap(move |hap| {
+            let Some(level) = signal_at(&lfo, hap.whole_or_part().begin) else {
+                return hap.clone();
+            };
+
+            let gain = match hap.context.metadata.get("gain") {
+                Some(Value::Number(gain)) => *gain,
+                _ => 1.0,
+            };
+
+            let mut new_context = hap.context.clone();
+            new_context
+                .metadata
+                .insert("gain".to_string(), Value::Number(gain * (1.0 - depth * level)));
+            Hap::with_context(hap.whole, hap.part, hap.value.clone(), new_context)
+        })
+    }
+
+    /// Vibrato - modulate each event's pitch with a sine LFO
+    ///
+    /// The LFO is sampled at each event's onset and the resulting pitch,
+    /// within `depth` semitones of the event's own, is written to the "note"
+    /// metadata entry. Values may be MIDI note numbers or note names like
+    /// "c4"; events without a pitch are left unchanged.
+    ///
+    /// # Arguments
+    /// * `rate` - LFO cycles per pattern cycle
+    /// * `depth` - Maximum pitch deviation in semitones
+    pub fn vibrato(self, rate: f64, depth: f64) -> Pattern {
+        let lfo = crate::sine().fast(rate);
+
+        self.with_hap(move |hap| {
+            let base = match &hap.value {
+                Value::Number(n) => Some(*n),
+                Value::String(s) => crate::scale::note_to_midi(s).map(f64::from),
+                _ => None,
+            };
+
+            match (base, signal_at(&lfo, hap.whole_or_part().begin)) {
+                (Some(base), Some(level)) => {
+                    let note = base + depth * (level * 2.0 - 1.0);
+                    let mut new_context = hap.context.clone();
+                    new_context.metadata.insert("note".to_string(), Value::Number(note));
+                    Hap::with_context(hap.whole, hap.part, hap.value.clone(), new_context)
+                }
+                _ => hap.clone(),
+            }
+        })
+    }
+}
+
+/// Narrow the part of the sample an event plays to `from..to` of it
+///
+/// The fractions are of the part already selected by the "begin" and "end"
+/// metadata entries (the whole sample if unset), so slicing composes.
+fn sample_slice(context: &Context, from: f64, to: f64) -> Context {
+    let control = |key: &str, default: f64| match context.metadata.get(key) {
+        Some(Value::Number(value)) => *value,
+        _ => default,
+    };
+    let begin = control("begin", 0.0);
+    let len = control("end", 1.0) - begin;
+
+    let mut context = context.clone();
+    context
+        .metadata
+        .insert("begin".to_string(), Value::Number(begin + from * len));
+    context
+        .metadata
+        .insert("end".to_string(), Value::Number(begin + to * len));
+    context
+}
+
+/// Get the numeric value of a continuous pattern (signal) at a point in time
+fn signal_at(pattern: &Pattern, time: Fraction) -> Option<f64> {
+    pattern
+        .query(State::new(TimeSpan::new(time, time)))
+        .into_iter()
+        .find_map(|hap| match hap.value {
+            Value::Number(n) => Some(n),
+            _ => None,
+        })
+}
+
+/// Get the numeric value a pattern holds at the start of a cycle
+fn number_at_cycle(pattern: &Pattern, cycle: Fraction) -> Option<f64> {
+    let span = TimeSpan::new(cycle, cycle + Fraction::from_int(1));
+    pattern
+        .query(State::new(span))
+        .into_iter()
+        .find(|hap| hap.part.begin <= cycle && cycle < hap.part.end)
+        .and_then(|hap| match hap.value {
+            Value::Number(n) => Some(n),
+            _ => None,
+        })
+}
+
+/// Helper function to determine if a value is "truthy"
+/// In the context of struct, we consider:
+/// - Numbers: 0 is false, anything else is true
+/// - Strings: empty string is false, anything else is true (including "~")
+/// - Booleans: use their boolean value
+/// - Lists: empty list is false, non-empty is true
+/// - Silence: false
+fn is_truthy(value: &crate::Value) -> bool {
+    match value {
+        crate::Value::Number(n) => *n != 0.0,
+        crate::Value::String(s) => !s.is_empty() && s != "~",
+        crate::Value::Bool(b) => *b,
+        crate::Value::List(l) => !l.is_empty(),
+        crate::Value::Silence => false,
+    }
+}
+
+// Implement Clone for Pattern
+impl Clone for Pattern {
+    fn clone(&self) -> Self {
+        Pattern {
+            query_func: self.query_func.clone(),
+            steps: self.steps,
+            kind: self.kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn test_pattern_creation() {
+        let pattern = Pattern::new(|_state| Vec::new());
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+
+        let haps = pattern.query(state);
+        assert_eq!(haps.len(), 0);
+    }
+
+    #[test]
+    fn test_pattern_with_value() {
+        let pattern = Pattern::new(|state| {
+            vec![Hap::new(
+                Some(state.span),
+                state.span,
+                Value::Number(10.0),
+            )]
+        });
+
+        let mapped = pattern.with_value(|v| match v {
+            Value::Number(n) => Value::Number(n + 5.0),
+            _ => v.clone(),
+        });
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = mapped.query(state);
+
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::Number(15.0));
+    }
+
+    #[test]
+    fn test_pattern_with_steps() {
+        let pattern =
+            Pattern::with_steps(|_| Vec::new(), Some(Fraction::from_int(4)));
+
+        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(4)));
+    }
+
+    #[test]
+    fn test_pattern_with_hap() {
+        let pattern = Pattern::new(|state| {
+            vec![Hap::new(
+                Some(state.span),
+                state.span,
+                Value::String("test".into()),
+            )]
+        });
+
+        let modified = pattern.with_hap(|hap| {
+            hap.with_value(|_| Value::String("modified".into()))
+        });
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = modified.query(state);
+
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("modified".into()));
+    }
+
+    #[test]
+    fn test_repeat_cycles() {
+        // Test based on Strudel's test: slowcat(0, 1).repeatCycles(2).fast(6).firstCycleValues
+        // should give [0, 0, 1, 1, 0, 0]
+        use crate::slowcat;
+
+        let pattern = slowcat(vec![
+            Pattern::new(|state| {
+                vec![Hap::new(Some(state.span), state.span, Value::Number(0.0))]
+            }),
+            Pattern::new(|state| {
+                vec![Hap::new(Some(state.span), state.span, Value::Number(1.0))]
+            }),
+        ]);
+
+        let repeated = pattern.repeat_cycles(2).fast(6.0);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = repeated.query(state);
+
+        // Should have 6 events
+        assert_eq!(haps.len(), 6);
+
+        // Extract values
+        let values: Vec<f64> = haps
+            .iter()
+            .map(|h| match &h.value {
+                Value::Number(n) => *n,
+                _ => panic!("Expected number"),
+            })
+            .collect();
+
+        // Should be [0, 0, 1, 1, 0, 0]
+        assert_eq!(values, vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_replicate() {
+        use crate::fastcat;
+
+        // Create pattern [bd sd]
+        let pattern = fastcat(vec![
+            Pattern::new(|state| {
+                vec![Hap::new(
+                    Some(state.span),
+                    state.span,
+                    Value::String("bd".into()),
+                )]
+            }),
+            Pattern::new(|state| {
+                vec![Hap::new(
+                    Some(state.span),
+                    state.span,
+                    Value::String("sd".into()),
+                )]
+            }),
+        ]);
+
+        // Replicate 2 times
+        let replicated = pattern.replicate(2);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = replicated.query(state);
+
+        // Should have 4 events: bd sd bd sd
+        assert_eq!(haps.len(), 4);
+
+        let values: Vec<String> = haps
+            .iter()
+            .map(|h| match &h.value {
+                Value::String(s) => s.clone(),
+                _ => panic!("Expected string"),
+            })
+            .collect();
+
+        assert_eq!(values, vec!["bd", "sd", "bd", "sd"]);
+
+        // Check timing - each should be 1/4 cycle
+        assert_eq!(haps[0].part.begin, Fraction::from_int(0));
+        assert_eq!(haps[0].part.end, Fraction::new(1, 4));
+        assert_eq!(haps[1].part.begin, Fraction::new(1, 4));
+        assert_eq!(haps[1].part.end, Fraction::new(1, 2));
+        assert_eq!(haps[2].part.begin, Fraction::new(1, 2));
+        assert_eq!(haps[2].part.end, Fraction::new(3, 4));
+        assert_eq!(haps[3].part.begin, Fraction::new(3, 4));
+        assert_eq!(haps[3].part.end, Fraction::from_int(1));
+    }
+
+    #[test]
+    fn test_euclid_pure() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).euclid(3, 8, None);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        // x..x..x. - three hits on steps 0, 3 and 6
+        assert_eq!(haps.len(), 3);
+        let onsets: Vec<Fraction> = haps.iter().map(|h| h.whole.unwrap().begin).collect();
+        assert_eq!(
+            onsets,
+            vec![Fraction::from_int(0), Fraction::new(3, 8), Fraction::new(6, 8)]
+        );
+        for hap in &haps {
+            let whole = hap.whole.unwrap();
+            assert_eq!(hap.value, Value::String("bd".into()));
+            assert_eq!(whole.end - whole.begin, Fraction::new(1, 8));
+        }
+    }
+
+    #[test]
+    fn test_euclid_every_cycle() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).euclid(5, 8, None);
+        for cycle in 0..3 {
+            let state = State::new(TimeSpan::new(
+                Fraction::from_int(cycle),
+                Fraction::from_int(cycle + 1),
+            ));
+            assert_eq!(pattern.query(state).len(), 5, "cycle {}", cycle);
+        }
+    }
+
+    #[test]
+    fn test_euclid_by_rotation() {
+        use crate::pure;
+
+        let onsets = |pattern: Pattern| -> Vec<Fraction> {
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            pattern.query(state).iter().map(|h| h.whole.unwrap().begin).collect()
+        };
+        let bd = || pure(Value::String("bd".into()));
+
+        // Rotating right by one is rotating left by step - 1
+        assert_eq!(
+            onsets(bd().euclid_by(3, 8, -1)),
+            onsets(bd().euclid_by(3, 8, 7))
+        );
+        assert_eq!(
+            onsets(bd().euclid_by(3, 8, 9)),
+            onsets(bd().euclid(3, 8, Some(1)))
+        );
+        assert!(onsets(bd().euclid_by(3, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_euclid_inv_is_complement() {
+        use crate::pure;
+
+        let steps = |pattern: Pattern| -> Vec<i64> {
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            pattern
+                .query(state)
+                .iter()
+                .map(|h| (h.whole.unwrap().begin * Fraction::from_int(8)).numerator)
+                .collect()
+        };
+        let bd = || pure(Value::String("bd".into()));
+
+        // x..x..x. and x.xx.xx.
+        assert_eq!(steps(bd().euclid(3, 8, None)), vec![0, 3, 6]);
+        assert_eq!(steps(bd().euclid_inv(3, 8, None)), vec![1, 2, 4, 5, 7]);
+        assert_eq!(steps(bd().euclid(5, 8, None)), vec![0, 2, 3, 5, 6]);
+        assert_eq!(steps(bd().euclid_inv(5, 8, None)), vec![1, 4, 7]);
+
+        // Rotation moves the rests along with the hits
+        for rotation in 0..8 {
+            let mut all = steps(bd().euclid(3, 8, Some(rotation)));
+            all.extend(steps(bd().euclid_inv(3, 8, Some(rotation))));
+            all.sort();
+            assert_eq!(all, (0..8).collect::<Vec<_>>(), "rotation {}", rotation);
+        }
+
+        // A negative patterned pulse inverts too
+        let inverted = bd().euclid_pat(pure(Value::Number(-3.0)), pure(Value::Number(8.0)), None);
+        assert_eq!(steps(inverted), vec![1, 2, 4, 5, 7]);
+    }
+
+    #[test]
+    fn test_euclid_full() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).euclid_full(
+            3,
+            8,
+            None,
+            pure(Value::String("hh".into())),
+        );
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let mut haps = pattern.query(state);
+        haps.sort_by_key(|h| h.whole.unwrap().begin);
+
+        let values: Vec<Value> = haps.into_iter().map(|h| h.value).collect();
+        let expected: Vec<Value> = ["bd", "hh", "hh", "bd", "hh", "hh", "bd", "hh"]
+            .iter()
+            .map(|s| Value::String(s.to_string()))
+            .collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_euclid_pat_alternating_pulse() {
+        use crate::{pure, slowcat};
+
+        let pulse = slowcat(vec![pure(Value::Number(3.0)), pure(Value::Number(5.0))]);
+        let pattern =
+            pure(Value::String("bd".into())).euclid_pat(pulse, pure(Value::Number(8.0)), None);
+
+        let count = |cycle: i64| {
+            let state = State::new(TimeSpan::new(
+                Fraction::from_int(cycle),
+                Fraction::from_int(cycle + 1),
+            ));
+            pattern.query(state).len()
+        };
+
+        assert_eq!(count(0), 3);
+        assert_eq!(count(1), 5);
+        assert_eq!(count(2), 3);
+
+        // A query spanning both cycles sees both rhythms
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(2)));
+        assert_eq!(pattern.query(state).len(), 8);
+    }
+
+    #[test]
+    fn test_scale_minor() {
+        use crate::{fastcat, pure};
+
+        let degrees = fastcat(vec![
+            pure(Value::Number(0.0)),
+            pure(Value::Number(2.0)),
+            pure(Value::Number(-1.0)),
+        ]);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let values: Vec<Value> = degrees
+            .scale("C:minor".to_string())
+            .query(state)
+            .into_iter()
+            .map(|h| h.value)
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                Value::String("C3".into()),
+                Value::String("Eb3".into()),
+                Value::String("Bb2".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tremolo() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![pure(Value::String("c4".into())); 8]).tremolo(1.0, 0.5);
+        let gains = |cycle: i64| -> Vec<f64> {
+            let state = State::new(TimeSpan::new(
+                Fraction::from_int(cycle),
+                Fraction::from_int(cycle + 1),
+            ));
+            pattern
+                .query(state)
+                .iter()
+                .map(|h| match h.context.metadata.get("gain") {
+                    Some(Value::Number(g)) => *g,
+                    _ => panic!("missing gain"),
+                })
+                .collect()
+        };
+
+        let cycle0 = gains(0);
+        assert_eq!(cycle0.len(), 8);
+        assert!(cycle0.iter().all(|g| (0.5..=1.0).contains(g)));
+        // Gain dips as the LFO rises and recovers as it falls
+        assert!(cycle0[2] < cycle0[0] && cycle0[6] > cycle0[0]);
+        // Same shape every cycle
+        assert_eq!(cycle0, gains(1));
+    }
+
+    #[test]
+    fn test_vibrato() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![pure(Value::Number(60.0)); 8]).vibrato(2.0, 0.5);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let notes: Vec<f64> = pattern
+            .query(state)
+            .iter()
+            .map(|h| match h.context.metadata.get("note") {
+                Some(Value::Number(n)) => *n,
+                _ => panic!("missing note"),
+            })
+            .collect();
+
+        assert_eq!(notes.len(), 8);
+        assert!(notes.iter().all(|n| (59.5..=60.5).contains(n)));
+        assert!(notes.iter().any(|n| *n > 60.1) && notes.iter().any(|n| *n < 59.9));
+
+        // Note names are modulated around their pitch
+        let named = pure(Value::String("c4".into())).vibrato(1.0, 1.0);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let hap = &named.query(state)[0];
+        assert_eq!(hap.context.metadata.get("note"), Some(&Value::Number(60.0)));
+    }
+
+    #[test]
+    fn test_rev() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+        ])
+        .rev();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(1), Fraction::from_int(2)));
+        let mut haps = pattern.query(state);
+        haps.sort_by_key(|h| h.part.begin);
+        let values: Vec<Value> = haps.iter().map(|h| h.value.clone()).collect();
+
+        assert_eq!(
+            values,
+            vec![
+                Value::String("c".into()),
+                Value::String("b".into()),
+                Value::String("a".into()),
+            ]
+        );
+        assert_eq!(
+            haps[0].whole,
+            Some(TimeSpan::new(Fraction::from_int(1), Fraction::new(4, 3)))
+        );
+    }
+
+    #[test]
+    fn test_restart() {
+        use crate::{pure, slowcat};
+
+        let abc = slowcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+        ]);
+
+        // A trigger firing every two cycles
+        let trigger = pure(Value::Bool(true)).slow(2.0);
+        let pattern = abc.clone().restart(trigger);
+
+        let values = |pattern: &Pattern| {
+            (0..6)
+                .map(|cycle| {
+                    let state = State::new(TimeSpan::from_ints(cycle, cycle + 1));
+                    let haps = pattern.query(state);
+                    assert_eq!(haps.len(), 1);
+                    assert_eq!(haps[0].whole, Some(TimeSpan::from_ints(cycle, cycle + 1)));
+                    haps[0].value.to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(values(&abc), vec!["a", "b", "c", "a", "b", "c"]);
+        assert_eq!(values(&pattern), vec!["a", "b", "a", "b", "a", "b"]);
+
+        // Falsy trigger events silence the pattern
+        let trigger = slowcat(vec![pure(Value::Bool(true)), pure(Value::Bool(false))]);
+        let state = State::new(TimeSpan::from_ints(1, 2));
+        assert!(abc.restart(trigger).query(state).is_empty());
+    }
+
+    #[test]
+    fn test_mask_keeps_source_onsets() {
+        use crate::{fastcat, pure};
+
+        // One long event, and a mask that is on for the first and third quarters
+        let source = pure(Value::String("a".into()));
+        let bools = fastcat(vec![
+            pure(Value::Bool(true)),
+            pure(Value::Bool(false)),
+            pure(Value::Bool(true)),
+            pure(Value::Bool(false)),
+        ]);
+        let onsets = |pattern: Pattern| {
+            let mut haps = pattern.query(State::new(TimeSpan::from_ints(0, 1)));
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.into_iter()
+                .filter(|hap| hap.has_onset())
+                .map(|hap| hap.whole.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let quarter = |n: i64| Fraction::new(n, 4);
+
+        // mask plays the original event once, from its own onset
+        let masked = source.clone().mask(bools.clone());
+        assert_eq!(onsets(masked.clone()), vec![TimeSpan::from_ints(0, 1)]);
+        let parts: Vec<TimeSpan> = masked
+            .query(State::new(TimeSpan::from_ints(0, 1)))
+            .into_iter()
+            .map(|hap| hap.part)
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                TimeSpan::new(quarter(0), quarter(1)),
+                TimeSpan::new(quarter(2), quarter(3)),
+            ]
+        );
+
+        // struct_ re-triggers on each truthy boolean event instead
+        assert_eq!(
+            onsets(source.struct_(bools)),
+            vec![
+                TimeSpan::new(quarter(0), quarter(1)),
+                TimeSpan::new(quarter(2), quarter(3)),
+            ]
+        );
+
+        // Events that start in a falsy region are dropped entirely
+        let values = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+        ]);
+        let second_half = fastcat(vec![pure(Value::Bool(false)), pure(Value::Bool(true))]);
+        let haps = values
+            .mask(second_half)
+            .query(State::new(TimeSpan::from_ints(0, 1)));
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("b".into()));
+    }
+
+    #[test]
+    fn test_swing() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
+        ]);
+        let onsets = |pattern: Pattern| {
+            let mut haps = pattern.query(State::new(TimeSpan::from_ints(0, 1)));
+            haps.retain(|hap| hap.has_onset());
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.into_iter()
+                .map(|hap| (hap.value.to_string(), hap.whole.unwrap().begin))
+                .collect::<Vec<_>>()
+        };
+
+        // On-beats stay put; off-beats move a tenth of a step (1/40 cycle) later
+        assert_eq!(
+            onsets(pattern.clone().swing(0.1)),
+            vec![
+                ("a".to_string(), Fraction::from_int(0)),
+                ("b".to_string(), Fraction::new(11, 40)),
+                ("c".to_string(), Fraction::new(1, 2)),
+                ("d".to_string(), Fraction::new(31, 40)),
+            ]
+        );
+
+        // The amount is clamped to half a step
+        assert_eq!(
+            onsets(pattern.clone().swing(2.0)),
+            onsets(pattern.clone().swing(0.5))
+        );
+        assert_eq!(onsets(pattern.clone().swing(0.0)), onsets(pattern));
+    }
+
+    #[test]
+    fn test_iter() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
+        ]);
+        let cycle = |pattern: &Pattern, cycle: i64| {
+            let mut haps = pattern.query(State::new(TimeSpan::from_ints(cycle, cycle + 1)));
+            haps.sort_by_key(|hap| hap.part.begin);
+            assert!(haps.iter().all(|hap| hap.has_onset()));
+            haps.iter()
+                .map(|hap| hap.value.to_string())
+                .collect::<String>()
+        };
+
+        let forward = pattern.clone().iter(4);
+        let rotations: Vec<String> = (0..5).map(|c| cycle(&forward, c)).collect();
+        assert_eq!(rotations, vec!["abcd", "bcda", "cdab", "dabc", "abcd"]);
+
+        let backward = pattern.iter_back(4);
+        let rotations: Vec<String> = (0..5).map(|c| cycle(&backward, c)).collect();
+        assert_eq!(rotations, vec!["abcd", "dabc", "cdab", "bcda", "abcd"]);
+    }
+
+    #[test]
+    fn test_rot() {
+        use crate::{fastcat, pure, silence};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            silence(),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
+        ]);
+        let events = |pattern: Pattern| {
+            let mut haps = pattern.query(State::new(TimeSpan::from_ints(0, 1)));
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.into_iter()
+                .map(|hap| (hap.whole.unwrap().begin, hap.value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let fifth = |n: i64| Fraction::new(n, 5);
+
+        // Values move; the onsets (including the rest) stay where they were
+        let rotated = events(pattern.clone().rot_left(1.0));
+        assert_eq!(
+            rotated,
+            vec![
+                (fifth(0), "b".to_string()),
+                (fifth(1), "c".to_string()),
+                (fifth(3), "d".to_string()),
+                (fifth(4), "a".to_string()),
+            ]
+        );
+        assert_eq!(
+            events(pattern.clone().rot_right(1.0))[0],
+            (fifth(0), "d".to_string())
+        );
+        let full_turn = pattern.clone().rot_left(4.0);
+        assert_eq!(events(full_turn), events(pattern.clone()));
+
+        // early moves the timing instead, carrying the rest along with it
+        let shifted = events(pattern.clone().early(0.2));
+        assert_eq!(shifted[0], (fifth(0), "b".to_string()));
+        assert_eq!(shifted[1], (fifth(2), "c".to_string()));
+
+        // Fragments of an event agree with its onset
+        let second_half = TimeSpan::new(Fraction::new(1, 10), fifth(1));
+        let haps = pattern.rot_left(1.0).query(State::new(second_half));
+        assert_eq!(haps[0].value, Value::String("b".into()));
+
+        // Every fragment of a split event takes the rotated value
+        let split = fastcat(vec![pure(Value::Number(1.0)), pure(Value::Number(2.0))])
+            .add(fastcat(vec![pure(Value::Number(0.0)); 4]));
+        let haps = split
+            .rot_left(1.0)
+            .query(State::new(TimeSpan::new(Fraction::new(1, 8), fifth(4))));
+        let values: Vec<Value> = haps.into_iter().map(|hap| hap.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                Value::Number(2.0),
+                Value::Number(2.0),
+                Value::Number(1.0),
+                Value::Number(1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accumulate() {
+        use crate::fastcat;
+
+        let sum = |a: &Value, b: &Value| {
+            Value::Number(a.as_number().unwrap_or(0.0) + b.as_number().unwrap_or(0.0))
+        };
+        let pattern = fastcat(vec![
+            crate::pure(Value::Number(1.0)),
+            crate::pure(Value::Number(2.0)),
+            crate::pure(Value::Number(3.0)),
+        ])
+        .accumulate(sum, Value::Number(0.0));
+
+        let events = |begin: Fraction, end: Fraction| {
+            let mut haps = pattern.query(State::new(TimeSpan::new(begin, end)));
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.into_iter()
+                .map(|hap| (hap.part.begin, hap.value.as_number().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let third = |n: i64| Fraction::new(n, 3);
+
+        // Running sums at each onset, starting over every cycle
+        assert_eq!(
+            events(third(0), third(3)),
+            vec![(third(0), 1.0), (third(1), 3.0), (third(2), 6.0)]
+        );
+        assert_eq!(
+            events(third(3), third(6)),
+            vec![(third(3), 1.0), (third(4), 3.0), (third(5), 6.0)]
+        );
+
+        // Querying part of a cycle still counts the earlier events
+        assert_eq!(
+            events(Fraction::new(1, 2), third(3)),
+            vec![(Fraction::new(1, 2), 3.0), (third(2), 6.0)]
+        );
+    }
+
+    #[test]
+    fn test_palindrome() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+        ])
+        .palindrome();
+
+        let values_in_cycle = |cycle: i64| {
+            let state = State::new(TimeSpan::new(
+                Fraction::from_int(cycle),
+                Fraction::from_int(cycle + 1),
+            ));
+            let mut haps = pattern.query(state);
+            haps.sort_by_key(|h| h.part.begin);
+            haps.into_iter().map(|h| h.value).collect::<Vec<Value>>()
+        };
+
+        let forward: Vec<Value> = ["a", "b", "c"]
+            .iter()
+            .map(|s| Value::String(s.to_string()))
+            .collect();
+        let backward: Vec<Value> = forward.iter().rev().cloned().collect();
+
+        assert_eq!(values_in_cycle(0), forward);
+        assert_eq!(values_in_cycle(1), backward);
+        assert_eq!(values_in_cycle(2), forward);
+        assert_eq!(values_in_cycle(-1), backward);
+    }
+
+    #[test]
+    fn test_chunk() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat((0..4).map(|i| pure(Value::Number(i as f64))).collect())
+            .chunk(4, |p| p.add_scalar(10.0));
+
+        for cycle in 0..8 {
+            let state = State::new(TimeSpan::new(
+                Fraction::from_int(cycle),
+                Fraction::from_int(cycle + 1),
+            ));
+            let mut haps = pattern.query(state);
+            haps.sort_by_key(|h| h.part.begin);
+            let values: Vec<Value> = haps.into_iter().map(|h| h.value).collect();
+
+            // Only the part matching this cycle is transformed
+            let expected: Vec<Value> = (0..4)
+                .map(|i| {
+                    let offset = if i == cycle % 4 { 10.0 } else { 0.0 };
+                    Value::Number(i as f64 + offset)
+                })
+                .collect();
+            assert_eq!(values, expected, "cycle {}", cycle);
+        }
+    }
+
+    #[test]
+    fn test_within() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+        ])
+        .within(0.0, 0.5, |p| p.fast(2.0));
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let mut haps = pattern.query(state);
+        haps.sort_by_key(|h| h.part.begin);
+
+        let events: Vec<(Fraction, Value)> = haps
+            .into_iter()
+            .map(|h| (h.whole.unwrap().begin, h.value))
+            .collect();
+
+        // The first half plays `a b` at double speed; the second half is untouched
+        assert_eq!(
+            events,
+            vec![
+                (Fraction::from_int(0), Value::String("a".into())),
+                (Fraction::new(1, 4), Value::String("b".into())),
+                (Fraction::new(1, 2), Value::String("b".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inside_and_outside() {
+        use crate::{fastcat, pure, slowcat};
+
+        let string = |s: &str| Value::String(s.into());
+        let values = |pattern: &Pattern, cycle: i64| -> Vec<Value> {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let mut haps = pattern.query(State::new(span));
+            haps.sort_by_key(|h| h.part.begin);
+            haps.into_iter().map(|h| h.value).collect()
+        };
+
+        // Inside two, each half cycle is reversed rather than the whole cycle
+        let sequence = fastcat(["a", "b", "c", "d"].map(|s| pure(string(s))).to_vec());
+        let inside = sequence.clone().inside(2.0, |p| p.rev());
+        assert_eq!(values(&inside, 0), ["b", "a", "d", "c"].map(string));
+        assert_eq!(
+            values(&sequence.clone().rev(), 0),
+            ["d", "c", "b", "a"].map(string)
+        );
+
+        // Outside two, the reversal spans two cycles, swapping them
+        let cycles = slowcat(vec![
+            fastcat(vec![pure(string("a")), pure(string("b"))]),
+            pure(string("c")),
+        ]);
+        let outside = cycles.outside(2.0, |p| p.rev());
+        assert_eq!(values(&outside, 0), [string("c")]);
+        assert_eq!(values(&outside, 1), ["b", "a"].map(string));
+
+        // Factors that cannot scale time give silence rather than panicking
+        for n in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+            assert!(values(&sequence.clone().inside(n, |p| p.rev()), 0).is_empty());
+            assert!(values(&sequence.clone().outside(n, |p| p.rev()), 0).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_sometimes_per_event_vs_per_cycle() {
+        use crate::{fastcat, pure};
+
+        let hats = fastcat(vec![pure(Value::String("hh".into())); 8]);
+        let mark = |p: Pattern| p.with_value(|_| Value::String("x".into()));
+
+        // Count transformed events in each of the first 16 cycles
+        let marked_per_cycle = |pattern: &Pattern| -> Vec<usize> {
+            (0..16)
+                .map(|cycle| {
+                    let state = State::new(TimeSpan::new(
+                        Fraction::from_int(cycle),
+                        Fraction::from_int(cycle + 1),
+                    ));
+                    pattern
+                        .query(state)
+                        .iter()
+                        .filter(|h| h.value == Value::String("x".into()))
+                        .count()
+                })
+                .collect()
+        };
+
+        // Per event: some cycles mix transformed and untransformed events
+        let per_event = marked_per_cycle(&hats.clone().sometimes_by(0.5, mark, 0));
+        assert!(per_event.iter().any(|&n| n > 0 && n < 8), "{:?}", per_event);
+
+        // Per cycle: every cycle is all or nothing, and both happen
+        let per_cycle = marked_per_cycle(&hats.clone().sometimes_cycle(0.5, mark, 0));
+        assert!(
+            per_cycle.iter().all(|&n| n == 0 || n == 8),
+            "{:?}",
+            per_cycle
+        );
+        assert!(
+            per_cycle.contains(&0) && per_cycle.contains(&8),
+            "{:?}",
+            per_cycle
+        );
+
+        // The seed picks which cycles are transformed
+        let reseeded = marked_per_cycle(&hats.sometimes_cycle(0.5, mark, 1));
+        assert_ne!(per_cycle, reseeded);
+    }
+
+    #[test]
+    fn test_steps_propagation() {
+        use crate::{fastcat, pure};
+
+        let abc = || {
+            fastcat(vec![
+                pure(Value::String("a".into())),
+                pure(Value::String("b".into())),
+                pure(Value::String("c".into())),
+            ])
+        };
+
+        assert_eq!(abc().fast(2.0).get_steps(), Some(Fraction::from_int(6)));
+        assert_eq!(abc().slow(3.0).get_steps(), Some(Fraction::from_int(1)));
+        assert_eq!(abc().rev().get_steps(), Some(Fraction::from_int(3)));
+        assert_eq!(abc().replicate(2).get_steps(), Some(Fraction::from_int(6)));
+        assert_eq!(abc().late(0.25).get_steps(), Some(Fraction::from_int(3)));
+        assert_eq!(
+            pure(Value::String("bd".into())).euclid(3, 8, None).get_steps(),
+            Some(Fraction::from_int(8))
+        );
+    }
+
+    #[test]
+    fn test_every() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ])
+        .every(4, |p| p.fast(2.0));
+
+        // Transform applies on cycles 0 and 4 only
+        for cycle in 0..=4 {
+            let state = State::new(TimeSpan::new(
+                Fraction::from_int(cycle),
+                Fraction::from_int(cycle + 1),
+            ));
+            let haps = pattern.query(state);
+
+            let expected = if cycle % 4 == 0 { 4 } else { 2 };
+            assert_eq!(haps.len(), expected, "cycle {}", cycle);
+        }
+    }
+
+    #[test]
+    fn test_every_zero_is_identity() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+        ]);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.clone().every(0, |p| p.fast(2.0)).query(state.clone());
+
+        assert_eq!(haps, pattern.query(state));
+    }
+
+    #[test]
+    fn test_tail() {
+        use crate::pure;
+
+        // Create two simple patterns
+        let pattern_a = pure(Value::String("a".into()));
+        let pattern_b = pure(Value::String("b".into()));
+
+        // Use tail to concatenate them
+        let combined = pattern_a.tail(pattern_b);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = combined.query(state);
+
+        // Should have 2 events: a in first half, b in second half
+        assert_eq!(haps.len(), 2);
+        assert_eq!(haps[0].value, Value::String("a".into()));
+        assert_eq!(haps[1].value, Value::String("b".into()));
+
+        // Check timing
+        assert_eq!(haps[0].part.begin, Fraction::from_int(0));
+        assert_eq!(haps[0].part.end, Fraction::new(1, 2));
+        assert_eq!(haps[1].part.begin, Fraction::new(1, 2));
+        assert_eq!(haps[1].part.end, Fraction::from_int(1));
+    }
+
+    #[test]
+    fn test_target() {
+        use crate::pure;
+
+        // Create a simple pattern
+        let pattern = pure(Value::String("bd".into()));
+
+        // Apply target
+        let targeted = pattern.target("drums".to_string());
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = targeted.query(state);
+
+        // Should have 1 event with target metadata
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+        assert_eq!(
+            haps[0].context.metadata.get("target"),
+            Some(&Value::String("drums".into()))
+        );
+    }
+
+    #[test]
+    fn test_bank() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("x_sd".into())),
+            pure(Value::Number(3.0)),
+        ])
+        .bank("x".to_string());
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let values: Vec<Value> = pattern.query(state).into_iter().map(|h| h.value).collect();
+
+        assert_eq!(
+            values,
+            vec![
+                Value::String("x_bd".into()),
+                Value::String("x_sd".into()),
+                Value::Number(3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_target_with_fastcat() {
+        use crate::fastcat;
+        use crate::pure;
+
+        // Create a sequence of patterns
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+            pure(Value::String("cp".into())),
+        ]);
+
+        // Apply target to the whole sequence
+        let targeted = pattern.target("percussion".to_string());
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = targeted.query(state);
+
+        // Should have 3 events, all with the same target
+        assert_eq!(haps.len(), 3);
+
+        for hap in &haps {
+            assert_eq!(
+                hap.context.metadata.get("target"),
+                Some(&Value::String("percussion".into()))
+            );
+        }
+
+        // Verify values are preserved
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+        assert_eq!(haps[1].value, Value::String("sd".into()));
+        assert_eq!(haps[2].value, Value::String("cp".into()));
+    }
+
+    #[test]
+    fn test_target_doesnt_affect_timing() {
+        use crate::fastcat;
+        use crate::pure;
+
+        // Create a pattern
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+        ]);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+
+        // Query without target
+        let haps_no_target = pattern.clone().query(state.clone());
+
+        // Query with target
+        let haps_with_target = pattern.target("test".to_string()).query(state);
+
+        // Should have same timing
+        assert_eq!(haps_no_target.len(), haps_with_target.len());
+        for i in 0..haps_no_target.len() {
+            assert_eq!(haps_no_target[i].part, haps_with_target[i].part);
+            assert_eq!(haps_no_target[i].whole, haps_with_target[i].whole);
+            assert_eq!(haps_no_target[i].value, haps_with_target[i].value);
+        }
+    }
+
+    #[test]
+    fn test_tag() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ])
+        .tag("color", Value::String("red".into()));
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 2);
+        assert!(haps
+            .iter()
+            .all(|h| h.context.metadata.get("color") == Some(&Value::String("red".into()))));
+    }
+
+    #[test]
+    fn test_n() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).n(3);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+        assert_eq!(haps[0].context.metadata.get("n"), Some(&Value::Number(3.0)));
+    }
+
+    #[test]
+    fn test_chop() {
+        use crate::{fastcat, pure};
+
+        let slice_of = |hap: &Hap| match (
+            hap.context.metadata.get("begin"),
+            hap.context.metadata.get("end"),
+        ) {
+            (Some(Value::Number(begin)), Some(Value::Number(end))) => (*begin, *end),
+            _ => panic!("Expected begin and end"),
+        };
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ])
+        .chop(4);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        // Four consecutive slices of each event, stepping through the sample
+        assert_eq!(haps.len(), 8);
+        for (i, hap) in haps.iter().enumerate() {
+            let slice = (i % 4) as f64;
+            let expected = if i < 4 { "bd" } else { "sd" };
+            assert_eq!(hap.whole.unwrap().begin, Fraction::new(i as i64, 8));
+            assert_eq!(hap.value, Value::String(expected.into()));
+            assert_eq!(slice_of(hap), (slice / 4.0, (slice + 1.0) / 4.0));
+        }
+
+        // Chopping a slice chops within it
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let halves = pure(Value::String("bd".into())).chop(2);
+        let haps = halves.chop(2).query(state);
+        let slices: Vec<(f64, f64)> = haps.iter().map(slice_of).collect();
+        let expected = vec![(0.0, 0.25), (0.25, 0.5), (0.5, 0.75), (0.75, 1.0)];
+        assert_eq!(slices, expected);
+    }
+
+    #[test]
+    fn test_striate() {
+        use crate::{fastcat, pure};
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ])
+        .striate(2);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        // bd and sd take turns, first playing their first halves, then their second
+        let slices: Vec<(String, Option<&Value>)> = haps
+            .iter()
+            .map(|h| (h.value.to_string(), h.context.metadata.get("begin")))
+            .collect();
+        assert_eq!(
+            slices,
+            vec![
+                ("bd".to_string(), Some(&Value::Number(0.0))),
+                ("sd".to_string(), Some(&Value::Number(0.0))),
+                ("bd".to_string(), Some(&Value::Number(0.5))),
+                ("sd".to_string(), Some(&Value::Number(0.5))),
+            ]
+        );
+
+        // Slices fall within a part of the sample that is already selected
+        let pattern = pure(Value::String("bd".into()))
+            .tag("begin", Value::Number(0.5))
+            .tag("end", Value::Number(1.0))
+            .striate(2);
+        let haps = pattern.query(State::new(TimeSpan::from_ints(0, 1)));
+        let ranges: Vec<(Option<&Value>, Option<&Value>)> = haps
+            .iter()
+            .map(|h| {
+                (
+                    h.context.metadata.get("begin"),
+                    h.context.metadata.get("end"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (Some(&Value::Number(0.5)), Some(&Value::Number(0.75))),
+                (Some(&Value::Number(0.75)), Some(&Value::Number(1.0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slice() {
+        use crate::{fastcat, pure, run};
+
+        let slices = |pattern: Pattern| -> Vec<(Fraction, f64, f64)> {
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            let mut haps = pattern.query(state);
+            haps.sort_by_key(|h| h.part.begin);
+            haps.iter()
+                .map(|h| {
+                    let control = |key| match h.context.metadata.get(key) {
+                        Some(Value::Number(value)) => *value,
+                        _ => panic!("Expected {}", key),
+                    };
+                    (h.whole.unwrap().begin, control("begin"), control("end"))
+                })
+                .collect()
+        };
+        let brk = pure(Value::String("break".into()));
+
+        // Counting up plays the slices in their original order
+        assert_eq!(
+            slices(brk.clone().slice(4, run(4))),
+            vec![
+                (Fraction::from_int(0), 0.0, 0.25),
+                (Fraction::new(1, 4), 0.25, 0.5),
+                (Fraction::new(1, 2), 0.5, 0.75),
+                (Fraction::new(3, 4), 0.75, 1.0),
+            ]
+        );
+
+        // Counting down reverses them, and out-of-range indices wrap
+        let indices = |values: [f64; 4]| fastcat(values.map(|i| pure(Value::Number(i))).to_vec());
+        let backwards = vec![
+            (Fraction::from_int(0), 0.75, 1.0),
+            (Fraction::new(1, 4), 0.5, 0.75),
+            (Fraction::new(1, 2), 0.25, 0.5),
+            (Fraction::new(3, 4), 0.0, 0.25),
+        ];
+        let reversed = brk.clone().slice(4, indices([3.0, 2.0, 1.0, 0.0]));
+        assert_eq!(slices(reversed), backwards);
+        let wrapped = brk.slice(4, indices([-1.0, 6.0, 5.0, 4.0]));
+        assert_eq!(slices(wrapped), backwards);
+
+        // Query controls reach the sliced pattern
+        let sound = Pattern::new(|state| {
+            let value = state
+                .controls
+                .get("sound")
+                .cloned()
+                .unwrap_or(Value::Silence);
+            vec![Hap::new(Some(state.span), state.span, value)]
+        });
+        let controls = [("sound".to_string(), Value::String("amen".into()))];
+        let state = State::with_controls(TimeSpan::from_ints(0, 1), controls.into());
+        let haps = sound.slice(2, pure(Value::Number(0.0))).query(state);
+        assert_eq!(haps[0].value, Value::String("amen".into()));
+    }
+
+    #[test]
+    fn test_jux() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ]);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.jux(|p| p).query(state);
+
+        // Two haps per event: one hard left, one hard right
+        assert_eq!(haps.len(), 4);
+        let (left, right): (Vec<_>, Vec<_>) = haps
+            .iter()
+            .partition(|h| h.context.metadata.get("pan") == Some(&Value::Number(0.0)));
+        assert_eq!(left.len(), 2);
+        assert_eq!(right.len(), 2);
+
+        for (l, r) in left.iter().zip(right.iter()) {
+            assert_eq!(r.context.metadata.get("pan"), Some(&Value::Number(1.0)));
+            assert_eq!(l.whole, r.whole);
+            assert_eq!(l.part, r.part);
+            assert_eq!(l.value, r.value);
+        }
+    }
+
+    #[test]
+    fn test_velocity() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![pure(Value::String("hh".into())); 4]);
+        let velocities = fastcat(vec![pure(Value::Number(1.0)), pure(Value::Number(0.5))]);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.velocity(velocities).query(state);
+
+        assert_eq!(haps.len(), 4);
+        for hap in &haps {
+            let expected = if hap.part.begin < Fraction::new(1, 2) { 1.0 } else { 0.5 };
+            assert_eq!(hap.context.metadata.get("gain"), Some(&Value::Number(expected)));
+        }
+
+        // Query controls reach the velocity pattern
+        let level = Pattern::new(|state| {
+            let value = state
+                .controls
+                .get("level")
+                .cloned()
+                .unwrap_or(Value::Silence);
+            vec![Hap::new(Some(state.span), state.span, value)]
+        });
+        let controls = [("level".to_string(), Value::Number(0.25))];
+        let state = State::with_controls(TimeSpan::from_ints(0, 1), controls.into());
+        let haps = pure(Value::String("hh".into()))
+            .velocity(level)
+            .query(state);
+        assert_eq!(
+            haps[0].context.metadata.get("gain"),
+            Some(&Value::Number(0.25))
+        );
+    }
+
+    #[test]
+    fn test_off() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).split_queries();
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.off(0.25, |p| p).query(state);
+
+        // Original event at the start of the cycle
+        assert!(haps
+            .iter()
+            .any(|h| h.part.begin == Fraction::from_int(0) && h.part.end == Fraction::from_int(1)));
+
+        // Copy shifted by a quarter cycle
+        let shifted: Vec<_> = haps
+            .iter()
+            .filter(|h| h.part.begin == Fraction::new(1, 4))
+            .collect();
+        assert_eq!(shifted.len(), 1);
+        assert_eq!(shifted[0].value, Value::String("bd".into()));
+    }
+
+    #[test]
+    fn test_off_applies_function_before_shift() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ]);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern
+            .off(0.25, |p| p.with_value(|_| Value::String("cp".into())))
+            .query(state);
+
+        let cp_onsets: Vec<_> = haps
+            .iter()
+            .filter(|h| h.value == Value::String("cp".into()))
+            .map(|h| h.part.begin)
+            .collect();
+        assert!(cp_onsets.contains(&Fraction::new(1, 4)));
+        assert!(cp_onsets.contains(&Fraction::new(3, 4)));
+        assert_eq!(haps.iter().filter(|h| h.value != Value::String("cp".into())).count(), 2);
+    }
+
+    #[test]
+    fn test_layer() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![pure(Value::Number(0.0)), pure(Value::Number(2.0))]);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let base_count = pattern.query(state.clone()).len();
+
+        let layered = pattern.layer(vec![|p: Pattern| p, |p: Pattern| p.add_scalar(7.0)]);
+        let haps = layered.query(state);
+
+        // Original plus a transposed copy
+        assert_eq!(haps.len(), base_count * 2);
+        let mut values: Vec<f64> = haps
+            .iter()
+            .map(|h| match h.value {
+                Value::Number(n) => n,
+                _ => panic!("expected numeric value"),
+            })
+            .collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(values, vec![0.0, 2.0, 7.0, 9.0]);
+    }
+
+    #[test]
+    fn test_add_scalar_ignores_strings() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).add_scalar(7.0);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+    }
+
+    #[test]
+    fn test_ply() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ])
+        .ply(2);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 4);
+        let expected = ["bd", "bd", "sd", "sd"];
+        for (i, hap) in haps.iter().enumerate() {
+            let begin = Fraction::new(i as i64, 4);
+            let end = Fraction::new(i as i64 + 1, 4);
+            assert_eq!(hap.value, Value::String(expected[i].into()));
+            assert_eq!(hap.whole, Some(TimeSpan::new(begin, end)));
+            assert_eq!(hap.part, TimeSpan::new(begin, end));
+        }
+    }
+
+    #[test]
+    fn test_ply_identity_and_zero() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("bd".into())),
+            pure(Value::String("sd".into())),
+        ]);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+
+        let plain = pattern.clone().query(state.clone());
+        let plied = pattern.clone().ply(1).query(state.clone());
+        assert_eq!(plain.len(), plied.len());
+        for (a, b) in plain.iter().zip(plied.iter()) {
+            assert_eq!(a.whole, b.whole);
+            assert_eq!(a.value, b.value);
+        }
+
+        assert!(pattern.ply(0).query(state).is_empty());
+    }
+
+    #[test]
+    fn test_segment() {
+        use crate::pure;
+
+        let pattern = pure(Value::Number(3.0)).segment(4.0);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 4);
+        for (i, hap) in haps.iter().enumerate() {
+            let begin = Fraction::new(i as i64, 4);
+            assert_eq!(hap.whole, Some(TimeSpan::new(begin, begin + Fraction::new(1, 4))));
+            assert_eq!(hap.value, Value::Number(3.0));
+        }
+        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(4)));
+    }
+
+    #[test]
+    fn test_segment_takes_value_at_step_start() {
+        use crate::fastcat;
+        use crate::pure;
+
+        // Four values per cycle, sampled twice per cycle
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
+        ])
+        .segment(2.0);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 2);
+        assert_eq!(haps[0].value, Value::String("a".into()));
+        assert_eq!(haps[1].value, Value::String("c".into()));
+    }
+
+    #[test]
+    fn test_degrade_by_rate() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![pure(Value::String("hh".into())); 8]).degrade_by(0.25, 3);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(100)));
+        let kept = pattern.query(state).len();
+
+        // 800 events, expect ~600 kept
+        assert!((540..=660).contains(&kept), "kept {} of 800", kept);
+    }
+
+    #[test]
+    fn test_degrade_by_query_independent() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![pure(Value::String("hh".into())); 8]).degrade_by(0.5, 9);
+
+        // Querying a whole cycle or its second half must agree on the second half
+        let full = pattern.query(State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1))));
+        let half = pattern.query(State::new(TimeSpan::new(Fraction::new(1, 2), Fraction::from_int(1))));
+
+        let full_onsets: Vec<_> = full
+            .iter()
+            .map(|h| h.part.begin)
+            .filter(|b| *b >= Fraction::new(1, 2))
+            .collect();
+        let half_onsets: Vec<_> = half.iter().map(|h| h.part.begin).collect();
+        assert_eq!(full_onsets, half_onsets);
+    }
+
+    #[test]
+    fn test_undegrade_by_is_complement() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![pure(Value::String("hh".into())); 8]);
+        let onsets = |pattern: Pattern| {
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(10)));
+            pattern
+                .query(state)
+                .into_iter()
+                .map(|h| h.part.begin)
+                .collect::<Vec<_>>()
+        };
+
+        let all = onsets(pattern.clone());
+        let kept = onsets(pattern.clone().degrade_by(0.3, 5));
+        let dropped = onsets(pattern.undegrade_by(0.3, 5));
+
+        // No event is both kept and dropped, and none goes missing
+        assert!(!kept.is_empty() && !dropped.is_empty());
+        assert!(kept.iter().all(|onset| !dropped.contains(onset)));
+        let mut together: Vec<_> = kept.into_iter().chain(dropped).collect();
+        together.sort();
+        assert_eq!(together, all);
+    }
+
+    #[test]
+    fn test_bite() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
+        ]);
+        let indices = fastcat(vec![
+            pure(Value::Number(0.0)),
+            pure(Value::Number(0.0)),
+            pure(Value::Number(1.0)),
+            pure(Value::Number(1.0)),
+        ]);
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.bite(4, indices).query(state);
+
+        assert_eq!(haps.len(), 4);
+        let expected = ["a", "a", "b", "b"];
+        for (i, hap) in haps.iter().enumerate() {
+            let begin = Fraction::new(i as i64, 4);
+            assert_eq!(hap.value, Value::String(expected[i].into()));
+            assert_eq!(hap.whole, Some(TimeSpan::new(begin, begin + Fraction::new(1, 4))));
+        }
+    }
+
+    #[test]
+    fn test_bite_wraps_indices() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+        ]);
+
+        // Index 3 wraps to chunk 1
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.bite(2, pure(Value::Number(3.0))).query(state);
+
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("b".into()));
+        assert_eq!(haps[0].part, TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
     }
 
     #[test]
-    fn test_pattern_with_value() {
-        let pattern = Pattern::new(|state| {
-            vec![Hap::new(
-                Some(state.span),
-                state.span,
-                Value::Number(10.0),
-            )]
-        });
+    fn test_fix() {
+        use crate::{fastcat, pure};
 
-        let mapped = pattern.with_value(|v| match v {
-            Value::Number(n) => Value::Number(n + 5.0),
-            _ => v.clone(),
-        });
+        let pattern = fastcat(
+            ["bd", "sd", "hh", "sd"]
+                .iter()
+                .map(|name| pure(Value::String(name.to_string())))
+                .collect(),
+        )
+        .fix(|p| p.tag("gain", Value::Number(0.5)), "sd");
 
-        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = mapped.query(state);
+        let mut haps = pattern.query(State::new(TimeSpan::from_ints(0, 1)));
+        haps.sort_by_key(|hap| hap.whole_or_part().begin);
+        let gains: Vec<(Value, Option<&Value>)> = haps
+            .iter()
+            .map(|hap| (hap.value.clone(), hap.context.metadata.get("gain")))
+            .collect();
 
-        assert_eq!(haps.len(), 1);
-        assert_eq!(haps[0].value, Value::Number(15.0));
+        let gain = Value::Number(0.5);
+        assert_eq!(
+            gains,
+            vec![
+                (Value::String("bd".into()), None),
+                (Value::String("sd".into()), Some(&gain)),
+                (Value::String("hh".into()), None),
+                (Value::String("sd".into()), Some(&gain)),
+            ]
+        );
     }
 
     #[test]
-    fn test_pattern_with_steps() {
-        let pattern =
-            Pattern::with_steps(|_| Vec::new(), Some(Fraction::from_int(4)));
+    fn test_fix_range() {
+        use crate::{fastcat, pure};
 
-        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(4)));
+        let pattern = fastcat(
+            [40.0, 60.0, 72.0, 59.5]
+                .iter()
+                .map(|note| pure(Value::Number(*note)))
+                .chain([pure(Value::String("60".into()))])
+                .collect(),
+        )
+        .fix_range(|p| p.tag("gain", Value::Number(0.5)), 50.0, 60.0);
+
+        let mut haps = pattern.query(State::new(TimeSpan::from_ints(0, 1)));
+        haps.sort_by_key(|hap| hap.whole_or_part().begin);
+        let gained: Vec<bool> = haps
+            .iter()
+            .map(|hap| hap.context.metadata.contains_key("gain"))
+            .collect();
+
+        // Bounds are inclusive, and only numbers are matched
+        assert_eq!(gained, vec![false, true, false, true, false]);
     }
 
     #[test]
-    fn test_pattern_with_hap() {
-        let pattern = Pattern::new(|state| {
-            vec![Hap::new(
-                Some(state.span),
-                state.span,
-                Value::String("test".into()),
-            )]
-        });
+    fn test_zoom() {
+        use crate::{fastcat, pure};
 
-        let modified = pattern.with_hap(|hap| {
-            hap.with_value(|_| Value::String("modified".into()))
-        });
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
+        ])
+        .zoom(0.5, 1.0);
+        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(2)));
 
-        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = modified.query(state);
+        for cycle in 0..2 {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let haps = pattern.query(State::new(span));
 
-        assert_eq!(haps.len(), 1);
-        assert_eq!(haps[0].value, Value::String("modified".into()));
+            assert_eq!(haps.len(), 2);
+            let expected = ["c", "d"];
+            for (i, hap) in haps.iter().enumerate() {
+                let begin = Fraction::from_int(cycle) + Fraction::new(i as i64, 2);
+                let end = begin + Fraction::new(1, 2);
+                assert_eq!(hap.value, Value::String(expected[i].into()));
+                assert_eq!(hap.whole, Some(TimeSpan::new(begin, end)));
+            }
+        }
     }
 
     #[test]
-    fn test_repeat_cycles() {
-        // Test based on Strudel's test: slowcat(0, 1).repeatCycles(2).fast(6).firstCycleValues
-        // should give [0, 0, 1, 1, 0, 0]
-        use crate::slowcat;
+    fn test_compress() {
+        use crate::{fastcat, pure};
 
-        let pattern = slowcat(vec![
-            Pattern::new(|state| {
-                vec![Hap::new(Some(state.span), state.span, Value::Number(0.0))]
-            }),
-            Pattern::new(|state| {
-                vec![Hap::new(Some(state.span), state.span, Value::Number(1.0))]
-            }),
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
         ]);
 
-        let repeated = pattern.repeat_cycles(2).fast(6.0);
-        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = repeated.query(state);
+        let compressed = pattern.clone().compress(0.25, 0.75);
+        for cycle in 0..2 {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let haps = compressed.query(State::new(span));
 
-        // Should have 6 events
-        assert_eq!(haps.len(), 6);
+            assert_eq!(haps.len(), 2);
+            let expected = ["a", "b"];
+            for (i, hap) in haps.iter().enumerate() {
+                let begin = Fraction::from_int(cycle) + Fraction::new(i as i64 + 1, 4);
+                let end = begin + Fraction::new(1, 4);
+                assert_eq!(hap.value, Value::String(expected[i].into()));
+                assert_eq!(hap.whole, Some(TimeSpan::new(begin, end)));
+            }
+        }
 
-        // Extract values
-        let values: Vec<f64> = haps
-            .iter()
-            .map(|h| match &h.value {
-                Value::Number(n) => *n,
-                _ => panic!("Expected number"),
-            })
-            .collect();
+        // Zooming back into the window restores the original
+        let restored = compressed.zoom(0.25, 0.75);
+        let haps = restored.query(State::new(TimeSpan::from_ints(0, 1)));
+        assert_eq!(haps, pattern.query(State::new(TimeSpan::from_ints(0, 1))));
 
-        // Should be [0, 0, 1, 1, 0, 0]
-        assert_eq!(values, vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0]);
+        // Empty and out of range windows are silent
+        let whole_cycle = State::new(TimeSpan::from_ints(0, 1));
+        let empty = pure(Value::Number(1.0)).compress(0.5, 0.5);
+        let out_of_range = pure(Value::Number(1.0)).compress(0.5, 1.5);
+        assert!(empty.query(whole_cycle.clone()).is_empty());
+        assert!(out_of_range.query(whole_cycle).is_empty());
     }
 
     #[test]
-    fn test_replicate() {
-        use crate::fastcat;
+    fn test_linger() {
+        use crate::{fastcat, pure};
 
-        // Create pattern [bd sd]
         let pattern = fastcat(vec![
-            Pattern::new(|state| {
-                vec![Hap::new(
-                    Some(state.span),
-                    state.span,
-                    Value::String("bd".into()),
-                )]
-            }),
-            Pattern::new(|state| {
-                vec![Hap::new(
-                    Some(state.span),
-                    state.span,
-                    Value::String("sd".into()),
-                )]
-            }),
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+            pure(Value::String("d".into())),
         ]);
 
-        // Replicate 2 times
-        let replicated = pattern.replicate(2);
-        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = replicated.query(state);
+        let values_in = |pattern: &Pattern, cycle: i64| -> Vec<Value> {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            let haps = pattern.query(State::new(span));
+            haps.into_iter().map(|h| h.value).collect()
+        };
+        let strings = |names: &[&str]| -> Vec<Value> {
+            names.iter().map(|n| Value::String(n.to_string())).collect()
+        };
 
-        // Should have 4 events: bd sd bd sd
-        assert_eq!(haps.len(), 4);
+        let lingered = pattern.clone().linger(0.5);
+        assert_eq!(values_in(&lingered, 0), strings(&["a", "b", "a", "b"]));
+        assert_eq!(values_in(&lingered, 3), strings(&["a", "b", "a", "b"]));
 
-        let values: Vec<String> = haps
-            .iter()
-            .map(|h| match &h.value {
-                Value::String(s) => s.clone(),
-                _ => panic!("Expected string"),
-            })
-            .collect();
+        let haps = lingered.query(State::new(TimeSpan::from_ints(0, 1)));
+        for (i, hap) in haps.iter().enumerate() {
+            let begin = Fraction::new(i as i64, 4);
+            let end = begin + Fraction::new(1, 4);
+            assert_eq!(hap.whole, Some(TimeSpan::new(begin, end)));
+        }
 
-        assert_eq!(values, vec!["bd", "sd", "bd", "sd"]);
+        let last_quarter = pattern.clone().linger(-0.25);
+        assert_eq!(values_in(&last_quarter, 0), strings(&["d", "d", "d", "d"]));
+        assert!(values_in(&pattern.clone().linger(0.0), 0).is_empty());
+        assert!(values_in(&pattern.clone().linger(f64::NAN), 0).is_empty());
+        assert_eq!(
+            values_in(&pattern.clone().linger(2.0), 0),
+            values_in(&pattern, 0)
+        );
 
-        // Check timing - each should be 1/4 cycle
-        assert_eq!(haps[0].part.begin, Fraction::from_int(0));
-        assert_eq!(haps[0].part.end, Fraction::new(1, 4));
-        assert_eq!(haps[1].part.begin, Fraction::new(1, 4));
-        assert_eq!(haps[1].part.end, Fraction::new(1, 2));
-        assert_eq!(haps[2].part.begin, Fraction::new(1, 2));
-        assert_eq!(haps[2].part.end, Fraction::new(3, 4));
-        assert_eq!(haps[3].part.begin, Fraction::new(3, 4));
-        assert_eq!(haps[3].part.end, Fraction::from_int(1));
+        // Each repeat is the next cycle of the zoomed pattern, as in Strudel
+        let cycles = crate::slowcat(vec![pattern.clone(), pattern.rev()]);
+        assert_eq!(
+            values_in(&cycles.linger(0.25), 0),
+            strings(&["a", "d", "a", "d"])
+        );
     }
 
     #[test]
-    fn test_tail() {
-        use crate::pure;
+    fn test_add_transposes_across_cycles() {
+        use crate::{fastcat, pure, slowcat};
 
-        // Create two simple patterns
-        let pattern_a = pure(Value::String("a".into()));
-        let pattern_b = pure(Value::String("b".into()));
+        let degrees = fastcat(vec![pure(Value::Number(0.0)), pure(Value::Number(2.0))]);
+        let octaves = slowcat(vec![pure(Value::Number(0.0)), pure(Value::Number(12.0))]);
+        let pattern = degrees.add(octaves);
+
+        let values_in = |cycle: i64| -> Vec<Value> {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            pattern.query(State::new(span)).into_iter().map(|h| h.value).collect()
+        };
+
+        assert_eq!(values_in(0), vec![Value::Number(0.0), Value::Number(2.0)]);
+        assert_eq!(values_in(1), vec![Value::Number(12.0), Value::Number(14.0)]);
+    }
+
+    #[test]
+    fn test_transpose() {
+        use crate::{fastcat, pure};
 
-        // Use tail to concatenate them
-        let combined = pattern_a.tail(pattern_b);
         let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = combined.query(state);
+        let values = |p: Pattern| -> Vec<Value> {
+            p.query(state.clone())
+                .into_iter()
+                .map(|h| h.value)
+                .collect()
+        };
+        let notes = |names: &[&str]| -> Pattern {
+            fastcat(
+                names
+                    .iter()
+                    .map(|name| pure(Value::String(name.to_string())))
+                    .collect(),
+            )
+        };
 
-        // Should have 2 events: a in first half, b in second half
-        assert_eq!(haps.len(), 2);
-        assert_eq!(haps[0].value, Value::String("a".into()));
-        assert_eq!(haps[1].value, Value::String("b".into()));
+        assert_eq!(
+            values(notes(&["c3", "e3"]).transpose(12)),
+            values(notes(&["c4", "e4"]))
+        );
+        assert_eq!(
+            values(notes(&["c3", "b3"]).transpose(2)),
+            values(notes(&["d3", "c#4"]))
+        );
 
-        // Check timing
-        assert_eq!(haps[0].part.begin, Fraction::from_int(0));
-        assert_eq!(haps[0].part.end, Fraction::new(1, 2));
-        assert_eq!(haps[1].part.begin, Fraction::new(1, 2));
-        assert_eq!(haps[1].part.end, Fraction::from_int(1));
+        // MIDI note numbers shift too, but sample names are left alone
+        let mixed = fastcat(vec![
+            pure(Value::Number(60.0)),
+            pure(Value::String("bd".into())),
+        ]);
+        assert_eq!(
+            values(mixed.transpose(-3)),
+            vec![Value::Number(57.0), Value::String("bd".into())]
+        );
     }
 
     #[test]
-    fn test_target() {
-        use crate::pure;
+    fn test_arithmetic_keeps_left_structure() {
+        use crate::{fastcat, pure};
 
-        // Create a simple pattern
-        let pattern = pure(Value::String("bd".into()));
+        let left = fastcat(vec![pure(Value::Number(6.0)), pure(Value::Number(8.0))]);
+        let right = pure(Value::Number(2.0));
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
 
-        // Apply target
-        let targeted = pattern.target("drums".to_string());
+        let values = |p: Pattern| -> Vec<Value> { p.query(state.clone()).into_iter().map(|h| h.value).collect() };
+
+        assert_eq!(values(left.clone().sub(right.clone())), vec![Value::Number(4.0), Value::Number(6.0)]);
+        assert_eq!(values(left.clone().mul(right.clone())), vec![Value::Number(12.0), Value::Number(16.0)]);
+        assert_eq!(values(left.clone().div(right.clone())), vec![Value::Number(3.0), Value::Number(4.0)]);
+
+        let haps = left.add(right).query(state.clone());
+        assert_eq!(haps[0].whole, Some(TimeSpan::new(Fraction::from_int(0), Fraction::new(1, 2))));
+        assert_eq!(haps[1].whole, Some(TimeSpan::new(Fraction::new(1, 2), Fraction::from_int(1))));
+    }
+
+    #[test]
+    fn test_arithmetic_keeps_events_without_a_right_value() {
+        use crate::{fastcat, pure, silence};
+
+        let left = fastcat(vec![pure(Value::Number(1.0)), pure(Value::Number(2.0))]);
+        let right = fastcat(vec![
+            pure(Value::Number(10.0)),
+            silence(),
+            silence(),
+            silence(),
+        ]);
         let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = targeted.query(state);
+        let mut haps = left.add(right).query(state);
+        haps.sort_by_key(|h| h.part.begin);
 
-        // Should have 1 event with target metadata
-        assert_eq!(haps.len(), 1);
-        assert_eq!(haps[0].value, Value::String("bd".into()));
+        let parts: Vec<(TimeSpan, Value)> = haps.into_iter().map(|h| (h.part, h.value)).collect();
+        let span = |b: (i64, i64), e: (i64, i64)| {
+            TimeSpan::new(Fraction::new(b.0, b.1), Fraction::new(e.0, e.1))
+        };
         assert_eq!(
-            haps[0].context.metadata.get("target"),
-            Some(&Value::String("drums".into()))
+            parts,
+            vec![
+                (span((0, 1), (1, 4)), Value::Number(11.0)),
+                (span((1, 4), (1, 2)), Value::Number(1.0)),
+                (span((1, 2), (1, 1)), Value::Number(2.0)),
+            ]
         );
     }
 
     #[test]
-    fn test_target_with_fastcat() {
-        use crate::fastcat;
+    fn test_add_leaves_strings_alone() {
         use crate::pure;
 
-        // Create a sequence of patterns
-        let pattern = fastcat(vec![
-            pure(Value::String("bd".into())),
-            pure(Value::String("sd".into())),
-            pure(Value::String("cp".into())),
-        ]);
+        let pattern = pure(Value::String("bd".into())).add(pure(Value::Number(7.0)));
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
 
-        // Apply target to the whole sequence
-        let targeted = pattern.target("percussion".to_string());
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+    }
+
+    #[test]
+    fn test_range() {
+        use crate::sine;
+
+        let sample_at = |pattern: &Pattern, t: Fraction| -> f64 {
+            let epsilon = Fraction::new(1, 1000);
+            let haps = pattern.query(State::new(TimeSpan::new(t - epsilon, t + epsilon)));
+            match haps[0].value {
+                Value::Number(n) => n,
+                _ => panic!("expected numeric value"),
+            }
+        };
+
+        let pattern = sine().range(0.0, 10.0);
+        assert!((sample_at(&pattern, Fraction::new(1, 4)) - 10.0).abs() < 1e-6);
+        assert!(sample_at(&pattern, Fraction::new(3, 4)).abs() < 1e-6);
+        assert!((sample_at(&pattern, Fraction::from_int(0)) - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_range_ignores_strings() {
+        use crate::pure;
+
+        let pattern = pure(Value::String("bd".into())).range(0.0, 10.0);
         let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
-        let haps = targeted.query(state);
 
-        // Should have 3 events, all with the same target
-        assert_eq!(haps.len(), 3);
+        assert_eq!(pattern.query(state)[0].value, Value::String("bd".into()));
+    }
 
-        for hap in &haps {
-            assert_eq!(
-                hap.context.metadata.get("target"),
-                Some(&Value::String("percussion".into()))
-            );
+    #[test]
+    fn test_sometimes_by_rate() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let hats = fastcat(vec![pure(Value::String("hh".into())); 4]);
+        let mark = |p: Pattern| p.with_value(|_| Value::String("x".into()));
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(100)));
+
+        for (prob, pattern) in [
+            (0.3, hats.clone().sometimes_by(0.3, mark, 5)),
+            (0.5, hats.clone().sometimes(mark)),
+            (0.75, hats.clone().often(mark)),
+            (0.25, hats.clone().rarely(mark)),
+        ] {
+            let haps = pattern.query(state.clone());
+
+            // Every event is either transformed or kept, never both
+            assert_eq!(haps.len(), 400);
+
+            let fired = haps.iter().filter(|h| h.value == Value::String("x".into())).count();
+            let rate = fired as f64 / 400.0;
+            assert!((rate - prob).abs() < 0.08, "rate {} for probability {}", rate, prob);
         }
+    }
 
-        // Verify values are preserved
-        assert_eq!(haps[0].value, Value::String("bd".into()));
-        assert_eq!(haps[1].value, Value::String("sd".into()));
-        assert_eq!(haps[2].value, Value::String("cp".into()));
+    #[test]
+    fn test_sometimes_by_deterministic() {
+        use crate::fastcat;
+        use crate::pure;
+
+        let hats = fastcat(vec![pure(Value::String("hh".into())); 4]);
+        let pattern = hats.sometimes_by(0.5, |p| p.with_value(|_| Value::String("x".into())), 2);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(4)));
+
+        let first: Vec<_> = pattern.query(state.clone()).into_iter().map(|h| (h.part, h.value)).collect();
+        let second: Vec<_> = pattern.query(state).into_iter().map(|h| (h.part, h.value)).collect();
+        assert_eq!(first, second);
     }
 
     #[test]
-    fn test_target_doesnt_affect_timing() {
+    fn test_fast_frac_exact() {
         use crate::fastcat;
         use crate::pure;
 
-        // Create a pattern
         let pattern = fastcat(vec![
             pure(Value::String("a".into())),
             pure(Value::String("b".into())),
-        ]);
+        ])
+        .fast_frac(Fraction::new(1, 3));
 
-        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(3)));
+        let wholes: Vec<_> = pattern.query(state).into_iter().filter_map(|h| h.whole).collect();
 
-        // Query without target
-        let haps_no_target = pattern.clone().query(state.clone());
+        assert_eq!(
+            wholes,
+            vec![
+                TimeSpan::new(Fraction::from_int(0), Fraction::new(3, 2)),
+                TimeSpan::new(Fraction::new(3, 2), Fraction::from_int(3)),
+            ]
+        );
+    }
 
-        // Query with target
-        let haps_with_target = pattern.target("test".to_string()).query(state);
+    #[test]
+    fn test_slow_frac_matches_fast_reciprocal() {
+        use crate::fastcat;
+        use crate::pure;
 
-        // Should have same timing
-        assert_eq!(haps_no_target.len(), haps_with_target.len());
-        for i in 0..haps_no_target.len() {
-            assert_eq!(haps_no_target[i].part, haps_with_target[i].part);
-            assert_eq!(haps_no_target[i].whole, haps_with_target[i].whole);
-            assert_eq!(haps_no_target[i].value, haps_with_target[i].value);
+        let pattern = fastcat(vec![
+            pure(Value::String("a".into())),
+            pure(Value::String("b".into())),
+            pure(Value::String("c".into())),
+        ]);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(2)));
+
+        let slowed: Vec<_> = pattern.clone().slow_frac(Fraction::new(2, 3)).query(state.clone());
+        let sped: Vec<_> = pattern.fast_frac(Fraction::new(3, 2)).query(state);
+
+        assert_eq!(slowed.len(), sped.len());
+        for (a, b) in slowed.iter().zip(sped.iter()) {
+            assert_eq!(a.whole, b.whole);
+            assert_eq!(a.value, b.value);
         }
     }
 }
@@ -0,0 +1,102 @@
+//! Deterministic randomness keyed by time
+//!
+//! Random decisions are derived from a hash of the event onset and a seed
+//! rather than from a stateful RNG, so the same event always gets the same
+//! value regardless of how or in which order the pattern is queried.
+
+use crate::Fraction;
+
+/// SplitMix64 finalizer - a fast, well-distributed 64-bit mixing function
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Combine a seed with a base seed, such as one chosen for a whole program
+///
+/// Each seed still gets a distinct result, and changing the base changes
+/// them all.
+pub fn mix_seed(seed: u64, base: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(base))
+}
+
+/// Get a uniformly distributed random number in `[0, 1)` for a point in time
+///
+/// The same `(time, seed)` pair always produces the same value.
+pub fn random_at(time: Fraction, seed: u64) -> f64 {
+    let mut h = splitmix64(seed);
+    h = splitmix64(h ^ time.numerator as u64);
+    h = splitmix64(h ^ time.denominator as u64);
+
+    // Use the top 53 bits for a full-precision f64 mantissa
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: i64 = 10_000;
+
+    fn samples(seed: u64) -> Vec<f64> {
+        (0..SAMPLES)
+            .map(|i| random_at(Fraction::new(i, 16), seed))
+            .collect()
+    }
+
+    #[test]
+    fn test_random_at_is_deterministic() {
+        let t = Fraction::new(3, 8);
+        assert_eq!(random_at(t, 42), random_at(t, 42));
+        assert_ne!(random_at(t, 42), random_at(t, 43));
+    }
+
+    #[test]
+    fn test_random_at_range() {
+        assert!(samples(0).iter().all(|&r| (0.0..1.0).contains(&r)));
+    }
+
+    #[test]
+    fn test_random_at_uniform() {
+        // Chi-square over 10 equal buckets (9 degrees of freedom)
+        const BUCKETS: usize = 10;
+        let mut counts = [0usize; BUCKETS];
+        for r in samples(7) {
+            counts[(r * BUCKETS as f64) as usize] += 1;
+        }
+
+        let expected = SAMPLES as f64 / BUCKETS as f64;
+        let chi_square: f64 = counts
+            .iter()
+            .map(|&c| (c as f64 - expected).powi(2) / expected)
+            .sum();
+
+        // Critical value for p = 0.001 is 27.88
+        assert!(chi_square < 27.88, "chi-square too high: {}", chi_square);
+    }
+
+    #[test]
+    fn test_random_at_adjacent_uncorrelated() {
+        // Chi-square over a 4x4 grid of (current, next) pairs (15 degrees of freedom)
+        const BUCKETS: usize = 4;
+        let values = samples(11);
+        let mut grid = [[0usize; BUCKETS]; BUCKETS];
+        for pair in values.windows(2) {
+            let a = (pair[0] * BUCKETS as f64) as usize;
+            let b = (pair[1] * BUCKETS as f64) as usize;
+            grid[a][b] += 1;
+        }
+
+        let expected = (values.len() - 1) as f64 / (BUCKETS * BUCKETS) as f64;
+        let chi_square: f64 = grid
+            .iter()
+            .flatten()
+            .map(|&c| (c as f64 - expected).powi(2) / expected)
+            .sum();
+
+        // Critical value for p = 0.001 is 37.70
+        assert!(chi_square < 37.70, "chi-square too high: {}", chi_square);
+    }
+}
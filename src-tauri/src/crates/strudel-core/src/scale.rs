@@ -0,0 +1,257 @@
+//! Musical scales for mapping scale degrees to note names
+//!
+//! Scales are named like Strudel's `scale`: `"<root>:<mode>"`, e.g.
+//! `"C:minor"`, `"F#:dorian"` or `"Eb:minor_pentatonic"`. The root may carry
+//! an octave (`"C4:major"`); without one, degree 0 is in octave 3.
+
+/// Octave of degree 0 when the root doesn't specify one
+const DEFAULT_OCTAVE: i32 = 3;
+
+/// Note names used when spelling a key with sharps
+const SHARP_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Note names used when spelling a key with flats
+const FLAT_NAMES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+
+/// A scale rooted at a specific pitch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    /// MIDI note number of degree 0
+    pub root: i32,
+    /// Semitone offsets of each degree within one octave
+    pub intervals: &'static [i32],
+    /// Whether to spell notes with flats rather than sharps
+    pub flats: bool,
+}
+
+impl Scale {
+    /// Parse a scale name like `"C:minor"` or `"Bb4:dorian"`
+    ///
+    /// Mode names are case-insensitive and may use spaces, dashes or
+    /// underscores (`"harmonic minor"`, `"harmonic_minor"`). Returns None for
+    /// unknown roots or modes.
+    pub fn parse(name: &str) -> Option<Scale> {
+        let (root, mode) = name.split_once(':')?;
+        let root = root.trim();
+        let (pitch_class, octave) = parse_root(root)?;
+        let mode = mode.trim().to_lowercase().replace([' ', '-'], "_");
+        let (intervals, relative_major) = mode_intervals(&mode)?;
+
+        Some(Scale {
+            root: (octave + 1) * 12 + pitch_class,
+            intervals,
+            flats: uses_flats(root, pitch_class - relative_major),
+        })
+    }
+
+    /// Get the MIDI note number of a scale degree
+    ///
+    /// Degrees outside `0..len` wrap into neighbouring octaves, so `-1` is
+    /// the last degree one octave below the root.
+    pub fn degree_to_midi(&self, degree: i64) -> i32 {
+        let len = self.intervals.len() as i64;
+        let octave = degree.div_euclid(len) as i32;
+        let step = degree.rem_euclid(len) as usize;
+        self.root + octave * 12 + self.intervals[step]
+    }
+
+    /// Get the note name (with octave) of a scale degree, e.g. `"Eb3"`
+    pub fn degree_to_note(&self, degree: i64) -> String {
+        midi_to_note(self.degree_to_midi(degree), self.flats)
+    }
+
+    /// Get the scale degree of a MIDI note number
+    ///
+    /// The inverse of `degree_to_midi`. Returns None for notes outside the scale.
+    pub fn midi_to_degree(&self, midi: i32) -> Option<i64> {
+        let offset = midi - self.root;
+        let step = self
+            .intervals
+            .iter()
+            .position(|&i| i == offset.rem_euclid(12))?;
+        Some(offset.div_euclid(12) as i64 * self.intervals.len() as i64 + step as i64)
+    }
+}
+
+/// Get the note name (with octave) of a MIDI note number, e.g. `"Eb3"`
+pub fn midi_to_note(midi: i32, flats: bool) -> String {
+    let names = if flats { &FLAT_NAMES } else { &SHARP_NAMES };
+    format!(
+        "{}{}",
+        names[midi.rem_euclid(12) as usize],
+        midi.div_euclid(12) - 1
+    )
+}
+
+/// Whether to spell notes with flats, given the root as written and the
+/// pitch class of the major key whose signature applies
+pub(crate) fn uses_flats(root: &str, major_key: i32) -> bool {
+    if root.contains('#') {
+        false
+    } else {
+        root[1..].starts_with('b') || matches!(major_key.rem_euclid(12), 1 | 3 | 5 | 6 | 8 | 10)
+    }
+}
+
+/// Get the MIDI note number of a note name like `"c4"`, `"Eb3"` or `"f#"`
+///
+/// Names without an octave are in octave 3.
+pub fn note_to_midi(name: &str) -> Option<i32> {
+    let (pitch_class, octave) = parse_root(name.trim())?;
+    Some((octave + 1) * 12 + pitch_class)
+}
+
+/// Transpose a note name by a number of semitones, e.g. `"c3"` up 2 is `"d3"`
+///
+/// The result keeps the name's case and its use of flats or sharps, and
+/// always has an octave. Returns None if `name` isn't a note name.
+pub fn transpose_note(name: &str, semitones: i32) -> Option<String> {
+    let name = name.trim();
+    let midi = note_to_midi(name)?;
+    let note = midi_to_note(midi + semitones, name[1..].starts_with('b'));
+    Some(if name.starts_with(|c: char| c.is_ascii_lowercase()) {
+        note.to_lowercase()
+    } else {
+        note
+    })
+}
+
+/// Parse a root like `"C"`, `"f#"`, `"Bb"` or `"Eb4"` into a pitch class and octave
+pub(crate) fn parse_root(root: &str) -> Option<(i32, i32)> {
+    let mut chars = root.chars();
+    let letter = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let accidentals = rest.len() - rest.trim_start_matches(['#', 'b']).len();
+    let offset: i32 = rest[..accidentals]
+        .chars()
+        .map(|c| if c == '#' { 1 } else { -1 })
+        .sum();
+
+    let octave = match &rest[accidentals..] {
+        "" => DEFAULT_OCTAVE,
+        digits => digits.parse().ok()?,
+    };
+
+    Some((letter + offset, octave))
+}
+
+/// Get the intervals of a mode and the degree its relative major starts on
+fn mode_intervals(mode: &str) -> Option<(&'static [i32], i32)> {
+    let scale: (&'static [i32], i32) = match mode {
+        "major" | "ionian" => (&[0, 2, 4, 5, 7, 9, 11], 0),
+        "dorian" => (&[0, 2, 3, 5, 7, 9, 10], 2),
+        "phrygian" => (&[0, 1, 3, 5, 7, 8, 10], 4),
+        "lydian" => (&[0, 2, 4, 6, 7, 9, 11], 5),
+        "mixolydian" => (&[0, 2, 4, 5, 7, 9, 10], 7),
+        "minor" | "aeolian" => (&[0, 2, 3, 5, 7, 8, 10], 9),
+        "locrian" => (&[0, 1, 3, 5, 6, 8, 10], 11),
+        "harmonic_minor" => (&[0, 2, 3, 5, 7, 8, 11], 9),
+        "melodic_minor" => (&[0, 2, 3, 5, 7, 9, 11], 9),
+        "pentatonic" | "major_pentatonic" => (&[0, 2, 4, 7, 9], 0),
+        "minor_pentatonic" => (&[0, 3, 5, 7, 10], 9),
+        "chromatic" => (&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], 0),
+        _ => return None,
+    };
+    Some(scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(name: &str, degrees: &[i64]) -> Vec<String> {
+        let scale = Scale::parse(name).unwrap();
+        degrees.iter().map(|&d| scale.degree_to_note(d)).collect()
+    }
+
+    #[test]
+    fn test_major() {
+        assert_eq!(
+            notes("C:major", &[0, 1, 2, 3, 4, 5, 6, 7]),
+            vec!["C3", "D3", "E3", "F3", "G3", "A3", "B3", "C4"]
+        );
+    }
+
+    #[test]
+    fn test_minor() {
+        assert_eq!(notes("C:minor", &[0, 2, 4]), vec!["C3", "Eb3", "G3"]);
+    }
+
+    #[test]
+    fn test_negative_degrees_wrap_below_root() {
+        assert_eq!(notes("C:minor", &[-1, -7]), vec!["Bb2", "C2"]);
+    }
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(notes("D:dorian", &[2, 6]), vec!["F3", "C4"]);
+        assert_eq!(notes("E:phrygian", &[1]), vec!["F3"]);
+        assert_eq!(notes("F:lydian", &[3]), vec!["B3"]);
+        assert_eq!(notes("G:mixolydian", &[6]), vec!["F4"]);
+        assert_eq!(notes("B:locrian", &[4]), vec!["F4"]);
+        assert_eq!(notes("A:harmonic_minor", &[6]), vec!["G#4"]);
+        assert_eq!(notes("A:minor pentatonic", &[5]), vec!["A4"]);
+        assert_eq!(notes("C:pentatonic", &[3, 4]), vec!["G3", "A3"]);
+    }
+
+    #[test]
+    fn test_root_spelling_and_octave() {
+        assert_eq!(notes("G:major", &[6]), vec!["F#4"]);
+        assert_eq!(notes("F:major", &[3]), vec!["Bb3"]);
+        assert_eq!(notes("Eb4:major", &[0, 2]), vec!["Eb4", "G4"]);
+        assert_eq!(notes("f#:minor", &[0]), vec!["F#3"]);
+    }
+
+    #[test]
+    fn test_midi_to_degree() {
+        let scale = Scale::parse("C:minor").unwrap();
+        for degree in -8..16 {
+            assert_eq!(
+                scale.midi_to_degree(scale.degree_to_midi(degree)),
+                Some(degree)
+            );
+        }
+        assert_eq!(scale.midi_to_degree(note_to_midi("e3").unwrap()), None);
+    }
+
+    #[test]
+    fn test_note_to_midi() {
+        assert_eq!(note_to_midi("c4"), Some(60));
+        assert_eq!(note_to_midi("Eb3"), Some(51));
+        assert_eq!(note_to_midi("f#"), Some(54));
+        assert_eq!(note_to_midi("bd"), None);
+    }
+
+    #[test]
+    fn test_transpose_note() {
+        assert_eq!(transpose_note("c3", 2).as_deref(), Some("d3"));
+        assert_eq!(transpose_note("b3", 1).as_deref(), Some("c4"));
+        assert_eq!(transpose_note("Eb3", 2).as_deref(), Some("F3"));
+        assert_eq!(transpose_note("eb3", 1).as_deref(), Some("e3"));
+        assert_eq!(transpose_note("f#", -1).as_deref(), Some("f3"));
+        assert_eq!(transpose_note("bb4", 2).as_deref(), Some("c5"));
+        assert_eq!(transpose_note("c4", -1).as_deref(), Some("b3"));
+        assert_eq!(transpose_note("bd", 2), None);
+    }
+
+    #[test]
+    fn test_unknown_scale() {
+        assert_eq!(Scale::parse("C:nonsense"), None);
+        assert_eq!(Scale::parse("H:major"), None);
+        assert_eq!(Scale::parse("major"), None);
+    }
+}
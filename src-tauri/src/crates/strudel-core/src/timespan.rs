@@ -76,6 +76,25 @@ impl TimeSpan {
     pub fn scale(&self, factor: Fraction) -> TimeSpan {
         TimeSpan::new(self.begin * factor, self.end * factor)
     }
+
+    /// Split the timespan at each cycle boundary it crosses
+    ///
+    /// A zero-width span is returned as is. Matches Strudel's `spanCycles`.
+    pub fn span_cycles(&self) -> Vec<TimeSpan> {
+        if self.is_empty() {
+            return vec![*self];
+        }
+
+        let mut spans = Vec::new();
+        let mut begin = self.begin;
+        while begin < self.end {
+            let next_cycle = begin.floor() + Fraction::from_int(1);
+            let end = self.end.min(next_cycle);
+            spans.push(TimeSpan::new(begin, end));
+            begin = end;
+        }
+        spans
+    }
 }
 
 impl fmt::Display for TimeSpan {
@@ -118,6 +137,25 @@ mod tests {
         assert!(!ts1.overlaps(&ts3));
     }
 
+    #[test]
+    fn test_timespan_span_cycles() {
+        assert_eq!(
+            TimeSpan::from_floats(0.5, 2.25).span_cycles(),
+            vec![
+                TimeSpan::from_floats(0.5, 1.0),
+                TimeSpan::from_ints(1, 2),
+                TimeSpan::from_floats(2.0, 2.25),
+            ]
+        );
+        assert_eq!(
+            TimeSpan::from_ints(-1, 1).span_cycles(),
+            vec![TimeSpan::from_ints(-1, 0), TimeSpan::from_ints(0, 1)]
+        );
+
+        let point = TimeSpan::from_ints(3, 3);
+        assert_eq!(point.span_cycles(), vec![point]);
+    }
+
     #[test]
     fn test_timespan_intersection() {
         let ts1 = TimeSpan::from_ints(0, 2);
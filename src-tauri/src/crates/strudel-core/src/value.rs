@@ -45,6 +45,19 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Convert to plain JSON, without the enum tags of the serde encoding
+    ///
+    /// Silence (and numbers JSON can't hold, like NaN) become `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Number(n) => serde_json::Value::from(*n),
+            Value::String(s) => serde_json::Value::from(s.as_str()),
+            Value::Bool(b) => serde_json::Value::from(*b),
+            Value::List(list) => list.iter().map(Value::to_json).collect(),
+            Value::Silence => serde_json::Value::Null,
+        }
+    }
 }
 
 impl fmt::Display for Value {
@@ -0,0 +1,125 @@
+//! Arrangement specs for stitching mini notation sections into a song
+//!
+//! An arrangement is a JSON list of sections, each playing a mini notation
+//! pattern for a number of cycles:
+//! ```json
+//! [
+//!   { "cycles": 4, "pattern": "bd sd bd sd" },
+//!   { "cycles": 2, "pattern": "bd*2 cp" }
+//! ]
+//! ```
+
+use crate::error::{ParseError, Result};
+use crate::evaluator::evaluate;
+use crate::parser::parse;
+use serde::{Deserialize, Serialize};
+use strudel_core::{arrange, Pattern};
+
+/// A single section of an arrangement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Section {
+    /// Number of cycles the section plays for
+    pub cycles: usize,
+    /// Mini notation pattern for the section
+    pub pattern: String,
+}
+
+/// Parse an arrangement spec from JSON
+pub fn parse_arrangement(json: &str) -> Result<Vec<Section>> {
+    serde_json::from_str(json)
+        .map_err(|e| ParseError::custom(format!("Invalid arrangement spec: {}", e), None))
+}
+
+/// Evaluate an arrangement into a single looping pattern
+pub fn evaluate_arrangement(sections: &[Section]) -> Result<Pattern> {
+    let patterns = sections
+        .iter()
+        .map(|section| {
+            let ast = parse(&section.pattern)?;
+            Ok((section.cycles, evaluate(&ast)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(arrange(patterns))
+}
+
+/// Render an arrangement as Strudel code using `arrange`
+///
+/// Patterns are quoted as JSON strings, which are also valid JavaScript
+/// string literals, so quotes and backslashes inside them are escaped.
+pub fn arrangement_to_strudel(sections: &[Section]) -> String {
+    let parts: Vec<String> = sections
+        .iter()
+        .map(|section| {
+            let pattern =
+                serde_json::to_string(&section.pattern).expect("strings always serialize to JSON");
+            format!("[{}, {}]", section.cycles, pattern)
+        })
+        .collect();
+
+    format!("arrange({})", parts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strudel_core::{Fraction, State, TimeSpan, Value};
+
+    const SPEC: &str = r#"[
+        { "cycles": 1, "pattern": "bd sd" },
+        { "cycles": 2, "pattern": "hh hh hh" }
+    ]"#;
+
+    fn values_in(pattern: &Pattern, cycle: i64) -> Vec<Value> {
+        let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+        pattern.query(State::new(span)).into_iter().map(|h| h.value).collect()
+    }
+
+    #[test]
+    fn test_parse_arrangement() {
+        let sections = parse_arrangement(SPEC).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].cycles, 1);
+        assert_eq!(sections[1].pattern, "hh hh hh");
+    }
+
+    #[test]
+    fn test_parse_arrangement_invalid() {
+        assert!(parse_arrangement("{ \"cycles\": 1 }").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_arrangement() {
+        let sections = parse_arrangement(SPEC).unwrap();
+        let pattern = evaluate_arrangement(&sections).unwrap();
+
+        let bd_sd = vec![Value::String("bd".into()), Value::String("sd".into())];
+        let hats = vec![Value::String("hh".into()); 3];
+
+        assert_eq!(values_in(&pattern, 0), bd_sd);
+        assert_eq!(values_in(&pattern, 1), hats);
+        assert_eq!(values_in(&pattern, 2), hats);
+        assert_eq!(values_in(&pattern, 3), bd_sd);
+    }
+
+    #[test]
+    fn test_arrangement_to_strudel() {
+        let sections = parse_arrangement(SPEC).unwrap();
+        assert_eq!(
+            arrangement_to_strudel(&sections),
+            "arrange([1, \"bd sd\"], [2, \"hh hh hh\"])"
+        );
+    }
+
+    #[test]
+    fn test_arrangement_to_strudel_escapes_patterns() {
+        let sections = vec![Section {
+            cycles: 1,
+            pattern: r#"bd "sd" \ cp"#.to_string(),
+        }];
+        assert_eq!(
+            arrangement_to_strudel(&sections),
+            r#"arrange([1, "bd \"sd\" \\ cp"])"#
+        );
+    }
+}
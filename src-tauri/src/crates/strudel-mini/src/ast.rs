@@ -35,6 +35,8 @@ pub enum AtomValue {
     Number(f64),
     String(String),
     Silence, // ~ or -
+    /// `run(n)`: the numbers 0 to n-1, one per step
+    Run(usize),
 }
 
 impl AtomNode {
@@ -98,6 +100,7 @@ pub enum Alignment {
     Rand,             // random choice (pipe-separated)
     Polymeter,        // polymeter ({})
     PolymeterSlowcat, // slow sequence (<>)
+    RandSlowcat,      // shuffled choice per cycle (<a | b>)
     Feet,             // dot operator (.)
 }
 
@@ -164,9 +167,17 @@ pub enum SliceOp {
     Tail {
         element: Box<Ast>,
     },
+    /// Sample index on a string atom, e.g. `bd:3`
+    SampleIndex {
+        index: usize,
+    },
     Range {
         element: Box<Ast>,
     },
+    /// Hold each cycle for several cycles, e.g. `<a b>$2`
+    RepeatCycles {
+        amount: usize,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -203,7 +214,12 @@ pub enum OperatorType {
     Struct,
     Shift,
     Bjorklund,
+    EuclidInv,
+    EuclidFull,
+    DegradeBy,
+    UndegradeBy,
     Target,
+    Bank,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -216,6 +232,17 @@ pub enum OperatorArgs {
         step: i64,
         rotation: Option<i64>,
     },
+    EuclidFull {
+        pulse: i64,
+        step: i64,
+        rotation: Option<i64>,
+        off: Box<Ast>,
+    },
+    /// Probability of removing each event, and the seed deciding which
+    DegradeBy {
+        amount: f64,
+        seed: Option<u64>,
+    },
 }
 
 /// Command - control commands
@@ -241,6 +268,28 @@ pub enum CommandType {
     Setcps,
     Setbpm,
     Hush,
+    /// Base seed for the program's randomness, e.g. `seed 42`
+    Seed,
+}
+
+/// Serialize an AST without its source spans, to compare trees parsed from
+/// differently spaced or formatted text
+#[cfg(test)]
+pub(crate) fn structure(ast: &Ast) -> serde_json::Value {
+    fn strip_spans(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.remove("span");
+                map.values_mut().for_each(strip_spans);
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(strip_spans),
+            _ => {}
+        }
+    }
+
+    let mut value = serde_json::to_value(ast).unwrap();
+    strip_spans(&mut value);
+    value
 }
 
 #[cfg(test)]
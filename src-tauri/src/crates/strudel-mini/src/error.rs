@@ -81,6 +81,18 @@ impl ParseError {
             ParseError::Custom { span, .. } => *span,
         }
     }
+
+    /// 1-based line and column of the error in `source`, if it has a location
+    ///
+    /// Running out of input is reported at the end of the source.
+    pub fn line_col(&self, source: &str) -> Option<(usize, usize)> {
+        match self {
+            ParseError::UnexpectedEof { .. } => {
+                Some(Span::new(source.len(), source.len()).line_col(source))
+            }
+            _ => self.span().map(|span| span.line_col(source)),
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -3,7 +3,8 @@
 /// Converts parsed AST nodes into executable strudel-core patterns
 use crate::ast::*;
 use crate::error::{ParseError, Result};
-use strudel_core::{choose, choose_weighted, fastcat, polymeter, pure, silence, slowcat, stack, Fraction, Pattern, State, TimeSpan, Value};
+use strudel_core::{choose, choose_cycles, choose_weighted, fastcat, polymeter, pure, run, silence, stack, time_cat, Chord, Fraction, Pattern, State, TimeSpan, Value};
+use strudel_core::random::mix_seed;
 
 /// Evaluate an AST node into a Pattern
 pub fn evaluate(ast: &Ast) -> Result<Pattern> {
@@ -16,13 +17,180 @@ pub fn evaluate(ast: &Ast) -> Result<Pattern> {
     }
 }
 
+/// Result of evaluating a program: its pattern and any commands it ran
+pub struct Evaluation {
+    /// The program's patterns, stacked; silence if it was hushed
+    pub pattern: Pattern,
+    /// Tempo in cycles per second set by `setcps` or `setbpm`, if any
+    pub cps: Option<f64>,
+    /// Whether the program ran `hush`
+    pub hushed: bool,
+}
+
+/// Evaluate a list of statements, applying commands instead of discarding them
+///
+/// The last `setcps`/`setbpm` sets the tempo, and `hush` silences the
+/// program. The last `seed` is mixed into every random choice and degrade,
+/// wherever it appears. The remaining patterns play together.
+pub fn evaluate_with_tempo(statements: &[Ast]) -> Result<Evaluation> {
+    let mut patterns = Vec::new();
+    let mut cps = None;
+    let mut hushed = false;
+
+    let seed = statements
+        .iter()
+        .rev()
+        .find_map(|statement| match statement {
+            Ast::Command(cmd) if cmd.cmd_type == CommandType::Seed => cmd.value,
+            _ => None,
+        });
+
+    for statement in statements {
+        match statement {
+            Ast::Command(cmd) => match cmd.cmd_type {
+                CommandType::Setcps | CommandType::Setbpm => {
+                    if let Some(value) = cmd.value {
+                        // A stopped or backwards clock never reaches the next cycle
+                        if !value.is_finite() || value <= 0.0 {
+                            return Err(ParseError::custom(
+                                "Tempo must be a positive number",
+                                Some(cmd.span),
+                            ));
+                        }
+                        cps = Some(value);
+                    }
+                }
+                CommandType::Hush => hushed = true,
+                CommandType::Seed => {}
+            },
+            _ => match seed {
+                Some(base) => patterns.push(evaluate(&reseed(statement, base as u64))?),
+                None => patterns.push(evaluate(statement)?),
+            },
+        }
+    }
+
+    let pattern = if hushed || patterns.is_empty() {
+        silence()
+    } else if patterns.len() == 1 {
+        patterns.remove(0)
+    } else {
+        stack(patterns)
+    };
+
+    Ok(Evaluation {
+        pattern,
+        cps,
+        hushed,
+    })
+}
+
+/// Mix a base seed into the seed of every random choice and degrade in an AST
+fn reseed(ast: &Ast, base: u64) -> Ast {
+    let boxed = |ast: &Ast| Box::new(reseed(ast, base));
+
+    match ast {
+        Ast::Pattern(pattern) => Ast::Pattern(PatternNode {
+            children: pattern
+                .children
+                .iter()
+                .map(|child| reseed(child, base))
+                .collect(),
+            seed: pattern.seed.map(|seed| mix_seed(seed, base)),
+            steps_per_cycle: pattern.steps_per_cycle.as_deref().map(boxed),
+            ..pattern.clone()
+        }),
+        Ast::Element(element) => Ast::Element(ElementNode {
+            source: boxed(&element.source),
+            ops: element
+                .ops
+                .iter()
+                .map(|op| match op {
+                    SliceOp::DegradeBy { amount, seed } => SliceOp::DegradeBy {
+                        amount: *amount,
+                        seed: mix_seed(*seed, base),
+                    },
+                    SliceOp::Stretch { amount, op_type } => SliceOp::Stretch {
+                        amount: boxed(amount),
+                        op_type: *op_type,
+                    },
+                    SliceOp::Bjorklund {
+                        pulse,
+                        step,
+                        rotation,
+                    } => SliceOp::Bjorklund {
+                        pulse: boxed(pulse),
+                        step: boxed(step),
+                        rotation: rotation.as_deref().map(boxed),
+                    },
+                    SliceOp::Tail { element } => SliceOp::Tail {
+                        element: boxed(element),
+                    },
+                    SliceOp::Range { element } => SliceOp::Range {
+                        element: boxed(element),
+                    },
+                    SliceOp::Replicate { .. }
+                    | SliceOp::SampleIndex { .. }
+                    | SliceOp::RepeatCycles { .. } => op.clone(),
+                })
+                .collect(),
+            ..element.clone()
+        }),
+        Ast::Operator(op) => Ast::Operator(OperatorNode {
+            args: match &op.args {
+                OperatorArgs::Pattern(pattern) => OperatorArgs::Pattern(boxed(pattern)),
+                OperatorArgs::EuclidFull {
+                    pulse,
+                    step,
+                    rotation,
+                    off,
+                } => OperatorArgs::EuclidFull {
+                    pulse: *pulse,
+                    step: *step,
+                    rotation: *rotation,
+                    off: boxed(off),
+                },
+                OperatorArgs::DegradeBy { amount, seed } => OperatorArgs::DegradeBy {
+                    amount: *amount,
+                    seed: Some(mix_seed(seed.unwrap_or(0), base)),
+                },
+                OperatorArgs::Number(_)
+                | OperatorArgs::String(_)
+                | OperatorArgs::Bjorklund { .. } => op.args.clone(),
+            },
+            source: boxed(&op.source),
+            ..op.clone()
+        }),
+        Ast::Atom(_) | Ast::Command(_) => ast.clone(),
+    }
+}
+
 /// Evaluate an atom into a constant pattern
+///
+/// Events are tagged with the atom's source span (`source_start` and
+/// `source_end` metadata) so the editor can highlight what's playing.
 fn eval_atom(atom: &AtomNode) -> Result<Pattern> {
-    match &atom.value {
-        AtomValue::Number(n) => Ok(pure(Value::Number(*n)).split_queries()),
-        AtomValue::String(s) => Ok(pure(Value::String(s.clone())).split_queries()),
-        AtomValue::Silence => Ok(silence()),
-    }
+    let pattern = match &atom.value {
+        AtomValue::Number(n) => pure(Value::Number(*n)).split_queries(),
+        // Only the `root:quality` form is a chord, so names like `em` stay samples
+        AtomValue::String(s) => match Chord::parse(s).filter(|_| s.contains(':')) {
+            // Chord names like `c:maj7` play all their notes at once
+            Some(chord) => stack(
+                chord
+                    .notes()
+                    .into_iter()
+                    .map(|note| pure(Value::String(note)).split_queries())
+                    .collect(),
+            ),
+            None => pure(Value::String(s.clone())).split_queries(),
+        },
+        AtomValue::Run(n) => run(*n),
+        AtomValue::Silence => return Ok(silence()),
+    };
+
+    Ok(pattern
+        .tag("source_start", Value::Number(atom.span.start as f64))
+        .tag("source_end", Value::Number(atom.span.end as f64)))
 }
 
 /// Evaluate a pattern node with alignment
@@ -35,12 +203,19 @@ fn eval_pattern(pattern: &PatternNode) -> Result<Pattern> {
     // Apply alignment
     let result = match pattern.alignment {
         Alignment::Fastcat => {
+            // Weighted steps (a@3 b, a!3 b) take a proportional share of the cycle
+            let weights: Vec<f64> = pattern.children.iter().map(extract_weight).collect();
             let child_patterns: Result<Vec<_>> = pattern
                 .children
                 .iter()
                 .map(evaluate)
                 .collect();
-            fastcat(child_patterns?)
+
+            if weights.iter().all(|&w| w == 1.0) {
+                fastcat(child_patterns?)
+            } else {
+                time_cat(weights.into_iter().zip(child_patterns?).collect())
+            }
         }
         Alignment::Stack => {
             let child_patterns: Result<Vec<_>> = pattern
@@ -51,12 +226,44 @@ fn eval_pattern(pattern: &PatternNode) -> Result<Pattern> {
             stack(child_patterns?)
         }
         Alignment::PolymeterSlowcat => {
+            // <a b c> plays one step per cycle: each comma-separated sequence
+            // is slowed down by its own step count
             let child_patterns: Result<Vec<_>> = pattern
                 .children
                 .iter()
-                .map(evaluate)
+                .map(|child| {
+                    let child_pattern = evaluate(child)?;
+                    Ok(match child_pattern.get_steps() {
+                        Some(steps) if steps > Fraction::from_int(0) => {
+                            child_pattern.slow_frac(steps)
+                        }
+                        _ => child_pattern,
+                    })
+                })
                 .collect();
-            slowcat(child_patterns?)
+            let mut child_patterns = child_patterns?;
+            if child_patterns.len() == 1 {
+                child_patterns.remove(0)
+            } else {
+                stack(child_patterns)
+            }
+        }
+        Alignment::RandSlowcat => {
+            // <a | b | c> plays every option once per round, in a shuffled
+            // order; with weights (<a@3 | b>) each cycle is a weighted choice
+            let seed = pattern.seed.unwrap_or(0);
+            let patterns_with_weights = pattern
+                .children
+                .iter()
+                .map(|child| Ok((evaluate(child)?, extract_weight(child))))
+                .collect::<Result<Vec<_>>>()?;
+
+            if patterns_with_weights.iter().any(|(_, w)| *w > 1.0) {
+                choose_weighted(patterns_with_weights, seed)
+            } else {
+                let child_patterns = patterns_with_weights.into_iter().map(|(p, _)| p).collect();
+                choose_cycles(child_patterns, seed)
+            }
         }
         Alignment::Rand => {
             let seed = pattern.seed.unwrap_or(0);
@@ -119,8 +326,7 @@ fn eval_element(element: &ElementNode) -> Result<Pattern> {
         pattern = apply_slice_op(pattern, op)?;
     }
 
-    // TODO: Apply weight
-    // Weight is used for weighted random choice, not implemented yet
+    // Weight is applied by the enclosing sequence or random choice
 
     Ok(pattern)
 }
@@ -133,8 +339,8 @@ fn apply_slice_op(pattern: Pattern, op: &SliceOp) -> Result<Pattern> {
             // Pattern-based stretch would require applicative pattern operations
             let amount_val = extract_number(amount)?;
             match op_type {
-                StretchType::Fast => Ok(pattern.fast(amount_val)),
-                StretchType::Slow => Ok(pattern.slow(amount_val)),
+                StretchType::Fast => Ok(fast_by(pattern, amount_val)),
+                StretchType::Slow => Ok(slow_by(pattern, amount_val)),
             }
         }
         SliceOp::Replicate { amount } => Ok(pattern.replicate(*amount)),
@@ -143,14 +349,28 @@ fn apply_slice_op(pattern: Pattern, op: &SliceOp) -> Result<Pattern> {
             step,
             rotation,
         } => {
-            let pulse_val = extract_number(pulse)? as usize;
-            let step_val = extract_number(step)? as usize;
-            let rotation_val = rotation
+            // Patterned arguments like `bd(<3 5>,8)` are resolved per cycle
+            if is_patterned(pulse)
+                || is_patterned(step)
+                || rotation.as_deref().is_some_and(is_patterned)
+            {
+                let rotation_pattern = rotation.as_deref().map(evaluate).transpose()?;
+                return Ok(pattern.euclid_pat(evaluate(pulse)?, evaluate(step)?, rotation_pattern));
+            }
+
+            let p = extract_number(pulse)?;
+            let s = extract_number(step)?;
+            let r = rotation
                 .as_ref()
                 .map(|r| extract_number(r).map(|v| v as usize))
                 .transpose()?;
 
-            Ok(pattern.euclid(pulse_val, step_val, rotation_val))
+            if p < 0.0 {
+                // A negative pulse plays the rests, as in `bd(-3,8)`
+                Ok(pattern.euclid_inv(-p as usize, s as usize, r))
+            } else {
+                Ok(pattern.euclid(p as usize, s as usize, r))
+            }
         }
         SliceOp::DegradeBy { amount, seed } => {
             let degrade_amount = amount.unwrap_or(0.5);
@@ -162,37 +382,25 @@ fn apply_slice_op(pattern: Pattern, op: &SliceOp) -> Result<Pattern> {
             let tail_pattern = evaluate(element)?;
             Ok(pattern.tail(tail_pattern))
         }
+        SliceOp::SampleIndex { index } => {
+            // Sample index (bd:3) selects a sample from the bank, it doesn't change the value
+            Ok(pattern.n(*index))
+        }
         SliceOp::Range { element } => {
             // Range operator: start..end expands to [start, start+1, ..., end]
             // Extract start from the source pattern (must be a number)
             let start = extract_number_from_pattern(&pattern)?;
             let end = extract_number(element)?;
 
-            // Create a sequence of numbers from start to end (inclusive)
-            let mut numbers = Vec::new();
-            if start <= end {
-                let mut current = start;
-                while current <= end {
-                    numbers.push(current);
-                    current += 1.0;
-                }
-            } else {
-                // Descending range
-                let mut current = start;
-                while current >= end {
-                    numbers.push(current);
-                    current -= 1.0;
-                }
-            }
-
             // Convert to patterns and fastcat them
-            let patterns: Vec<Pattern> = numbers
+            let patterns: Vec<Pattern> = range_values(start, end)
                 .into_iter()
                 .map(|n| pure(Value::Number(n)).split_queries())
                 .collect();
 
             Ok(fastcat(patterns))
         }
+        SliceOp::RepeatCycles { amount } => Ok(pattern.repeat_cycles(*amount)),
     }
 }
 
@@ -202,14 +410,14 @@ fn eval_operator(op: &OperatorNode) -> Result<Pattern> {
 
     match op.op_type {
         OperatorType::Fast => match &op.args {
-            OperatorArgs::Number(n) => Ok(source_pattern.fast(*n)),
+            OperatorArgs::Number(n) => Ok(fast_by(source_pattern, *n)),
             _ => Err(ParseError::custom(
                 "Fast operator requires numeric argument",
                 Some(op.span),
             )),
         },
         OperatorType::Slow => match &op.args {
-            OperatorArgs::Number(n) => Ok(source_pattern.slow(*n)),
+            OperatorArgs::Number(n) => Ok(slow_by(source_pattern, *n)),
             _ => Err(ParseError::custom(
                 "Slow operator requires numeric argument",
                 Some(op.span),
@@ -230,6 +438,56 @@ fn eval_operator(op: &OperatorNode) -> Result<Pattern> {
                 Some(op.span),
             )),
         },
+        OperatorType::EuclidInv => match &op.args {
+            OperatorArgs::Bjorklund {
+                pulse,
+                step,
+                rotation,
+            } => Ok(source_pattern.euclid_inv(
+                *pulse as usize,
+                *step as usize,
+                rotation.map(|r| r as usize),
+            )),
+            _ => Err(ParseError::custom(
+                "EuclidInv operator requires pulse, step, and optional rotation",
+                Some(op.span),
+            )),
+        },
+        OperatorType::EuclidFull => match &op.args {
+            OperatorArgs::EuclidFull {
+                pulse,
+                step,
+                rotation,
+                off,
+            } => Ok(source_pattern.euclid_full(
+                *pulse as usize,
+                *step as usize,
+                rotation.map(|r| r as usize),
+                evaluate(off)?,
+            )),
+            _ => Err(ParseError::custom(
+                "EuclidFull operator requires pulse, step, optional rotation, and an off pattern",
+                Some(op.span),
+            )),
+        },
+        OperatorType::DegradeBy => match &op.args {
+            OperatorArgs::DegradeBy { amount, seed } => {
+                Ok(source_pattern.degrade_by(*amount, seed.unwrap_or(0)))
+            }
+            _ => Err(ParseError::custom(
+                "DegradeBy operator requires an amount and optional seed",
+                Some(op.span),
+            )),
+        },
+        OperatorType::UndegradeBy => match &op.args {
+            OperatorArgs::DegradeBy { amount, seed } => {
+                Ok(source_pattern.undegrade_by(*amount, seed.unwrap_or(0)))
+            }
+            _ => Err(ParseError::custom(
+                "UndegradeBy operator requires an amount and optional seed",
+                Some(op.span),
+            )),
+        },
         OperatorType::Scale => match &op.args {
             OperatorArgs::String(scale_name) => Ok(source_pattern.scale(scale_name.clone())),
             _ => Err(ParseError::custom(
@@ -262,6 +520,13 @@ fn eval_operator(op: &OperatorNode) -> Result<Pattern> {
                 Some(op.span),
             )),
         },
+        OperatorType::Bank => match &op.args {
+            OperatorArgs::String(bank_name) => Ok(source_pattern.bank(bank_name.clone())),
+            _ => Err(ParseError::custom(
+                "Bank operator requires string argument",
+                Some(op.span),
+            )),
+        },
     }
 }
 
@@ -286,6 +551,70 @@ fn extract_number(ast: &Ast) -> Result<f64> {
     }
 }
 
+/// Whether an argument is a pattern (like `<3 5>` or `3*2`) rather than a single atom
+fn is_patterned(ast: &Ast) -> bool {
+    match ast {
+        Ast::Element(element) => {
+            !element.ops.is_empty() || matches!(element.source.as_ref(), Ast::Pattern(_))
+        }
+        Ast::Pattern(_) | Ast::Operator(_) => true,
+        Ast::Atom(_) | Ast::Command(_) => false,
+    }
+}
+
+/// Largest denominator considered when recovering an exact ratio from a float
+const MAX_RATIO_DENOMINATOR: i64 = 64;
+
+/// Helper function to recover an exact fraction from a float that is a clean ratio
+/// (e.g. 1.5 -> 3/2, 0.3333333333333333 -> 1/3). Returns None for anything else.
+fn clean_ratio(n: f64) -> Option<Fraction> {
+    (1..=MAX_RATIO_DENOMINATOR).find_map(|d| {
+        let scaled = n * d as f64;
+        let rounded = scaled.round();
+        if (scaled - rounded).abs() < 1e-9 {
+            Some(Fraction::new(rounded as i64, d))
+        } else {
+            None
+        }
+    })
+}
+
+/// Speed up a pattern, using exact fractions when the factor is a clean ratio
+fn fast_by(pattern: Pattern, factor: f64) -> Pattern {
+    match clean_ratio(factor) {
+        Some(ratio) => pattern.fast_frac(ratio),
+        None => pattern.fast(factor),
+    }
+}
+
+/// Slow down a pattern, using exact fractions when the factor is a clean ratio
+fn slow_by(pattern: Pattern, factor: f64) -> Pattern {
+    match clean_ratio(factor) {
+        Some(ratio) => pattern.slow_frac(ratio),
+        None => pattern.slow(factor),
+    }
+}
+
+/// Expand `start .. end` into the numbers from start to end (inclusive), one apart
+///
+/// Clean ratios are stepped as exact fractions, so values far into a range
+/// like `0.1 .. 20` don't pick up floating point error from repeated adding.
+fn range_values(start: f64, end: f64) -> Vec<f64> {
+    let step = if start <= end { 1 } else { -1 };
+    match (clean_ratio(start), clean_ratio(end)) {
+        (Some(start), Some(end)) => {
+            let count = (end - start).abs().floor().numerator;
+            (0..=count)
+                .map(|i| (start + Fraction::from_int(i * step)).to_float())
+                .collect()
+        }
+        _ => {
+            let count = (end - start).abs().floor() as i64;
+            (0..=count).map(|i| start + (i * step) as f64).collect()
+        }
+    }
+}
+
 /// Helper function to extract a number from a Pattern by querying it
 /// Returns the numeric value if the pattern produces exactly one numeric event
 fn extract_number_from_pattern(pattern: &Pattern) -> Result<f64> {
@@ -405,6 +734,16 @@ mod tests {
         assert_eq!(haps.len(), 2); // Should have 2 events in one cycle
     }
 
+    #[test]
+    fn test_eval_fast_by_zero() {
+        // Stopped subsequences play nothing and leave no steps to line up
+        for source in ["[a b]*0 [c d]*0", "{a b}%0*0 [a b c d]*0"] {
+            let pattern = evaluate(&parse(source).unwrap()).unwrap();
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            assert!(pattern.query(state).is_empty(), "{}", source);
+        }
+    }
+
     #[test]
     fn test_eval_with_slow() {
         let ast = parse("bd/2").unwrap();
@@ -416,6 +755,39 @@ mod tests {
         assert_eq!(haps.len(), 1); // Should have 1 event over 2 cycles
     }
 
+    #[test]
+    fn test_clean_ratio() {
+        assert_eq!(clean_ratio(1.5), Some(Fraction::new(3, 2)));
+        assert_eq!(clean_ratio(1.0 / 3.0), Some(Fraction::new(1, 3)));
+        assert_eq!(clean_ratio(4.0), Some(Fraction::from_int(4)));
+        assert_eq!(clean_ratio(std::f64::consts::PI), None);
+    }
+
+    #[test]
+    fn test_eval_fractional_slow_exact() {
+        let ast = parse("[bd sd]/1.5").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(3)));
+        let onsets: Vec<_> = pattern
+            .query(state)
+            .into_iter()
+            .filter(|h| h.has_onset())
+            .map(|h| h.part.begin)
+            .collect();
+
+        // Each step lasts exactly 3/4 of a cycle
+        assert_eq!(
+            onsets,
+            vec![
+                Fraction::from_int(0),
+                Fraction::new(3, 4),
+                Fraction::new(3, 2),
+                Fraction::new(9, 4),
+            ]
+        );
+    }
+
     #[test]
     fn test_eval_with_replicate() {
         let ast = parse("bd!3").unwrap();
@@ -436,7 +808,319 @@ mod tests {
         let haps = pattern.query(state);
 
         // Should have 3 events distributed across 8 steps
-        assert!(haps.len() <= 3);
+        assert_eq!(haps.len(), 3);
+        assert_eq!(haps[1].whole.unwrap().begin, Fraction::new(3, 8));
+    }
+
+    #[test]
+    fn test_eval_euclidean_patterned_pulse() {
+        let ast = parse("bd(<3 5>,8)").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let cycle0 = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let cycle1 = State::new(TimeSpan::new(Fraction::from_int(1), Fraction::from_int(2)));
+
+        assert_eq!(pattern.query(cycle0).len(), 3);
+        assert_eq!(pattern.query(cycle1).len(), 5);
+    }
+
+    #[test]
+    fn test_eval_euclidean_non_numeric_argument() {
+        // A typo is an error rather than a silent fallback to silence
+        assert!(evaluate(&parse("bd(x,8)").unwrap()).is_err());
+        assert!(evaluate(&parse("bd(3,x)").unwrap()).is_err());
+        assert!(evaluate(&parse("bd(3,8,x)").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_eval_euclidean_patterned_rotation() {
+        let rotated = evaluate(&parse("bd(3,8,<0 1>)").unwrap()).unwrap();
+        let plain = evaluate(&parse("bd(3,8)").unwrap()).unwrap();
+        let onsets = |pattern: &Pattern, cycle: i64| -> Vec<Fraction> {
+            let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+            pattern
+                .query(State::new(span))
+                .iter()
+                .map(|h| h.whole.unwrap().begin)
+                .collect()
+        };
+
+        assert_eq!(onsets(&rotated, 0), onsets(&plain, 0));
+        assert_ne!(onsets(&rotated, 1), onsets(&plain, 1));
+    }
+
+    #[test]
+    fn test_eval_euclidean_inverse() {
+        let onsets = |source: &str| -> Vec<Fraction> {
+            let pattern = evaluate(&parse(source).unwrap()).unwrap();
+            let span = TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1));
+            pattern
+                .query(State::new(span))
+                .iter()
+                .map(|h| h.whole.unwrap().begin)
+                .collect()
+        };
+
+        // A negative pulse plays the five steps that bd(3,8) rests on
+        let steps: Vec<Fraction> = [1, 2, 4, 5, 7]
+            .iter()
+            .map(|&i| Fraction::new(i, 8))
+            .collect();
+        assert_eq!(onsets("bd(-3,8)"), steps);
+        assert_eq!(onsets("bd(<-3 3>,8)"), steps);
+    }
+
+    #[test]
+    fn test_eval_run() {
+        let cycle = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let pattern = evaluate(&parse("run(4)").unwrap()).unwrap();
+        let haps = pattern.query(cycle.clone());
+
+        assert_eq!(haps.len(), 4);
+        for (i, hap) in haps.iter().enumerate() {
+            let begin = Fraction::new(i as i64, 4);
+            let end = begin + Fraction::new(1, 4);
+            assert_eq!(hap.value, Value::Number(i as f64));
+            assert_eq!(hap.whole, Some(TimeSpan::new(begin, end)));
+        }
+
+        // With a step count, `run` is still an ordinary Euclidean rhythm
+        let pattern = evaluate(&parse("run(3,8)").unwrap()).unwrap();
+        let haps = pattern.query(cycle);
+        assert_eq!(haps.len(), 3);
+        assert!(haps.iter().all(|h| h.value == Value::String("run".into())));
+    }
+
+    #[test]
+    fn test_euclid_full_operator() {
+        use crate::ast::*;
+        use crate::span::Span;
+
+        // euclidFull(3,8,hh) bd
+        let ast = Ast::Operator(OperatorNode::new(
+            OperatorType::EuclidFull,
+            OperatorArgs::EuclidFull {
+                pulse: 3,
+                step: 8,
+                rotation: None,
+                off: Box::new(parse("hh").unwrap()),
+            },
+            parse("bd").unwrap(),
+            Span::new(0, 20),
+        ));
+        assert_eq!(crate::format(&ast), "euclidFull(3,8,hh) bd");
+
+        let pattern = evaluate(&ast).unwrap();
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let mut haps = pattern.query(state);
+        haps.sort_by_key(|h| h.whole.unwrap().begin);
+
+        let values: Vec<String> = haps.iter().map(|h| h.value.to_string()).collect();
+        assert_eq!(values, vec!["bd", "hh", "hh", "bd", "hh", "hh", "bd", "hh"]);
+    }
+
+    #[test]
+    fn test_euclid_operators_roundtrip() {
+        fn values(input: &str) -> Vec<String> {
+            let pattern = evaluate(&parse(input).unwrap()).unwrap();
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            let mut haps = pattern.query(state);
+            haps.sort_by_key(|h| h.whole.unwrap().begin);
+            haps.iter().map(|h| h.value.to_string()).collect()
+        }
+
+        let ast = parse("euclidFull(3,8,hh) bd").unwrap();
+        let formatted = crate::format(&ast);
+        assert_eq!(formatted, "euclidFull(3,8,hh) bd");
+        assert_eq!(
+            values(&formatted),
+            vec!["bd", "hh", "hh", "bd", "hh", "hh", "bd", "hh"]
+        );
+
+        let ast = parse("euclidInv(3,8) bd").unwrap();
+        assert_eq!(crate::format(&ast), "euclidInv(3,8) bd");
+        assert_eq!(values("euclidInv(3,8) bd"), values("bd(-3,8)"));
+        assert_eq!(values("euclidInv(3,8,2) bd"), values("bd(-3,8,2)"));
+
+        assert!(parse("euclidInv(3.5,8) bd").is_err());
+        assert!(parse("euclidFull(3,8) bd").is_err());
+    }
+
+    #[test]
+    fn test_degrade_operators_partition_events() {
+        let onsets = |input: &str| -> Vec<(Fraction, String)> {
+            let pattern = evaluate(&parse(input).unwrap()).unwrap();
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(4)));
+            let mut onsets: Vec<(Fraction, String)> = pattern
+                .query(state)
+                .into_iter()
+                .map(|h| (h.whole.unwrap().begin, h.value.to_string()))
+                .collect();
+            onsets.sort_by_key(|(begin, _)| *begin);
+            onsets
+        };
+
+        // Kept and dropped events are complements, so every step plays once
+        let split = onsets("degradeBy(0.5) hh*16, undegradeBy(0.5) oh*16");
+        let steps: Vec<Fraction> = split.iter().map(|(begin, _)| *begin).collect();
+        let all: Vec<Fraction> = onsets("hh*16").iter().map(|(begin, _)| *begin).collect();
+        assert_eq!(steps, all);
+        assert!(split.iter().any(|(_, value)| value == "hh"));
+        assert!(split.iter().any(|(_, value)| value == "oh"));
+
+        assert_eq!(
+            onsets("degradeBy(0.3,7) hh*16, undegradeBy(0.3,7) hh*16"),
+            onsets("hh*16")
+        );
+        assert!(parse("degradeBy(2) hh").is_err());
+    }
+
+    #[test]
+    fn test_to_mini_parses_back_to_same_events() {
+        use strudel_core::{fastcat, pure, silence, slowcat, stack};
+
+        let s = |value: &str| pure(Value::String(value.into()));
+        let events = |pattern: &Pattern| {
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(4)));
+            let mut haps: Vec<_> = pattern
+                .query(state)
+                .into_iter()
+                .map(|h| (h.whole, h.part, h.value.to_string()))
+                .collect();
+            haps.sort_by_key(|(whole, part, _)| (whole.map(|w| w.begin), part.begin));
+            haps
+        };
+
+        let patterns = [
+            fastcat(vec![s("bd"), fastcat(vec![s("sd"), s("hh")]), silence()]),
+            stack(vec![fastcat(vec![s("bd"), s("sd")]), s("hh")]),
+            slowcat(vec![s("bd"), fastcat(vec![s("sd"), s("cp")])]),
+            fastcat(vec![s("bd"), s("sd")]).fast(2.0),
+            fastcat(vec![s("bd"), s("sd")]).slow(2.0),
+            s("bd").euclid(3, 8, Some(2)),
+            fastcat(vec![s("bd"), s("sd"), s("hh")]).rev(),
+        ];
+
+        for pattern in patterns {
+            let mini = pattern.to_mini().unwrap();
+            let parsed = evaluate(&parse(&mini).unwrap()).unwrap();
+            assert_eq!(events(&parsed), events(&pattern), "{}", mini);
+        }
+    }
+
+    #[test]
+    fn test_bank_operator() {
+        use crate::ast::*;
+        use crate::span::Span;
+
+        // bank("x") bd:3 sd
+        let ast = Ast::Operator(OperatorNode::new(
+            OperatorType::Bank,
+            OperatorArgs::String("x".to_string()),
+            parse("bd:3 sd").unwrap(),
+            Span::new(0, 20),
+        ));
+
+        let pattern = evaluate(&ast).unwrap();
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps[0].value, Value::String("x_bd".into()));
+        assert_eq!(haps[0].context.metadata.get("n"), Some(&Value::Number(3.0)));
+        assert_eq!(haps[1].value, Value::String("x_sd".into()));
+    }
+
+    #[test]
+    fn test_eval_slow_sequence() {
+        let ast = parse("<bd sd cp>").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        for (cycle, expected) in ["bd", "sd", "cp", "bd"].iter().enumerate() {
+            let span = TimeSpan::new(
+                Fraction::from_int(cycle as i64),
+                Fraction::from_int(cycle as i64 + 1),
+            );
+            let haps = pattern.query(State::new(span));
+            assert_eq!(haps.len(), 1, "cycle {}", cycle);
+            assert_eq!(haps[0].value, Value::String(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_eval_alternation() {
+        // Values heard in each of the first few cycles, with their wholes
+        let cycles = |source: &str, count: i64| {
+            let pattern = evaluate(&parse(source).unwrap()).unwrap();
+            (0..count)
+                .map(|cycle| {
+                    let span = TimeSpan::from_ints(cycle, cycle + 1);
+                    let mut haps = pattern.query(State::new(span));
+                    haps.sort_by_key(|hap| hap.value.to_string());
+                    haps.iter()
+                        .map(|hap| (hap.value.to_string(), hap.whole.unwrap()))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let cycle = |n: i64| TimeSpan::from_ints(n, n + 1);
+
+        // A nested alternation advances once each time the outer one reaches it
+        assert_eq!(
+            cycles("<a <b c>>", 4),
+            vec![
+                vec![("a".to_string(), cycle(0))],
+                vec![("b".to_string(), cycle(1))],
+                vec![("a".to_string(), cycle(2))],
+                vec![("c".to_string(), cycle(3))],
+            ]
+        );
+
+        // A weighted step holds for that many cycles, as one event
+        let held = TimeSpan::from_ints(0, 3);
+        assert_eq!(
+            cycles("<a@3 b>", 4),
+            vec![
+                vec![("a".to_string(), held)],
+                vec![("a".to_string(), held)],
+                vec![("a".to_string(), held)],
+                vec![("b".to_string(), cycle(3))],
+            ]
+        );
+
+        // Comma-separated sequences alternate side by side, each at its own length
+        assert_eq!(
+            cycles("<a b, c d e>", 3),
+            vec![
+                vec![("a".to_string(), cycle(0)), ("c".to_string(), cycle(0))],
+                vec![("b".to_string(), cycle(1)), ("d".to_string(), cycle(1))],
+                vec![("a".to_string(), cycle(2)), ("e".to_string(), cycle(2))],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_repeat_cycles() {
+        use strudel_core::slowcat;
+
+        let events = |pattern: &Pattern| -> Vec<(Option<TimeSpan>, Value)> {
+            let span = TimeSpan::new(Fraction::from_int(0), Fraction::from_int(6));
+            let mut haps = pattern.query(State::new(span));
+            haps.sort_by_key(|h| h.part.begin);
+            haps.into_iter().map(|h| (h.whole, h.value)).collect()
+        };
+        let bd = pure(Value::String("bd".into()));
+        let sd = pure(Value::String("sd".into()));
+        let hh = pure(Value::String("hh".into()));
+
+        // Each cycle of the alternation is held for two cycles
+        let pattern = evaluate(&parse("<bd sd>$2").unwrap()).unwrap();
+        let direct = slowcat(vec![bd.clone(), sd.clone()]).repeat_cycles(2);
+        assert_eq!(events(&pattern), events(&direct));
+
+        // Within a sequence, only the marked step is held
+        let pattern = evaluate(&parse("<bd sd>$3 hh").unwrap()).unwrap();
+        let direct = fastcat(vec![slowcat(vec![bd, sd]).repeat_cycles(3), hh]);
+        assert_eq!(events(&pattern), events(&direct));
     }
 
     #[test]
@@ -459,6 +1143,16 @@ mod tests {
         assert!(values.iter().any(|v| matches!(v, Value::String(s) if s == "hh")));
     }
 
+    #[test]
+    fn test_eval_polymeter_steps() {
+        // Layers of 2 and 3 steps line up on a grid of 6
+        let pattern = evaluate(&parse("{bd sd, a b c}").unwrap()).unwrap();
+        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(6)));
+
+        let pattern = evaluate(&parse("bd sd hh").unwrap()).unwrap();
+        assert_eq!(pattern.get_steps(), Some(Fraction::from_int(3)));
+    }
+
     #[test]
     fn test_eval_rand() {
         let ast = parse("bd | sd | cp").unwrap();
@@ -607,6 +1301,107 @@ mod tests {
         assert!(sd_count < 40, "sd appeared {} times, expected < 40", sd_count);
     }
 
+    #[test]
+    fn test_eval_rand_slowcat_plays_each_per_round() {
+        let ast = parse("<a | b | c>").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        for round in 0..2 {
+            let mut values: Vec<String> = (round * 3..round * 3 + 3)
+                .map(|cycle| {
+                    let (begin, end) = (Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+                    let haps = pattern.query(State::new(TimeSpan::new(begin, end)));
+                    assert_eq!(haps.len(), 1);
+                    match &haps[0].value {
+                        Value::String(s) => s.clone(),
+                        other => panic!("unexpected value {:?}", other),
+                    }
+                })
+                .collect();
+            values.sort();
+            assert_eq!(values, vec!["a", "b", "c"]);
+        }
+    }
+
+    #[test]
+    fn test_eval_rand_slowcat_weighted() {
+        let ast = parse("<a@3 | b>").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let a_count = (0..100)
+            .filter(|&cycle| {
+                let span = TimeSpan::new(Fraction::from_int(cycle), Fraction::from_int(cycle + 1));
+                let haps = pattern.query(State::new(span));
+                matches!(&haps[..], [hap] if hap.value == Value::String("a".into()))
+            })
+            .count();
+        assert!(a_count > 60, "a appeared {} times, expected > 60", a_count);
+        assert!(a_count < 90, "a appeared {} times, expected < 90", a_count);
+    }
+
+    #[test]
+    fn test_eval_weighted_sequence() {
+        let wholes = |input: &str| -> Vec<(Fraction, Fraction)> {
+            let pattern = evaluate(&parse(input).unwrap()).unwrap();
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            let mut haps = pattern.query(state);
+            haps.sort_by_key(|h| h.part.begin);
+            haps.iter()
+                .map(|h| (h.whole.unwrap().begin, h.whole.unwrap().end))
+                .collect()
+        };
+        let quarter = Fraction::new(1, 4);
+        let three_quarters = Fraction::new(3, 4);
+
+        // `a` takes three quarters of the cycle, however its weight is written
+        let expected = vec![
+            (Fraction::from_int(0), three_quarters),
+            (three_quarters, Fraction::from_int(1)),
+        ];
+        assert_eq!(wholes("a@3 b"), expected);
+        assert_eq!(wholes("a _ _ b"), expected);
+
+        // Replicated steps each take their own step
+        assert_eq!(
+            wholes("a!3 b"),
+            vec![
+                (Fraction::from_int(0), quarter),
+                (quarter, Fraction::new(1, 2)),
+                (Fraction::new(1, 2), three_quarters),
+                (three_quarters, Fraction::from_int(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_weighted_fastcat_wholes() {
+        let whole_of = |input: &str, value: &str| -> TimeSpan {
+            let pattern = evaluate(&parse(input).unwrap()).unwrap();
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+            pattern
+                .query(state)
+                .into_iter()
+                .find(|h| h.value == Value::String(value.into()))
+                .and_then(|h| h.whole)
+                .unwrap()
+        };
+
+        assert_eq!(
+            whole_of("bd@3 sd", "bd"),
+            TimeSpan::new(Fraction::from_int(0), Fraction::new(3, 4))
+        );
+        assert_eq!(
+            whole_of("bd@3 sd", "sd"),
+            TimeSpan::new(Fraction::new(3, 4), Fraction::from_int(1))
+        );
+
+        // Weights apply within their own sub-sequence
+        assert_eq!(
+            whole_of("[bd@3 sd] hh", "bd"),
+            TimeSpan::new(Fraction::from_int(0), Fraction::new(3, 8))
+        );
+    }
+
     #[test]
     fn test_extract_weight() {
         // Test weight extraction from different AST nodes
@@ -623,6 +1418,142 @@ mod tests {
         assert_eq!(weight3, 5.0);
     }
 
+    #[test]
+    fn test_eval_source_spans() {
+        let ast = parse("bd sd").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        let spans: Vec<(Option<&Value>, Option<&Value>)> = haps
+            .iter()
+            .map(|h| {
+                (
+                    h.context.metadata.get("source_start"),
+                    h.context.metadata.get("source_end"),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (Some(&Value::Number(0.0)), Some(&Value::Number(2.0))),
+                (Some(&Value::Number(3.0)), Some(&Value::Number(5.0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_chord() {
+        let ast = parse("c:maj7").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        let notes: Vec<Value> = haps.iter().map(|h| h.value.clone()).collect();
+        assert_eq!(
+            notes,
+            vec![
+                Value::String("C3".into()),
+                Value::String("E3".into()),
+                Value::String("G3".into()),
+                Value::String("B3".into()),
+            ]
+        );
+        // All chord tones sound together
+        assert!(haps.iter().all(|h| h.whole == haps[0].whole));
+    }
+
+    #[test]
+    fn test_eval_chord_sequence() {
+        let ast = parse("a:min c:dim bd").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        // Two triads and a drum hit
+        assert_eq!(haps.len(), 7);
+        assert_eq!(haps[3].value, Value::String("C3".into()));
+        assert_eq!(haps[4].value, Value::String("Eb3".into()));
+        assert_eq!(haps[5].value, Value::String("Gb3".into()));
+        assert_eq!(haps[6].value, Value::String("bd".into()));
+    }
+
+    #[test]
+    fn test_eval_compact_chord_name_is_sample() {
+        let ast = parse("em bm am Cmaj7").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let values: Vec<Value> = pattern.query(state).into_iter().map(|h| h.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                Value::String("em".into()),
+                Value::String("bm".into()),
+                Value::String("am".into()),
+                Value::String("Cmaj7".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_numeric_chord_quality_is_sample_index() {
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+
+        // A number after the colon is a sample index, even where it names a chord
+        for (source, root, index) in [("g:7", "g", 7.0), ("c:6", "c", 6.0), ("c:9", "c", 9.0)] {
+            let haps = evaluate(&parse(source).unwrap())
+                .unwrap()
+                .query(state.clone());
+            assert_eq!(haps.len(), 1, "{}", source);
+            assert_eq!(haps[0].value, Value::String(root.into()));
+            assert_eq!(
+                haps[0].context.metadata.get("n"),
+                Some(&Value::Number(index))
+            );
+        }
+
+        // The named qualities are chords
+        for (source, tones) in [("g:dom7", 4), ("c:maj6", 4), ("c:dom9", 5)] {
+            let haps = evaluate(&parse(source).unwrap())
+                .unwrap()
+                .query(state.clone());
+            assert_eq!(haps.len(), tones, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_eval_sample_index() {
+        let ast = parse("bd:3").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 1);
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+        assert_eq!(haps[0].context.metadata.get("n"), Some(&Value::Number(3.0)));
+    }
+
+    #[test]
+    fn test_eval_sample_index_in_sequence() {
+        let ast = parse("bd:1 sd:2 hh").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 3);
+        assert_eq!(haps[0].context.metadata.get("n"), Some(&Value::Number(1.0)));
+        assert_eq!(haps[1].context.metadata.get("n"), Some(&Value::Number(2.0)));
+        assert_eq!(haps[2].context.metadata.get("n"), None);
+    }
+
     #[test]
     fn test_eval_tail_simple() {
         // Test simple tail operator: a:b should concatenate a and b
@@ -862,6 +1793,37 @@ mod tests {
         assert_eq!(haps[0].value, Value::Number(3.0));
     }
 
+    #[test]
+    fn test_eval_range_fractional_start() {
+        // Every value is exactly one more than the last, with no accumulated error
+        let ast = parse("0.1 .. 20").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        assert_eq!(haps.len(), 20);
+        assert_eq!(haps[0].value, Value::Number(0.1));
+        assert_eq!(haps[19].value, Value::Number(19.1));
+    }
+
+    #[test]
+    fn test_eval_nested_subdivision_is_exact() {
+        let ast = parse("[[a b] [c d]]*7").unwrap();
+        let pattern = evaluate(&ast).unwrap();
+
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = pattern.query(state);
+
+        // 28 events, each starting and ending exactly on a multiple of 1/28
+        assert_eq!(haps.len(), 28);
+        for (i, hap) in haps.iter().enumerate() {
+            let whole = hap.whole.unwrap();
+            assert_eq!(whole.begin, Fraction::new(i as i64, 28));
+            assert_eq!(whole.end, Fraction::new(i as i64 + 1, 28));
+        }
+    }
+
     #[test]
     fn test_scale_operator_basic() {
         // Test basic scale operator with numbers
@@ -1364,4 +2326,73 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_evaluate_with_tempo() {
+        let statements = crate::parser::parse_program("setcps 0.5 bd sd").unwrap();
+        let evaluation = evaluate_with_tempo(&statements).unwrap();
+        assert_eq!(evaluation.cps, Some(0.5));
+        assert!(!evaluation.hushed);
+
+        // The pattern after the command still plays
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        let haps = evaluation.pattern.query(state);
+        assert_eq!(haps.len(), 2);
+        assert_eq!(haps[0].value, Value::String("bd".into()));
+
+        // setbpm is converted to cycles per second
+        let statements = crate::parser::parse_program("setbpm 120").unwrap();
+        assert_eq!(evaluate_with_tempo(&statements).unwrap().cps, Some(0.5));
+
+        // hush silences the program
+        let statements = crate::parser::parse_program("bd sd hush").unwrap();
+        let evaluation = evaluate_with_tempo(&statements).unwrap();
+        assert!(evaluation.hushed);
+        assert_eq!(evaluation.cps, None);
+        let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(1)));
+        assert!(evaluation.pattern.query(state).is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_rejects_stopped_tempo() {
+        for program in ["setcps 0 bd", "setcps -1 bd", "setbpm 0 bd"] {
+            let statements = crate::parser::parse_program(program).unwrap();
+            let err = evaluate_with_tempo(&statements).err().unwrap();
+            assert_eq!(err.span(), Some(statements[0].span()), "{}", program);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_with_seed() {
+        let onsets = |program: &str| {
+            let statements = crate::parser::parse_program(program).unwrap();
+            let evaluation = evaluate_with_tempo(&statements).unwrap();
+            let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(4)));
+            let mut onsets: Vec<Fraction> = evaluation
+                .pattern
+                .query(state)
+                .iter()
+                .map(|hap| hap.whole_or_part().begin)
+                .collect();
+            onsets.sort();
+            onsets
+        };
+
+        // The same seed always degrades the same events
+        let seeded = onsets("seed 1 hh*16?");
+        assert_eq!(seeded, onsets("seed 1 hh*16?"));
+        assert!(!seeded.is_empty() && seeded.len() < 64);
+
+        // A different seed, or none, drops different ones
+        assert_ne!(seeded, onsets("seed 2 hh*16?"));
+        assert_ne!(seeded, onsets("hh*16?"));
+
+        // The seed applies wherever it appears in the program
+        assert_eq!(seeded, onsets("hh*16? seed 1"));
+
+        // Seeds reach the degrade operators too
+        let degraded = onsets("seed 1 degradeBy(0.5) hh*16");
+        assert_ne!(degraded, onsets("seed 2 degradeBy(0.5) hh*16"));
+        assert_ne!(degraded, onsets("degradeBy(0.5) hh*16"));
+    }
 }
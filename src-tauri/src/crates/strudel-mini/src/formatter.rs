@@ -12,21 +12,144 @@ pub fn format(ast: &Ast) -> String {
     }
 }
 
-fn format_atom(atom: &AtomNode) -> String {
-    match &atom.value {
-        AtomValue::Number(n) => {
-            // Format number nicely
-            if n.fract() == 0.0 {
-                format!("{}", *n as i64)
-            } else {
-                format!("{}", n)
+/// Simplify an AST without changing what it plays
+///
+/// Brackets around a single step are dropped (`[[bd]] sd` becomes `bd sd`),
+/// as are operators that do nothing (`bd!1`, `bd*1`). With `compact`, runs
+/// of the same plain step are merged into replication (`bd bd bd` becomes `bd!3`).
+pub fn tidy(ast: &Ast, compact: bool) -> Ast {
+    match ast {
+        Ast::Pattern(pattern) => {
+            let mut children: Vec<Ast> = pattern
+                .children
+                .iter()
+                .map(|child| tidy(child, compact))
+                .collect();
+
+            // A group holding one bracketed pattern is just that pattern
+            if is_plain_group(pattern) {
+                if let [Ast::Element(element)] = children.as_slice() {
+                    if element.ops.is_empty() && matches!(*element.source, Ast::Pattern(_)) {
+                        return (*element.source).clone();
+                    }
+                }
+            }
+
+            if compact && pattern.alignment == Alignment::Fastcat {
+                children = merge_repeats(children);
             }
+            Ast::Pattern(PatternNode {
+                children,
+                ..pattern.clone()
+            })
         }
+        Ast::Element(element) => Ast::Element(tidy_element(element, compact)),
+        Ast::Operator(op) => Ast::Operator(OperatorNode {
+            source: Box::new(tidy(&op.source, compact)),
+            ..op.clone()
+        }),
+        Ast::Atom(_) | Ast::Command(_) => ast.clone(),
+    }
+}
+
+/// Check if a pattern is a bracket group that does nothing with one child
+fn is_plain_group(pattern: &PatternNode) -> bool {
+    matches!(pattern.alignment, Alignment::Fastcat | Alignment::Stack)
+        && !pattern.steps_marked
+        && pattern.steps_per_cycle.is_none()
+}
+
+fn tidy_element(element: &ElementNode, compact: bool) -> ElementNode {
+    let mut element = ElementNode {
+        source: Box::new(tidy(&element.source, compact)),
+        ..element.clone()
+    };
+
+    // `[bd*2]!3` is `bd*2!3`, but replication and ranges add steps, so
+    // `[bd!2]` (two steps squeezed into one) has to keep its brackets
+    let single = match element.source.as_ref() {
+        Ast::Pattern(pattern) if is_plain_group(pattern) => match pattern.children.as_slice() {
+            [Ast::Element(inner)] if !inner.ops.iter().any(adds_steps) => Some(inner.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(inner) = single {
+        element.source = inner.source;
+        element.ops = inner.ops.into_iter().chain(element.ops).collect();
+    }
+
+    // `!1` is a no-op unless it overrides an earlier replication
+    let replicates = element
+        .ops
+        .iter()
+        .filter(|op| matches!(op, SliceOp::Replicate { .. }))
+        .count();
+    element.ops.retain(|op| match op {
+        SliceOp::Replicate { amount } => *amount != 1 || replicates > 1,
+        SliceOp::Stretch { amount, .. } => !matches!(
+            amount.as_ref(),
+            Ast::Atom(AtomNode { value: AtomValue::Number(n), .. }) if *n == 1.0
+        ),
+        _ => true,
+    });
+
+    element
+}
+
+/// Check if an operator turns a step into several
+fn adds_steps(op: &SliceOp) -> bool {
+    matches!(op, SliceOp::Replicate { .. } | SliceOp::Range { .. })
+}
+
+/// Merge runs of the same plain step in a sequence into one replicated step
+fn merge_repeats(children: Vec<Ast>) -> Vec<Ast> {
+    let is_plain = |child: &Ast| match child {
+        Ast::Element(element) => element.ops.is_empty() && element.weight == 1.0,
+        _ => false,
+    };
+
+    let mut runs: Vec<(Ast, usize)> = Vec::new();
+    for child in children {
+        match runs.last_mut() {
+            Some((last, count)) if is_plain(&child) && format(last) == format(&child) => {
+                *count += 1;
+            }
+            _ => runs.push((child, 1)),
+        }
+    }
+
+    runs.into_iter()
+        .map(|(child, count)| match child {
+            Ast::Element(element) if count > 1 => Ast::Element(ElementNode {
+                ops: vec![SliceOp::Replicate { amount: count }],
+                weight: count as f64,
+                reps: count,
+                ..element
+            }),
+            child => child,
+        })
+        .collect()
+}
+
+fn format_atom(atom: &AtomNode) -> String {
+    match &atom.value {
+        AtomValue::Number(n) => format_number(*n),
         AtomValue::String(s) => s.clone(),
+        AtomValue::Run(n) => format!("run({})", n),
         AtomValue::Silence => "~".to_string(),
     }
 }
 
+fn format_number(n: f64) -> String {
+    // Format number nicely
+    if n.fract() == 0.0 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
 fn format_pattern(pattern: &PatternNode) -> String {
     let children: Vec<String> = pattern.children.iter().map(format).collect();
 
@@ -53,8 +176,12 @@ fn format_pattern(pattern: &PatternNode) -> String {
             }
         }
         Alignment::PolymeterSlowcat => {
-            // Angle brackets slow sequence
-            format!("<{}>", children.join(" "))
+            // Angle brackets slow sequence, with comma-separated layers
+            format!("<{}>", children.join(", "))
+        }
+        Alignment::RandSlowcat => {
+            // Angle brackets choice, with pipe-separated options
+            format!("<{}>", children.join(" | "))
         }
         Alignment::Feet => {
             // Dot-separated feet
@@ -75,7 +202,7 @@ fn format_element(element: &ElementNode) -> String {
                     // Already has braces in format_pattern
                     result.push_str(&format_pattern(pattern));
                 }
-                Alignment::PolymeterSlowcat => {
+                Alignment::PolymeterSlowcat | Alignment::RandSlowcat => {
                     // Already has angle brackets in format_pattern
                     result.push_str(&format_pattern(pattern));
                 }
@@ -92,17 +219,20 @@ fn format_element(element: &ElementNode) -> String {
         }
     }
 
-    // Add operators
+    // Add operators, tracking the weight they imply (replication sets it)
+    let mut implied_weight = 1.0;
     for op in &element.ops {
+        if let SliceOp::Replicate { amount } = op {
+            implied_weight = *amount as f64;
+        }
         result.push_str(&format_slice_op(op));
     }
 
-    // Add weight if not default (1.0)
-    if element.weight != 1.0 && element.weight.fract() == 0.0 {
-        let weight = element.weight as i64 - 1;
-        if weight > 0 {
-            result.push_str(&"_".repeat(weight as usize));
-        }
+    // Add any remaining weight with @ (parsed as adding weight - 1)
+    let extra_weight = element.weight - implied_weight;
+    if extra_weight != 0.0 {
+        result.push('@');
+        result.push_str(&format_number(extra_weight + 1.0));
     }
 
     result
@@ -137,8 +267,15 @@ fn format_slice_op(op: &SliceOp) -> String {
         SliceOp::Tail { element } => {
             format!(":{}", format(element))
         }
+        SliceOp::SampleIndex { index } => {
+            format!(":{}", index)
+        }
         SliceOp::Range { element } => {
-            format!("..{}", format(element))
+            // Spaced so the lexer doesn't read `0..3` as a single atom
+            format!(" .. {}", format(element))
+        }
+        SliceOp::RepeatCycles { amount } => {
+            format!("${}", amount)
         }
     }
 }
@@ -151,7 +288,12 @@ fn format_operator(op: &OperatorNode) -> String {
         OperatorType::Struct => "struct",
         OperatorType::Shift => "shift",
         OperatorType::Bjorklund => "bjorklund",
+        OperatorType::EuclidInv => "euclidInv",
+        OperatorType::EuclidFull => "euclidFull",
+        OperatorType::DegradeBy => "degradeBy",
+        OperatorType::UndegradeBy => "undegradeBy",
         OperatorType::Target => "target",
+        OperatorType::Bank => "bank",
     };
 
     let args = match &op.args {
@@ -165,9 +307,55 @@ fn format_operator(op: &OperatorNode) -> String {
                 format!("{},{}", pulse, step)
             }
         }
+        OperatorArgs::EuclidFull {
+            pulse,
+            step,
+            rotation,
+            off,
+        } => {
+            if let Some(rot) = rotation {
+                format!("{},{},{},{}", pulse, step, rot, format(off))
+            } else {
+                format!("{},{},{}", pulse, step, format(off))
+            }
+        }
+        OperatorArgs::DegradeBy { amount, seed } => match seed {
+            Some(seed) => format!("{},{}", amount, seed),
+            None => amount.to_string(),
+        },
     };
 
-    format!("{}({}) {}", op_name, args, format(&op.source))
+    format!("{}({}) {}", op_name, args, format_operand(&op.source))
+}
+
+/// Format the pattern an operator applies to, bracketed unless it is a single step
+fn format_operand(ast: &Ast) -> String {
+    if is_single_step(ast) {
+        format(ast)
+    } else {
+        format!("[{}]", format(ast))
+    }
+}
+
+/// Check if an AST formats as one step with no operators of its own
+fn is_single_step(ast: &Ast) -> bool {
+    match ast {
+        Ast::Atom(_) | Ast::Operator(_) | Ast::Command(_) => true,
+        Ast::Element(element) => {
+            // A bracketed pattern is already one step
+            element.ops.is_empty()
+                && element.weight == 1.0
+                && (matches!(*element.source, Ast::Pattern(_)) || is_single_step(&element.source))
+        }
+        Ast::Pattern(pattern) => match pattern.alignment {
+            Alignment::Polymeter | Alignment::PolymeterSlowcat | Alignment::RandSlowcat => true,
+            Alignment::Fastcat => match pattern.children.as_slice() {
+                [child] => !pattern.steps_marked && is_single_step(child),
+                _ => false,
+            },
+            _ => false,
+        },
+    }
 }
 
 fn format_command(cmd: &CommandNode) -> String {
@@ -175,6 +363,7 @@ fn format_command(cmd: &CommandNode) -> String {
         CommandType::Setcps => "setcps",
         CommandType::Setbpm => "setbpm",
         CommandType::Hush => "hush",
+        CommandType::Seed => "seed",
     };
 
     if let Some(val) = cmd.value {
@@ -187,19 +376,36 @@ fn format_command(cmd: &CommandNode) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::structure;
+    use crate::evaluator::evaluate;
     use crate::parser::parse;
+    use strudel_core::{Fraction, State, TimeSpan};
 
     fn roundtrip(input: &str) {
         let ast = parse(input).unwrap();
         let formatted = format(&ast);
-        let ast2 = parse(&formatted).unwrap();
+        let ast2 = parse(&formatted).unwrap_or_else(|e| {
+            panic!(
+                "'{}' formatted to unparseable '{}': {}",
+                input, formatted, e
+            )
+        });
+
+        // Both ASTs should be structurally equivalent (ignoring spans)
+        assert_eq!(
+            structure(&ast),
+            structure(&ast2),
+            "Roundtrip failed for '{}' -> '{}' -> AST",
+            input,
+            formatted
+        );
 
-        // Both ASTs should be equivalent (ignoring spans)
-        // For now, just check it parses
+        // Formatting is stable once normalized
         assert_eq!(
-            serde_json::to_value(&ast).unwrap(),
-            serde_json::to_value(&ast2).unwrap(),
-            "Roundtrip failed for '{}' -> '{}' -> AST", input, formatted
+            format(&ast2),
+            formatted,
+            "Formatting '{}' is not stable",
+            input
         );
     }
 
@@ -252,10 +458,138 @@ mod tests {
     fn test_format_euclidean() {
         roundtrip("bd(3,8)");
         roundtrip("sd(5,16,2)");
+
+        // Spacing inside the arguments is normalized away
+        roundtrip("bd( 3 , 8 )");
+        assert_eq!(format(&parse("bd(3, 8, 1)").unwrap()), "bd(3,8,1)");
+    }
+
+    #[test]
+    fn test_format_sample_index() {
+        roundtrip("bd:3");
+        roundtrip("bd:1 sd:2 hh");
+    }
+
+    #[test]
+    fn test_format_chord() {
+        roundtrip("c:maj7 Am");
+        roundtrip("<c:min7 f:dom7>");
     }
 
     #[test]
     fn test_format_complex() {
         roundtrip("bd*2 [sd cp]*3");
     }
+
+    /// Tidy a pattern, checking it still parses and plays the same
+    fn tidied(input: &str, compact: bool) -> String {
+        let ast = parse(input).unwrap();
+        let formatted = format(&tidy(&ast, compact));
+        let ast2 = parse(&formatted)
+            .unwrap_or_else(|e| panic!("'{}' tidied to unparseable '{}': {}", input, formatted, e));
+
+        let span = TimeSpan::new(Fraction::from(0), Fraction::from(4));
+        let haps = |ast: &Ast| -> Vec<_> {
+            // Source locations move with the text, so compare only the events
+            let mut haps = evaluate(ast).unwrap().query(State::new(span));
+            haps.sort_by_key(|hap| hap.part.begin);
+            haps.into_iter()
+                .map(|hap| (hap.whole, hap.part, hap.value))
+                .collect()
+        };
+        assert_eq!(
+            haps(&ast),
+            haps(&ast2),
+            "'{}' plays differently tidied",
+            input
+        );
+        formatted
+    }
+
+    #[test]
+    fn test_tidy() {
+        assert_eq!(tidied("[[bd]] sd", false), "bd sd");
+        assert_eq!(tidied("[bd*2]!3 [sd]/2", false), "bd*2!3 sd/2");
+        assert_eq!(tidied("bd!1 sd*1 hh/1", false), "bd sd hh");
+        assert_eq!(tidied("[[bd sd]]", false), "bd sd");
+        assert_eq!(tidied("[<bd sd>] [bd [sd]]", false), "<bd sd> [bd sd]");
+
+        // Replication inside brackets squeezes several steps into one
+        assert_eq!(tidied("[bd!2] sd", false), "[bd!2] sd");
+        assert_eq!(tidied("bd bd bd", false), "bd bd bd");
+    }
+
+    #[test]
+    fn test_tidy_compact() {
+        assert_eq!(tidied("bd bd bd", true), "bd!3");
+        assert_eq!(tidied("bd bd sd [hh hh]", true), "bd!2 sd [hh!2]");
+        assert_eq!(tidied("[bd] bd ~ ~", true), "bd!2 ~!2");
+
+        // Only plain steps are merged
+        assert_eq!(tidied("bd*2 bd*2 bd@2 bd", true), "bd*2 bd*2 bd@2 bd");
+        assert_eq!(tidied("bd, bd", true), "bd,bd");
+    }
+
+    #[test]
+    fn test_format_roundtrip_corpus() {
+        let corpus = [
+            // Euclidean rhythms
+            "bd(3,8)",
+            "bd(3,8,2) sd(5,8)",
+            "bd(<3 5>,8)",
+            "[bd sd](3,8)",
+            // Polymeter
+            "{bd sd, hh hh hh}",
+            "{bd sd, hh hh hh}%4",
+            "{bd sd cp}%<2 4>",
+            // Weights and elongation
+            "bd@3 sd",
+            "bd@1.5 sd",
+            "bd _ _ sd",
+            "[bd sd]@2 hh",
+            // Replication
+            "bd!3 sd",
+            "bd! sd",
+            "bd!!",
+            "bd!2@3",
+            "bd@3!2",
+            // Degrade
+            "bd? sd",
+            "hh?0.3*8",
+            "bd?? sd?",
+            // Alternation
+            "<bd sd cp>",
+            "<bd [sd sd]> hh",
+            "<bd sd, hh hh hh>",
+            "<bd sd>*2",
+            "<bd sd>$2 hh",
+            "<bd sd>$",
+            "<bd | sd cp | hh>",
+            // Nested brackets
+            "bd [sd [cp cp]]",
+            "[[bd sd] [hh [oh oh]]]*2",
+            "[bd, sd cp] hh",
+            // Mixed
+            "bd*2 . hh hh hh . sd",
+            "bd | sd | cp",
+            "bd:3*2 [sd:1 ~]/2",
+            "0 .. 3",
+            "-1 2.5 ~ -",
+            // Counting
+            "run(4) bd",
+            "<run(3) run(8)>*2",
+            // Euclidean operators
+            "euclidInv(3,8) bd",
+            "euclidInv(3,8,2) [bd sd]",
+            "hh euclidFull(3,8,[~ cp]) bd*2",
+            "euclidFull(3,8,1,hh) [bd*2]",
+            "degradeBy(0.3) hh*8",
+            "undegradeBy(0.5,2) [bd sd]",
+            "degradeBy(0.5) hh*8, undegradeBy(0.5) oh*8",
+        ];
+
+        for input in corpus {
+            roundtrip(input);
+        }
+    }
 }
@@ -79,6 +79,8 @@ pub enum Token {
     Setbpm,
     #[token("hush")]
     Hush,
+    #[token("seed")]
+    Seed,
     #[token("slow")]
     Slow,
     #[token("fast")]
@@ -91,6 +93,14 @@ pub enum Token {
     Target,
     #[token("euclid")]
     Euclid,
+    #[token("euclidInv")]
+    EuclidInv,
+    #[token("euclidFull")]
+    EuclidFull,
+    #[token("degradeBy")]
+    DegradeBy,
+    #[token("undegradeBy")]
+    UndegradeBy,
     #[token("rotL")]
     RotL,
     #[token("rotR")]
@@ -118,12 +128,17 @@ impl Token {
             Token::Setcps
                 | Token::Setbpm
                 | Token::Hush
+                | Token::Seed
                 | Token::Slow
                 | Token::Fast
                 | Token::Scale
                 | Token::Struct
                 | Token::Target
                 | Token::Euclid
+                | Token::EuclidInv
+                | Token::EuclidFull
+                | Token::DegradeBy
+                | Token::UndegradeBy
                 | Token::RotL
                 | Token::RotR
                 | Token::Cat
@@ -157,6 +172,7 @@ impl Token {
                 | Token::DotDot
                 | Token::Percent
                 | Token::Caret
+                | Token::Dollar
         )
     }
 }
@@ -195,12 +211,17 @@ impl std::fmt::Display for Token {
             Token::Setcps => write!(f, "setcps"),
             Token::Setbpm => write!(f, "setbpm"),
             Token::Hush => write!(f, "hush"),
+            Token::Seed => write!(f, "seed"),
             Token::Slow => write!(f, "slow"),
             Token::Fast => write!(f, "fast"),
             Token::Scale => write!(f, "scale"),
             Token::Struct => write!(f, "struct"),
             Token::Target => write!(f, "target"),
             Token::Euclid => write!(f, "euclid"),
+            Token::EuclidInv => write!(f, "euclidInv"),
+            Token::EuclidFull => write!(f, "euclidFull"),
+            Token::DegradeBy => write!(f, "degradeBy"),
+            Token::UndegradeBy => write!(f, "undegradeBy"),
             Token::RotL => write!(f, "rotL"),
             Token::RotR => write!(f, "rotR"),
             Token::Cat => write!(f, "cat"),
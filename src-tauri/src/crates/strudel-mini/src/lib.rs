@@ -22,17 +22,27 @@
 //! - Stacking (layering): `a,b,c`
 //! - Polymeter: `{a b c, d e}`
 //! - Random choice: `a|b|c`
+//! - Shuffled choice, each option once per round: `<a | b | c>`
 //! - Silence: `~`
 //! - Replication: `a!3`
-//! - Euclidean rhythms: `bd(3,8)`
+//! - Holding each cycle for several cycles: `<a b>$2`
+//! - Random removal: `a?`, or split events between layers with
+//!   `degradeBy(0.5) a, undegradeBy(0.5) b`
+//! - Euclidean rhythms: `bd(3,8)`, or the rests between them with `bd(-3,8)`
+//! - Counting up from 0: `run(4)`
+//! - Chords: `c:maj7`, `a:min`; `g:7` is a sample index, so write `g:dom7`
 //!
 //! # Main Functions
 //!
 //! - [`parse`]: Parse mini notation string to AST
 //! - [`evaluate`]: Evaluate AST to executable pattern
+//! - [`evaluate_with_tempo`]: Evaluate a program, applying `setcps`/`setbpm`/`hush`/`seed`
 //! - [`format()`]: Format AST back to mini notation
+//! - [`tidy`]: Drop redundant brackets and no-ops before formatting
 //! - [`extract_patterns`]: Extract mini notation from .strudel files
+//! - [`evaluate_arrangement`]: Stitch mini notation sections into a song
 
+pub mod arrange;
 pub mod ast;
 pub mod error;
 pub mod evaluator;
@@ -45,11 +55,12 @@ pub mod span;
 #[cfg(test)]
 mod parser_tests;
 
+pub use arrange::{arrangement_to_strudel, evaluate_arrangement, parse_arrangement, Section};
 pub use ast::{Ast, Alignment};
 pub use error::{ParseError, Result};
-pub use evaluator::evaluate;
-pub use formatter::format;
+pub use evaluator::{evaluate, evaluate_with_tempo, Evaluation};
+pub use formatter::{format, tidy};
 pub use lexer::{Lexer, Token};
-pub use parser::{parse, parse_mini, Parser};
+pub use parser::{parse, parse_all_errors, parse_mini, parse_program, Parser};
 pub use preprocessor::{extract_patterns, combine_patterns, CombineStrategy, ExtractedPattern};
 pub use span::Span;
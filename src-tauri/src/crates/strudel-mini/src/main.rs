@@ -1,6 +1,9 @@
 use clap::{Parser, Subcommand};
 use anyhow::Result;
-use strudel_mini::{parse, format, evaluate, extract_patterns, combine_patterns, CombineStrategy};
+use strudel_mini::{
+    parse, format, evaluate, extract_patterns, combine_patterns, CombineStrategy,
+    parse_arrangement, evaluate_arrangement, arrangement_to_strudel,
+};
 use strudel_core::{Fraction, State, TimeSpan};
 
 #[derive(Parser)]
@@ -18,10 +21,18 @@ enum Commands {
         /// Pattern to validate
         pattern: String,
     },
-    /// Format a mini notation pattern (TODO)
+    /// Format a mini notation pattern in canonical form
     Fmt {
         /// Pattern to format
         pattern: String,
+
+        /// Drop redundant brackets and no-op operators like `!1` and `*1`
+        #[arg(long)]
+        tidy: bool,
+
+        /// Tidy, and merge repeated steps into replication (`bd bd` to `bd!2`)
+        #[arg(long)]
+        compact: bool,
     },
     /// Generate AST for a pattern
     Ast {
@@ -45,7 +56,7 @@ enum Commands {
         #[arg(short, long, default_value = "1")]
         duration: f64,
 
-        /// Output format (json or debug)
+        /// Output format (json events or debug)
         #[arg(long, default_value = "debug")]
         format: String,
     },
@@ -58,6 +69,15 @@ enum Commands {
         #[arg(short, long, default_value = "separate")]
         strategy: String,
     },
+    /// Stitch sections from a JSON arrangement spec into one pattern
+    Arrange {
+        /// Path to JSON spec: a list of {"cycles": n, "pattern": "..."}
+        file: String,
+
+        /// Output format (strudel or json events over the whole arrangement)
+        #[arg(long, default_value = "strudel")]
+        format: String,
+    },
     /// Play a pattern using audio output
     #[cfg(feature = "audio")]
     Play {
@@ -84,9 +104,55 @@ enum Commands {
         /// Duration in seconds (default: 10)
         #[arg(short, long, default_value = "10")]
         duration: f64,
+
+        /// Play exactly this many cycles, then stop (instead of --duration)
+        #[arg(short, long, conflicts_with = "duration")]
+        loops: Option<u32>,
+
+        /// Keep playing until Ctrl-C (instead of --duration)
+        #[arg(long, conflicts_with_all = ["duration", "loops", "out", "osc"])]
+        forever: bool,
+
+        /// Render to a WAV file instead of playing through the speakers
+        #[arg(short, long)]
+        out: Option<String>,
+
+        /// Send events as SuperDirt OSC instead of playing, to --osc=host:port (default 127.0.0.1:57120)
+        #[arg(
+            long,
+            conflicts_with = "out",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = strudel_audio::osc::DEFAULT_OSC_ADDR
+        )]
+        osc: Option<String>,
     },
 }
 
+/// Seconds to play for: `loops` cycles at `tempo` BPM, or else `duration`
+///
+/// A cycle is four beats. Rejects tempos and durations that would never end.
+#[cfg(feature = "audio")]
+fn play_seconds(tempo: f64, duration: f64, loops: Option<u32>) -> Result<f64> {
+    if !tempo.is_finite() || tempo <= 0.0 {
+        return Err(anyhow::anyhow!(
+            "Tempo must be a positive number of BPM, got {}",
+            tempo
+        ));
+    }
+    let seconds = match loops {
+        Some(n) => n as f64 * 4.0 * 60.0 / tempo,
+        None => duration,
+    };
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(anyhow::anyhow!(
+            "Duration must be a non-negative number of seconds, got {}",
+            seconds
+        ));
+    }
+    Ok(seconds)
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -98,14 +164,24 @@ fn main() -> Result<()> {
                     Ok(())
                 }
                 Err(e) => {
-                    eprintln!("✗ Parse error: {}", e);
+                    match e.line_col(&pattern) {
+                        Some((line, column)) => {
+                            eprintln!("✗ Parse error at line {}, column {}: {}", line, column, e)
+                        }
+                        None => eprintln!("✗ Parse error: {}", e),
+                    }
                     std::process::exit(1);
                 }
             }
         }
-        Commands::Fmt { pattern } => {
+        Commands::Fmt { pattern, tidy, compact } => {
             match parse(&pattern) {
                 Ok(ast) => {
+                    let ast = if tidy || compact {
+                        strudel_mini::tidy(&ast, compact)
+                    } else {
+                        ast
+                    };
                     let formatted = format(&ast);
                     println!("{}", formatted);
                     Ok(())
@@ -150,7 +226,14 @@ fn main() -> Result<()> {
 
                             match format.as_str() {
                                 "json" => {
-                                    let json = serde_json::to_string_pretty(&haps)?;
+                                    // Steps give tools the grid the pattern was written on
+                                    let events: Vec<_> =
+                                        haps.iter().map(|hap| hap.to_event()).collect();
+                                    let output = serde_json::json!({
+                                        "steps": pat.get_steps().map(|steps| steps.to_float()),
+                                        "events": events,
+                                    });
+                                    let json = serde_json::to_string_pretty(&output)?;
                                     println!("{}", json);
                                 }
                                 _ => {
@@ -205,10 +288,49 @@ fn main() -> Result<()> {
 
             Ok(())
         }
+        Commands::Arrange { file, format } => {
+            use std::fs;
+
+            let source = fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("Failed to read file '{}': {}", file, e))?;
+
+            let sections = match parse_arrangement(&source) {
+                Ok(sections) => sections,
+                Err(e) => {
+                    eprintln!("✗ {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match format.as_str() {
+                "json" => {
+                    let pat = match evaluate_arrangement(&sections) {
+                        Ok(pat) => pat,
+                        Err(e) => {
+                            eprintln!("✗ Evaluation error: {}", e);
+                            std::process::exit(1);
+                        }
+                    };
+
+                    let total: usize = sections.iter().map(|s| s.cycles).sum();
+                    let span = TimeSpan::new(Fraction::from_int(0), Fraction::from_int(total as i64));
+                    let haps = pat.query(State::new(span));
+
+                    let json = serde_json::to_string_pretty(&haps)?;
+                    println!("{}", json);
+                }
+                _ => {
+                    println!("{}", arrangement_to_strudel(&sections));
+                }
+            }
+
+            Ok(())
+        }
         #[cfg(feature = "audio")]
-        Commands::Play { pattern, file, strudel_file, combine, tempo, duration } => {
-            use strudel_audio::{Player, PlayerConfig};
-            use std::thread;
+        Commands::Play { pattern, file, strudel_file, combine, tempo, duration, loops, forever, out, osc } => {
+            use strudel_audio::render::DEFAULT_SAMPLE_RATE;
+            use strudel_audio::{OscSender, Player, PlayerConfig, Renderer};
+            use strudel_mini::{evaluate_with_tempo, parse_program};
             use std::time::Duration as StdDuration;
             use std::fs;
 
@@ -246,20 +368,63 @@ fn main() -> Result<()> {
                 pattern.unwrap()
             };
 
-            // Parse the pattern
-            let ast = parse(&pattern_str)?;
-            let pat = evaluate(&ast)?;
+            // Parse the pattern, along with any setcps/setbpm/hush/seed commands
+            let statements = parse_program(&pattern_str)?;
+            let evaluation = evaluate_with_tempo(&statements)?;
+            let pat = evaluation.pattern;
 
             println!("Pattern parsed successfully!");
+            if evaluation.hushed {
+                println!("Pattern is hushed, nothing to play");
+                return Ok(());
+            }
+
+            // A tempo set in the pattern wins over --tempo (one cycle is four beats)
+            let tempo = match evaluation.cps {
+                Some(cps) => cps * 60.0 * 4.0,
+                None => tempo,
+            };
+            let duration = play_seconds(tempo, duration, loops)?;
+
             println!("Tempo: {} BPM", tempo);
-            println!("Duration: {} seconds\n", duration);
+            if let Some(n) = loops {
+                println!("Loops: {} cycles", n);
+            }
+            if forever {
+                println!("Duration: until Ctrl-C\n");
+            } else {
+                println!("Duration: {} seconds\n", duration);
+            }
+
+            if let Some(addr) = osc {
+                println!("Sending to {}...", addr);
+                let mut sender = OscSender::new(addr.as_str(), tempo)
+                    .map_err(|e| anyhow::anyhow!("Failed to open OSC socket for '{}': {}", addr, e))?;
+                let sent = sender.send(&pat, duration)
+                    .map_err(|e| anyhow::anyhow!("Failed to send to '{}': {}", addr, e))?;
+
+                println!("\nSent {} events!", sent);
+                return Ok(());
+            }
 
             // Create player with custom tempo
             let config = PlayerConfig {
                 tempo,
+                cycles: loops,
                 ..Default::default()
             };
 
+            // Rendering needs no audio device
+            if let Some(out_path) = out {
+                println!("Rendering to {}...", out_path);
+                Renderer::new(config, DEFAULT_SAMPLE_RATE)
+                    .render_to_wav(&pat, duration, std::path::Path::new(&out_path))
+                    .map_err(|e| anyhow::anyhow!("Failed to render '{}': {}", out_path, e))?;
+
+                println!("\nRendering finished!");
+                return Ok(());
+            }
+
             let player = Player::new(config)
                 .map_err(|e| anyhow::anyhow!("Failed to create audio player: {}", e))?;
 
@@ -267,10 +432,14 @@ fn main() -> Result<()> {
             player.play(pat)
                 .map_err(|e| anyhow::anyhow!("Failed to start playback: {}", e))?;
 
-            // Play for the specified duration
-            thread::sleep(StdDuration::from_secs_f64(duration));
+            // Ctrl-C stops playback cleanly instead of cutting off mid-buffer
+            let stop = player.stop_handle();
+            ctrlc::set_handler(move || stop.stop())
+                .map_err(|e| anyhow::anyhow!("Failed to install Ctrl-C handler: {}", e))?;
 
-            player.stop()
+            // Play for the specified duration, or until Ctrl-C with --forever
+            let duration = (!forever).then(|| StdDuration::from_secs_f64(duration));
+            player.play_until_stopped(duration)
                 .map_err(|e| anyhow::anyhow!("Failed to stop playback: {}", e))?;
 
             println!("\nPlayback finished!");
@@ -278,3 +447,59 @@ fn main() -> Result<()> {
         }
     }
 }
+
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    use super::*;
+
+    /// Parse the `pattern` and `osc` arguments of a play command line
+    fn play_args(args: &[&str]) -> (Option<String>, Option<String>) {
+        let cli = Cli::try_parse_from(["strudel-mini", "play"].iter().chain(args)).unwrap();
+        match cli.command {
+            Commands::Play { pattern, osc, .. } => (pattern, osc),
+            _ => panic!("Expected Play"),
+        }
+    }
+
+    #[test]
+    fn test_osc_does_not_take_the_pattern() {
+        let default_addr = Some(strudel_audio::osc::DEFAULT_OSC_ADDR.to_string());
+        assert_eq!(
+            play_args(&["--osc", "bd sd"]),
+            (Some("bd sd".into()), default_addr)
+        );
+        assert_eq!(
+            play_args(&["--osc=127.0.0.1:9000", "bd sd"]),
+            (Some("bd sd".into()), Some("127.0.0.1:9000".into()))
+        );
+        assert_eq!(play_args(&["bd sd"]), (Some("bd sd".into()), None));
+    }
+
+    #[test]
+    fn test_forever_flag() {
+        let parse =
+            |args: &[&str]| Cli::try_parse_from(["strudel-mini", "play"].iter().chain(args));
+        match parse(&["--forever", "bd sd"]).unwrap().command {
+            Commands::Play { forever, .. } => assert!(forever),
+            _ => panic!("Expected Play"),
+        }
+
+        // Too close to --loops to keep
+        assert!(parse(&["--loop", "bd sd"]).is_err());
+        assert!(parse(&["--forever", "--loops", "2", "bd sd"]).is_err());
+    }
+
+    #[test]
+    fn test_play_seconds() {
+        // Two loops of four beats at 120 BPM
+        assert_eq!(play_seconds(120.0, 10.0, Some(2)).unwrap(), 4.0);
+        assert_eq!(play_seconds(120.0, 10.0, None).unwrap(), 10.0);
+
+        // A stopped or backwards clock would never finish
+        assert!(play_seconds(0.0, 10.0, Some(1)).is_err());
+        assert!(play_seconds(-60.0, 10.0, None).is_err());
+        assert!(play_seconds(f64::NAN, 10.0, None).is_err());
+        assert!(play_seconds(120.0, -1.0, None).is_err());
+        assert!(play_seconds(120.0, f64::INFINITY, None).is_err());
+    }
+}
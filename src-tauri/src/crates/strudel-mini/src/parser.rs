@@ -2,11 +2,22 @@ use crate::ast::*;
 use crate::error::{ParseError, Result};
 use crate::lexer::{Lexer, Token};
 use crate::span::Span;
+use strudel_core::Chord;
+
+/// Longest `run(n)` the parser accepts, so a typo can't exhaust memory
+const MAX_RUN_LENGTH: usize = 1024;
+
+/// Most cycles `$n` may hold each cycle for
+const MAX_REPEAT_CYCLES: usize = 1024;
 
 /// Parser for mini notation
 pub struct Parser<'source> {
     lexer: Lexer<'source>,
     seed_counter: u64,
+    /// Whether to record errors and carry on instead of stopping at the first
+    recovering: bool,
+    /// Errors recorded while recovering
+    errors: Vec<ParseError>,
 }
 
 impl<'source> Parser<'source> {
@@ -15,14 +26,44 @@ impl<'source> Parser<'source> {
         Parser {
             lexer: Lexer::new(source),
             seed_counter: 0,
+            recovering: false,
+            errors: Vec::new(),
         }
     }
 
+    /// Parse a statement, collecting every error instead of stopping at the first
+    ///
+    /// Anything that fails to parse is skipped up to the next separator or
+    /// closing delimiter and left as silence in the AST, which is None only if
+    /// parsing couldn't carry on at all.
+    pub fn parse_recovering(&mut self) -> (Option<Ast>, Vec<ParseError>) {
+        self.recovering = true;
+        let ast = match self.parse_statement() {
+            Ok(ast) => {
+                if let Some((token, span)) = self.peek() {
+                    self.errors.push(ParseError::unexpected_token(
+                        "end of pattern",
+                        token.to_string(),
+                        span,
+                    ));
+                }
+                Some(ast)
+            }
+            Err(err) => {
+                self.errors.push(err);
+                None
+            }
+        };
+        self.recovering = false;
+
+        (ast, std::mem::take(&mut self.errors))
+    }
+
     /// Parse a complete statement (either a mini pattern or command)
     pub fn parse_statement(&mut self) -> Result<Ast> {
-        // Try to parse a command first (setcps, setbpm, hush)
+        // Try to parse a command first (setcps, setbpm, hush, seed)
         if let Some((token, _)) = self.peek() {
-            if matches!(token, Token::Setcps | Token::Setbpm | Token::Hush) {
+            if matches!(token, Token::Setcps | Token::Setbpm | Token::Hush | Token::Seed) {
                 return self.parse_command();
             }
         }
@@ -31,6 +72,18 @@ impl<'source> Parser<'source> {
         self.parse_mini_definition()
     }
 
+    /// Parse statements until the end of the input
+    ///
+    /// Lets commands share the source with the pattern they apply to, as in
+    /// `setcps 0.5 bd sd`.
+    pub fn parse_statements(&mut self) -> Result<Vec<Ast>> {
+        let mut statements = Vec::new();
+        while self.peek().is_some() {
+            statements.push(self.parse_statement()?);
+        }
+        Ok(statements)
+    }
+
     /// Parse a mini notation pattern (may or may not have quotes)
     pub fn parse_mini_definition(&mut self) -> Result<Ast> {
         // Check if we have quotes
@@ -143,18 +196,7 @@ impl<'source> Parser<'source> {
         loop {
             // Stop at separators or closing delimiters
             if let Some((token, _)) = self.peek() {
-                if matches!(
-                    token,
-                    Token::Comma
-                        | Token::Pipe
-                        | Token::Dot
-                        | Token::RBracket
-                        | Token::RBrace
-                        | Token::RAngle
-                        | Token::RParen
-                        | Token::Quote
-                        | Token::SingleQuote
-                ) {
+                if is_boundary(&token) {
                     break;
                 }
             } else {
@@ -216,13 +258,13 @@ impl<'source> Parser<'source> {
                         2.0 // default weight increment
                     };
                     element.weight += amount - 1.0;
-                    None
+                    continue;
                 }
                 Some((Token::Underscore, _)) => {
                     self.next();
                     // Same as @ operator
                     element.weight += 1.0;
-                    None
+                    continue;
                 }
                 Some((Token::Bang, _)) => {
                     self.next();
@@ -240,7 +282,30 @@ impl<'source> Parser<'source> {
                 Some((Token::LParen, _)) => {
                     // Bjorklund (Euclidean rhythm): (pulse, step, rotation?)
                     self.next(); // consume (
-                    let pulse = Box::new(Ast::Element(self.parse_slice_with_ops()?));
+                    let pulse = self.parse_slice_with_ops()?;
+
+                    // `run(4)` counts up from 0; `run(3,8)` is still a rhythm
+                    let run = run_length(&element, &pulse);
+                    if let (Some((n, count_span)), Some((Token::RParen, _))) = (run, self.peek()) {
+                        let end_span = self.expect_token(Token::RParen)?;
+                        let span = start_span.merge(end_span);
+                        let value = if n >= 0.0 && n.fract() == 0.0 && n <= MAX_RUN_LENGTH as f64 {
+                            AtomValue::Run(n as usize)
+                        } else {
+                            self.recover(ParseError::custom(
+                                format!(
+                                    "run takes a whole number from 0 to {}, got {}",
+                                    MAX_RUN_LENGTH, n
+                                ),
+                                Some(count_span),
+                            ))?;
+                            AtomValue::Silence
+                        };
+                        element.source = Box::new(Ast::Atom(AtomNode::new(value, span)));
+                        continue;
+                    }
+
+                    let pulse = Box::new(Ast::Element(pulse));
                     self.expect_token(Token::Comma)?;
                     let step = Box::new(Ast::Element(self.parse_slice_with_ops()?));
 
@@ -276,14 +341,78 @@ impl<'source> Parser<'source> {
                 }
                 Some((Token::Colon, _)) => {
                     self.next();
-                    let element = Box::new(self.parse_slice()?);
-                    Some(SliceOp::Tail { element })
+
+                    // `bd:3` selects a sample index; anything else is a tail
+                    let is_sample = element.ops.is_empty()
+                        && matches!(
+                            element.source.as_ref(),
+                            Ast::Atom(AtomNode { value: AtomValue::String(_), .. })
+                        );
+
+                    match self.peek() {
+                        Some((Token::Number(n), _)) if is_sample && n >= 0.0 && n.fract() == 0.0 => {
+                            self.next();
+                            Some(SliceOp::SampleIndex { index: n as usize })
+                        }
+                        // `c:maj7` is a chord name; keep it as a single atom
+                        Some((Token::Atom, span)) if is_sample => {
+                            let chord = match element.source.as_ref() {
+                                Ast::Atom(AtomNode {
+                                    value: AtomValue::String(root),
+                                    span: root_span,
+                                }) => {
+                                    let name = format!("{}:{}", root, self.lexer.slice(span));
+                                    Chord::parse(&name).map(|_| (name, root_span.merge(span)))
+                                }
+                                _ => None,
+                            };
+
+                            match chord {
+                                Some((name, chord_span)) => {
+                                    self.next();
+                                    element.source =
+                                        Box::new(Ast::Atom(AtomNode::string(name, chord_span)));
+                                    continue;
+                                }
+                                None => {
+                                    let element = Box::new(self.parse_slice()?);
+                                    Some(SliceOp::Tail { element })
+                                }
+                            }
+                        }
+                        _ => {
+                            let element = Box::new(self.parse_slice()?);
+                            Some(SliceOp::Tail { element })
+                        }
+                    }
                 }
                 Some((Token::DotDot, _)) => {
                     self.next();
                     let element = Box::new(self.parse_slice()?);
                     Some(SliceOp::Range { element })
                 }
+                Some((Token::Dollar, _)) => {
+                    self.next();
+                    // Repeat cycles - optional count
+                    let amount = if let Some((Token::Number(n), span)) = self.peek() {
+                        self.next();
+                        if n >= 1.0 && n.fract() == 0.0 && n <= MAX_REPEAT_CYCLES as f64 {
+                            n as usize
+                        } else {
+                            self.recover(ParseError::custom(
+                                format!(
+                                    "$ takes a whole number from 1 to {}, got {}",
+                                    MAX_REPEAT_CYCLES, n
+                                ),
+                                Some(span),
+                            ))?;
+                            1
+                        }
+                    } else {
+                        2
+                    };
+                    Some(SliceOp::RepeatCycles { amount })
+                }
                 _ => None,
             };
 
@@ -304,6 +433,8 @@ impl<'source> Parser<'source> {
             Some((Token::LBracket, _)) => self.parse_sub_cycle(),
             Some((Token::LBrace, _)) => self.parse_polymeter(),
             Some((Token::LAngle, _)) => self.parse_slow_sequence(),
+            Some((Token::EuclidInv | Token::EuclidFull, _)) => self.parse_euclid_operator(),
+            Some((Token::DegradeBy | Token::UndegradeBy, _)) => self.parse_degrade_operator(),
             Some((Token::Tilde | Token::Dash, span)) => {
                 self.next();
                 Ok(Ast::Atom(AtomNode::silence(span)))
@@ -321,16 +452,108 @@ impl<'source> Parser<'source> {
                 Ok(Ast::Atom(AtomNode::string(atom_str, sp)))
             }
             Some((token, span)) => {
-                Err(ParseError::unexpected_token(
+                self.recover(ParseError::unexpected_token(
                     "atom, number, or opening delimiter",
                     token.to_string(),
                     span,
-                ))
+                ))?;
+                // Skip the bad token, leaving boundaries for the enclosing sequence
+                if !is_boundary(&token) {
+                    self.next();
+                }
+                Ok(Ast::Atom(AtomNode::silence(span)))
+            }
+            None => {
+                self.recover(ParseError::unexpected_eof("slice"))?;
+                Ok(Ast::Atom(AtomNode::silence(self.current_span())))
             }
-            None => Err(ParseError::unexpected_eof("slice")),
         }
     }
 
+    /// Parse `euclidInv(pulse,step,rotation?) slice` or
+    /// `euclidFull(pulse,step,rotation?,off) slice`
+    fn parse_euclid_operator(&mut self) -> Result<Ast> {
+        let (token, start_span) = self.next().ok_or(ParseError::unexpected_eof("operator"))?;
+        self.expect_token(Token::LParen)?;
+        let pulse = self.parse_whole_number()?;
+        self.expect_token(Token::Comma)?;
+        let step = self.parse_whole_number()?;
+
+        let args = if token == Token::EuclidFull {
+            // The last argument is the off pattern, after an optional rotation
+            self.expect_token(Token::Comma)?;
+            let mut off = self.parse_slice_with_ops()?;
+            let mut rotation = None;
+            if let Some((Token::Comma, _)) = self.peek() {
+                self.next();
+                rotation = Some(whole_number(&off)?);
+                off = self.parse_slice_with_ops()?;
+            }
+            OperatorArgs::EuclidFull {
+                pulse,
+                step,
+                rotation,
+                off: Box::new(Ast::Element(off)),
+            }
+        } else {
+            let rotation = if let Some((Token::Comma, _)) = self.peek() {
+                self.next();
+                Some(self.parse_whole_number()?)
+            } else {
+                None
+            };
+            OperatorArgs::Bjorklund {
+                pulse,
+                step,
+                rotation,
+            }
+        };
+        self.expect_token(Token::RParen)?;
+
+        let op_type = match token {
+            Token::EuclidFull => OperatorType::EuclidFull,
+            _ => OperatorType::EuclidInv,
+        };
+        let source = self.parse_slice()?;
+        let span = start_span.merge(source.span());
+        Ok(Ast::Operator(OperatorNode::new(
+            op_type, args, source, span,
+        )))
+    }
+
+    /// Parse `degradeBy(amount[,seed]) slice` or `undegradeBy(amount[,seed]) slice`
+    ///
+    /// With the same amount and seed the two keep complementary events, so
+    /// stacking them splits a pattern's events between the layers.
+    fn parse_degrade_operator(&mut self) -> Result<Ast> {
+        let (token, start_span) = self.next().ok_or(ParseError::unexpected_eof("operator"))?;
+        self.expect_token(Token::LParen)?;
+        let amount = probability(&self.parse_slice_with_ops()?)?;
+        let seed = if let Some((Token::Comma, _)) = self.peek() {
+            self.next();
+            Some(self.parse_whole_number()? as u64)
+        } else {
+            None
+        };
+        self.expect_token(Token::RParen)?;
+
+        let op_type = match token {
+            Token::UndegradeBy => OperatorType::UndegradeBy,
+            _ => OperatorType::DegradeBy,
+        };
+        let args = OperatorArgs::DegradeBy { amount, seed };
+        let source = self.parse_slice()?;
+        let span = start_span.merge(source.span());
+        Ok(Ast::Operator(OperatorNode::new(
+            op_type, args, source, span,
+        )))
+    }
+
+    /// Parse a whole, non-negative number argument
+    fn parse_whole_number(&mut self) -> Result<i64> {
+        whole_number(&self.parse_slice_with_ops()?)
+    }
+
     /// Parse a sub-cycle: [pattern]
     fn parse_sub_cycle(&mut self) -> Result<Ast> {
         let start_span = self.expect_token(Token::LBracket)?;
@@ -380,15 +603,20 @@ impl<'source> Parser<'source> {
         Ok(Ast::Pattern(pattern))
     }
 
-    /// Parse a slow sequence: <pattern1 pattern2>
+    /// Parse a slow sequence: <pattern1 pattern2>, or a per-cycle choice: <pattern1 | pattern2>
     fn parse_slow_sequence(&mut self) -> Result<Ast> {
         let start_span = self.expect_token(Token::LAngle)?;
 
-        // Parse as polymeter_stack (comma-separated sequences)
+        // Parse as polymeter_stack (comma-separated sequences), or as
+        // pipe-separated sequences to choose between
         let first = self.parse_sequence()?;
         let mut children = vec![Ast::Pattern(first)];
 
-        while let Some((Token::Comma, _)) = self.peek() {
+        let (alignment, separator) = match self.peek() {
+            Some((Token::Pipe, _)) => (Alignment::RandSlowcat, Token::Pipe),
+            _ => (Alignment::PolymeterSlowcat, Token::Comma),
+        };
+        while self.peek().is_some_and(|(token, _)| token == separator) {
             self.next();
             children.push(Ast::Pattern(self.parse_sequence()?));
         }
@@ -396,22 +624,28 @@ impl<'source> Parser<'source> {
         let end_span = self.expect_token(Token::RAngle)?;
         let span = start_span.merge(end_span);
 
+        // Each choice needs its own seed, as with `|`
+        let seed = if alignment == Alignment::RandSlowcat {
+            let s = self.seed_counter;
+            self.seed_counter += 1;
+            Some(s)
+        } else {
+            None
+        };
+
         Ok(Ast::Pattern(PatternNode::new(
-            children,
-            Alignment::PolymeterSlowcat,
-            None,
-            false,
-            span,
+            children, alignment, seed, false, span,
         )))
     }
 
-    /// Parse a command (setcps, setbpm, hush)
+    /// Parse a command (setcps, setbpm, hush, seed)
     fn parse_command(&mut self) -> Result<Ast> {
         let (token, span) = self.next().ok_or(ParseError::unexpected_eof("command"))?;
 
         let cmd_type = match token {
             Token::Setcps => CommandType::Setcps,
             Token::Setbpm => CommandType::Setbpm,
+            Token::Seed => CommandType::Seed,
             Token::Hush => {
                 return Ok(Ast::Command(CommandNode::new(CommandType::Hush, None, span)));
             }
@@ -433,6 +667,14 @@ impl<'source> Parser<'source> {
             }
         };
 
+        // Seeds pick a random sequence, so only whole numbers name one
+        if matches!(cmd_type, CommandType::Seed) && !(value >= 0.0 && value.fract() == 0.0) {
+            return Err(ParseError::custom(
+                format!("seed takes a whole, non-negative number, got {}", value),
+                Some(num_span),
+            ));
+        }
+
         // Convert BPM to CPS if needed
         let final_value = if matches!(cmd_type, CommandType::Setbpm) {
             value / 120.0 / 2.0 // BPM to CPS conversion
@@ -459,14 +701,40 @@ impl<'source> Parser<'source> {
     }
 
     fn expect_token(&mut self, expected: Token) -> Result<Span> {
-        match self.next() {
-            Some((token, span)) if token == expected => Ok(span),
-            Some((token, span)) => Err(ParseError::unexpected_token(
-                expected.to_string(),
-                token.to_string(),
-                span,
-            )),
-            None => Err(ParseError::unexpected_eof(expected.to_string())),
+        match self.peek() {
+            Some((token, span)) if token == expected => {
+                self.next();
+                Ok(span)
+            }
+            Some((token, span)) => {
+                self.recover(ParseError::unexpected_token(
+                    expected.to_string(),
+                    token.to_string(),
+                    span,
+                ))?;
+                // A mismatched closing delimiter stands in for the expected one
+                if matches!(
+                    token,
+                    Token::RBracket | Token::RBrace | Token::RAngle | Token::RParen
+                ) {
+                    self.next();
+                }
+                Ok(span)
+            }
+            None => {
+                self.recover(ParseError::unexpected_eof(expected.to_string()))?;
+                Ok(self.current_span())
+            }
+        }
+    }
+
+    /// Record an error and carry on when recovering, or fail with it otherwise
+    fn recover(&mut self, error: ParseError) -> Result<()> {
+        if self.recovering {
+            self.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
         }
     }
 
@@ -477,12 +745,85 @@ impl<'source> Parser<'source> {
     }
 }
 
+/// The count of a `run(n)` call and its span, if `element` is `run` and `arg` a number
+fn run_length(element: &ElementNode, arg: &ElementNode) -> Option<(f64, Span)> {
+    if !element.ops.is_empty() || !arg.ops.is_empty() {
+        return None;
+    }
+
+    match (element.source.as_ref(), arg.source.as_ref()) {
+        (Ast::Atom(name), Ast::Atom(count)) => match (&name.value, &count.value) {
+            (AtomValue::String(name), AtomValue::Number(n)) if name == "run" => {
+                Some((*n, count.span))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The value of an element that is a whole, non-negative number
+fn whole_number(element: &ElementNode) -> Result<i64> {
+    match element.source.as_ref() {
+        Ast::Atom(AtomNode {
+            value: AtomValue::Number(n),
+            ..
+        }) if element.ops.is_empty() && *n >= 0.0 && n.fract() == 0.0 => Ok(*n as i64),
+        _ => Err(ParseError::custom(
+            "Expected a whole number",
+            Some(element.span),
+        )),
+    }
+}
+
+/// Read a probability argument: a plain number from 0 to 1
+fn probability(element: &ElementNode) -> Result<f64> {
+    match element.source.as_ref() {
+        Ast::Atom(AtomNode {
+            value: AtomValue::Number(n),
+            ..
+        }) if element.ops.is_empty() && (0.0..=1.0).contains(n) => Ok(*n),
+        _ => Err(ParseError::custom(
+            "Expected a probability from 0 to 1",
+            Some(element.span),
+        )),
+    }
+}
+
+/// Whether a token ends a sequence: a separator or closing delimiter
+fn is_boundary(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Comma
+            | Token::Pipe
+            | Token::Dot
+            | Token::RBracket
+            | Token::RBrace
+            | Token::RAngle
+            | Token::RParen
+            | Token::Quote
+            | Token::SingleQuote
+    )
+}
+
 /// Convenience function to parse a mini notation string
 pub fn parse(source: &str) -> Result<Ast> {
     let mut parser = Parser::new(source);
     parser.parse_statement()
 }
 
+/// Parse every statement in a source string, commands included
+pub fn parse_program(source: &str) -> Result<Vec<Ast>> {
+    let mut parser = Parser::new(source);
+    parser.parse_statements()
+}
+
+/// Parse a mini notation string, reporting every error rather than just the first
+pub fn parse_all_errors(source: &str) -> (Option<Ast>, Vec<ParseError>) {
+    let mut parser = Parser::new(source);
+    parser.parse_recovering()
+}
+
 /// Parse a mini notation pattern (with or without quotes)
 pub fn parse_mini(source: &str) -> Result<Ast> {
     let mut parser = Parser::new(source);
@@ -492,6 +833,9 @@ pub fn parse_mini(source: &str) -> Result<Ast> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::structure;
+    use crate::evaluator::evaluate;
+    use strudel_core::{Fraction, State, TimeSpan};
 
     #[test]
     fn test_parse_single_atom() {
@@ -628,6 +972,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_euclid_whitespace() {
+        for (spaced, compact) in [
+            ("bd(3, 8)", "bd(3,8)"),
+            ("bd( 3 , 8 )", "bd(3,8)"),
+            ("bd(3, 8, 1)", "bd(3,8,1)"),
+            ("bd( 3 , 8 , 1 ) sd(<3 5>, 8)", "bd(3,8,1) sd(<3 5>,8)"),
+        ] {
+            let spaced_ast = parse(spaced).unwrap_or_else(|e| panic!("'{}': {}", spaced, e));
+            let compact_ast = parse(compact).unwrap();
+            assert_eq!(
+                structure(&spaced_ast),
+                structure(&compact_ast),
+                "{}",
+                spaced
+            );
+
+            // And both play the same events, though from different source locations
+            let events = |ast: &Ast| {
+                let state = State::new(TimeSpan::new(Fraction::from_int(0), Fraction::from_int(2)));
+                let haps = evaluate(ast).unwrap().query(state);
+                haps.into_iter()
+                    .map(|h| (h.whole, h.part, h.value))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(events(&spaced_ast), events(&compact_ast), "{}", spaced);
+        }
+    }
+
     #[test]
     fn test_parse_command_setcps() {
         let ast = parse("setcps 0.5").unwrap();
@@ -640,6 +1013,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_program() {
+        let statements = parse_program("setcps 0.5\nbd sd").unwrap();
+        assert_eq!(statements.len(), 2);
+        assert!(matches!(&statements[0], Ast::Command(c) if c.cmd_type == CommandType::Setcps));
+        assert!(matches!(&statements[1], Ast::Pattern(_)));
+
+        assert!(parse_program("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_error_line_col() {
+        let source = "[bd sd\nhh cp\n  cp )";
+        let err = parse(source).unwrap_err();
+        assert_eq!(err.line_col(source), Some((3, 6)));
+
+        // Running out of input points past the last character
+        let source = "[bd\nsd";
+        let err = parse(source).unwrap_err();
+        assert_eq!(err.line_col(source), Some((2, 3)));
+    }
+
+    #[test]
+    fn test_parse_all_errors() {
+        // Both mismatched brackets are reported, each at its own closer
+        let source = "[bd sd> [cp hh)";
+        assert!(parse(source).is_err());
+        let (ast, errors) = parse_all_errors(source);
+        assert!(ast.is_some());
+        let spans: Vec<Span> = errors.iter().filter_map(|e| e.span()).collect();
+        assert_eq!(spans, vec![Span::new(6, 7), Span::new(14, 15)]);
+
+        // Bad tokens are skipped, leaving the rest of the sequence intact
+        let (ast, errors) = parse_all_errors("bd % sd");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(ast, Some(Ast::Pattern(p)) if p.children.len() == 3));
+
+        let (ast, errors) = parse_all_errors("bd [sd cp]");
+        assert!(ast.is_some());
+        assert!(errors.is_empty());
+    }
+
     #[test]
     fn test_parse_command_hush() {
         let ast = parse("hush").unwrap();
@@ -651,4 +1066,56 @@ mod tests {
             _ => panic!("Expected Command"),
         }
     }
+
+    #[test]
+    fn test_parse_run() {
+        let ast = parse("run(4)").unwrap();
+        match ast {
+            Ast::Pattern(p) => match &p.children[0] {
+                Ast::Element(e) => match e.source.as_ref() {
+                    Ast::Atom(a) => assert_eq!(a.value, AtomValue::Run(4)),
+                    _ => panic!("Expected Atom"),
+                },
+                _ => panic!("Expected Element"),
+            },
+            _ => panic!("Expected Pattern"),
+        }
+
+        // Lengths that aren't whole or would take too much memory are errors
+        for source in ["run(1000000000000)", "run(2.5)", "run(-1)"] {
+            assert!(parse(source).is_err(), "{} should not parse", source);
+        }
+        let (_, errors) = parse_all_errors("run(1000000000000) bd");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_repeat_cycles_count() {
+        assert!(parse("<bd sd>$3").is_ok());
+        for source in [
+            "<bd sd>$2.5",
+            "<bd sd>$0",
+            "<bd sd>$-1",
+            "<bd sd>$99999999999999999999",
+            "<bd sd>$9223372036854775808",
+        ] {
+            assert!(parse(source).is_err(), "{} should not parse", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_command_seed() {
+        let ast = parse("seed 42").unwrap();
+        match ast {
+            Ast::Command(c) => {
+                assert_eq!(c.cmd_type, CommandType::Seed);
+                assert_eq!(c.value, Some(42.0));
+            }
+            _ => panic!("Expected Command"),
+        }
+
+        for source in ["seed -1", "seed 0.5"] {
+            assert!(parse(source).is_err(), "{} should not parse", source);
+        }
+    }
 }
@@ -186,6 +186,22 @@ mod tests {
     fn test_underscore_operator() {
         assert_parses("a _ b _ _");
         assert_parses("bd _ sd _");
+
+        // Repeated weights add up without ending the sequence
+        match parse("a _ _ b@2 _").unwrap() {
+            Ast::Pattern(p) => {
+                let weights: Vec<f64> = p
+                    .children
+                    .iter()
+                    .map(|child| match child {
+                        Ast::Element(e) => e.weight,
+                        _ => panic!("Expected Element"),
+                    })
+                    .collect();
+                assert_eq!(weights, vec![3.0, 3.0]);
+            }
+            _ => panic!("Expected Pattern"),
+        }
     }
 
     #[test]
@@ -33,6 +33,16 @@ impl Span {
     pub fn to_range(&self) -> Range<usize> {
         self.start..self.end
     }
+
+    /// 1-based line and column where this span starts in `source`
+    ///
+    /// Columns count characters rather than bytes, matching what an editor shows.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = source.get(..self.start).unwrap_or(source);
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        (line, before[line_start..].chars().count() + 1)
+    }
 }
 
 impl From<Range<usize>> for Span {
@@ -76,4 +86,14 @@ mod tests {
         assert_eq!(merged.start, 0);
         assert_eq!(merged.end, 10);
     }
+
+    #[test]
+    fn test_span_line_col() {
+        let source = "bd sd\nhh\n  cp é x";
+        assert_eq!(Span::new(0, 2).line_col(source), (1, 1));
+        assert_eq!(Span::new(6, 8).line_col(source), (2, 1));
+        assert_eq!(Span::new(11, 13).line_col(source), (3, 3));
+        // Multi-byte characters count as one column
+        assert_eq!(Span::new(17, 18).line_col(source), (3, 8));
+    }
 }
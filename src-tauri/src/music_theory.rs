@@ -2,6 +2,7 @@
 // Provides scales, chord progressions, and euclidean rhythms
 
 use std::collections::HashMap;
+use strudel_core::{pure, State, TimeSpan, Value};
 
 /// Note names in chromatic order
 const NOTE_NAMES: [&str; 12] = [
@@ -168,10 +169,9 @@ impl MusicTheory {
         Ok(chords.join(" "))
     }
 
-    /// Generate Euclidean rhythm pattern
-    /// Distributes `hits` evenly across `steps` using Bjorklund's algorithm
-    /// Returns pattern like "1 ~ 1 ~ 1 1 ~ 1" for 5 hits in 8 steps
-    pub fn generate_euclidean_rhythm(hits: usize, steps: usize) -> Result<String, String> {
+    /// Which of `steps` steps a Euclidean rhythm of `hits` plays on, or its
+    /// rests when `inverse` is set, as laid out by strudel-core
+    fn euclidean_hits(hits: usize, steps: usize, inverse: bool) -> Result<Vec<bool>, String> {
         if hits > steps {
             return Err("Hits cannot exceed steps".to_string());
         }
@@ -180,30 +180,50 @@ impl MusicTheory {
             return Err("Steps must be greater than 0".to_string());
         }
 
-        if hits == 0 {
-            return Ok(vec!["~"; steps].join(" "));
-        }
+        let pulse = pure(Value::Bool(true));
+        let rhythm = if inverse {
+            pulse.euclid_inv(hits, steps, None)
+        } else {
+            pulse.euclid(hits, steps, None)
+        };
 
-        // Bjorklund's algorithm (simplified)
         let mut pattern = vec![false; steps];
-        let interval = steps as f32 / hits as f32;
-
-        for i in 0..hits {
-            let index = (i as f32 * interval).floor() as usize;
-            if index < steps {
-                pattern[index] = true;
+        for hap in rhythm.query(State::new(TimeSpan::from_ints(0, 1))) {
+            let step = (hap.whole_or_part().begin.to_float() * steps as f64).round() as usize;
+            if let Some(hit) = pattern.get_mut(step) {
+                *hit = true;
             }
         }
 
-        Ok(pattern
+        Ok(pattern)
+    }
+
+    /// Format hits as a struct string like "1 ~ 1 ~"
+    fn format_rhythm(pattern: &[bool]) -> String {
+        pattern
             .iter()
             .map(|&hit| if hit { "1" } else { "~" })
             .collect::<Vec<_>>()
-            .join(" "))
+            .join(" ")
+    }
+
+    /// Generate Euclidean rhythm pattern
+    /// Distributes `hits` evenly across `steps` using Bjorklund's algorithm
+    /// Returns pattern like "1 ~ 1 1 ~ 1 1 ~" for 5 hits in 8 steps
+    pub fn generate_euclidean_rhythm(hits: usize, steps: usize) -> Result<String, String> {
+        let pattern = Self::euclidean_hits(hits, steps, false)?;
+        Ok(Self::format_rhythm(&pattern))
+    }
+
+    /// Generate the inverse of a Euclidean rhythm (hits on its rests)
+    /// Returns pattern like "~ 1 ~ ~ 1 ~ ~ 1" for 5 hits in 8 steps
+    pub fn generate_euclidean_rhythm_inv(hits: usize, steps: usize) -> Result<String, String> {
+        let pattern = Self::euclidean_hits(hits, steps, true)?;
+        Ok(Self::format_rhythm(&pattern))
     }
 
     /// Generate Euclidean rhythm as Strudel pattern
-    /// Returns pattern like: s("bd").struct("1 ~ 1 ~ 1 1 ~ 1")
+    /// Returns pattern like: s("bd").struct("1 ~ 1 1 ~ 1 1 ~")
     pub fn generate_euclidean_pattern(
         hits: usize,
         steps: usize,
@@ -213,6 +233,24 @@ impl MusicTheory {
         Ok(format!(r#"s("{}").struct("{}")"#, sound, rhythm))
     }
 
+    /// Generate a Euclidean rhythm layered with its inverse as a Strudel stack
+    /// The main sound plays the rhythm and the fill sound plays the rests, so the
+    /// two layers never hit together. Returns a pattern like:
+    /// stack(s("bd").struct("1 ~ 1 ~"), s("hh").struct("~ 1 ~ 1"))
+    pub fn generate_complementary_euclidean_pattern(
+        hits: usize,
+        steps: usize,
+        sound: &str,
+        fill_sound: &str,
+    ) -> Result<String, String> {
+        let rhythm = Self::generate_euclidean_rhythm(hits, steps)?;
+        let inverse = Self::generate_euclidean_rhythm_inv(hits, steps)?;
+        Ok(format!(
+            "stack(\n  s(\"{}\").struct(\"{}\"),\n  s(\"{}\").struct(\"{}\")\n)",
+            sound, rhythm, fill_sound, inverse
+        ))
+    }
+
     /// List available scale types
     #[allow(dead_code)]
     pub fn available_scales() -> Vec<&'static str> {
@@ -260,6 +298,12 @@ mod tests {
         // Should distribute 5 hits across 8 steps
         assert_eq!(rhythm.matches('1').count(), 5);
         assert_eq!(rhythm.split_whitespace().count(), 8);
+        // The same steps as `euclid(5, 8)` in strudel-core
+        assert_eq!(rhythm, "1 ~ 1 1 ~ 1 1 ~");
+        assert_eq!(
+            MusicTheory::generate_euclidean_rhythm_inv(5, 8).unwrap(),
+            "~ 1 ~ ~ 1 ~ ~ 1"
+        );
     }
 
     #[test]
@@ -269,6 +313,31 @@ mod tests {
         assert!(pattern.contains("struct("));
     }
 
+    #[test]
+    fn test_complementary_euclidean_pattern() {
+        let pattern =
+            MusicTheory::generate_complementary_euclidean_pattern(3, 8, "bd", "hh").unwrap();
+        assert!(pattern.starts_with("stack("));
+
+        // Each layer's struct string, in order
+        let layers: Vec<Vec<&str>> = pattern
+            .split(".struct(\"")
+            .skip(1)
+            .map(|rest| rest.split('"').next().unwrap().split_whitespace().collect())
+            .collect();
+        assert_eq!(layers.len(), 2);
+        assert!(pattern.contains(r#"s("bd")"#) && pattern.contains(r#"s("hh")"#));
+
+        // Every step is a hit in exactly one layer
+        assert_eq!(layers[0].len(), 8);
+        assert_eq!(layers[1].len(), 8);
+        for (kick, hat) in layers[0].iter().zip(&layers[1]) {
+            assert_ne!(kick, hat);
+        }
+        assert_eq!(layers[0].iter().filter(|&&s| s == "1").count(), 3);
+        assert_eq!(layers[1].iter().filter(|&&s| s == "1").count(), 5);
+    }
+
     #[test]
     fn test_transpose_note() {
         assert_eq!(MusicTheory::transpose_note("C", 2).unwrap(), "D");
@@ -1,9 +1,51 @@
 // In-memory vector store with pre-computed embeddings
 // No ML dependencies - embeddings are generated at build time and shipped with the app
 
+use super::types::EmbeddingChunk;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+/// Environment variable naming an Ollama model to embed the docs with at startup
+pub const OLLAMA_EMBEDDING_MODEL_VAR: &str = "OLLAMA_EMBEDDING_MODEL";
+
+/// Ollama server used when `OLLAMA_API_BASE_URL` isn't set
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+/// Shortest word kept as a token; shorter words are mostly stop words
+pub const MIN_TOKEN_LEN: usize = 3;
+
+/// Split text into tokens: lowercase runs of letters and digits
+///
+/// Shared by index building and query embedding, so a query matches the
+/// vocabulary however it is cased or punctuated. With `stem`, common suffixes
+/// are stripped too.
+pub fn tokenize(text: &str, stem: bool) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() >= MIN_TOKEN_LEN)
+        .map(|word| if stem { stem_word(word) } else { word })
+        .map(String::from)
+        .collect()
+}
+
+/// Strip a common suffix ("-ing", "-ed", "-s") so "patterns" matches "pattern"
+///
+/// At least `MIN_TOKEN_LEN` characters are kept, and words ending in "ss" are
+/// left alone.
+pub fn stem_word(word: &str) -> &str {
+    if word.ends_with("ss") {
+        return word;
+    }
+    for suffix in ["ing", "ed", "s"] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            if stem.len() >= MIN_TOKEN_LEN {
+                return stem;
+            }
+        }
+    }
+    word
+}
+
 /// Pre-computed embedding entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingEntry {
@@ -18,6 +60,7 @@ pub struct VectorStore {
     dimension: usize,
     vocabulary: Vec<String>,
     idf_scores: std::collections::HashMap<String, f32>,
+    stemmed: bool,
 }
 
 impl VectorStore {
@@ -27,6 +70,29 @@ impl VectorStore {
         vocabulary: Vec<String>,
         idf_scores: std::collections::HashMap<String, f32>,
     ) -> Result<Self> {
+        let store = Self::from_embeddings(embeddings)?;
+
+        // Validate vocabulary matches dimension
+        if vocabulary.len() != store.dimension {
+            return Err(anyhow::anyhow!(
+                "Vocabulary size ({}) doesn't match dimension ({})",
+                vocabulary.len(),
+                store.dimension
+            ));
+        }
+
+        Ok(Self {
+            vocabulary,
+            idf_scores,
+            ..store
+        })
+    }
+
+    /// Create a vector store from model embeddings, without a TF-IDF vocabulary
+    ///
+    /// `embed_query` can't embed queries for such a store; they have to be
+    /// embedded by the same model as the documents.
+    pub fn from_embeddings(embeddings: Vec<EmbeddingEntry>) -> Result<Self> {
         let dimension = embeddings
             .first()
             .map(|e| e.vector.len())
@@ -43,32 +109,24 @@ impl VectorStore {
             }
         }
 
-        // Validate vocabulary matches dimension
-        if vocabulary.len() != dimension {
-            return Err(anyhow::anyhow!(
-                "Vocabulary size ({}) doesn't match dimension ({})",
-                vocabulary.len(),
-                dimension
-            ));
-        }
-
         Ok(Self {
             embeddings,
             dimension,
-            vocabulary,
-            idf_scores,
+            vocabulary: Vec::new(),
+            idf_scores: std::collections::HashMap::new(),
+            stemmed: false,
         })
     }
 
+    /// Stem query words, for a vocabulary that was built from stemmed words
+    pub fn with_stemming(mut self, stemmed: bool) -> Self {
+        self.stemmed = stemmed;
+        self
+    }
+
     /// Embed a query text using TF-IDF (same as document embeddings)
     pub fn embed_query(&self, text: &str) -> Vec<f32> {
-        let words: Vec<String> = text
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.trim_matches(|c: char| !c.is_alphanumeric()))
-            .filter(|s| !s.is_empty())
-            .map(String::from)
-            .collect();
+        let words = tokenize(text, self.stemmed);
 
         // Count term frequency
         let mut term_freq: std::collections::HashMap<String, usize> =
@@ -126,6 +184,14 @@ impl VectorStore {
         Ok(similarities.into_iter().take(k).collect())
     }
 
+    /// Get the embedding vector stored for an ID
+    pub fn vector(&self, id: &str) -> Option<&[f32]> {
+        self.embeddings
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.vector.as_slice())
+    }
+
     /// Get the number of embeddings in the store
     pub fn len(&self) -> usize {
         self.embeddings.len()
@@ -142,6 +208,80 @@ impl VectorStore {
     }
 }
 
+/// Computes embeddings with a local Ollama server's `/api/embeddings` endpoint
+///
+/// Lets the docs be indexed at startup without a prebuilt embeddings file.
+/// Queries must then be embedded by the same model as the docs.
+#[derive(Debug, Clone)]
+pub struct OllamaEmbedder {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaEmbedder {
+    /// Create an embedder for a model on the Ollama server at `base_url`
+    pub fn new(base_url: &str, model: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// Create an embedder for the model in `OLLAMA_EMBEDDING_MODEL`, if it is set
+    ///
+    /// The server is `OLLAMA_API_BASE_URL`, as for chat, or the local default.
+    pub fn from_env() -> Option<Self> {
+        let model = std::env::var(OLLAMA_EMBEDDING_MODEL_VAR).ok()?;
+        if model.trim().is_empty() {
+            return None;
+        }
+        let base_url = std::env::var("OLLAMA_API_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_OLLAMA_BASE_URL.to_string());
+        Some(Self::new(&base_url, model.trim()))
+    }
+
+    /// Embed a single text
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        #[derive(Deserialize)]
+        struct EmbeddingResponse {
+            embedding: Vec<f32>,
+        }
+
+        let response: EmbeddingResponse = self
+            .client
+            .post(format!("{}/api/embeddings", self.base_url))
+            .json(&serde_json::json!({ "model": self.model, "prompt": text }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if response.embedding.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Ollama returned no embedding; is {} an embedding model?",
+                self.model
+            ));
+        }
+
+        Ok(response.embedding)
+    }
+
+    /// Embed the content of each chunk, one request per chunk
+    pub async fn embed_chunks(&self, chunks: &[EmbeddingChunk]) -> Result<Vec<EmbeddingEntry>> {
+        let mut embeddings = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            embeddings.push(EmbeddingEntry {
+                id: chunk.id.clone(),
+                vector: self.embed(&chunk.content).await?,
+            });
+        }
+        Ok(embeddings)
+    }
+}
+
 /// Calculate cosine similarity between two vectors
 /// Returns a value between -1 and 1, where 1 means identical direction
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
@@ -161,6 +301,9 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_cosine_similarity() {
@@ -237,4 +380,134 @@ mod tests {
         let magnitude: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
         assert!((magnitude - 1.0).abs() < 0.01 || magnitude == 0.0);
     }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Fast(2) DRUM-pattern, on a beat!", false),
+            vec!["fast", "drum", "pattern", "beat"]
+        );
+        assert_eq!(
+            tokenize("Patterns playing delayed bass", true),
+            vec!["pattern", "play", "delay", "bass"]
+        );
+    }
+
+    #[test]
+    fn test_query_ignores_case_and_punctuation() {
+        let embeddings = vec![EmbeddingEntry {
+            id: "doc1".to_string(),
+            vector: vec![1.0, 0.0, 0.0],
+        }];
+        let vocabulary = vec![
+            "drum".to_string(),
+            "pattern".to_string(),
+            "fast".to_string(),
+        ];
+        let store =
+            VectorStore::new(embeddings, vocabulary, std::collections::HashMap::new()).unwrap();
+
+        let normalized = store.embed_query("drum pattern fast");
+        assert!(normalized.iter().any(|&x| x > 0.0));
+        assert_eq!(store.embed_query("Drum-PATTERN, fast(2)!"), normalized);
+
+        // Only unseen words: a zero vector, not NaNs
+        assert!(store
+            .embed_query("Unknown words!")
+            .iter()
+            .all(|&x| x == 0.0));
+    }
+
+    /// Serve Ollama-style embedding responses, recording each prompt
+    ///
+    /// The embedding of a prompt is `[prompt length, 1.0]`.
+    fn mock_ollama() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let recorded = prompts.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+
+                // Requests may share a kept-alive connection
+                'requests: loop {
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            break 'requests;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                    assert_eq!(request["model"], "nomic-embed-text");
+                    let prompt = request["prompt"].as_str().unwrap().to_string();
+                    let response =
+                        serde_json::json!({ "embedding": [prompt.len() as f32, 1.0] }).to_string();
+                    recorded.lock().unwrap().push(prompt);
+
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        response.len(),
+                        response
+                    )
+                    .unwrap();
+                }
+            }
+        });
+
+        (base_url, prompts)
+    }
+
+    #[tokio::test]
+    async fn test_ollama_embeds_each_chunk() {
+        use crate::rag::types::{ChunkMetadata, ChunkType};
+
+        let chunk = |id: &str, content: &str| EmbeddingChunk {
+            id: id.to_string(),
+            chunk_type: ChunkType::Function,
+            content: content.to_string(),
+            metadata: ChunkMetadata {
+                name: None,
+                signature: None,
+                category: None,
+                code: None,
+                style: None,
+                tags: None,
+                description: None,
+            },
+        };
+        let chunks = vec![chunk("func_0", "scale function"), chunk("func_1", "rev")];
+
+        let (base_url, prompts) = mock_ollama();
+        let embedder = OllamaEmbedder::new(&format!("{}/", base_url), "nomic-embed-text");
+        let embeddings = embedder.embed_chunks(&chunks).await.unwrap();
+
+        // One request per chunk, each stored under the chunk's ID
+        assert_eq!(*prompts.lock().unwrap(), vec!["scale function", "rev"]);
+        let entries: Vec<(&str, &[f32])> = embeddings
+            .iter()
+            .map(|e| (e.id.as_str(), e.vector.as_slice()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![("func_0", &[14.0, 1.0][..]), ("func_1", &[3.0, 1.0][..])]
+        );
+
+        let store = VectorStore::from_embeddings(embeddings).unwrap();
+        assert_eq!(store.dimension(), 2);
+        let query = embedder.embed("rev").await.unwrap();
+        assert_eq!(store.search(&query, 1).unwrap()[0].0, "func_1");
+    }
 }
@@ -1,7 +1,7 @@
 // Embedding generator using TF-IDF for pure Rust, offline embedding generation
 // This runs at build time to create embeddings from Strudel documentation
 
-use super::embeddings::EmbeddingEntry;
+use super::embeddings::{tokenize, EmbeddingEntry};
 use super::types::{ChunkMetadata, ChunkType, EmbeddingChunk};
 use anyhow::Result;
 use std::collections::HashMap;
@@ -12,23 +12,28 @@ pub struct TfIdfEmbedder {
     pub vocabulary: Vec<String>,
     pub idf_scores: HashMap<String, f32>,
     pub dimension: usize,
+    pub stemmed: bool,
 }
 
 impl TfIdfEmbedder {
     /// Create a new TF-IDF embedder from a corpus of documents
     pub fn new(documents: &[String], dimension: usize) -> Result<Self> {
+        Self::build(documents, dimension, false)
+    }
+
+    /// Create a TF-IDF embedder whose vocabulary is made of stemmed words
+    pub fn new_stemmed(documents: &[String], dimension: usize) -> Result<Self> {
+        Self::build(documents, dimension, true)
+    }
+
+    fn build(documents: &[String], dimension: usize, stemmed: bool) -> Result<Self> {
         let mut word_doc_count: HashMap<String, usize> = HashMap::new();
         let total_docs = documents.len() as f32;
 
         // Count document frequency for each word
         for doc in documents {
-            let words: std::collections::HashSet<String> = doc
-                .to_lowercase()
-                .split_whitespace()
-                .map(|s| s.trim_matches(|c: char| !c.is_alphanumeric()))
-                .filter(|s| !s.is_empty() && s.len() > 2) // Filter short words
-                .map(String::from)
-                .collect();
+            let words: std::collections::HashSet<String> =
+                tokenize(doc, stemmed).into_iter().collect();
 
             for word in words {
                 *word_doc_count.entry(word).or_insert(0) += 1;
@@ -59,18 +64,13 @@ impl TfIdfEmbedder {
             vocabulary,
             idf_scores,
             dimension: actual_dimension,
+            stemmed,
         })
     }
 
     /// Generate TF-IDF embedding for a document
     pub fn embed(&self, text: &str) -> Vec<f32> {
-        let words: Vec<String> = text
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.trim_matches(|c: char| !c.is_alphanumeric()))
-            .filter(|s| !s.is_empty() && s.len() > 2) // Filter short words (same as new())
-            .map(String::from)
-            .collect();
+        let words = tokenize(text, self.stemmed);
 
         // Guard against empty token list - return all-zero vector
         if words.is_empty() {
@@ -107,18 +107,13 @@ impl TfIdfEmbedder {
     }
 }
 
-/// Generate embeddings from Strudel documentation JSON
-/// Returns (embeddings, chunks, embedder) where embedder contains vocabulary for runtime queries
-pub fn generate_from_strudel_docs(
-    docs_json: &str,
-    dimension: usize,
-) -> Result<(Vec<EmbeddingEntry>, Vec<EmbeddingChunk>, TfIdfEmbedder)> {
+/// Split Strudel documentation JSON into chunks to embed: one per function and example
+pub fn chunks_from_strudel_docs(docs_json: &str) -> Result<Vec<EmbeddingChunk>> {
     use serde_json::Value;
 
     let docs: Value = serde_json::from_str(docs_json)?;
 
     let mut chunks = Vec::new();
-    let mut texts = Vec::new();
 
     // Process function documentation
     if let Some(docs_array) = docs["docs"].as_array() {
@@ -131,7 +126,6 @@ pub fn generate_from_strudel_docs(
                     .replace("</code>", "");
 
                 let content = format!("{} {}", name, clean_desc);
-                texts.push(content.clone());
 
                 let tags = doc["tags"]
                     .as_array()
@@ -145,7 +139,7 @@ pub fn generate_from_strudel_docs(
                 let chunk = EmbeddingChunk {
                     id: format!("func_{}", idx),
                     chunk_type: ChunkType::Function,
-                    content: content.clone(),
+                    content,
                     metadata: ChunkMetadata {
                         name: Some(name.to_string()),
                         signature: doc["signature"].as_str().map(|s| s.to_string()),
@@ -168,7 +162,6 @@ pub fn generate_from_strudel_docs(
             if let (Some(name), Some(code)) = (example["name"].as_str(), example["code"].as_str()) {
                 let description = example["description"].as_str().unwrap_or("");
                 let content = format!("{} {} {}", name, description, code);
-                texts.push(content.clone());
 
                 let chunk = EmbeddingChunk {
                     id: format!("ex_{}", idx),
@@ -190,12 +183,30 @@ pub fn generate_from_strudel_docs(
         }
     }
 
+    Ok(chunks)
+}
+
+/// Generate embeddings from Strudel documentation JSON
+/// Returns (embeddings, chunks, embedder) where embedder contains vocabulary for runtime queries
+/// With `stem`, words are stemmed so different forms of a word match
+pub fn generate_from_strudel_docs(
+    docs_json: &str,
+    dimension: usize,
+    stem: bool,
+) -> Result<(Vec<EmbeddingEntry>, Vec<EmbeddingChunk>, TfIdfEmbedder)> {
+    let chunks = chunks_from_strudel_docs(docs_json)?;
+    let texts: Vec<String> = chunks.iter().map(|chunk| chunk.content.clone()).collect();
+
     // Build TF-IDF embedder from corpus
     println!(
         "Building TF-IDF vocabulary from {} documents...",
         texts.len()
     );
-    let embedder = TfIdfEmbedder::new(&texts, dimension)?;
+    let embedder = if stem {
+        TfIdfEmbedder::new_stemmed(&texts, dimension)?
+    } else {
+        TfIdfEmbedder::new(&texts, dimension)?
+    };
 
     // Generate embeddings
     println!("Generating embeddings...");
@@ -232,6 +243,7 @@ pub fn save_to_json(
         idf_scores: HashMap<String, f32>,
         dimension: usize,
         count: usize,
+        stemmed: bool,
     }
 
     let data = EmbeddingsData {
@@ -241,6 +253,7 @@ pub fn save_to_json(
         chunks: chunks.to_vec(),
         vocabulary: embedder.vocabulary.clone(),
         idf_scores: embedder.idf_scores.clone(),
+        stemmed: embedder.stemmed,
     };
 
     let json = serde_json::to_string_pretty(&data)?;
@@ -275,6 +288,35 @@ mod tests {
         assert!((mag - 1.0).abs() < 0.01 || mag == 0.0);
     }
 
+    #[test]
+    fn test_stemmed_index_matches_queries() {
+        use crate::rag::embeddings::VectorStore;
+
+        let docs = vec![
+            "Layering drum patterns".to_string(),
+            "Playing chords on a piano".to_string(),
+        ];
+        let embedder = TfIdfEmbedder::new_stemmed(&docs, 10).unwrap();
+        assert!(embedder.vocabulary.contains(&"pattern".to_string()));
+
+        let entries = vec![EmbeddingEntry {
+            id: "doc1".to_string(),
+            vector: embedder.embed(&docs[0]),
+        }];
+        let store = VectorStore::new(
+            entries,
+            embedder.vocabulary.clone(),
+            embedder.idf_scores.clone(),
+        )
+        .unwrap()
+        .with_stemming(true);
+
+        // The query is tokenized exactly as the index was
+        let query = store.embed_query("PATTERN?");
+        assert!(query.iter().any(|&x| x > 0.0));
+        assert_eq!(query, embedder.embed("patterns"));
+    }
+
     #[test]
     fn test_generate_from_docs() {
         let docs_json = r#"{
@@ -289,7 +331,8 @@ mod tests {
             ]
         }"#;
 
-        let (embeddings, chunks, _embedder) = generate_from_strudel_docs(docs_json, 50).unwrap();
+        let (embeddings, chunks, _embedder) =
+            generate_from_strudel_docs(docs_json, 50, false).unwrap();
 
         assert_eq!(embeddings.len(), 1);
         assert_eq!(chunks.len(), 1);
@@ -9,8 +9,8 @@ pub mod types;
 
 // Re-export Tauri commands
 pub use commands::*;
-pub use embeddings::{EmbeddingEntry, VectorStore};
-pub use retriever::StrudelRetriever;
+pub use embeddings::{EmbeddingEntry, OllamaEmbedder, VectorStore};
+pub use retriever::{normalize_scores, StrudelRetriever, DEFAULT_MMR_LAMBDA};
 pub use types::{ChunkType, EmbeddingChunk, SearchResult};
 
 use anyhow::Result;
@@ -18,16 +18,20 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 /// Main RAG state for the application
-/// Uses pre-computed embeddings loaded from JSON at runtime
+/// Uses pre-computed embeddings loaded from JSON at runtime, or embeddings
+/// computed at startup by a local Ollama server
 #[derive(Clone)]
 pub struct RagState {
     pub retriever: Arc<RwLock<Option<StrudelRetriever>>>,
+    /// Model that embedded the docs, when it wasn't the shipped TF-IDF
+    query_embedder: Arc<RwLock<Option<OllamaEmbedder>>>,
 }
 
 impl RagState {
     pub fn new() -> Self {
         Self {
             retriever: Arc::new(RwLock::new(None)),
+            query_embedder: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -42,23 +46,56 @@ impl RagState {
             chunks: Vec<EmbeddingChunk>,
             vocabulary: Vec<String>,
             idf_scores: HashMap<String, f32>,
+            #[serde(default)]
+            stemmed: bool,
         }
 
         let data: EmbeddingsData = serde_json::from_str(json_data)?;
 
-        let vector_store = VectorStore::new(data.embeddings, data.vocabulary, data.idf_scores)?;
+        let vector_store = VectorStore::new(data.embeddings, data.vocabulary, data.idf_scores)?
+            .with_stemming(data.stemmed);
         let retriever = StrudelRetriever::new(vector_store, data.chunks)?;
 
         *self.retriever.write().await = Some(retriever);
+        *self.query_embedder.write().await = None;
 
         Ok(())
     }
 
-    /// Embed a query text using TF-IDF
+    /// Initialize RAG system by embedding Strudel documentation JSON with Ollama
+    ///
+    /// Makes one request per function and example, so this can take a while.
+    pub async fn load_with_ollama(&self, docs_json: &str, embedder: OllamaEmbedder) -> Result<()> {
+        let chunks = generator::chunks_from_strudel_docs(docs_json)?;
+        let embeddings = embedder.embed_chunks(&chunks).await?;
+
+        let vector_store = VectorStore::from_embeddings(embeddings)?;
+        let retriever = StrudelRetriever::new(vector_store, chunks)?;
+
+        *self.retriever.write().await = Some(retriever);
+        *self.query_embedder.write().await = Some(embedder);
+
+        Ok(())
+    }
+
+    /// Embed a query text the same way as the indexed docs (TF-IDF or Ollama)
     pub async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        // Clone the embedder so the lock isn't held across the request
+        let query_embedder = self.query_embedder.read().await.clone();
+        if let Some(embedder) = query_embedder {
+            return embedder.embed(query).await;
+        }
+
         let retriever_lock = self.retriever.read().await;
         match retriever_lock.as_ref() {
-            Some(retriever) => Ok(retriever.embed_query(query)),
+            Some(retriever) => {
+                let embedding = retriever.embed_query(query);
+                // A query without indexed words matches nothing, so callers can fall back
+                if embedding.iter().all(|&x| x == 0.0) {
+                    return Err(anyhow::anyhow!("No query words are in the RAG vocabulary"));
+                }
+                Ok(embedding)
+            }
             None => Err(anyhow::anyhow!("RAG not initialized")),
         }
     }
@@ -78,6 +115,29 @@ impl RagState {
         }
     }
 
+    /// Search for results to show in chat: reranked for diversity, with
+    /// relevance scores normalized to `[0, 1]`
+    ///
+    /// Only chunks of the given types are returned, or all if `types` is empty
+    pub async fn search_for_chat(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        types: &[ChunkType],
+    ) -> Result<Vec<SearchResult>> {
+        let retriever_lock = self.retriever.read().await;
+
+        match retriever_lock.as_ref() {
+            Some(retriever) => {
+                let mut results =
+                    retriever.search_mmr(query_embedding, limit, DEFAULT_MMR_LAMBDA, types)?;
+                normalize_scores(&mut results);
+                Ok(results)
+            }
+            None => Err(anyhow::anyhow!("RAG not initialized")),
+        }
+    }
+
     /// Get the number of indexed documents
     pub async fn count(&self) -> Result<usize> {
         let retriever_lock = self.retriever.read().await;
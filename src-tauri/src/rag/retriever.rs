@@ -1,10 +1,13 @@
 // In-memory retriever for semantic search over Strudel documentation
 
-use super::embeddings::VectorStore;
+use super::embeddings::{cosine_similarity, VectorStore};
 use super::types::{ChunkType, EmbeddingChunk, SearchResult};
 use anyhow::Result;
 use std::collections::HashMap;
 
+/// Default balance of relevance (1.0) against diversity (0.0) for MMR reranking
+pub const DEFAULT_MMR_LAMBDA: f32 = 0.7;
+
 /// Retriever for searching Strudel documentation using in-memory vector search
 pub struct StrudelRetriever {
     vector_store: VectorStore,
@@ -52,21 +55,66 @@ impl StrudelRetriever {
         Ok(results)
     }
 
-    /// Search with optional type filtering
-    pub fn search_filtered(
+    /// Search, reranking results by maximal marginal relevance (MMR)
+    ///
+    /// Each result is picked to maximize `lambda * relevance - (1 - lambda) *
+    /// similarity to the results already picked`, so near-duplicate chunks
+    /// don't crowd out others. A `lambda` of 1.0 keeps plain relevance order.
+    /// Only chunks of the given `types` are searched, or all if it is empty.
+    pub fn search_mmr(
         &self,
         query_embedding: &[f32],
         limit: usize,
-        chunk_types: Option<Vec<ChunkType>>,
+        lambda: f32,
+        types: &[ChunkType],
     ) -> Result<Vec<SearchResult>> {
-        let mut results = self.search(query_embedding, limit * 2)?; // Get more to account for filtering
+        // Rerank from a wider pool than is returned
+        let mut candidates = self.search_filtered(query_embedding, limit * 4, types)?;
+        let mut selected: Vec<SearchResult> = Vec::with_capacity(limit);
 
-        // Filter by chunk type if specified
-        if let Some(types) = chunk_types {
-            results.retain(|r| types.contains(&r.chunk.chunk_type));
+        while selected.len() < limit && !candidates.is_empty() {
+            let mmr = |candidate: &SearchResult| {
+                let redundancy = selected
+                    .iter()
+                    .map(|picked| self.similarity(&candidate.chunk.id, &picked.chunk.id))
+                    .fold(0.0, f32::max);
+                lambda * candidate.score - (1.0 - lambda) * redundancy
+            };
+
+            // On a tie, keep the more relevant candidate
+            let best = (1..candidates.len()).fold(0, |best, i| {
+                if mmr(&candidates[i]) > mmr(&candidates[best]) {
+                    i
+                } else {
+                    best
+                }
+            });
+            selected.push(candidates.remove(best));
         }
 
-        // Trim to requested limit
+        Ok(selected)
+    }
+
+    /// Cosine similarity between two indexed chunks, 0.0 if either is missing
+    fn similarity(&self, a: &str, b: &str) -> f32 {
+        match (self.vector_store.vector(a), self.vector_store.vector(b)) {
+            (Some(a), Some(b)) => cosine_similarity(a, b),
+            _ => 0.0,
+        }
+    }
+
+    /// Search only chunks of the given types, or all chunks if `types` is empty
+    pub fn search_filtered(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        types: &[ChunkType],
+    ) -> Result<Vec<SearchResult>> {
+        // Filter before limiting, so chunks of other types can't crowd out matches
+        let mut results = self.search(query_embedding, self.count())?;
+        if !types.is_empty() {
+            results.retain(|r| types.contains(&r.chunk.chunk_type));
+        }
         results.truncate(limit);
 
         Ok(results)
@@ -88,6 +136,30 @@ impl StrudelRetriever {
     }
 }
 
+/// Rescale result scores to `[0, 1]`, from the weakest result to the strongest
+///
+/// Raw cosine scores depend on how the query is worded, so this makes the
+/// relevance of results comparable between searches. If all scores are equal,
+/// they all become 1.0.
+pub fn normalize_scores(results: &mut [SearchResult]) {
+    let min = results
+        .iter()
+        .map(|r| r.score)
+        .fold(f32::INFINITY, f32::min);
+    let max = results
+        .iter()
+        .map(|r| r.score)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    for result in results.iter_mut() {
+        result.score = if max > min {
+            (result.score - min) / (max - min)
+        } else {
+            1.0
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,10 +306,134 @@ mod tests {
         // Search only for functions
         let query = vec![1.0, 0.0];
         let results = retriever
-            .search_filtered(&query, 10, Some(vec![ChunkType::Function]))
+            .search_filtered(&query, 10, &[ChunkType::Function])
             .unwrap();
 
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].chunk.chunk_type, ChunkType::Function);
     }
+
+    #[test]
+    fn test_filter_excludes_other_types() {
+        let entry = |id: &str, vector: Vec<f32>| EmbeddingEntry {
+            id: id.to_string(),
+            vector,
+        };
+
+        // Examples are all more relevant than the one function
+        let embeddings = vec![
+            entry("ex1", vec![1.0, 0.0]),
+            entry("ex2", vec![0.9, 0.1]),
+            entry("ex3", vec![0.8, 0.2]),
+            entry("func1", vec![0.1, 0.9]),
+        ];
+        let chunks = ["ex1", "ex2", "ex3", "func1"]
+            .iter()
+            .map(|&id| EmbeddingChunk {
+                chunk_type: if id.starts_with("ex") {
+                    ChunkType::Example
+                } else {
+                    ChunkType::Function
+                },
+                ..chunk(id)
+            })
+            .collect();
+        let vocabulary = vec!["a".to_string(), "b".to_string()];
+
+        let vector_store = VectorStore::new(embeddings, vocabulary, HashMap::new()).unwrap();
+        let retriever = StrudelRetriever::new(vector_store, chunks).unwrap();
+        let query = vec![1.0, 0.0];
+
+        let functions = retriever
+            .search_filtered(&query, 1, &[ChunkType::Function])
+            .unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].chunk.id, "func1");
+
+        let examples = retriever
+            .search_filtered(&query, 10, &[ChunkType::Example])
+            .unwrap();
+        assert_eq!(examples.len(), 3);
+        assert!(examples
+            .iter()
+            .all(|r| r.chunk.chunk_type == ChunkType::Example));
+
+        let reranked = retriever
+            .search_mmr(&query, 10, DEFAULT_MMR_LAMBDA, &[ChunkType::Function])
+            .unwrap();
+        assert_eq!(reranked.len(), 1);
+        assert_eq!(reranked[0].chunk.id, "func1");
+
+        // No types searches everything
+        let all = retriever.search_filtered(&query, 10, &[]).unwrap();
+        assert_eq!(all.len(), 4);
+    }
+
+    fn chunk(id: &str) -> EmbeddingChunk {
+        EmbeddingChunk {
+            id: id.to_string(),
+            chunk_type: ChunkType::Function,
+            content: id.to_string(),
+            metadata: ChunkMetadata {
+                name: None,
+                signature: None,
+                category: None,
+                code: None,
+                style: None,
+                tags: None,
+                description: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_mmr_prefers_diverse_results() {
+        let entry = |id: &str, vector: Vec<f32>| EmbeddingEntry {
+            id: id.to_string(),
+            vector,
+        };
+
+        // Two near-identical chunks, and a less relevant but different one
+        let embeddings = vec![
+            entry("dup1", vec![1.0, 0.1, 0.0]),
+            entry("dup2", vec![1.0, 0.12, 0.0]),
+            entry("diverse", vec![0.0, 1.0, 0.0]),
+        ];
+        let chunks = vec![chunk("dup1"), chunk("dup2"), chunk("diverse")];
+        let vocabulary = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        let vector_store = VectorStore::new(embeddings, vocabulary, HashMap::new()).unwrap();
+        let retriever = StrudelRetriever::new(vector_store, chunks).unwrap();
+        let ids = |results: Vec<SearchResult>| -> Vec<String> {
+            results.into_iter().map(|r| r.chunk.id).collect()
+        };
+
+        let query = vec![1.0, 1.0, 0.0];
+        let plain = retriever.search(&query, 3).unwrap();
+        assert_eq!(ids(plain), vec!["dup2", "dup1", "diverse"]);
+
+        let reranked = retriever
+            .search_mmr(&query, 3, DEFAULT_MMR_LAMBDA, &[])
+            .unwrap();
+        assert_eq!(ids(reranked), vec!["dup2", "diverse", "dup1"]);
+
+        // Without the diversity term, MMR is plain relevance order
+        let relevance_only = retriever.search_mmr(&query, 3, 1.0, &[]).unwrap();
+        assert_eq!(ids(relevance_only), vec!["dup2", "dup1", "diverse"]);
+    }
+
+    #[test]
+    fn test_normalize_scores() {
+        let result = |id: &str, score: f32| SearchResult::new(chunk(id), score, 1.0 - score);
+
+        let mut results = vec![result("a", 0.5), result("b", 0.375), result("c", 0.25)];
+        normalize_scores(&mut results);
+        let scores: Vec<f32> = results.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![1.0, 0.5, 0.0]);
+
+        // A single result is fully relevant
+        let mut results = vec![result("a", 0.25)];
+        normalize_scores(&mut results);
+        assert_eq!(results[0].score, 1.0);
+    }
 }
@@ -5,7 +5,7 @@
 
 use serde::{Deserialize, Serialize};
 use strudel_core::{Fraction, Hap, State, TimeSpan, Value};
-use strudel_mini::{evaluate, format, parse, ParseError};
+use strudel_mini::{evaluate, format, parse, ParseError, Span};
 use tauri::command;
 
 /// Error type for Strudel commands
@@ -23,13 +23,20 @@ pub struct ErrorLocation {
     pub span_end: usize,
 }
 
-impl From<ParseError> for StrudelError {
-    fn from(err: ParseError) -> Self {
+impl StrudelError {
+    /// Build an error from a parse error, locating it in the pattern source
+    ///
+    /// Errors without a span, such as running out of input, are located at
+    /// the end of the source.
+    fn parse(err: ParseError, source: &str) -> Self {
+        let span = err
+            .span()
+            .unwrap_or_else(|| Span::new(source.len(), source.len()));
         StrudelError {
             message: err.to_string(),
-            location: err.span().map(|span| ErrorLocation {
-                line: 0, // TODO: Calculate line from span
-                column: 0,
+            location: err.line_col(source).map(|(line, column)| ErrorLocation {
+                line,
+                column,
                 span_start: span.start,
                 span_end: span.end,
             }),
@@ -73,7 +80,7 @@ impl From<Hap> for SerializableHap {
 /// Returns Ok(()) if the pattern is valid, or an error with location information
 #[command]
 pub fn validate_pattern(pattern: String) -> Result<(), StrudelError> {
-    parse(&pattern)?;
+    parse(&pattern).map_err(|e| StrudelError::parse(e, &pattern))?;
     Ok(())
 }
 
@@ -82,7 +89,7 @@ pub fn validate_pattern(pattern: String) -> Result<(), StrudelError> {
 /// Parses the pattern and returns a canonical formatted version
 #[command]
 pub fn format_pattern(pattern: String) -> Result<String, StrudelError> {
-    let ast = parse(&pattern)?;
+    let ast = parse(&pattern).map_err(|e| StrudelError::parse(e, &pattern))?;
     Ok(format(&ast))
 }
 
@@ -96,7 +103,7 @@ pub fn evaluate_pattern(
     duration_cycles: f64,
 ) -> Result<Vec<SerializableHap>, StrudelError> {
     // Parse the pattern
-    let ast = parse(&pattern)?;
+    let ast = parse(&pattern).map_err(|e| StrudelError::parse(e, &pattern))?;
 
     // Evaluate to a Pattern
     let pat = evaluate(&ast).map_err(|e| StrudelError::from(e.to_string()))?;
@@ -120,7 +127,7 @@ pub fn analyze_pattern(
     cycles: f64,
 ) -> Result<PatternMetrics, StrudelError> {
     // Parse and evaluate the pattern
-    let ast = parse(&pattern)?;
+    let ast = parse(&pattern).map_err(|e| StrudelError::parse(e, &pattern))?;
     let pat = evaluate(&ast).map_err(|e| StrudelError::from(e.to_string()))?;
 
     // Query for the specified number of cycles
@@ -249,6 +256,14 @@ mod tests {
         assert!(validate_pattern("invalid!@#$%".to_string()).is_err());
     }
 
+    #[test]
+    fn test_validate_pattern_unexpected_eof() {
+        let err = validate_pattern("[bd sd".to_string()).unwrap_err();
+        let location = err.location.expect("EOF errors should be located");
+        assert_eq!((location.line, location.column), (1, 7));
+        assert_eq!((location.span_start, location.span_end), (6, 6));
+    }
+
     #[test]
     fn test_format_pattern() {
         let result = format_pattern("bd   sd    cp".to_string()).unwrap();
@@ -3,6 +3,7 @@
 
 use crate::chatbridge::RateLimiter;
 use crate::music_theory::MusicTheory;
+use crate::rag::ChunkType;
 use anyhow::{anyhow, Result as AnyResult};
 use rig::{completion::ToolDefinition as RigToolDefinition, tool::Tool as RigTool};
 use serde::{Deserialize, Serialize};
@@ -52,6 +53,7 @@ impl ToolRuntimeContext {
         &self,
         query: String,
         limit: Option<usize>,
+        chunk_type: Option<ChunkType>,
     ) -> AnyResult<String> {
         self.check_rate_limit("search_strudel_docs").await?;
 
@@ -64,7 +66,7 @@ impl ToolRuntimeContext {
         if let Ok(query_embedding) = self.rag_state.embed_query(&query).await {
             if let Ok(rag_results) = self
                 .rag_state
-                .search_with_embedding(&query_embedding, limit)
+                .search_for_chat(&query_embedding, limit, chunk_type.as_slice())
                 .await
             {
                 if !rag_results.is_empty() {
@@ -334,6 +336,7 @@ impl ToolRuntimeContext {
         hits: usize,
         steps: usize,
         sound: Option<String>,
+        complementary: bool,
     ) -> AnyResult<String> {
         self.check_rate_limit("generate_euclidean_rhythm").await?;
 
@@ -346,8 +349,12 @@ impl ToolRuntimeContext {
             return Err(anyhow!("Sound name too long"));
         }
 
-        let pattern = MusicTheory::generate_euclidean_pattern(hits, steps, &sound_name)
-            .map_err(|e| anyhow!("{}", e))?;
+        let pattern = if complementary {
+            MusicTheory::generate_complementary_euclidean_pattern(hits, steps, &sound_name, "hh")
+        } else {
+            MusicTheory::generate_euclidean_pattern(hits, steps, &sound_name)
+        }
+        .map_err(|e| anyhow!("{}", e))?;
 
         let rhythm =
             MusicTheory::generate_euclidean_rhythm(hits, steps).map_err(|e| anyhow!("{}", e))?;
@@ -418,6 +425,8 @@ impl RigSearchDocsTool {
 pub struct RigSearchDocsArgs {
     query: String,
     limit: Option<usize>,
+    #[serde(default)]
+    chunk_type: Option<ChunkType>,
 }
 
 impl RigTool for RigSearchDocsTool {
@@ -442,6 +451,11 @@ impl RigTool for RigSearchDocsTool {
                     "limit": {
                         "type": "number",
                         "description": "Maximum number of results to return (default 5)."
+                    },
+                    "chunk_type": {
+                        "type": "string",
+                        "enum": ["function", "example"],
+                        "description": "Only search function reference or code examples (default both)."
                     }
                 },
                 "required": ["query"]
@@ -451,7 +465,7 @@ impl RigTool for RigSearchDocsTool {
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         self.ctx
-            .search_strudel_docs(args.query, args.limit)
+            .search_strudel_docs(args.query, args.limit, args.chunk_type)
             .await
             .map_err(ToolInvocationError::from)
     }
@@ -663,6 +677,8 @@ pub struct RigEuclideanRhythmArgs {
     hits: usize,
     steps: usize,
     sound: Option<String>,
+    #[serde(default)]
+    complementary: bool,
 }
 
 impl RigTool for RigEuclideanRhythmTool {
@@ -691,6 +707,10 @@ impl RigTool for RigEuclideanRhythmTool {
                     "sound": {
                         "type": "string",
                         "description": "Sound to use (default: bd)"
+                    },
+                    "complementary": {
+                        "type": "boolean",
+                        "description": "Also play hats (hh) on the rests, stacked with the main sound (default: false)"
                     }
                 },
                 "required": ["hits", "steps"]
@@ -700,7 +720,7 @@ impl RigTool for RigEuclideanRhythmTool {
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         self.ctx
-            .generate_euclidean_rhythm(args.hits, args.steps, args.sound)
+            .generate_euclidean_rhythm(args.hits, args.steps, args.sound, args.complementary)
             .await
             .map_err(ToolInvocationError::from)
     }